@@ -1,9 +1,17 @@
 USE @DB_NOMBRE;
 
+CREATE TABLE IF NOT EXISTS centros (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  nombre varchar(100) NOT NULL,
+  direccion varchar(250) NOT NULL DEFAULT '',
+  PRIMARY KEY (id),
+  UNIQUE KEY centros_nombre_unique (nombre)
+) AUTO_INCREMENT=1 COMMENT='Centros de trabajo de la compañia';
+
 CREATE TABLE IF NOT EXISTS usuarios (
   nombre varchar(50) NOT NULL,
   id int(10) unsigned NOT NULL AUTO_INCREMENT,
-  password varchar(500) NOT NULL,
+  password varchar(500) NOT NULL COMMENT 'Hash PBKDF2 de la password',
   activo datetime DEFAULT NULL,
   primer_apellido varchar(100) NOT NULL,
   segundo_apellido varchar(100) NOT NULL,
@@ -11,8 +19,15 @@ CREATE TABLE IF NOT EXISTS usuarios (
   inicio datetime DEFAULT NULL,
   dni_hash char(64) NOT NULL,
   email varchar(254) NOT NULL,
+  intentos_fallidos int(10) unsigned NOT NULL DEFAULT 0 COMMENT 'Intentos fallidos de login consecutivos',
+  ultimo_fallo datetime DEFAULT NULL COMMENT 'Fecha del último intento fallido de login',
+  bloqueado_hasta datetime DEFAULT NULL COMMENT 'Fecha hasta la que el acceso está bloqueado',
+  zona_horaria varchar(64) DEFAULT NULL COMMENT 'Zona horaria IANA del usuario. Si es NULL se usa la de la configuración',
+  centro int(10) unsigned DEFAULT NULL COMMENT 'Centro de trabajo del usuario',
   PRIMARY KEY (id),
-  UNIQUE KEY dni_hash_unique (dni_hash)
+  UNIQUE KEY dni_hash_unique (dni_hash),
+  KEY usuarios_centros_FK (centro),
+  CONSTRAINT usuarios_centros_FK FOREIGN KEY (centro) REFERENCES centros (id) ON UPDATE CASCADE
 ) AUTO_INCREMENT=1 COMMENT='Son los usuarios de la compañia';
 
 CREATE TABLE IF NOT EXISTS roles_usuario (
@@ -31,8 +46,12 @@ CREATE TABLE IF NOT EXISTS trazas (
   tipo smallint(5) unsigned NOT NULL,
   autor int(10) unsigned DEFAULT NULL,
   entidad smallint(5) unsigned NOT NULL,
+  eslabon int(10) unsigned DEFAULT NULL COMMENT 'Posición en la cadena de hashes de la entidad',
+  hash char(64) DEFAULT NULL COMMENT 'HMAC del hash anterior y los datos',
+  sello char(64) DEFAULT NULL COMMENT 'HMAC del hash',
   PRIMARY KEY (id),
   KEY trazas_usuarios_FK_1 (autor),
+  KEY trazas_entidad_IDX (entidad, entidad_id, fecha),
   CONSTRAINT trazas_usuarios_FK_1 FOREIGN KEY (autor) REFERENCES usuarios (id) ON UPDATE CASCADE
 ) AUTO_INCREMENT=1 COMMENT='Son las trazas de cada registro';
 
@@ -41,14 +60,26 @@ CREATE TABLE `horarios` (
   `usuario` int(10) unsigned NOT NULL,
   `fecha_creacion` date NOT NULL,
   `dia` char(1) NOT NULL,
-  `horas` TINYINT UNSIGNED NOT NULL,
+  `minutos` SMALLINT UNSIGNED NOT NULL DEFAULT 0 COMMENT 'Minutos a trabajar en el día',
   `caducidad_fecha_ini` date NOT NULL DEFAULT '1900-01-01',
   `caducidad_fecha_fin` date DEFAULT NULL,
   `cortesia` TINYINT UNSIGNED DEFAULT 0,
+  `pausa_comida_retribuida` SMALLINT UNSIGNED NOT NULL DEFAULT 0 COMMENT 'Minutos de pausa para comer retribuidos al día',
+  `pausa_personal_retribuida` SMALLINT UNSIGNED NOT NULL DEFAULT 0 COMMENT 'Minutos de pausa personal retribuidos al día',
   PRIMARY KEY (`id`),
   UNIQUE KEY `idx_usuario_fecha` (`usuario`,`fecha_creacion` DESC, `dia`, `caducidad_fecha_ini`),
   CONSTRAINT `horarios_usuarios_FK` FOREIGN KEY (`usuario`) REFERENCES `usuarios` (`id`) ON UPDATE CASCADE
-) AUTO_INCREMENT=1 COMMENT='Define las horas a trabajar por un usuario para un día de la semana a partir de una fecha.';
+) AUTO_INCREMENT=1 COMMENT='Define los minutos a trabajar por un usuario para un día de la semana a partir de una fecha.';
+
+CREATE TABLE IF NOT EXISTS horarios_franjas (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  horario int(10) unsigned NOT NULL,
+  hora_inicio time NOT NULL,
+  hora_fin time NOT NULL,
+  PRIMARY KEY (id),
+  KEY horarios_franjas_horario_IDX (horario, hora_inicio),
+  CONSTRAINT horarios_franjas_horarios_FK FOREIGN KEY (horario) REFERENCES horarios (id) ON DELETE CASCADE ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Tramos del día a trabajar de cada horario';
 
 CREATE TABLE IF NOT EXISTS marcajes (
   id int(10) unsigned NOT NULL AUTO_INCREMENT,
@@ -56,10 +87,15 @@ CREATE TABLE IF NOT EXISTS marcajes (
   fecha date NOT NULL,
   hora_inicio time NOT NULL,
   hora_fin time DEFAULT NULL,
+  inicio_utc datetime DEFAULT NULL COMMENT 'Instante UTC de la entrada',
+  fin_utc datetime DEFAULT NULL COMMENT 'Instante UTC de la salida',
   horario int(10) unsigned NOT NULL,
   usuario_registrador int(10) unsigned DEFAULT NULL,
   modificado_por int(10) unsigned DEFAULT NULL,
   eliminado tinyint(1) DEFAULT NULL,
+  eslabon int(10) unsigned DEFAULT NULL COMMENT 'Posición en la cadena de hashes del usuario',
+  hash char(64) DEFAULT NULL COMMENT 'HMAC del hash anterior y los datos inmutables',
+  sello char(64) DEFAULT NULL COMMENT 'HMAC del hash y los datos modificables',
   PRIMARY KEY (id),
   KEY `marcajes_horarios_FK` (`horario`),
   KEY registros_usuarios_FK_1 (usuario_registrador),
@@ -69,6 +105,48 @@ CREATE TABLE IF NOT EXISTS marcajes (
   CONSTRAINT registros_usuarios_FK_1 FOREIGN KEY (usuario_registrador) REFERENCES usuarios (id) ON UPDATE CASCADE
 ) AUTO_INCREMENT=1 COMMENT='Son los registros de cada empleado (usuario)';
 
+CREATE TABLE IF NOT EXISTS pausas (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  marcaje int(10) unsigned NOT NULL,
+  tipo smallint(5) unsigned NOT NULL COMMENT '1 comida, 2 personal',
+  hora_inicio time NOT NULL,
+  hora_fin time DEFAULT NULL,
+  eslabon int(10) unsigned DEFAULT NULL COMMENT 'Posición en la cadena de hashes del marcaje',
+  hash char(64) DEFAULT NULL COMMENT 'HMAC del hash anterior y los datos inmutables',
+  sello char(64) DEFAULT NULL COMMENT 'HMAC del hash y los datos modificables',
+  PRIMARY KEY (id),
+  KEY pausas_marcaje_IDX (marcaje, hora_inicio),
+  CONSTRAINT pausas_marcajes_FK FOREIGN KEY (marcaje) REFERENCES marcajes (id) ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Pausas dentro de un marcaje abierto';
+
+CREATE TABLE IF NOT EXISTS marcajes_historial (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  marcaje int(10) unsigned NOT NULL,
+  operacion smallint(5) unsigned NOT NULL COMMENT '1 creación, 2 finalización, 3 corrección, 4 eliminación, 5 inicio pausa, 6 fin pausa',
+  fecha datetime NOT NULL,
+  autor int(10) unsigned DEFAULT NULL,
+  motivo varchar(500) DEFAULT NULL,
+  hora_inicio_anterior time DEFAULT NULL,
+  hora_fin_anterior time DEFAULT NULL,
+  inicio_utc_anterior datetime DEFAULT NULL,
+  fin_utc_anterior datetime DEFAULT NULL,
+  hora_inicio time DEFAULT NULL,
+  hora_fin time DEFAULT NULL,
+  inicio_utc datetime DEFAULT NULL,
+  fin_utc datetime DEFAULT NULL,
+  marcaje_nuevo int(10) unsigned DEFAULT NULL COMMENT 'Marcaje que sustituye a este en una corrección',
+  pausa int(10) unsigned DEFAULT NULL COMMENT 'Pausa que se inicia o finaliza',
+  PRIMARY KEY (id),
+  KEY marcajes_historial_marcaje_IDX (marcaje, fecha),
+  KEY marcajes_historial_usuarios_FK (autor),
+  KEY marcajes_historial_marcajes_FK_1 (marcaje_nuevo),
+  KEY marcajes_historial_pausas_FK (pausa),
+  CONSTRAINT marcajes_historial_marcajes_FK FOREIGN KEY (marcaje) REFERENCES marcajes (id) ON UPDATE CASCADE,
+  CONSTRAINT marcajes_historial_marcajes_FK_1 FOREIGN KEY (marcaje_nuevo) REFERENCES marcajes (id) ON UPDATE CASCADE,
+  CONSTRAINT marcajes_historial_pausas_FK FOREIGN KEY (pausa) REFERENCES pausas (id) ON UPDATE CASCADE,
+  CONSTRAINT marcajes_historial_usuarios_FK FOREIGN KEY (autor) REFERENCES usuarios (id) ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Cambios de los marcajes con los valores anteriores y posteriores';
+
 CREATE TABLE IF NOT EXISTS incidencias (
   id int(10) unsigned NOT NULL AUTO_INCREMENT,
   tipo smallint(5) unsigned NOT NULL,
@@ -78,7 +156,7 @@ CREATE TABLE IF NOT EXISTS incidencias (
   marcaje int(10) unsigned DEFAULT NULL,
   estado smallint(5) unsigned NOT NULL,
   error varchar(500) DEFAULT NULL,
-  usuario_creador int(10) unsigned NOT NULL,
+  usuario_creador int(10) unsigned DEFAULT NULL COMMENT 'Nulo si la crea el sistema',
   usuario_gestor int(10) unsigned DEFAULT NULL,
   fecha date NOT NULL,
   motivo_solicitud varchar(200) DEFAULT NULL,
@@ -86,6 +164,9 @@ CREATE TABLE IF NOT EXISTS incidencias (
   fecha_resolucion datetime DEFAULT NULL,
   fecha_estado datetime DEFAULT NULL,
   usuario int(10) unsigned NOT NULL,
+  eslabon int(10) unsigned DEFAULT NULL COMMENT 'Posición en la cadena de hashes del usuario',
+  hash char(64) DEFAULT NULL COMMENT 'HMAC del hash anterior y los datos inmutables',
+  sello char(64) DEFAULT NULL COMMENT 'HMAC del hash y los datos modificables',
   PRIMARY KEY (id),
   KEY Incidencias_marcajes_FK (marcaje),
   KEY Incidencias_usuarios_FK (usuario_creador),
@@ -124,6 +205,100 @@ CREATE TABLE IF NOT EXISTS calendarios_usuario (
   CONSTRAINT usuario_calendario_calendario_FK FOREIGN KEY (calendario) REFERENCES calendarios (id) ON UPDATE CASCADE
 ) COMMENT='Calendarios asignados a un usuario';
 
+CREATE TABLE IF NOT EXISTS centros_calendarios (
+  centro int(10) unsigned NOT NULL,
+  calendario int(10) unsigned NOT NULL,
+  PRIMARY KEY (centro, calendario),
+  KEY centros_calendarios_calendario_FK (calendario),
+  CONSTRAINT centros_calendarios_centro_FK FOREIGN KEY (centro) REFERENCES centros (id) ON UPDATE CASCADE,
+  CONSTRAINT centros_calendarios_calendario_FK FOREIGN KEY (calendario) REFERENCES calendarios (id) ON UPDATE CASCADE
+) COMMENT='Calendarios por defecto de los usuarios de un centro de trabajo';
+
+CREATE TABLE IF NOT EXISTS bolsa_horas (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  usuario int(10) unsigned NOT NULL,
+  fecha date NOT NULL COMMENT 'Fecha a la que se imputa el movimiento',
+  tipo tinyint(3) unsigned NOT NULL COMMENT '1 saldo mensual, 2 ajuste, 3 compensación, 4 caducidad, 5 tope',
+  minutos int(11) NOT NULL COMMENT 'Minutos a favor (positivo) o en contra (negativo) del empleado',
+  motivo varchar(250) DEFAULT NULL,
+  autor int(10) unsigned DEFAULT NULL COMMENT 'Usuario que registra el movimiento. NULL si lo registra el sistema',
+  fecha_registro datetime NOT NULL,
+  cierre date GENERATED ALWAYS AS (IF(tipo = 1, fecha, NULL)) VIRTUAL,
+  PRIMARY KEY (id),
+  KEY bolsa_horas_usuario_fecha_IDX (usuario, fecha),
+  UNIQUE KEY bolsa_horas_cierre_unique (usuario, cierre),
+  KEY bolsa_horas_autor_FK (autor),
+  CONSTRAINT bolsa_horas_usuarios_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT bolsa_horas_autor_FK FOREIGN KEY (autor) REFERENCES usuarios (id) ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Movimientos de la bolsa de horas de los empleados. Nunca se modifican ni eliminan';
+
+CREATE TABLE IF NOT EXISTS horas_extra (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  usuario int(10) unsigned NOT NULL,
+  fecha date NOT NULL,
+  minutos int(10) unsigned NOT NULL,
+  causa tinyint(3) unsigned NOT NULL COMMENT '1 estructural, 2 fuerza mayor',
+  compensacion tinyint(3) unsigned NOT NULL COMMENT '1 retribuida, 2 descanso',
+  motivo varchar(250) DEFAULT NULL,
+  autor int(10) unsigned NOT NULL COMMENT 'Gestor que registra las horas',
+  fecha_registro datetime NOT NULL,
+  PRIMARY KEY (id),
+  KEY horas_extra_usuario_fecha_IDX (usuario, fecha),
+  KEY horas_extra_autor_FK (autor),
+  CONSTRAINT horas_extra_usuarios_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT horas_extra_autor_FK FOREIGN KEY (autor) REFERENCES usuarios (id) ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Registro de horas extraordinarias de los empleados';
+
+CREATE TABLE IF NOT EXISTS calendarios_personales (
+  usuario int(10) unsigned NOT NULL,
+  calendario int(10) unsigned NOT NULL,
+  PRIMARY KEY (usuario),
+  UNIQUE KEY calendarios_personales_calendario_UN (calendario),
+  CONSTRAINT calendarios_personales_usuario_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT calendarios_personales_calendario_FK FOREIGN KEY (calendario) REFERENCES calendarios (id) ON UPDATE CASCADE
+) COMMENT='Calendario personal de cada usuario con sus ausencias aprobadas';
+
+CREATE TABLE IF NOT EXISTS solicitudes_ausencia (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  usuario int(10) unsigned NOT NULL,
+  tipo smallint(5) unsigned NOT NULL COMMENT '1 vacaciones, 2 días propios, 3 permiso',
+  fecha_inicio date NOT NULL,
+  fecha_fin date NOT NULL,
+  motivo varchar(250) DEFAULT NULL,
+  estado tinyint(3) unsigned NOT NULL COMMENT '1 solicitud, 2 aprobada, 3 rechazada, 4 cancelada',
+  fecha_solicitud datetime NOT NULL,
+  usuario_gestor int(10) unsigned DEFAULT NULL,
+  fecha_estado datetime DEFAULT NULL,
+  motivo_rechazo varchar(250) DEFAULT NULL,
+  calendario_fecha int(10) unsigned DEFAULT NULL COMMENT 'Fecha creada al aprobar la solicitud',
+  PRIMARY KEY (id),
+  KEY solicitudes_ausencia_usuario_IDX (usuario, fecha_inicio),
+  KEY solicitudes_ausencia_estado_IDX (estado),
+  KEY solicitudes_ausencia_gestor_FK (usuario_gestor),
+  KEY solicitudes_ausencia_calendario_fecha_FK (calendario_fecha),
+  CONSTRAINT solicitudes_ausencia_usuarios_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT solicitudes_ausencia_gestor_FK FOREIGN KEY (usuario_gestor) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT solicitudes_ausencia_calendario_fecha_FK FOREIGN KEY (calendario_fecha) REFERENCES calendario_fechas (id) ON UPDATE CASCADE ON DELETE SET NULL
+) AUTO_INCREMENT=1 COMMENT='Solicitudes de vacaciones, días propios y permisos de los empleados';
+
+CREATE TABLE IF NOT EXISTS sesiones (
+  id char(36) NOT NULL,
+  usuario int(10) unsigned NOT NULL,
+  creada_en datetime NOT NULL,
+  caduca_en datetime NOT NULL,
+  PRIMARY KEY (id),
+  KEY sesiones_usuarios_FK (usuario),
+  CONSTRAINT sesiones_usuarios_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE ON DELETE CASCADE
+) COMMENT='Sesiones activas de los usuarios. Solo se usa si se persisten las sesiones';
+
+CREATE TABLE IF NOT EXISTS cadenas_hash (
+  tabla varchar(20) NOT NULL,
+  clave varchar(50) NOT NULL,
+  eslabones int(10) unsigned NOT NULL,
+  hash char(64) NOT NULL,
+  PRIMARY KEY (tabla, clave)
+) COMMENT='Último eslabón de cada cadena de hashes';
+
 CREATE TABLE  IF NOT EXISTS schema_info (
   id int(11) NOT NULL CHECK (id = 1),
   version_actual varchar(20) NOT NULL,
@@ -0,0 +1,311 @@
+USE @DB_NOMBRE;
+
+-- Password con hash
+
+-- Las password se almacenan con un hash PBKDF2 (pbkdf2-sha256$iteraciones$salt$hash)
+-- en lugar de encriptadas con el secreto. Las password existentes no se pueden
+-- convertir desde la base de datos, la aplicación las migra en el siguiente
+-- login correcto de cada usuario.
+ALTER TABLE usuarios MODIFY COLUMN password varchar(500) NOT NULL
+  COMMENT 'Hash PBKDF2 de la password. Si no tiene el prefijo pbkdf2-sha256 está pendiente de migrar';
+
+-- Bloqueo de accesos por intentos fallidos de login
+
+ALTER TABLE usuarios
+  ADD COLUMN intentos_fallidos int(10) unsigned NOT NULL DEFAULT 0 COMMENT 'Intentos fallidos de login consecutivos',
+  ADD COLUMN ultimo_fallo datetime DEFAULT NULL COMMENT 'Fecha del último intento fallido de login',
+  ADD COLUMN bloqueado_hasta datetime DEFAULT NULL COMMENT 'Fecha hasta la que el acceso está bloqueado';
+
+-- Sesiones activas
+
+CREATE TABLE IF NOT EXISTS sesiones (
+  id char(36) NOT NULL,
+  usuario int(10) unsigned NOT NULL,
+  creada_en datetime NOT NULL,
+  caduca_en datetime NOT NULL,
+  PRIMARY KEY (id),
+  KEY sesiones_usuarios_FK (usuario),
+  CONSTRAINT sesiones_usuarios_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE ON DELETE CASCADE
+) COMMENT='Sesiones activas de los usuarios. Solo se usa si se persisten las sesiones';
+
+-- Consultas de la inspección de trabajo
+
+-- Las consultas se registran en trazas con la entidad 3 (Inspeccion) y el
+-- empleado consultado como entidad_id. El índice agiliza las consultas de
+-- trazas por entidad.
+ALTER TABLE trazas
+  ADD KEY trazas_entidad_IDX (entidad, entidad_id, fecha);
+
+-- Cadenas de hashes de los registros
+
+-- Cada fila de marcajes, incidencias y trazas se encadena con la anterior de
+-- la misma clave (usuario o entidad) mediante un HMAC. Las filas existentes
+-- quedan sin sellar y no se pueden verificar.
+ALTER TABLE marcajes
+  ADD COLUMN eslabon int(10) unsigned DEFAULT NULL COMMENT 'Posición en la cadena de hashes del usuario',
+  ADD COLUMN hash char(64) DEFAULT NULL COMMENT 'HMAC del hash anterior y los datos inmutables',
+  ADD COLUMN sello char(64) DEFAULT NULL COMMENT 'HMAC del hash y los datos modificables';
+
+ALTER TABLE incidencias
+  ADD COLUMN eslabon int(10) unsigned DEFAULT NULL COMMENT 'Posición en la cadena de hashes del usuario',
+  ADD COLUMN hash char(64) DEFAULT NULL COMMENT 'HMAC del hash anterior y los datos inmutables',
+  ADD COLUMN sello char(64) DEFAULT NULL COMMENT 'HMAC del hash y los datos modificables';
+
+ALTER TABLE trazas
+  ADD COLUMN eslabon int(10) unsigned DEFAULT NULL COMMENT 'Posición en la cadena de hashes de la entidad',
+  ADD COLUMN hash char(64) DEFAULT NULL COMMENT 'HMAC del hash anterior y los datos',
+  ADD COLUMN sello char(64) DEFAULT NULL COMMENT 'HMAC del hash';
+
+CREATE TABLE IF NOT EXISTS cadenas_hash (
+  tabla varchar(20) NOT NULL,
+  clave varchar(50) NOT NULL,
+  eslabones int(10) unsigned NOT NULL,
+  hash char(64) NOT NULL,
+  PRIMARY KEY (tabla, clave)
+) COMMENT='Último eslabón de cada cadena de hashes';
+
+-- Historial de cambios de los marcajes
+
+-- Cada cambio en un marcaje se anota en la misma transacción con las horas
+-- anteriores y posteriores, el autor y el motivo. Los marcajes existentes no
+-- tienen historial previo.
+CREATE TABLE IF NOT EXISTS marcajes_historial (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  marcaje int(10) unsigned NOT NULL,
+  operacion smallint(5) unsigned NOT NULL COMMENT '1 creación, 2 finalización, 3 corrección, 4 eliminación',
+  fecha datetime NOT NULL,
+  autor int(10) unsigned DEFAULT NULL,
+  motivo varchar(500) DEFAULT NULL,
+  hora_inicio_anterior time DEFAULT NULL,
+  hora_fin_anterior time DEFAULT NULL,
+  hora_inicio time DEFAULT NULL,
+  hora_fin time DEFAULT NULL,
+  marcaje_nuevo int(10) unsigned DEFAULT NULL COMMENT 'Marcaje que sustituye a este en una corrección',
+  PRIMARY KEY (id),
+  KEY marcajes_historial_marcaje_IDX (marcaje, fecha),
+  KEY marcajes_historial_usuarios_FK (autor),
+  KEY marcajes_historial_marcajes_FK_1 (marcaje_nuevo),
+  CONSTRAINT marcajes_historial_marcajes_FK FOREIGN KEY (marcaje) REFERENCES marcajes (id) ON UPDATE CASCADE,
+  CONSTRAINT marcajes_historial_marcajes_FK_1 FOREIGN KEY (marcaje_nuevo) REFERENCES marcajes (id) ON UPDATE CASCADE,
+  CONSTRAINT marcajes_historial_usuarios_FK FOREIGN KEY (autor) REFERENCES usuarios (id) ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Cambios de los marcajes con los valores anteriores y posteriores';
+
+-- Franjas horarias
+
+-- Cada horario puede tener varias franjas con hora de inicio y fin
+-- (jornada partida). Los horarios sin franjas siguen usando las horas.
+CREATE TABLE IF NOT EXISTS horarios_franjas (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  horario int(10) unsigned NOT NULL,
+  hora_inicio time NOT NULL,
+  hora_fin time NOT NULL,
+  PRIMARY KEY (id),
+  KEY horarios_franjas_horario_IDX (horario, hora_inicio),
+  CONSTRAINT horarios_franjas_horarios_FK FOREIGN KEY (horario) REFERENCES horarios (id) ON DELETE CASCADE ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Tramos del día a trabajar de cada horario';
+
+-- Minutos a trabajar en los horarios
+
+-- Las horas enteras no permiten contratos de 7,5 horas o 6h45. Se guardan
+-- los minutos a trabajar en el día y se convierten las horas existentes.
+ALTER TABLE horarios
+  ADD COLUMN minutos SMALLINT UNSIGNED NOT NULL DEFAULT 0 COMMENT 'Minutos a trabajar en el día' AFTER horas;
+
+UPDATE horarios SET minutos = horas * 60;
+
+ALTER TABLE horarios DROP COLUMN horas;
+
+-- Pausas de los marcajes
+
+-- Las pausas se registran dentro de un marcaje abierto. Cada horario indica
+-- los minutos de pausa de cada tipo que se retribuyen al día; el resto de
+-- tiempo de pausa se descuenta de las horas trabajadas. Cada pausa se encadena
+-- con las anteriores del mismo marcaje en la cadena de hashes.
+ALTER TABLE horarios
+  ADD COLUMN pausa_comida_retribuida SMALLINT UNSIGNED NOT NULL DEFAULT 0 COMMENT 'Minutos de pausa para comer retribuidos al día',
+  ADD COLUMN pausa_personal_retribuida SMALLINT UNSIGNED NOT NULL DEFAULT 0 COMMENT 'Minutos de pausa personal retribuidos al día';
+
+CREATE TABLE IF NOT EXISTS pausas (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  marcaje int(10) unsigned NOT NULL,
+  tipo smallint(5) unsigned NOT NULL COMMENT '1 comida, 2 personal',
+  hora_inicio time NOT NULL,
+  hora_fin time DEFAULT NULL,
+  eslabon int(10) unsigned DEFAULT NULL COMMENT 'Posición en la cadena de hashes del marcaje',
+  hash char(64) DEFAULT NULL COMMENT 'HMAC del hash anterior y los datos inmutables',
+  sello char(64) DEFAULT NULL COMMENT 'HMAC del hash y los datos modificables',
+  PRIMARY KEY (id),
+  KEY pausas_marcaje_IDX (marcaje, hora_inicio),
+  CONSTRAINT pausas_marcajes_FK FOREIGN KEY (marcaje) REFERENCES marcajes (id) ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Pausas dentro de un marcaje abierto';
+
+-- Instantes UTC de los marcajes
+
+-- Las horas locales son ambiguas en los cambios de hora (la hora repetida
+-- de octubre) y no reflejan el tiempo real trabajado. Se guarda el instante
+-- UTC de la entrada y la salida. Los marcajes existentes se convierten más
+-- abajo, una vez creada la zona horaria de cada usuario.
+ALTER TABLE marcajes
+  ADD COLUMN inicio_utc datetime DEFAULT NULL COMMENT 'Instante UTC de la entrada' AFTER hora_fin,
+  ADD COLUMN fin_utc datetime DEFAULT NULL COMMENT 'Instante UTC de la salida' AFTER inicio_utc;
+
+-- Zona horaria por usuario
+
+-- Los empleados de otras zonas horarias (por ejemplo Atlantic/Canary)
+-- marcan y consultan sus fechas en su hora local. Si es NULL se usa la
+-- zona horaria de la configuración.
+ALTER TABLE usuarios
+  ADD COLUMN zona_horaria varchar(64) DEFAULT NULL COMMENT 'Zona horaria IANA del usuario. Si es NULL se usa la de la configuración' AFTER bloqueado_hasta;
+
+-- Conversión de los marcajes existentes a instantes UTC
+
+-- Cada marcaje se convierte con la zona horaria de su usuario o, si no tiene,
+-- con @zona_horaria. Si la configuración no usa Europe/Madrid, el operador
+-- debe asignar antes de ejecutar el script el valor de `zona_horaria` del
+-- config-api.json:
+--   SET @zona_horaria = 'Atlantic/Canary';
+-- Los usuarios de otras zonas se pueden convertir de nuevo tras asignarles su
+-- zona_horaria, poniendo a NULL sus instantes y repitiendo este UPDATE. Si el
+-- servidor no tiene cargadas las tablas de zonas horarias los instantes
+-- quedan a NULL y se usan las horas locales.
+SET @zona_horaria = IFNULL(@zona_horaria, 'Europe/Madrid');
+
+UPDATE marcajes m
+  JOIN usuarios u ON u.id = m.usuario
+  SET m.inicio_utc = CONVERT_TZ(TIMESTAMP(m.fecha, m.hora_inicio),
+      IFNULL(u.zona_horaria, @zona_horaria), '+00:00'),
+    m.fin_utc = CONVERT_TZ(
+      TIMESTAMP(IF(m.hora_fin < m.hora_inicio, m.fecha + INTERVAL 1 DAY, m.fecha), m.hora_fin),
+      IFNULL(u.zona_horaria, @zona_horaria), '+00:00')
+  WHERE m.inicio_utc IS NULL;
+
+-- Centros de trabajo
+
+-- Cada usuario puede pertenecer a un centro de trabajo. Los calendarios
+-- del centro (fiestas locales) se añaden a los usuarios al asignarlos.
+CREATE TABLE IF NOT EXISTS centros (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  nombre varchar(100) NOT NULL,
+  direccion varchar(250) NOT NULL DEFAULT '',
+  PRIMARY KEY (id),
+  UNIQUE KEY centros_nombre_unique (nombre)
+) AUTO_INCREMENT=1 COMMENT='Centros de trabajo de la compañia';
+
+CREATE TABLE IF NOT EXISTS centros_calendarios (
+  centro int(10) unsigned NOT NULL,
+  calendario int(10) unsigned NOT NULL,
+  PRIMARY KEY (centro, calendario),
+  KEY centros_calendarios_calendario_FK (calendario),
+  CONSTRAINT centros_calendarios_centro_FK FOREIGN KEY (centro) REFERENCES centros (id) ON UPDATE CASCADE,
+  CONSTRAINT centros_calendarios_calendario_FK FOREIGN KEY (calendario) REFERENCES calendarios (id) ON UPDATE CASCADE
+) COMMENT='Calendarios por defecto de los usuarios de un centro de trabajo';
+
+ALTER TABLE usuarios
+  ADD COLUMN centro int(10) unsigned DEFAULT NULL COMMENT 'Centro de trabajo del usuario' AFTER zona_horaria,
+  ADD KEY usuarios_centros_FK (centro),
+  ADD CONSTRAINT usuarios_centros_FK FOREIGN KEY (centro) REFERENCES centros (id) ON UPDATE CASCADE;
+
+-- Bolsa de horas
+
+-- Libro de movimientos en minutos. Cada mes se cierra una sola vez por
+-- usuario: la columna cierre solo tiene valor en los saldos mensuales.
+CREATE TABLE IF NOT EXISTS bolsa_horas (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  usuario int(10) unsigned NOT NULL,
+  fecha date NOT NULL COMMENT 'Fecha a la que se imputa el movimiento',
+  tipo tinyint(3) unsigned NOT NULL COMMENT '1 saldo mensual, 2 ajuste, 3 compensación, 4 caducidad, 5 tope',
+  minutos int(11) NOT NULL COMMENT 'Minutos a favor (positivo) o en contra (negativo) del empleado',
+  motivo varchar(250) DEFAULT NULL,
+  autor int(10) unsigned DEFAULT NULL COMMENT 'Usuario que registra el movimiento. NULL si lo registra el sistema',
+  fecha_registro datetime NOT NULL,
+  cierre date GENERATED ALWAYS AS (IF(tipo = 1, fecha, NULL)) VIRTUAL,
+  PRIMARY KEY (id),
+  KEY bolsa_horas_usuario_fecha_IDX (usuario, fecha),
+  UNIQUE KEY bolsa_horas_cierre_unique (usuario, cierre),
+  KEY bolsa_horas_autor_FK (autor),
+  CONSTRAINT bolsa_horas_usuarios_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT bolsa_horas_autor_FK FOREIGN KEY (autor) REFERENCES usuarios (id) ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Movimientos de la bolsa de horas de los empleados. Nunca se modifican ni eliminan';
+
+-- Horas extraordinarias
+
+-- Los gestores clasifican el exceso de jornada de cada día por causa
+-- y forma de compensación.
+CREATE TABLE IF NOT EXISTS horas_extra (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  usuario int(10) unsigned NOT NULL,
+  fecha date NOT NULL,
+  minutos int(10) unsigned NOT NULL,
+  causa tinyint(3) unsigned NOT NULL COMMENT '1 estructural, 2 fuerza mayor',
+  compensacion tinyint(3) unsigned NOT NULL COMMENT '1 retribuida, 2 descanso',
+  motivo varchar(250) DEFAULT NULL,
+  autor int(10) unsigned NOT NULL COMMENT 'Gestor que registra las horas',
+  fecha_registro datetime NOT NULL,
+  PRIMARY KEY (id),
+  KEY horas_extra_usuario_fecha_IDX (usuario, fecha),
+  KEY horas_extra_autor_FK (autor),
+  CONSTRAINT horas_extra_usuarios_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT horas_extra_autor_FK FOREIGN KEY (autor) REFERENCES usuarios (id) ON UPDATE CASCADE
+) AUTO_INCREMENT=1 COMMENT='Registro de horas extraordinarias de los empleados';
+
+-- Solicitudes de ausencia
+
+-- Los empleados solicitan vacaciones, días propios y permisos. Al aprobar
+-- una solicitud se crea la fecha en el calendario personal del empleado,
+-- que se crea y asigna la primera vez.
+CREATE TABLE IF NOT EXISTS calendarios_personales (
+  usuario int(10) unsigned NOT NULL,
+  calendario int(10) unsigned NOT NULL,
+  PRIMARY KEY (usuario),
+  UNIQUE KEY calendarios_personales_calendario_UN (calendario),
+  CONSTRAINT calendarios_personales_usuario_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT calendarios_personales_calendario_FK FOREIGN KEY (calendario) REFERENCES calendarios (id) ON UPDATE CASCADE
+) COMMENT='Calendario personal de cada usuario con sus ausencias aprobadas';
+
+CREATE TABLE IF NOT EXISTS solicitudes_ausencia (
+  id int(10) unsigned NOT NULL AUTO_INCREMENT,
+  usuario int(10) unsigned NOT NULL,
+  tipo smallint(5) unsigned NOT NULL COMMENT '1 vacaciones, 2 días propios, 3 permiso',
+  fecha_inicio date NOT NULL,
+  fecha_fin date NOT NULL,
+  motivo varchar(250) DEFAULT NULL,
+  estado tinyint(3) unsigned NOT NULL COMMENT '1 solicitud, 2 aprobada, 3 rechazada, 4 cancelada',
+  fecha_solicitud datetime NOT NULL,
+  usuario_gestor int(10) unsigned DEFAULT NULL,
+  fecha_estado datetime DEFAULT NULL,
+  motivo_rechazo varchar(250) DEFAULT NULL,
+  calendario_fecha int(10) unsigned DEFAULT NULL COMMENT 'Fecha creada al aprobar la solicitud',
+  PRIMARY KEY (id),
+  KEY solicitudes_ausencia_usuario_IDX (usuario, fecha_inicio),
+  KEY solicitudes_ausencia_estado_IDX (estado),
+  KEY solicitudes_ausencia_gestor_FK (usuario_gestor),
+  KEY solicitudes_ausencia_calendario_fecha_FK (calendario_fecha),
+  CONSTRAINT solicitudes_ausencia_usuarios_FK FOREIGN KEY (usuario) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT solicitudes_ausencia_gestor_FK FOREIGN KEY (usuario_gestor) REFERENCES usuarios (id) ON UPDATE CASCADE,
+  CONSTRAINT solicitudes_ausencia_calendario_fecha_FK FOREIGN KEY (calendario_fecha) REFERENCES calendario_fechas (id) ON UPDATE CASCADE ON DELETE SET NULL
+) AUTO_INCREMENT=1 COMMENT='Solicitudes de vacaciones, días propios y permisos de los empleados';
+
+-- Historial de pausas e instantes UTC
+
+-- El historial anota también el inicio y el fin de las pausas, indicando la
+-- pausa afectada, y guarda los instantes UTC anteriores y posteriores de cada
+-- cambio. Las filas existentes quedan con los instantes a NULL.
+ALTER TABLE marcajes_historial
+  MODIFY COLUMN operacion smallint(5) unsigned NOT NULL COMMENT '1 creación, 2 finalización, 3 corrección, 4 eliminación, 5 inicio pausa, 6 fin pausa',
+  ADD COLUMN inicio_utc_anterior datetime DEFAULT NULL AFTER hora_fin_anterior,
+  ADD COLUMN fin_utc_anterior datetime DEFAULT NULL AFTER inicio_utc_anterior,
+  ADD COLUMN inicio_utc datetime DEFAULT NULL AFTER hora_fin,
+  ADD COLUMN fin_utc datetime DEFAULT NULL AFTER inicio_utc,
+  ADD COLUMN pausa int(10) unsigned DEFAULT NULL COMMENT 'Pausa que se inicia o finaliza' AFTER marcaje_nuevo,
+  ADD KEY marcajes_historial_pausas_FK (pausa),
+  ADD CONSTRAINT marcajes_historial_pausas_FK FOREIGN KEY (pausa) REFERENCES pausas (id) ON UPDATE CASCADE;
+
+-- Incidencias creadas por el sistema
+
+-- Las incidencias que genera el cierre automático de marcajes olvidados no
+-- tienen creador. Las existentes conservan el empleado como creador.
+ALTER TABLE incidencias
+  MODIFY COLUMN usuario_creador int(10) unsigned DEFAULT NULL COMMENT 'Nulo si la crea el sistema';
+
+-- ACTUALIZACIÓN VERSIÓN
+UPDATE schema_info SET version_actual = '1.5.0' WHERE id = 1;
@@ -1,9 +1,10 @@
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
 use axum::{
   Extension, Router,
-  extract::{Json, Path, State},
-  http::StatusCode,
+  extract::{ConnectInfo, Json, Path, State},
+  http::{HeaderMap, StatusCode, header},
   response::IntoResponse,
   routing::{delete, get, post, put},
 };
@@ -14,17 +15,30 @@ use crate::{
   app::{
     AppState,
     dto::{
-      CalendarioDTO, CalendarioFechaDTO, ConfigHorarioDTO,
-      DescriptorUsuarioDTO, DominiosWithCacheUsuarioDTO, HorarioDTO,
-      IncidenciaDTO, IncidenciaInProcesoDTO, IncidenciaOutProcesoDTO,
-      IncidenciaSolictudDTO, IncidenciasFiltroParams, InformeCumplimientoDTO,
-      MarcajeInDTO, MarcajeOutDTO, PasswordDniDTO, PasswordUsuarioDTO,
-      UsuarioBodyDTO, UsuarioOutDTO, vec_dominio_to_dtos,
+      CalendarioDTO, CalendarioFechaDTO, CentroDTO, CierreBolsaDTO,
+      ConfigHorarioDTO, DescriptorUsuarioDTO, DominiosWithCacheUsuarioDTO,
+      HistorialMarcajeDTO, HoraExtraInDTO, HorarioDTO, IncidenciaDTO,
+      IncidenciaInProcesoDTO, IncidenciaOutProcesoDTO, IncidenciaSolictudDTO,
+      IncidenciasFiltroParams, InformeCumplimientoAnualDTO,
+      InformeCumplimientoDTO, InformeEquipoDTO, InformeIntegridadDTO,
+      InformePuntualidadDTO, MarcajeInDTO, MarcajeOutDTO, MovimientoBolsaDTO,
+      MovimientoBolsaInDTO, PaginaTrazasDTO, PasswordDniDTO,
+      PasswordUsuarioDTO, PausaDTO, RechazoAusenciaDTO, RegistroHoraExtraDTO,
+      ResumenAnualBolsaDTO, ResumenHorasExtraDTO, SesionDTO,
+      SolicitudAusenciaDTO, SolicitudAusenciaInDTO, UsuarioBodyDTO,
+      UsuarioOutDTO, VerificacionFirmaDTO, vec_dominio_to_dtos,
     },
   },
-  inc::{EstadoIncidencia, IncidenciaProceso},
-  infra::{Dni, Password},
-  usuarios::Rol,
+  ausencias::EstadoAusencia,
+  inc::{EstadoIncidencia, Incidencia, IncidenciaProceso, IncidenciaSolictud},
+  informes::{Documento, EmpleadoRegistro, FormatoExportacion},
+  infra::{
+    Dni, Password, ServicioError,
+    middleware::{DatosSesion, permitir},
+  },
+  marcaje::{Marcaje, TipoPausa},
+  traza::{Entidad, FiltroTrazas, TipoTraza},
+  usuarios::{Rol, Usuario},
 };
 
 #[derive(Deserialize)]
@@ -35,12 +49,107 @@ pub struct FiltroParams {
   pub usuario_reg: Option<u32>,
 }
 
+/// Si se indica el centro, el empleado debe pertenecer a él.
 #[derive(Deserialize)]
 pub struct InformeCumplimientoParams {
   #[serde(rename = "empleadoId")]
   empleado_id: u32,
   mes: u32,
   anio: i32,
+  centro: Option<u32>,
+}
+
+/// Si se indica el centro, el empleado debe pertenecer a él.
+#[derive(Deserialize)]
+struct InformeAnualParams {
+  #[serde(rename = "empleadoId")]
+  empleado_id: u32,
+  anio: i32,
+  centro: Option<u32>,
+}
+
+/// Empleados del informe de un equipo.
+///
+/// Si se indican los usuarios, separados por comas, se ignoran el rol
+/// y el centro. Por defecto se incluyen todos los empleados.
+#[derive(Deserialize)]
+struct InformeEquipoParams {
+  mes: u32,
+  anio: i32,
+  rol: Option<u8>,
+  centro: Option<u32>,
+  usuarios: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum FormatoParam {
+  Csv,
+  Pdf,
+}
+
+impl From<FormatoParam> for FormatoExportacion {
+  fn from(formato: FormatoParam) -> Self {
+    match formato {
+      FormatoParam::Csv => FormatoExportacion::Csv,
+      FormatoParam::Pdf => FormatoExportacion::Pdf,
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct RegistroJornadaParams {
+  #[serde(rename = "empleadoId")]
+  empleado_id: u32,
+  mes: u32,
+  anio: i32,
+  formato: FormatoParam,
+}
+
+#[derive(Deserialize)]
+struct RegistrosJornadaParams {
+  mes: u32,
+  anio: i32,
+  formato: FormatoParam,
+  centro: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct MesParams {
+  mes: u32,
+  anio: i32,
+}
+
+#[derive(Deserialize)]
+struct MesFormatoParams {
+  mes: u32,
+  anio: i32,
+  formato: FormatoParam,
+}
+
+#[derive(Deserialize)]
+struct FirmaParams {
+  firma: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnioParams {
+  anio: i32,
+}
+
+/// Si no se indica el usuario se devuelven las de todos los
+/// usuarios, solo para los gestores. Permite filtrar por centro
+/// de trabajo.
+#[derive(Deserialize)]
+struct AusenciasParams {
+  usuario: Option<u32>,
+  estado: Option<u8>,
+  centro: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct CentroParams {
+  centro: Option<u32>,
 }
 
 #[derive(Deserialize)]
@@ -49,6 +158,13 @@ struct UsuarioFechaParams {
   fecha: NaiveDateTime,
 }
 
+#[derive(Deserialize)]
+struct UsuarioFechaTipoParams {
+  id: u32,
+  fecha: NaiveDateTime,
+  tipo: u8,
+}
+
 #[derive(Deserialize)]
 struct UsuarioFechaRegParams {
   id: u32,
@@ -61,92 +177,376 @@ pub struct UsuarioParams {
   todos_los_calendarios: bool,
 }
 
+#[derive(Deserialize)]
+struct TrazasFiltroParams {
+  entidad: Option<u8>,
+  entidad_id: Option<u32>,
+  autor: Option<u32>,
+  tipo: Option<u8>,
+  fecha_inicio: Option<NaiveDate>,
+  fecha_fin: Option<NaiveDate>,
+  #[serde(default)]
+  pagina: u32,
+}
+
+#[derive(Deserialize)]
+pub(in crate::app) struct PaginaParams {
+  #[serde(default)]
+  pub pagina: u32,
+}
+
 #[derive(Deserialize)]
 pub struct CalendarioFechaFiltroParams {
   pub fecha_inicio: Option<NaiveDate>,
   pub fecha_fin: Option<NaiveDate>,
 }
 
+// Roles permitidos por ruta. Si se cambia algún rol se debe revisar
+// las rutas de acceso del web/src/modelos/usuarios.ts
+const TODOS: &[Rol] = &[
+  Rol::Empleado,
+  Rol::Gestor,
+  Rol::Admin,
+  Rol::Director,
+  Rol::Registrador,
+  Rol::Inspector,
+  Rol::Supervidor,
+];
+const ADMIN: &[Rol] = &[Rol::Admin];
+const MARCAJE: &[Rol] = &[Rol::Empleado, Rol::Registrador];
+const CONSULTA_MARCAJES: &[Rol] = &[
+  Rol::Empleado,
+  Rol::Registrador,
+  Rol::Supervidor,
+  Rol::Director,
+  Rol::Inspector,
+];
+const SELECCION_EMPLEADOS: &[Rol] = &[
+  Rol::Admin,
+  Rol::Registrador,
+  Rol::Supervidor,
+  Rol::Director,
+  Rol::Inspector,
+];
+// Pueden consultar los marcajes y horarios de cualquier empleado
+const CONSULTA_MARCAJES_EMPLEADOS: &[Rol] = &[
+  Rol::Registrador,
+  Rol::Supervidor,
+  Rol::Director,
+  Rol::Inspector,
+];
+// Pueden consultar el perfil de cualquier usuario
+const CONSULTA_USUARIOS: &[Rol] = &[
+  Rol::Admin,
+  Rol::Gestor,
+  Rol::Registrador,
+  Rol::Supervidor,
+  Rol::Director,
+  Rol::Inspector,
+];
+const SOLICITUD_INC: &[Rol] =
+  &[Rol::Empleado, Rol::Registrador, Rol::Supervidor];
+const GESTION_INC: &[Rol] = &[Rol::Gestor, Rol::Supervidor];
+const CONSULTA_INC: &[Rol] = &[
+  Rol::Empleado,
+  Rol::Registrador,
+  Rol::Gestor,
+  Rol::Supervidor,
+];
+// Pueden consultar las incidencias de cualquier empleado
+const CONSULTA_INC_EMPLEADOS: &[Rol] =
+  &[Rol::Registrador, Rol::Gestor, Rol::Supervidor];
+const INFORMES: &[Rol] = &[
+  Rol::Empleado,
+  Rol::Supervidor,
+  Rol::Director,
+  Rol::Inspector,
+];
+// Pueden consultar los informes de cualquier empleado
+const INFORMES_EMPLEADOS: &[Rol] =
+  &[Rol::Supervidor, Rol::Director, Rol::Inspector];
+const PUNTUALIDAD: &[Rol] = &[Rol::Gestor];
+const DIRECCION: &[Rol] = &[Rol::Director];
+// Los empleados solo pueden consultar su propia bolsa de horas
+const CONSULTA_BOLSA: &[Rol] = &[
+  Rol::Empleado,
+  Rol::Gestor,
+  Rol::Supervidor,
+  Rol::Director,
+  Rol::Inspector,
+];
+const GESTION_BOLSA: &[Rol] = &[Rol::Gestor, Rol::Supervidor];
+// Los empleados solo pueden consultar sus propias horas extraordinarias
+const CONSULTA_HORAS_EXTRA: &[Rol] = &[
+  Rol::Empleado,
+  Rol::Gestor,
+  Rol::Supervidor,
+  Rol::Director,
+  Rol::Inspector,
+];
+const GESTION_HORAS_EXTRA: &[Rol] = &[Rol::Gestor, Rol::Supervidor];
+// Los empleados solo pueden consultar sus propias solicitudes de ausencia
+const SOLICITUD_AUSENCIAS: &[Rol] = &[Rol::Empleado];
+const CONSULTA_AUSENCIAS: &[Rol] =
+  &[Rol::Empleado, Rol::Gestor, Rol::Supervidor];
+const GESTION_AUSENCIAS: &[Rol] = &[Rol::Gestor, Rol::Supervidor];
+const HISTORIAL_MARCAJES: &[Rol] = &[
+  Rol::Empleado,
+  Rol::Gestor,
+  Rol::Supervidor,
+  Rol::Director,
+  Rol::Admin,
+];
+const INTEGRIDAD: &[Rol] = &[Rol::Admin, Rol::Inspector];
+// La inspección consulta las trazas desde sus propias rutas
+// para que cada consulta quede registrada.
+const TRAZAS: &[Rol] = &[Rol::Admin, Rol::Director];
+
 /// Define las rutas de la aplicación.
 ///
 /// Recibe el código de la aplicación para el tenant.
+///
+/// Cada ruta privada indica los roles que pueden acceder a ella.
+/// Si el usuario de la sesión no tiene ninguno se rechaza la petición.
 pub fn rutas(cod_app: &str, app: Arc<AppState>) -> Router {
   // Rutas públicas (sin autenticación)
   let rutas_auth = Router::new().route("/usuarios/login", post(login));
 
   // Rutas seguras (con autenticación)
   let rutas_privadas = Router::new()
-    .route("/usuarios/{id}/logout", get(logout))
-    .route("/usuarios", post(crear_usuario))
-    .route("/usuarios", put(actualizar_usuario))
-    .route("/usuarios/password", put(actualizar_passw_usuario))
+    .route("/usuarios/{id}/logout", permitir(TODOS, get(logout)))
+    .route("/usuarios", permitir(ADMIN, post(crear_usuario)))
+    .route("/usuarios", permitir(ADMIN, put(actualizar_usuario)))
+    .route(
+      "/usuarios/password",
+      permitir(TODOS, put(actualizar_passw_usuario)),
+    )
     .route(
       "/usuarios/{id}/finalizar/marcaje/{fecha}",
-      put(marcaje_finalizar),
+      permitir(MARCAJE, put(marcaje_finalizar)),
+    )
+    .route(
+      "/usuarios/{id}/iniciar/pausa/{fecha}/{tipo}",
+      permitir(MARCAJE, put(pausa_iniciar)),
+    )
+    .route(
+      "/usuarios/{id}/finalizar/pausa/{fecha}",
+      permitir(MARCAJE, put(pausa_finalizar)),
+    )
+    .route("/usuarios", permitir(ADMIN, get(usuarios)))
+    .route(
+      "/usuarios/{id}/desbloquear",
+      permitir(ADMIN, put(desbloquear_usuario)),
+    )
+    .route(
+      "/usuarios/{id}/sesiones",
+      permitir(ADMIN, get(sesiones_usuario)),
+    )
+    .route(
+      "/usuarios/{id}/sesiones",
+      permitir(ADMIN, delete(revocar_sesiones_usuario)),
     )
-    .route("/usuarios", get(usuarios))
-    .route("/usuarios/{id}", get(usuario))
+    .route("/sesiones/{id}", permitir(ADMIN, delete(revocar_sesion)))
+    .route("/usuarios/{id}", permitir(TODOS, get(usuario)))
     .route(
       "/usuarios/{id}/marcajes/por/fecha/{fecha}",
-      get(marcaje_por_fecha),
+      permitir(CONSULTA_MARCAJES, get(marcaje_por_fecha)),
+    )
+    .route(
+      "/usuarios/{id}/ultimos_marcajes",
+      permitir(CONSULTA_MARCAJES, get(ultimos_marcajes)),
     )
-    .route("/usuarios/{id}/ultimos_marcajes", get(ultimos_marcajes))
     .route(
       "/usuarios/{id}/horario/cercano/{fecha}",
-      get(horario_cercano),
+      permitir(CONSULTA_MARCAJES, get(horario_cercano)),
     )
     .route(
       "/usuarios/{id}/marcajes/sin/inc/{fecha}",
-      get(marcaje_sin_inc_por_fecha),
+      permitir(SOLICITUD_INC, get(marcaje_sin_inc_por_fecha)),
     )
     .route(
       "/usuarios/{id}/marcajes/sin/inc/{fecha}/registrador/{usuario_reg}",
-      get(marcaje_sin_inc_por_fecha_reg),
+      permitir(SOLICITUD_INC, get(marcaje_sin_inc_por_fecha_reg)),
     )
     .route(
       "/usuarios/{id}/marcajes/fecha/{fecha}/sin/finalizar",
-      get(marcaje_sin_finalizar),
+      permitir(MARCAJE, get(marcaje_sin_finalizar)),
+    )
+    .route(
+      "/usuarios/{id}/horarios",
+      permitir(ADMIN, get(config_horarios)),
     )
-    .route("/usuarios/{id}/horarios", get(config_horarios))
     .route(
       "/usuarios/{id}/horarios/duplicar/{fecha}",
-      post(duplicar_config_horario),
+      permitir(ADMIN, post(duplicar_config_horario)),
+    )
+    .route("/horarios/{id}", permitir(ADMIN, get(horario)))
+    .route("/horarios", permitir(ADMIN, post(crear_horario)))
+    .route("/horarios", permitir(ADMIN, put(modificar_config_horario)))
+    .route(
+      "/horarios/{id}",
+      permitir(ADMIN, delete(eliminar_config_horario)),
+    )
+    .route(
+      "/roles/{id}/usuarios",
+      permitir(SELECCION_EMPLEADOS, get(usuarios_por_rol)),
+    )
+    .route("/marcajes", permitir(MARCAJE, post(registrar)))
+    .route(
+      "/marcajes/{id}/historial",
+      permitir(HISTORIAL_MARCAJES, get(historial_marcaje)),
+    )
+    .route(
+      "/marcajes/{id}/pausas",
+      permitir(HISTORIAL_MARCAJES, get(pausas_marcaje)),
+    )
+    .route(
+      "/marcajes/entre/fechas",
+      permitir(CONSULTA_MARCAJES, post(marcajes_entre_fechas)),
+    )
+    .route(
+      "/incidencias",
+      permitir(SOLICITUD_INC, post(crear_incidencia)),
     )
-    .route("/horarios/{id}", get(horario))
-    .route("/horarios", post(crear_horario))
-    .route("/horarios", put(modificar_config_horario))
-    .route("/horarios/{id}", delete(eliminar_config_horario))
-    .route("/roles/{id}/usuarios", get(usuarios_por_rol))
-    .route("/marcajes", post(registrar))
-    .route("/marcajes/entre/fechas", post(marcajes_entre_fechas))
-    .route("/incidencias", post(crear_incidencia))
     .route(
       "/incidencias/cambiar/a/solicitud",
-      put(cambiar_incidencia_solicitud),
+      permitir(SOLICITUD_INC, put(cambiar_incidencia_solicitud)),
     )
     .route(
       "/incidencias/cambiar/a/cancelada",
-      put(cambiar_incidencia_cancelada),
-    )
-    .route("/incidencias/procesar", post(procesar_incidencias))
-    .route("/incidencias/por/fechas", post(incidencias_por_fechas))
-    .route("/calendarios", get(calendarios))
-    .route("/calendarios/{id}", get(calendario))
-    .route("/calendarios", post(crear_calendario))
-    .route("/calendarios", put(actualizar_calendario))
-    .route("/calendarios/{id}", delete(eliminar_calendario))
-    .route("/calendarios/{id}/fechas", get(fechas_calendario))
-    .route("/calendarios/fechas/{id}", get(fecha_calendario))
-    .route("/calendarios/fechas", post(crear_fecha_calendario))
-    .route("/calendarios/fechas", put(actualizar_fecha_calendario))
+      permitir(SOLICITUD_INC, put(cambiar_incidencia_cancelada)),
+    )
+    .route(
+      "/incidencias/procesar",
+      permitir(GESTION_INC, post(procesar_incidencias)),
+    )
+    .route(
+      "/incidencias/por/fechas",
+      permitir(CONSULTA_INC, post(incidencias_por_fechas)),
+    )
+    .route("/calendarios", permitir(ADMIN, get(calendarios)))
+    .route("/calendarios/{id}", permitir(ADMIN, get(calendario)))
+    .route("/calendarios", permitir(ADMIN, post(crear_calendario)))
+    .route("/calendarios", permitir(ADMIN, put(actualizar_calendario)))
+    .route(
+      "/calendarios/{id}",
+      permitir(ADMIN, delete(eliminar_calendario)),
+    )
+    .route(
+      "/calendarios/{id}/fechas",
+      permitir(ADMIN, get(fechas_calendario)),
+    )
     .route(
       "/calendarios/fechas/{id}",
-      delete(eliminar_fecha_calendario),
+      permitir(ADMIN, get(fecha_calendario)),
     )
+    .route(
+      "/calendarios/fechas",
+      permitir(ADMIN, post(crear_fecha_calendario)),
+    )
+    .route(
+      "/calendarios/fechas",
+      permitir(ADMIN, put(actualizar_fecha_calendario)),
+    )
+    .route(
+      "/calendarios/fechas/{id}",
+      permitir(ADMIN, delete(eliminar_fecha_calendario)),
+    )
+    .route("/centros", permitir(SELECCION_EMPLEADOS, get(centros)))
+    .route("/centros/{id}", permitir(ADMIN, get(centro)))
+    .route("/centros", permitir(ADMIN, post(crear_centro)))
+    .route("/centros", permitir(ADMIN, put(actualizar_centro)))
+    .route("/centros/{id}", permitir(ADMIN, delete(eliminar_centro)))
     .route(
       "/informes/cumplimiento/horario",
-      get(informe_cumplimiento_horario),
+      permitir(INFORMES, get(informe_cumplimiento_horario)),
+    )
+    .route(
+      "/informes/cumplimiento/anual",
+      permitir(INFORMES, get(informe_cumplimiento_anual)),
+    )
+    .route(
+      "/informes/cumplimiento/equipo",
+      permitir(DIRECCION, get(informe_cumplimiento_equipo)),
+    )
+    .route(
+      "/informes/puntualidad",
+      permitir(PUNTUALIDAD, get(informe_puntualidad)),
+    )
+    .route(
+      "/informes/registro/jornada",
+      permitir(INFORMES, get(registro_jornada)),
+    )
+    .route(
+      "/informes/firma/verificar",
+      permitir(INFORMES, post(verificar_firma)),
+    )
+    .route(
+      "/informes/registro/jornada/todos",
+      permitir(INFORMES_EMPLEADOS, get(registros_jornada)),
+    )
+    .route(
+      "/bolsa/usuarios/{id}",
+      permitir(CONSULTA_BOLSA, get(bolsa_horas)),
+    )
+    .route(
+      "/bolsa/usuarios/{id}/cierre",
+      permitir(GESTION_BOLSA, post(cerrar_mes_bolsa)),
+    )
+    .route(
+      "/bolsa/usuarios/{id}/ajustes",
+      permitir(GESTION_BOLSA, post(ajustar_bolsa)),
+    )
+    .route(
+      "/bolsa/usuarios/{id}/compensaciones",
+      permitir(GESTION_BOLSA, post(compensar_bolsa)),
+    )
+    .route(
+      "/extras/usuarios/{id}",
+      permitir(CONSULTA_HORAS_EXTRA, get(horas_extra)),
+    )
+    .route(
+      "/extras/usuarios/{id}",
+      permitir(GESTION_HORAS_EXTRA, post(registrar_horas_extra)),
+    )
+    .route(
+      "/extras/usuarios/{id}/exportar",
+      permitir(CONSULTA_HORAS_EXTRA, get(exportar_horas_extra)),
+    )
+    .route(
+      "/ausencias",
+      permitir(CONSULTA_AUSENCIAS, get(solicitudes_ausencia)),
+    )
+    .route(
+      "/ausencias",
+      permitir(SOLICITUD_AUSENCIAS, post(solicitar_ausencia)),
+    )
+    .route(
+      "/ausencias/{id}/aprobar",
+      permitir(GESTION_AUSENCIAS, post(aprobar_ausencia)),
+    )
+    .route(
+      "/ausencias/{id}/rechazar",
+      permitir(GESTION_AUSENCIAS, post(rechazar_ausencia)),
+    )
+    .route(
+      "/ausencias/{id}/cancelar",
+      permitir(SOLICITUD_AUSENCIAS, post(cancelar_ausencia)),
+    )
+    .route("/trazas", permitir(TRAZAS, get(trazas)))
+    .route(
+      "/trazas/usuarios/{id}",
+      permitir(TRAZAS, get(historial_usuario)),
+    )
+    .route(
+      "/trazas/incidencias/{id}",
+      permitir(TRAZAS, get(historial_incidencia)),
+    )
+    .route(
+      "/integridad/verificar",
+      permitir(INTEGRIDAD, get(verificar_integridad)),
     )
+    .nest("/inspeccion", super::inspeccion::rutas())
     .layer(axum::middleware::from_fn(
       crate::infra::middleware::autenticacion,
     ));
@@ -161,25 +561,68 @@ pub fn rutas(cod_app: &str, app: Arc<AppState>) -> Router {
     .nest(format!("{}/auth", ruta_app).as_str(), rutas_auth)
     .nest(format!("{}/api", ruta_app).as_str(), rutas_privadas)
     .layer(Extension(app.manejador_sesion.clone()))
+    .layer(Extension(app.sesion_servicio.clone()))
     .with_state(app)
 }
 
 /// Api para des-logear al usuario
 ///
-/// Elimina la cookie del cliente.
+/// Revoca la sesión y elimina la cookie del cliente.
+/// Si no se puede eliminar la sesión persistida devuelve un estado:
+/// INTERNAL_SERVER_ERROR.
 async fn logout(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Path(id): Path<u32>,
 ) -> impl IntoResponse {
   tracing::info!("Logout para el usuario: {}", id);
 
-  (
-    [(
-      axum::http::header::SET_COOKIE,
-      state.manejador_sesion.eliminar_sesion().to_string(),
-    )],
-    StatusCode::NO_CONTENT,
-  )
+  let cookie = [(
+    axum::http::header::SET_COOKIE,
+    state.manejador_sesion.eliminar_sesion().to_string(),
+  )];
+
+  // Aunque falle la persistencia, la sesión ya está revocada en memoria
+  // y se elimina la cookie del cliente
+  match state.sesion_servicio.revocar(&sesion.id).await {
+    Ok(()) => (cookie, StatusCode::NO_CONTENT).into_response(),
+    Err(err) => (
+      StatusCode::INTERNAL_SERVER_ERROR,
+      cookie,
+      err.mensaje_usuario(),
+    )
+      .into_response(),
+  }
+}
+
+/// Obtiene la IP del cliente.
+///
+/// Las cabeceras que añade el proxy inverso solo se tienen en cuenta
+/// si la conexión llega de un proxy de confianza. En otro caso
+/// cualquier cliente podría falsear su IP.
+/// De `x-forwarded-for` se toma la última IP que no sea de un proxy
+/// de confianza, ya que las anteriores las puede añadir el cliente.
+fn ip_cliente(
+  cabeceras: &HeaderMap,
+  conexion: SocketAddr,
+  proxies: &[IpAddr],
+) -> IpAddr {
+  if !proxies.contains(&conexion.ip()) {
+    return conexion.ip();
+  }
+
+  let cabecera =
+    |nombre| cabeceras.get(nombre).and_then(|valor| valor.to_str().ok());
+
+  cabecera("x-real-ip")
+    .and_then(|ip| ip.trim().parse().ok())
+    .or_else(|| {
+      cabecera("x-forwarded-for")?
+        .rsplit(',')
+        .map_while(|ip| ip.trim().parse::<IpAddr>().ok())
+        .find(|ip| !proxies.contains(ip))
+    })
+    .unwrap_or_else(|| conexion.ip())
 }
 
 /// Api para logear el usuario
@@ -187,35 +630,54 @@ async fn logout(
 /// Verifica que la clave sea correcta y si es correcto envía
 /// la información del usuario y la cookie de sesión.
 /// En caso contrario devuelve un estado: UNAUTHORIZED.
+/// Si el acceso está bloqueado por exceso de intentos fallidos
+/// devuelve un estado: TOO_MANY_REQUESTS.
 async fn login(
   State(state): State<Arc<AppState>>,
+  ConnectInfo(conexion): ConnectInfo<SocketAddr>,
+  cabeceras: HeaderMap,
   Json(params): Json<PasswordDniDTO>,
 ) -> impl IntoResponse {
   let result = state
     .usuario_servicio
-    .login_usuario(&Dni::new(params.dni), &Password::new(params.password))
+    .login_usuario(
+      &Dni::new(params.dni),
+      &Password::new(params.password),
+      Some(ip_cliente(&cabeceras, conexion, &state.proxies_confiables)),
+    )
     .await;
 
   match result {
     Ok(usuario) => {
       if let Some(usr) = usuario {
         // Crear token de sesión
-        let token_cookie = match state.manejador_sesion.crear_sesion() {
-          Ok(token) => token,
-          Err(err) => {
-            tracing::error!(
+        let token_cookie =
+          match state.manejador_sesion.crear_sesion(usr.id, &usr.roles) {
+            Ok((datos_sesion, token)) => {
+              if let Err(err) =
+                state.sesion_servicio.registrar(&datos_sesion).await
+              {
+                return Err((
+                  StatusCode::INTERNAL_SERVER_ERROR,
+                  err.mensaje_usuario(),
+                ));
+              }
+              token
+            }
+            Err(err) => {
+              tracing::error!(
               usuario = ?usr, error = ?err,
               "Error al crear sesión");
 
-            return Err((
-              StatusCode::INTERNAL_SERVER_ERROR,
-              "@@:Error al crear la sesión. \
+              return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "@@:Error al crear la sesión. \
               Intentelo de nuevo y si persiste el error \
               contacte con el administrador"
-                .to_string(),
-            ));
-          }
-        };
+                  .to_string(),
+              ));
+            }
+          };
 
         // Devolver respuesta con cookie y datos del usuario
         Ok((
@@ -229,6 +691,9 @@ async fn login(
         ))
       }
     }
+    Err(err @ ServicioError::AccesoBloqueado(_)) => {
+      Err((StatusCode::TOO_MANY_REQUESTS, err.mensaje_usuario()))
+    }
     Err(_) => Err((
       StatusCode::UNAUTHORIZED,
       "Usuario no autorizado".to_string(),
@@ -236,53 +701,174 @@ async fn login(
   }
 }
 
+/// Api para obtener las sesiones activas de un usuario
+async fn sesiones_usuario(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<u32>,
+) -> impl IntoResponse {
+  Json(vec_dominio_to_dtos::<_, SesionDTO>(
+    state.sesion_servicio.sesiones_usuario(id),
+  ))
+}
+
+/// Api para finalizar todas las sesiones activas de un usuario
+async fn revocar_sesiones_usuario(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<u32>,
+) -> impl IntoResponse {
+  state
+    .sesion_servicio
+    .revocar_usuario(id)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|_| StatusCode::NO_CONTENT)
+}
+
+/// Api para finalizar una sesión activa
+async fn revocar_sesion(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<String>,
+) -> impl IntoResponse {
+  state
+    .sesion_servicio
+    .revocar(&id)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|_| StatusCode::NO_CONTENT)
+}
+
+/// Api para desbloquear un usuario bloqueado por intentos fallidos
+///
+/// El autor del desbloqueo es el usuario de la sesión.
+async fn desbloquear_usuario(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+) -> impl IntoResponse {
+  state
+    .usuario_servicio
+    .desbloquear_usuario(sesion.usuario, id)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|_| StatusCode::NO_CONTENT)
+}
+
 /// Api para crear un nuevo usuario
+///
+/// El autor de la creación es el usuario de la sesión.
 async fn crear_usuario(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Json(usuario): Json<UsuarioBodyDTO>,
 ) -> impl IntoResponse {
   state
     .usuario_servicio
-    .crear_usuario(usuario.autor, &usuario.into())
+    .crear_usuario(sesion.usuario, &usuario.into())
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
     .map(|id| (StatusCode::CREATED, Json(id)))
 }
 
 /// Api para actualizar un usuario existente
+///
+/// El autor de la modificación es el usuario de la sesión.
 async fn actualizar_usuario(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Json(usuario): Json<UsuarioBodyDTO>,
 ) -> impl IntoResponse {
   state
     .usuario_servicio
-    .actualizar_usuario(usuario.autor, &usuario.into())
+    .actualizar_usuario(sesion.usuario, &usuario.into())
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
     .map(|_| StatusCode::NO_CONTENT)
 }
 
 /// Api para actualizar la password de un usuario existente
+///
+/// Solo el propio usuario o un administrador pueden cambiarla.
 async fn actualizar_passw_usuario(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Json(passw): Json<PasswordUsuarioDTO>,
 ) -> impl IntoResponse {
+  if passw.id != sesion.usuario && !sesion.tiene_algun_rol(&[Rol::Admin]) {
+    tracing::warn!(
+      usuario = passw.id,
+      autor = sesion.usuario,
+      "Cambio de password denegado"
+    );
+    return Err((
+      StatusCode::FORBIDDEN,
+      "@@:No tiene permisos para realizar la operación".to_string(),
+    ));
+  }
+
   state
     .usuario_servicio
-    .actualizar_password(passw.id, &Password::new(passw.password))
+    .actualizar_password(
+      sesion.usuario,
+      passw.id,
+      &Password::new(passw.password),
+    )
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
     .map(|_| StatusCode::NO_CONTENT)
 }
 
 /// Api que finaliza un marcaje de un usuario en una fecha determinada
+///
+/// Solo el propio usuario o un registrador pueden finalizarlo.
 async fn marcaje_finalizar(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Path(param): Path<UsuarioFechaParams>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_marcaje(&sesion, param.id)?;
+
+  state
+    .marcaje_servicio
+    .finalizar_marcaje(param.id, param.fecha, sesion.usuario)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|_| StatusCode::NO_CONTENT)
+}
+
+/// Api que inicia una pausa en el marcaje sin finalizar de un usuario
+///
+/// Solo el propio usuario o un registrador pueden iniciarla.
+async fn pausa_iniciar(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(param): Path<UsuarioFechaTipoParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_marcaje(&sesion, param.id)?;
+
+  let tipo =
+    TipoPausa::from_u8(param.tipo).ok_or_else(|| param_no_valido("tipo"))?;
+
+  state
+    .marcaje_servicio
+    .iniciar_pausa(param.id, param.fecha, tipo, sesion.usuario)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|id| (StatusCode::CREATED, Json(id)))
+}
+
+/// Api que finaliza la pausa abierta del marcaje de un usuario
+///
+/// Solo el propio usuario o un registrador pueden finalizarla.
+async fn pausa_finalizar(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(param): Path<UsuarioFechaParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_marcaje(&sesion, param.id)?;
+
   state
     .marcaje_servicio
-    .finalizar_marcaje(param.id, param.fecha)
+    .finalizar_pausa(param.id, param.fecha, sesion.usuario)
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
     .map(|_| StatusCode::NO_CONTENT)
@@ -298,11 +884,17 @@ async fn usuarios(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     .map(|usrs| Json(vec_dominio_to_dtos::<_, UsuarioOutDTO>(usrs)))
 }
 
+/// Api para obtener un usuario con sus calendarios.
+///
+/// Los empleados solo pueden consultar su propio usuario.
 async fn usuario(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Path(id): Path<u32>,
   axum::extract::Query(params): axum::extract::Query<UsuarioParams>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_empleado(&sesion, id, CONSULTA_USUARIOS)?;
+
   state
     .usuario_servicio
     .usuario_con_calendarios(id, params.todos_los_calendarios)
@@ -312,10 +904,15 @@ async fn usuario(
 }
 
 /// Api para obtener los marcajes entre fechas para un usuario
+///
+/// Los empleados solo pueden consultar sus propios marcajes.
 async fn marcajes_entre_fechas(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Json(param): Json<FiltroParams>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_empleado(&sesion, param.usuario, CONSULTA_MARCAJES_EMPLEADOS)?;
+
   state
     .marcaje_servicio
     .marcajes_entre_fechas_reg(
@@ -329,30 +926,88 @@ async fn marcajes_entre_fechas(
     .map(|regs| Json(DominiosWithCacheUsuarioDTO::<MarcajeOutDTO>::from(regs)))
 }
 
-/// Api para obtener los marcajes sin incidencias por fecha
-async fn marcaje_sin_inc_por_fecha(
+/// Api para obtener todas las versiones de un marcaje
+///
+/// Los empleados solo pueden consultar el historial de sus marcajes.
+async fn historial_marcaje(
   State(state): State<Arc<AppState>>,
-  Path(param): Path<UsuarioFechaParams>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
 ) -> impl IntoResponse {
+  let usuario = (!sesion.tiene_algun_rol(&[
+    Rol::Gestor,
+    Rol::Supervidor,
+    Rol::Director,
+    Rol::Admin,
+  ]))
+  .then_some(sesion.usuario);
+
   state
     .marcaje_servicio
-    .marcajes_inc_por_fecha_reg(param.id, param.fecha.date(), None)
+    .historial(id, usuario)
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|regs| Json(DominiosWithCacheUsuarioDTO::<MarcajeOutDTO>::from(regs)))
+    .map(|h| Json(DominiosWithCacheUsuarioDTO::<HistorialMarcajeDTO>::from(h)))
 }
 
-/// Api para obtener el marcaje sin incidencias
-/// por fecha y marcaje creado por un usuario registrador
-async fn marcaje_sin_inc_por_fecha_reg(
+/// Api para obtener las pausas de un marcaje
+///
+/// Los empleados solo pueden consultar las pausas de sus marcajes.
+async fn pausas_marcaje(
   State(state): State<Arc<AppState>>,
-  Path(param): Path<UsuarioFechaRegParams>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
 ) -> impl IntoResponse {
+  let usuario = (!sesion.tiene_algun_rol(&[
+    Rol::Gestor,
+    Rol::Supervidor,
+    Rol::Director,
+    Rol::Admin,
+  ]))
+  .then_some(sesion.usuario);
+
   state
     .marcaje_servicio
-    .marcajes_inc_por_fecha_reg(
-      param.id,
-      param.fecha.date(),
+    .pausas(id, usuario)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|p| Json(p.into_iter().map(PausaDTO::from).collect::<Vec<_>>()))
+}
+
+/// Api para obtener los marcajes sin incidencias por fecha
+///
+/// Los empleados solo pueden consultar sus propios marcajes.
+async fn marcaje_sin_inc_por_fecha(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(param): Path<UsuarioFechaParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_empleado(&sesion, param.id, CONSULTA_MARCAJES_EMPLEADOS)?;
+
+  state
+    .marcaje_servicio
+    .marcajes_inc_por_fecha_reg(param.id, param.fecha.date(), None)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|regs| Json(DominiosWithCacheUsuarioDTO::<MarcajeOutDTO>::from(regs)))
+}
+
+/// Api para obtener el marcaje sin incidencias
+/// por fecha y marcaje creado por un usuario registrador
+///
+/// Los empleados solo pueden consultar sus propios marcajes.
+async fn marcaje_sin_inc_por_fecha_reg(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(param): Path<UsuarioFechaRegParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_empleado(&sesion, param.id, CONSULTA_MARCAJES_EMPLEADOS)?;
+
+  state
+    .marcaje_servicio
+    .marcajes_inc_por_fecha_reg(
+      param.id,
+      param.fecha.date(),
       Some(param.usuario_reg),
     )
     .await
@@ -369,7 +1024,7 @@ async fn marcaje_sin_finalizar(
 ) -> impl IntoResponse {
   state
     .marcaje_servicio
-    .hora_fin_vacia(param.id, param.fecha.date())
+    .hora_fin_vacia(param.id, param.fecha)
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
     .map(|vacia| {
@@ -382,10 +1037,15 @@ async fn marcaje_sin_finalizar(
 }
 
 /// Api para obtener el registro por usuario y fecha.
+///
+/// Los empleados solo pueden consultar sus propios marcajes.
 async fn marcaje_por_fecha(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Path(param): Path<UsuarioFechaParams>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_empleado(&sesion, param.id, CONSULTA_MARCAJES_EMPLEADOS)?;
+
   state
     .marcaje_servicio
     .marcaje_por_fecha(param.id, param.fecha.date())
@@ -395,10 +1055,15 @@ async fn marcaje_por_fecha(
 }
 
 /// Api para obtener los últimos marcajes horarios de un usuario.
+///
+/// Los empleados solo pueden consultar sus propios marcajes.
 async fn ultimos_marcajes(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Path(usuario): Path<u32>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_empleado(&sesion, usuario, CONSULTA_MARCAJES_EMPLEADOS)?;
+
   state
     .marcaje_servicio
     .ultimos_marcajes(usuario)
@@ -408,10 +1073,19 @@ async fn ultimos_marcajes(
 }
 
 /// Api para obtener el horario de un usuario más próximo
+///
+/// Los empleados solo pueden consultar su propio horario.
 async fn horario_cercano(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Path(params): Path<UsuarioFechaParams>,
 ) -> impl IntoResponse {
+  if let Err(err) =
+    permiso_empleado(&sesion, params.id, CONSULTA_MARCAJES_EMPLEADOS)
+  {
+    return err.into_response();
+  }
+
   match state
     .horario_servicio
     .horario_usuario_cercano(params.id, params.fecha.date())
@@ -441,168 +1115,924 @@ async fn config_horarios(
 /// Api para duplicar la configuración de horarios de un usuario.
 async fn duplicar_config_horario(
   State(state): State<Arc<AppState>>,
-  Path((id, fecha)): Path<(u32, NaiveDate)>,
+  Path((id, fecha)): Path<(u32, NaiveDate)>,
+) -> impl IntoResponse {
+  state
+    .horario_servicio
+    .duplicar_config_horario(id, fecha)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|configs| Json(vec_dominio_to_dtos::<_, ConfigHorarioDTO>(configs)))
+}
+
+/// Api para obtener el horario dado el id.
+async fn horario(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<u32>,
+) -> impl IntoResponse {
+  state
+    .horario_servicio
+    .config_horario_por_id(id)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|h| Json(ConfigHorarioDTO::from(h)))
+}
+
+/// Api para crear una nueva configuración de horario.
+async fn crear_horario(
+  State(state): State<Arc<AppState>>,
+  Json(config): Json<ConfigHorarioDTO>,
+) -> impl IntoResponse {
+  state
+    .horario_servicio
+    .agregar_config_horario(&config.into())
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|id| (StatusCode::CREATED, Json(id)))
+}
+
+/// Api para modificar una configuración de horario.
+async fn modificar_config_horario(
+  State(state): State<Arc<AppState>>,
+  Json(config): Json<ConfigHorarioDTO>,
+) -> impl IntoResponse {
+  state
+    .horario_servicio
+    .modificar_config_horario(&config.into())
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|_| StatusCode::NO_CONTENT)
+}
+
+/// Api para eliminar una configuración de horario.
+async fn eliminar_config_horario(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<u32>,
+) -> impl IntoResponse {
+  state
+    .horario_servicio
+    .eliminar_config_horario(id)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|_| StatusCode::NO_CONTENT)
+}
+
+/// Api para obtener los usuarios que tienen un rol específico.
+///
+/// Permite filtrar por centro de trabajo.
+async fn usuarios_por_rol(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<u8>,
+  axum::extract::Query(params): axum::extract::Query<CentroParams>,
+) -> impl IntoResponse {
+  state
+    .usuario_servicio
+    .usuarios_por_rol(Rol::from(id), params.centro)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|usrs| Json(vec_dominio_to_dtos::<_, DescriptorUsuarioDTO>(usrs)))
+}
+
+/// Api para crear un nuevo marcaje de empleado completo.
+///
+/// Si el marcaje es para otro usuario, el usuario de la sesión
+/// debe ser registrador y queda como usuario registrador.
+async fn registrar(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Json(reg): Json<MarcajeInDTO>,
+) -> impl IntoResponse {
+  let mut marcaje: Marcaje = reg.into();
+
+  if marcaje.usuario != sesion.usuario {
+    if !sesion.tiene_algun_rol(&[Rol::Registrador]) {
+      tracing::warn!(
+        usuario = marcaje.usuario,
+        autor = sesion.usuario,
+        "Registro de marcaje para otro usuario denegado"
+      );
+      return Err((
+        StatusCode::FORBIDDEN,
+        "@@:No tiene permisos para realizar la operación".to_string(),
+      ));
+    }
+    marcaje.usuario_reg = Some(sesion.usuario);
+  }
+
+  state
+    .marcaje_servicio
+    .agregar(&marcaje)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|id| (StatusCode::CREATED, Json(id)))
+}
+
+/// Api para crear una solicitud de incidencia por el usuario
+///
+/// El creador de la incidencia es el usuario de la sesión.
+/// Solo el registrador y el supervisor pueden crear incidencias
+/// para otros usuarios.
+async fn crear_incidencia(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Json(inc): Json<IncidenciaDTO>,
+) -> impl IntoResponse {
+  let mut inc: Incidencia = inc.into();
+
+  if inc.usuario != sesion.usuario
+    && !sesion.tiene_algun_rol(&[Rol::Registrador, Rol::Supervidor])
+  {
+    tracing::warn!(
+      usuario = inc.usuario,
+      autor = sesion.usuario,
+      "Creación de incidencia para otro usuario denegada"
+    );
+    return Err((
+      StatusCode::FORBIDDEN,
+      "@@:No tiene permisos para realizar la operación".to_string(),
+    ));
+  }
+  inc.usuario_creador = Some(sesion.usuario);
+
+  state
+    .inc_servicio
+    .agregar(&inc)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|id| (StatusCode::CREATED, Json(id)))
+}
+
+/// Api para crear una solicitud desde un estado previo.
+///
+/// El estado previo viene en la propia incidencia
+///
+/// Devuelve las incidencias modificada
+///
+/// Solo el creador de la incidencia puede volver a solicitarla.
+async fn cambiar_incidencia_solicitud(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Json(solicitud): Json<IncidenciaSolictudDTO>,
+) -> impl IntoResponse {
+  let id = solicitud.id;
+  permiso_incidencia(&state, &sesion, id).await?;
+
+  let mut solicitud: IncidenciaSolictud = solicitud.into();
+  solicitud.usuario_creador = sesion.usuario;
+
+  state
+    .inc_servicio
+    .cambiar_estado_a_solicitud(&solicitud)
+    .await
+    .map_err(|err| {
+      (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+    })?;
+
+  state
+    .inc_servicio
+    .incidencias(Some(id), None, None, &[], false, None)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|regs| Json(DominiosWithCacheUsuarioDTO::<IncidenciaDTO>::from(regs)))
+}
+
+/// Api para cancelar una incidencia.
+///
+/// Solo el creador de la incidencia puede cancelarla.
+async fn cambiar_incidencia_cancelada(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Json(cancelada): Json<UsuarioFechaParams>,
+) -> impl IntoResponse {
+  let id = cancelada.id;
+  permiso_incidencia(&state, &sesion, id).await?;
+
+  state
+    .inc_servicio
+    .cambiar_estado_a_cancelada(sesion.usuario, id, cancelada.fecha)
+    .await
+    .map_err(|err| {
+      (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+    })?;
+
+  state
+    .inc_servicio
+    .incidencias(Some(id), None, None, &[], false, None)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|regs| Json(DominiosWithCacheUsuarioDTO::<IncidenciaDTO>::from(regs)))
+}
+
+/// Api para obtener el informe de cumplimiento horario
+///
+/// Los empleados solo pueden consultar su propio informe.
+/// Las consultas de la inspección quedan registradas en las trazas.
+async fn informe_cumplimiento_horario(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  axum::extract::Query(params): axum::extract::Query<InformeCumplimientoParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_empleado(&sesion, params.empleado_id, INFORMES_EMPLEADOS)?;
+  permiso_centro(&state, params.empleado_id, params.centro).await?;
+
+  if sesion.tiene_algun_rol(&[Rol::Inspector]) {
+    state
+      .traza_servicio
+      .registrar_inspeccion(
+        sesion.usuario,
+        params.empleado_id,
+        format!(
+          "Consulta del cumplimiento horario {:02}/{}",
+          params.mes, params.anio
+        ),
+      )
+      .await
+      .map_err(|err| {
+        (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+      })?;
+  }
+
+  state
+    .informe_servicio
+    .cumplimiento_horario(params.empleado_id, params.mes, params.anio)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|informe| Json(InformeCumplimientoDTO::from(informe)))
+}
+
+/// Api para obtener el informe de cumplimiento anual de un empleado.
+///
+/// Los empleados solo pueden consultar su propio informe.
+/// Las consultas de la inspección quedan registradas en las trazas.
+async fn informe_cumplimiento_anual(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  axum::extract::Query(params): axum::extract::Query<InformeAnualParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_empleado(&sesion, params.empleado_id, INFORMES_EMPLEADOS)?;
+
+  permiso_centro(&state, params.empleado_id, params.centro).await?;
+
+  if sesion.tiene_algun_rol(&[Rol::Inspector]) {
+    state
+      .traza_servicio
+      .registrar_inspeccion(
+        sesion.usuario,
+        params.empleado_id,
+        format!("Consulta del cumplimiento anual {}", params.anio),
+      )
+      .await
+      .map_err(|err| {
+        (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+      })?;
+  }
+
+  state
+    .informe_servicio
+    .cumplimiento_anual(params.empleado_id, params.anio)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|informe| Json(InformeCumplimientoAnualDTO::from(informe)))
+}
+
+/// Api para obtener el informe de cumplimiento de un equipo de empleados.
+///
+/// Los empleados se seleccionan por identificador o por rol y centro.
+async fn informe_cumplimiento_equipo(
+  State(state): State<Arc<AppState>>,
+  axum::extract::Query(params): axum::extract::Query<InformeEquipoParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  let empleados = match params.usuarios.as_deref() {
+    Some(usuarios) => {
+      let ids = usuarios
+        .split(',')
+        .map(|id| id.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| {
+          (
+            StatusCode::BAD_REQUEST,
+            "@@:Lista de usuarios no válida".to_string(),
+          )
+        })?;
+
+      state
+        .usuario_servicio
+        .usuarios_por_ids(&ids, params.centro)
+        .await
+        .map_err(|err| {
+          (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+        })?
+    }
+    None => {
+      let rol = match params.rol {
+        Some(rol) => Rol::from_u8(rol).ok_or_else(|| param_no_valido("rol"))?,
+        None => Rol::Empleado,
+      };
+
+      state
+        .usuario_servicio
+        .usuarios_por_rol(rol, params.centro)
+        .await
+        .map_err(|err| {
+          (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+        })?
+    }
+  };
+
+  state
+    .informe_servicio
+    .cumplimiento_equipo(empleados, params.mes, params.anio)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|informe| Json(InformeEquipoDTO::from(informe)))
+}
+
+/// Api para obtener el informe de puntualidad de un empleado.
+async fn informe_puntualidad(
+  State(state): State<Arc<AppState>>,
+  axum::extract::Query(params): axum::extract::Query<InformeCumplimientoParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  let usuario = state
+    .usuario_servicio
+    .usuario(params.empleado_id)
+    .await
+    .map_err(|err| {
+      (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+    })?;
+
+  if !usuario.pertenece_centro(params.centro) {
+    return Err(fuera_de_centro());
+  }
+
+  state
+    .informe_servicio
+    .puntualidad(empleado_registro(&usuario), params.mes, params.anio)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|informe| Json(InformePuntualidadDTO::from(informe)))
+}
+
+/// Api para exportar el registro diario de jornada de un empleado.
+///
+/// Los empleados solo pueden exportar su propio registro.
+/// Las exportaciones de la inspección quedan registradas en las trazas.
+async fn registro_jornada(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  axum::extract::Query(params): axum::extract::Query<RegistroJornadaParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_empleado(&sesion, params.empleado_id, INFORMES_EMPLEADOS)?;
+
+  if sesion.tiene_algun_rol(&[Rol::Inspector]) {
+    state
+      .traza_servicio
+      .registrar_inspeccion(
+        sesion.usuario,
+        params.empleado_id,
+        format!(
+          "Exportación del registro de jornada {:02}/{}",
+          params.mes, params.anio
+        ),
+      )
+      .await
+      .map_err(|err| {
+        (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+      })?;
+  }
+
+  let usuario = state
+    .usuario_servicio
+    .usuario(params.empleado_id)
+    .await
+    .map_err(|err| {
+      (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+    })?;
+
+  state
+    .informe_servicio
+    .exportar_registro(
+      empleado_registro(&usuario),
+      params.mes,
+      params.anio,
+      params.formato.into(),
+    )
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(descarga)
+}
+
+/// Api para exportar en un ZIP el registro diario de jornada
+/// de todos los empleados.
+///
+/// Permite filtrar por centro de trabajo.
+async fn registros_jornada(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  axum::extract::Query(params): axum::extract::Query<RegistrosJornadaParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  if sesion.tiene_algun_rol(&[Rol::Inspector]) {
+    state
+      .traza_servicio
+      .registrar_inspeccion(
+        sesion.usuario,
+        0,
+        match params.centro {
+          Some(centro) => format!(
+            "Exportación de los registros de jornada {:02}/{} del centro {}",
+            params.mes, params.anio, centro
+          ),
+          None => format!(
+            "Exportación de los registros de jornada {:02}/{}",
+            params.mes, params.anio
+          ),
+        },
+      )
+      .await
+      .map_err(|err| {
+        (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+      })?;
+  }
+
+  let empleados = state
+    .usuario_servicio
+    .usuarios()
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))?
+    .iter()
+    .filter(|u| u.roles.contains(&Rol::Empleado))
+    .filter(|u| u.pertenece_centro(params.centro))
+    .map(empleado_registro)
+    .collect();
+
+  state
+    .informe_servicio
+    .exportar_registros(
+      empleados,
+      params.mes,
+      params.anio,
+      params.formato.into(),
+    )
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(descarga)
+}
+
+/// Api para obtener la vista anual de la bolsa de horas de un empleado.
+///
+/// Los empleados solo pueden consultar su propia bolsa.
+async fn bolsa_horas(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+  axum::extract::Query(params): axum::extract::Query<AnioParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  if id != sesion.usuario
+    && !sesion.tiene_algun_rol(INFORMES_EMPLEADOS)
+    && !sesion.tiene_algun_rol(GESTION_BOLSA)
+  {
+    return Err((
+      StatusCode::FORBIDDEN,
+      "@@:No tiene permisos para realizar la operación".to_string(),
+    ));
+  }
+
+  state
+    .bolsa_servicio
+    .resumen_anual(id, params.anio)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|resumen| Json(ResumenAnualBolsaDTO::from(resumen)))
+}
+
+/// Api para cerrar un mes de la bolsa de horas de un empleado.
+async fn cerrar_mes_bolsa(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+  Json(cierre): Json<CierreBolsaDTO>,
 ) -> impl IntoResponse {
   state
-    .horario_servicio
-    .duplicar_config_horario(id, fecha)
+    .bolsa_servicio
+    .cerrar_mes(id, cierre.mes, cierre.anio, sesion.usuario)
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|configs| Json(vec_dominio_to_dtos::<_, ConfigHorarioDTO>(configs)))
+    .map(|movs| {
+      (
+        StatusCode::CREATED,
+        Json(vec_dominio_to_dtos::<_, MovimientoBolsaDTO>(movs)),
+      )
+    })
 }
 
-/// Api para obtener el horario dado el id.
-async fn horario(
+/// Api para anotar un ajuste manual en la bolsa de horas de un empleado.
+async fn ajustar_bolsa(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Path(id): Path<u32>,
+  Json(ajuste): Json<MovimientoBolsaInDTO>,
 ) -> impl IntoResponse {
   state
-    .horario_servicio
-    .config_horario_por_id(id)
+    .bolsa_servicio
+    .ajustar(
+      id,
+      ajuste.fecha,
+      ajuste.minutos,
+      ajuste.motivo.as_deref().unwrap_or_default(),
+      sesion.usuario,
+    )
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|h| Json(ConfigHorarioDTO::from(h)))
+    .map(|id| (StatusCode::CREATED, Json(id)))
 }
 
-/// Api para crear una nueva configuración de horario.
-async fn crear_horario(
+/// Api para compensar con tiempo libre la bolsa de horas de un empleado.
+async fn compensar_bolsa(
   State(state): State<Arc<AppState>>,
-  Json(config): Json<ConfigHorarioDTO>,
-) -> impl IntoResponse {
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+  Json(compensacion): Json<MovimientoBolsaInDTO>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  let minutos = u32::try_from(compensacion.minutos).map_err(|_| {
+    (
+      StatusCode::BAD_REQUEST,
+      "@@:Los minutos a compensar deben ser positivos".to_string(),
+    )
+  })?;
+
   state
-    .horario_servicio
-    .agregar_config_horario(&config.into())
+    .bolsa_servicio
+    .compensar(
+      id,
+      compensacion.fecha,
+      minutos,
+      compensacion.motivo.as_deref(),
+      sesion.usuario,
+    )
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
     .map(|id| (StatusCode::CREATED, Json(id)))
 }
 
-/// Api para modificar una configuración de horario.
-async fn modificar_config_horario(
+/// Comprueba que el usuario de la sesión puede modificar
+/// los marcajes de un usuario.
+///
+/// Solo el propio usuario o un registrador.
+fn permiso_marcaje(
+  sesion: &DatosSesion,
+  usuario: u32,
+) -> Result<(), (StatusCode, String)> {
+  if usuario != sesion.usuario && !sesion.tiene_algun_rol(&[Rol::Registrador]) {
+    tracing::warn!(
+      usuario = usuario,
+      autor = sesion.usuario,
+      "Modificación de marcaje de otro usuario denegada"
+    );
+    return Err((
+      StatusCode::FORBIDDEN,
+      "@@:No tiene permisos para realizar la operación".to_string(),
+    ));
+  }
+  Ok(())
+}
+
+/// Comprueba que el usuario de la sesión es el empleado consultado
+/// o tiene alguno de los roles que permiten consultar a otros.
+fn permiso_empleado(
+  sesion: &DatosSesion,
+  empleado: u32,
+  roles: &[Rol],
+) -> Result<(), (StatusCode, String)> {
+  if empleado != sesion.usuario && !sesion.tiene_algun_rol(roles) {
+    tracing::warn!(
+      empleado = empleado,
+      autor = sesion.usuario,
+      "Consulta de datos de otro empleado denegada"
+    );
+    return Err((
+      StatusCode::FORBIDDEN,
+      "@@:No tiene permisos para realizar la operación".to_string(),
+    ));
+  }
+  Ok(())
+}
+
+/// Comprueba que el empleado pertenece al centro de trabajo.
+///
+/// Si no se indica el centro no se comprueba.
+async fn permiso_centro(
+  state: &AppState,
+  empleado: u32,
+  centro: Option<u32>,
+) -> Result<(), (StatusCode, String)> {
+  if centro.is_none() {
+    return Ok(());
+  }
+
+  let usuario =
+    state
+      .usuario_servicio
+      .usuario(empleado)
+      .await
+      .map_err(|err| {
+        (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+      })?;
+
+  if !usuario.pertenece_centro(centro) {
+    return Err(fuera_de_centro());
+  }
+
+  Ok(())
+}
+
+/// Respuesta cuando el empleado no pertenece al centro indicado.
+fn fuera_de_centro() -> (StatusCode, String) {
+  (
+    StatusCode::NOT_FOUND,
+    "@@:El empleado no pertenece al centro de trabajo".to_string(),
+  )
+}
+
+/// Comprueba que el usuario de la sesión es el creador
+/// de una incidencia o su empleado si la creó el sistema.
+async fn permiso_incidencia(
+  state: &AppState,
+  sesion: &DatosSesion,
+  id: u32,
+) -> Result<(), (StatusCode, String)> {
+  let incidencias = state
+    .inc_servicio
+    .incidencias(Some(id), None, None, &[], false, None)
+    .await
+    .map_err(|err| {
+      (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+    })?;
+
+  let Some(inc) = incidencias.items.first() else {
+    return Err((
+      StatusCode::NOT_FOUND,
+      "@@:No existe la incidencia".to_string(),
+    ));
+  };
+
+  // Las incidencias creadas por el sistema las gestiona el empleado
+  if inc.usuario_creador.unwrap_or(inc.usuario) != sesion.usuario {
+    tracing::warn!(
+      incidencia = id,
+      autor = sesion.usuario,
+      "Cambio de estado de incidencia de otro usuario denegado"
+    );
+    return Err((
+      StatusCode::FORBIDDEN,
+      "@@:No tiene permisos para realizar la operación".to_string(),
+    ));
+  }
+  Ok(())
+}
+
+/// Comprueba que el usuario de la sesión puede consultar
+/// las horas extraordinarias de un empleado.
+fn permiso_horas_extra(
+  sesion: &DatosSesion,
+  empleado: u32,
+) -> Result<(), (StatusCode, String)> {
+  if empleado != sesion.usuario
+    && !sesion.tiene_algun_rol(INFORMES_EMPLEADOS)
+    && !sesion.tiene_algun_rol(GESTION_HORAS_EXTRA)
+  {
+    return Err((
+      StatusCode::FORBIDDEN,
+      "@@:No tiene permisos para realizar la operación".to_string(),
+    ));
+  }
+  Ok(())
+}
+
+/// Api para obtener el resumen mensual de horas extraordinarias
+/// de un empleado.
+async fn horas_extra(
   State(state): State<Arc<AppState>>,
-  Json(config): Json<ConfigHorarioDTO>,
-) -> impl IntoResponse {
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+  axum::extract::Query(params): axum::extract::Query<MesParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_horas_extra(&sesion, id)?;
+
+  let usuario = state.usuario_servicio.usuario(id).await.map_err(|err| {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+  })?;
+
   state
-    .horario_servicio
-    .modificar_config_horario(&config.into())
+    .hora_extra_servicio
+    .resumen_mensual(empleado_registro(&usuario), params.mes, params.anio)
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|_| StatusCode::NO_CONTENT)
+    .map(|resumen| Json(ResumenHorasExtraDTO::from(resumen)))
 }
 
-/// Api para eliminar una configuración de horario.
-async fn eliminar_config_horario(
+/// Api para registrar horas extraordinarias de un empleado.
+///
+/// Devuelve los avisos sobre el tope legal anual.
+async fn registrar_horas_extra(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Path(id): Path<u32>,
+  Json(hora): Json<HoraExtraInDTO>,
 ) -> impl IntoResponse {
   state
-    .horario_servicio
-    .eliminar_config_horario(id)
+    .hora_extra_servicio
+    .registrar(hora.into_dominio(id, sesion.usuario))
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|_| StatusCode::NO_CONTENT)
+    .map(|(id, avisos)| {
+      (
+        StatusCode::CREATED,
+        Json(RegistroHoraExtraDTO { id, avisos }),
+      )
+    })
 }
 
-/// Api para obtener los usuarios que tienen un rol específico.
-async fn usuarios_por_rol(
+/// Api para exportar el resumen mensual de horas extraordinarias
+/// de un empleado.
+///
+/// Las exportaciones de la inspección quedan registradas en las trazas.
+async fn exportar_horas_extra(
   State(state): State<Arc<AppState>>,
-  Path(id): Path<u8>,
-) -> impl IntoResponse {
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+  axum::extract::Query(params): axum::extract::Query<MesFormatoParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  permiso_horas_extra(&sesion, id)?;
+
+  if sesion.tiene_algun_rol(&[Rol::Inspector]) {
+    state
+      .traza_servicio
+      .registrar_inspeccion(
+        sesion.usuario,
+        id,
+        format!(
+          "Exportación de las horas extraordinarias {:02}/{}",
+          params.mes, params.anio
+        ),
+      )
+      .await
+      .map_err(|err| {
+        (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+      })?;
+  }
+
+  let usuario = state.usuario_servicio.usuario(id).await.map_err(|err| {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
+  })?;
+
   state
-    .usuario_servicio
-    .usuarios_por_rol(Rol::from(id))
+    .hora_extra_servicio
+    .exportar(
+      empleado_registro(&usuario),
+      params.mes,
+      params.anio,
+      params.formato.into(),
+    )
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|usrs| Json(vec_dominio_to_dtos::<_, DescriptorUsuarioDTO>(usrs)))
+    .map(descarga)
 }
 
-/// Api para crear un nuevo marcaje de empleado completo.
-async fn registrar(
+/// Api para obtener las solicitudes de ausencia.
+///
+/// Los empleados solo pueden consultar sus propias solicitudes.
+async fn solicitudes_ausencia(
   State(state): State<Arc<AppState>>,
-  Json(reg): Json<MarcajeInDTO>,
-) -> impl IntoResponse {
+  sesion: DatosSesion,
+  axum::extract::Query(params): axum::extract::Query<AusenciasParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+  let gestor = sesion.tiene_algun_rol(GESTION_AUSENCIAS);
+
+  if !gestor && params.usuario != Some(sesion.usuario) {
+    return Err((
+      StatusCode::FORBIDDEN,
+      "@@:No tiene permisos para realizar la operación".to_string(),
+    ));
+  }
+
+  let estado = params
+    .estado
+    .map(|e| EstadoAusencia::try_from(e).map_err(|_| param_no_valido("estado")))
+    .transpose()?;
+
   state
-    .marcaje_servicio
-    .agregar(&reg.into())
+    .ausencia_servicio
+    .solicitudes(params.usuario, estado, params.centro)
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|id| (StatusCode::CREATED, Json(id)))
+    .map(|sols| Json(vec_dominio_to_dtos::<_, SolicitudAusenciaDTO>(sols)))
 }
 
-/// Api para crear una solicitud de incidencia por el usuario
-async fn crear_incidencia(
+/// Api para que un empleado solicite una ausencia.
+async fn solicitar_ausencia(
   State(state): State<Arc<AppState>>,
-  Json(inc): Json<IncidenciaDTO>,
+  sesion: DatosSesion,
+  Json(dto): Json<SolicitudAusenciaInDTO>,
 ) -> impl IntoResponse {
   state
-    .inc_servicio
-    .agregar(&inc.into())
+    .ausencia_servicio
+    .solicitar(&mut dto.into_dominio(sesion.usuario))
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
     .map(|id| (StatusCode::CREATED, Json(id)))
 }
 
-/// Api para crear una solicitud desde un estado previo.
-///
-/// El estado previo viene en la propia incidencia
+/// Api para aprobar una solicitud de ausencia.
 ///
-/// Devuelve las incidencias modificada
-async fn cambiar_incidencia_solicitud(
+/// La ausencia se añade al calendario personal del empleado.
+async fn aprobar_ausencia(
   State(state): State<Arc<AppState>>,
-  Json(solicitud): Json<IncidenciaSolictudDTO>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
 ) -> impl IntoResponse {
-  let id = solicitud.id;
-
-  state
-    .inc_servicio
-    .cambiar_estado_a_solicitud(&solicitud.into())
-    .await
-    .map_err(|err| {
-      (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
-    })?;
-
   state
-    .inc_servicio
-    .incidencias(Some(id), None, None, &[], false, None)
+    .ausencia_servicio
+    .aprobar(sesion.usuario, id)
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|regs| Json(DominiosWithCacheUsuarioDTO::<IncidenciaDTO>::from(regs)))
+    .map(|_| StatusCode::NO_CONTENT)
 }
 
-/// Api para cancelar una incidencia.
-async fn cambiar_incidencia_cancelada(
+/// Api para rechazar una solicitud de ausencia.
+async fn rechazar_ausencia(
   State(state): State<Arc<AppState>>,
-  Json(cancelada): Json<UsuarioFechaParams>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+  Json(dto): Json<RechazoAusenciaDTO>,
 ) -> impl IntoResponse {
-  let id = cancelada.id;
-
   state
-    .inc_servicio
-    .cambiar_estado_a_cancelada(id, cancelada.fecha)
+    .ausencia_servicio
+    .rechazar(sesion.usuario, id, dto.motivo.as_deref())
     .await
-    .map_err(|err| {
-      (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario())
-    })?;
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|_| StatusCode::NO_CONTENT)
+}
 
+/// Api para que un empleado cancele su solicitud de ausencia pendiente.
+async fn cancelar_ausencia(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+) -> impl IntoResponse {
   state
-    .inc_servicio
-    .incidencias(Some(id), None, None, &[], false, None)
+    .ausencia_servicio
+    .cancelar(sesion.usuario, id)
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|regs| Json(DominiosWithCacheUsuarioDTO::<IncidenciaDTO>::from(regs)))
+    .map(|_| StatusCode::NO_CONTENT)
 }
 
-/// Api para obtener el informe de cumplimiento horario
-async fn informe_cumplimiento_horario(
+fn empleado_registro(usuario: &Usuario) -> EmpleadoRegistro {
+  EmpleadoRegistro {
+    id: usuario.id,
+    dni: usuario.dni.to_string(),
+    nombre: format!(
+      "{} {} {}",
+      usuario.nombre, usuario.primer_apellido, usuario.segundo_apellido
+    )
+    .trim_end()
+    .to_string(),
+  }
+}
+
+/// Respuesta para descargar un documento como adjunto
+///
+/// La firma del documento se envía en la cabecera `x-firma-hmac-sha256`.
+fn descarga(doc: Documento) -> impl IntoResponse {
+  (
+    [
+      (header::CONTENT_TYPE, doc.tipo_mime.to_string()),
+      (
+        header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"{}\"", doc.nombre),
+      ),
+      (header::HeaderName::from_static(CABECERA_FIRMA), doc.firma),
+    ],
+    doc.contenido,
+  )
+}
+
+/// Cabecera con la firma de los documentos descargados
+const CABECERA_FIRMA: &str = "x-firma-hmac-sha256";
+
+/// Api para verificar la firma de un documento exportado.
+///
+/// Recibe el documento tal y como se descargó. Si no se indica la
+/// firma se usa la incluida en el documento, que solo tienen los PDF.
+async fn verificar_firma(
   State(state): State<Arc<AppState>>,
-  axum::extract::Query(params): axum::extract::Query<InformeCumplimientoParams>,
+  axum::extract::Query(params): axum::extract::Query<FirmaParams>,
+  contenido: axum::body::Bytes,
 ) -> impl IntoResponse {
-  state
+  let (firma, valida) = state
     .informe_servicio
-    .cumplimiento_horario(params.empleado_id, params.mes, params.anio)
-    .await
-    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
-    .map(|informe| Json(InformeCumplimientoDTO::from(informe)))
+    .verificar_firma(&contenido, params.firma.as_deref());
+
+  Json(VerificacionFirmaDTO { firma, valida })
 }
 
 /// Api para obtener todos los calendarios laborales.
@@ -667,6 +2097,68 @@ async fn eliminar_calendario(
     .map(|_| StatusCode::NO_CONTENT)
 }
 
+/// Api para obtener todos los centros de trabajo.
+async fn centros(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+  state
+    .centro_servicio
+    .centros()
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|centros| Json(vec_dominio_to_dtos::<_, CentroDTO>(centros)))
+}
+
+/// Api para obtener un centro de trabajo por su id.
+async fn centro(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<u32>,
+) -> impl IntoResponse {
+  state
+    .centro_servicio
+    .centro(id)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|c| Json(CentroDTO::from(c)))
+}
+
+/// Api para crear un nuevo centro de trabajo.
+async fn crear_centro(
+  State(state): State<Arc<AppState>>,
+  Json(dto): Json<CentroDTO>,
+) -> impl IntoResponse {
+  state
+    .centro_servicio
+    .crear_centro(&dto.into())
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|id| (StatusCode::CREATED, Json(id)))
+}
+
+/// Api para actualizar un centro de trabajo existente.
+async fn actualizar_centro(
+  State(state): State<Arc<AppState>>,
+  Json(dto): Json<CentroDTO>,
+) -> impl IntoResponse {
+  state
+    .centro_servicio
+    .actualizar_centro(&dto.into())
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|_| StatusCode::NO_CONTENT)
+}
+
+/// Api para eliminar un centro de trabajo.
+async fn eliminar_centro(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<u32>,
+) -> impl IntoResponse {
+  state
+    .centro_servicio
+    .eliminar_centro(id)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|_| StatusCode::NO_CONTENT)
+}
+
 /// Api para obtener las fechas señaladas de un calendario.
 ///
 /// Permite filtrar por fecha de inicio y fin.
@@ -741,8 +2233,11 @@ async fn eliminar_fecha_calendario(
 ///
 /// Devuelve las incidencias según el filtro como parámetro
 /// y un array de incidencias con errores fatales.
+///
+/// El gestor de las incidencias es el usuario de la sesión.
 async fn procesar_incidencias(
   State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
   Json(entrada): Json<IncidenciaInProcesoDTO>,
 ) -> Result<(StatusCode, Json<IncidenciaOutProcesoDTO>), (StatusCode, String)> {
   let incidencias_vec: Vec<IncidenciaProceso> =
@@ -750,7 +2245,7 @@ async fn procesar_incidencias(
 
   let incs_erroneas = match state
     .inc_servicio
-    .procesar_incidencias(entrada.usuario_gestor, incidencias_vec.as_slice())
+    .procesar_incidencias(sesion.usuario, incidencias_vec.as_slice())
     .await
   {
     Ok(v) => v,
@@ -794,10 +2289,18 @@ async fn procesar_incidencias(
 }
 
 /// Devuelve las incidencias filtradas por una serie de filtros
+///
+/// Los empleados solo pueden consultar sus propias incidencias.
 async fn incidencias_por_fechas(
   State(state): State<Arc<AppState>>,
-  Json(param): Json<IncidenciasFiltroParams>,
+  sesion: DatosSesion,
+  Json(mut param): Json<IncidenciasFiltroParams>,
 ) -> impl IntoResponse {
+  if !sesion.tiene_algun_rol(CONSULTA_INC_EMPLEADOS) {
+    param.usuario = Some(sesion.usuario);
+    param.supervisor = false;
+  }
+
   let estados_vec: Vec<EstadoIncidencia> = param
     .estados
     .into_iter()
@@ -818,3 +2321,123 @@ async fn incidencias_por_fechas(
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
     .map(|regs| Json(DominiosWithCacheUsuarioDTO::<IncidenciaDTO>::from(regs)))
 }
+
+/// Api para verificar las cadenas de hashes de los registros
+///
+/// Informa de las filas alteradas, eliminadas o insertadas
+/// fuera de la aplicación.
+async fn verificar_integridad(
+  State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+  state
+    .integridad_servicio
+    .verificar()
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|informe| Json(InformeIntegridadDTO::from(informe)))
+}
+
+/// Api para consultar las trazas con filtros y paginación
+async fn trazas(
+  State(state): State<Arc<AppState>>,
+  axum::extract::Query(params): axum::extract::Query<TrazasFiltroParams>,
+) -> impl IntoResponse {
+  let filtro = filtro_trazas(None, params)?;
+
+  state
+    .traza_servicio
+    .trazas(&filtro)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|pagina| Json(PaginaTrazasDTO::from(pagina)))
+}
+
+/// Convierte los parámetros de consulta en el filtro de trazas
+fn filtro_trazas(
+  usuario: Option<u32>,
+  params: TrazasFiltroParams,
+) -> Result<FiltroTrazas, (StatusCode, String)> {
+  if let (Some(inicio), Some(fin)) = (params.fecha_inicio, params.fecha_fin)
+    && inicio > fin
+  {
+    return Err((
+      StatusCode::BAD_REQUEST,
+      ServicioError::Validacion(
+        "La fecha de inicio no puede ser posterior a la fecha fin".to_string(),
+      )
+      .mensaje_usuario(),
+    ));
+  }
+
+  let entidad = params
+    .entidad
+    .map(|e| Entidad::from_u8(e).ok_or_else(|| param_no_valido("entidad")))
+    .transpose()?;
+  let tipo = params
+    .tipo
+    .map(|t| TipoTraza::from_u8(t).ok_or_else(|| param_no_valido("tipo")))
+    .transpose()?;
+
+  Ok(FiltroTrazas {
+    usuario,
+    entidad,
+    entidad_id: params.entidad_id,
+    autor: params.autor,
+    tipo,
+    fecha_inicio: params.fecha_inicio,
+    fecha_fin: params.fecha_fin,
+    pagina: params.pagina,
+  })
+}
+
+/// Api para obtener el historial de trazas de un usuario con
+/// filtros y paginación
+///
+/// Incluye las trazas de sus incidencias y las provocadas por él.
+async fn historial_usuario(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<u32>,
+  axum::extract::Query(params): axum::extract::Query<TrazasFiltroParams>,
+) -> impl IntoResponse {
+  let filtro = filtro_trazas(Some(id), params)?;
+
+  state
+    .traza_servicio
+    .trazas(&filtro)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|pagina| Json(PaginaTrazasDTO::from(pagina)))
+}
+
+/// Api para obtener el historial de trazas de una incidencia
+async fn historial_incidencia(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<u32>,
+  axum::extract::Query(params): axum::extract::Query<PaginaParams>,
+) -> impl IntoResponse {
+  let filtro = FiltroTrazas {
+    entidad: Some(Entidad::Incidencia),
+    entidad_id: Some(id),
+    pagina: params.pagina,
+    ..Default::default()
+  };
+
+  state
+    .traza_servicio
+    .trazas(&filtro)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|pagina| Json(PaginaTrazasDTO::from(pagina)))
+}
+
+/// Error para un parámetro de consulta con un valor desconocido
+fn param_no_valido(nombre: &str) -> (StatusCode, String) {
+  (
+    StatusCode::BAD_REQUEST,
+    ServicioError::Validacion(format!(
+      "El valor del parámetro {} no es válido",
+      nombre
+    ))
+    .mensaje_usuario(),
+  )
+}
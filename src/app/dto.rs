@@ -1,20 +1,34 @@
 use std::collections::HashMap;
 
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
+use crate::ausencias::{EstadoAusencia, SolicitudAusencia};
+use crate::bolsa::{MovimientoBolsa, ResumenAnualBolsa, ResumenMesBolsa};
+use crate::centros::Centro;
+use crate::extras::{ExcesoJornada, HoraExtra, ResumenHorasExtra};
 use crate::horario::{
   Calendario, CalendarioFecha, ConfigHorario, DescriptorHorario, Dia,
-  TipoCalendarioFecha,
+  FranjaHoraria, ReglasPausa, TipoCalendarioFecha,
+};
+use crate::informes::{
+  CumplimientoEmpleado, CumplimientoHorario, CumplimientoMes,
+  InformeCumplimiento, InformeCumplimientoAnual, InformeEquipo,
+  InformePuntualidad, LineaPuntualidad,
 };
-use crate::informes::{CumplimientoHorario, InformeCumplimiento};
 use crate::{
   inc::{
     EstadoIncidencia, Incidencia, IncidenciaProceso, IncidenciaSolictud,
     TipoIncidencia,
   },
   infra::{Dni, DominioWithCacheUsuario, Password, ShortDateTimeFormat},
-  marcaje::{DescriptorMarcaje, Marcaje},
+  integridad::{Alteracion, InformeIntegridad},
+  marcaje::{
+    DescriptorMarcaje, HistorialMarcaje, Marcaje, Pausa, TiempoPausas,
+  },
+  sesiones::Sesion,
+  traza::{PaginaTrazas, Traza},
   usuarios::{DescriptorUsuario, Rol, Usuario},
 };
 
@@ -48,7 +62,6 @@ impl From<&crate::usuarios::UsuarioCalendario> for UsuarioCalendarioDTO {
 #[derive(Deserialize)]
 pub(in crate::app) struct UsuarioBodyDTO {
   pub id: u32,
-  pub autor: u32, // Es el usuario que lo manipula y sirve para trazas
   pub dni: String,
   pub email: String,
   pub nombre: String,
@@ -60,6 +73,10 @@ pub(in crate::app) struct UsuarioBodyDTO {
   pub roles: Vec<u8>,
   #[serde(default)]
   pub calendarios: Vec<u32>,
+  #[serde(default)]
+  pub zona_horaria: Option<Tz>,
+  #[serde(default)]
+  pub centro: Option<u32>,
 }
 
 #[derive(Serialize)]
@@ -74,6 +91,8 @@ pub(in crate::app) struct UsuarioOutDTO {
   pub inicio: Option<NaiveDateTime>,
   pub roles: Vec<u8>,
   pub calendarios: Vec<UsuarioCalendarioDTO>,
+  pub zona_horaria: Option<Tz>,
+  pub centro: Option<u32>,
 }
 
 impl From<Usuario> for UsuarioOutDTO {
@@ -93,6 +112,8 @@ impl From<Usuario> for UsuarioOutDTO {
         .iter()
         .map(UsuarioCalendarioDTO::from)
         .collect(),
+      zona_horaria: usr.zona_horaria,
+      centro: usr.centro,
     }
   }
 }
@@ -119,6 +140,8 @@ impl From<UsuarioBodyDTO> for Usuario {
           asignado: true,
         })
         .collect(),
+      zona_horaria: usr.zona_horaria,
+      centro: usr.centro,
     }
   }
 }
@@ -167,11 +190,39 @@ pub struct PasswordUsuarioDTO {
   pub password: String,
 }
 
+/// Define la entidad de intercambio de una franja horaria
+#[derive(Serialize, Deserialize)]
+pub(in crate::app) struct FranjaHorariaDTO {
+  pub hora_inicio: NaiveTime,
+  pub hora_fin: NaiveTime,
+}
+
+impl From<FranjaHoraria> for FranjaHorariaDTO {
+  fn from(franja: FranjaHoraria) -> Self {
+    FranjaHorariaDTO {
+      hora_inicio: franja.hora_inicio,
+      hora_fin: franja.hora_fin,
+    }
+  }
+}
+
+impl From<FranjaHorariaDTO> for FranjaHoraria {
+  fn from(dto: FranjaHorariaDTO) -> Self {
+    FranjaHoraria {
+      hora_inicio: dto.hora_inicio,
+      hora_fin: dto.hora_fin,
+    }
+  }
+}
+
 #[derive(Serialize, Deserialize)]
 pub(in crate::app) struct HorarioDTO {
   pub id: u32,
   pub dia: String,
-  pub horas: u8,
+  /// Minutos a trabajar en el día, calculados con las franjas si las tiene
+  pub minutos: u32,
+  #[serde(default)]
+  pub franjas: Vec<FranjaHorariaDTO>,
 }
 
 impl From<DescriptorHorario> for HorarioDTO {
@@ -179,7 +230,8 @@ impl From<DescriptorHorario> for HorarioDTO {
     HorarioDTO {
       id: h.id,
       dia: h.dia.letra().to_string(),
-      horas: h.horas,
+      minutos: h.minutos_a_trabajar(),
+      franjas: h.franjas.into_iter().map(FranjaHorariaDTO::from).collect(),
     }
   }
 }
@@ -189,11 +241,19 @@ pub(in crate::app) struct ConfigHorarioDTO {
   pub id: u32,
   pub usuario: u32,
   pub dia: String,
-  pub horas: u8,
+  pub minutos: u16,
+  #[serde(default)]
+  pub franjas: Vec<FranjaHorariaDTO>,
   pub fecha_creacion: NaiveDate,
   pub caducidad_fecha_ini: Option<NaiveDate>,
   pub caducidad_fecha_fin: Option<NaiveDate>,
   pub cortesia: u8,
+  /// Minutos de pausa para comer retribuidos al día
+  #[serde(default)]
+  pub pausa_comida_retribuida: u16,
+  /// Minutos de pausa personal retribuidos al día
+  #[serde(default)]
+  pub pausa_personal_retribuida: u16,
 }
 
 impl From<ConfigHorario> for ConfigHorarioDTO {
@@ -202,11 +262,18 @@ impl From<ConfigHorario> for ConfigHorarioDTO {
       id: config.id,
       usuario: config.usuario,
       dia: config.dia.letra().to_string(),
-      horas: config.horas,
+      minutos: config.minutos,
+      franjas: config
+        .franjas
+        .into_iter()
+        .map(FranjaHorariaDTO::from)
+        .collect(),
       fecha_creacion: config.fecha_creacion,
       caducidad_fecha_ini: config.caducidad_fecha_ini,
       caducidad_fecha_fin: config.caducidad_fecha_fin,
       cortesia: config.cortesia,
+      pausa_comida_retribuida: config.pausas.comida_retribuida,
+      pausa_personal_retribuida: config.pausas.personal_retribuida,
     }
   }
 }
@@ -217,11 +284,16 @@ impl From<ConfigHorarioDTO> for ConfigHorario {
       id: dto.id,
       usuario: dto.usuario,
       dia: Dia::from(dto.dia.as_str()),
-      horas: dto.horas,
+      minutos: dto.minutos,
+      franjas: dto.franjas.into_iter().map(FranjaHoraria::from).collect(),
       fecha_creacion: dto.fecha_creacion,
       caducidad_fecha_ini: dto.caducidad_fecha_ini,
       caducidad_fecha_fin: dto.caducidad_fecha_fin,
       cortesia: dto.cortesia,
+      pausas: ReglasPausa {
+        comida_retribuida: dto.pausa_comida_retribuida,
+        personal_retribuida: dto.pausa_personal_retribuida,
+      },
     }
   }
 }
@@ -258,7 +330,6 @@ impl From<DescriptorMarcaje> for DescriptorMarcajeDTO {
 #[derive(Deserialize)]
 pub(in crate::app) struct MarcajeInDTO {
   pub usuario: u32,
-  pub usuario_reg: Option<u32>,
   pub fecha: NaiveDate,
   pub hora_inicio: NaiveTime,
   pub hora_fin: Option<NaiveTime>,
@@ -269,11 +340,14 @@ impl From<MarcajeInDTO> for Marcaje {
     Marcaje {
       id: 0, // Es auto incremental
       usuario: reg.usuario,
-      usuario_reg: reg.usuario_reg,
+      usuario_reg: None, // Se asigna desde la sesión
       fecha: reg.fecha,
       hora_inicio: reg.hora_inicio,
       hora_fin: reg.hora_fin,
+      inicio_utc: None, // Se calcula al registrar el marcaje
+      fin_utc: None,
       horario: None,
+      pausas: TiempoPausas::default(),
     }
   }
 }
@@ -289,6 +363,10 @@ pub(in crate::app) struct MarcajeOutDTO {
   pub hora_inicio: String,
   pub hora_fin: Option<String>,
   pub hora_trabajadas: Option<f64>,
+  /// Minutos de pausa para comer finalizadas
+  pub pausa_comida: u32,
+  /// Minutos de pausa personal finalizadas
+  pub pausa_personal: u32,
 }
 
 impl From<Marcaje> for MarcajeOutDTO {
@@ -304,6 +382,74 @@ impl From<Marcaje> for MarcajeOutDTO {
       hora_inicio: reg.hora_inicio.formato_corto(),
       hora_fin: reg.hora_fin.map(|hf| hf.formato_corto()),
       hora_trabajadas: horas_trabajadas,
+      pausa_comida: reg.pausas.comida,
+      pausa_personal: reg.pausas.personal,
+    }
+  }
+}
+
+/// Define la entidad de salida para una pausa de un marcaje
+#[derive(Serialize)]
+pub(in crate::app) struct PausaDTO {
+  pub id: u32,
+  pub marcaje: u32,
+  pub tipo: u8,
+  pub hora_inicio: String,
+  pub hora_fin: Option<String>,
+}
+
+impl From<Pausa> for PausaDTO {
+  fn from(pausa: Pausa) -> Self {
+    PausaDTO {
+      id: pausa.id,
+      marcaje: pausa.marcaje,
+      tipo: pausa.tipo as u8,
+      hora_inicio: pausa.hora_inicio.formato_corto(),
+      hora_fin: pausa.hora_fin.map(|hf| hf.formato_corto()),
+    }
+  }
+}
+
+/// Define la entidad de salida para una versión del historial de un marcaje
+#[derive(Serialize)]
+pub(in crate::app) struct HistorialMarcajeDTO {
+  pub id: u32,
+  pub marcaje: u32,
+  pub operacion: u8,
+  pub fecha: NaiveDateTime,
+  pub autor: Option<u32>,
+  pub motivo: Option<String>,
+  pub hora_inicio_anterior: Option<String>,
+  pub hora_fin_anterior: Option<String>,
+  pub inicio_utc_anterior: Option<DateTime<Utc>>,
+  pub fin_utc_anterior: Option<DateTime<Utc>>,
+  pub hora_inicio: Option<String>,
+  pub hora_fin: Option<String>,
+  pub inicio_utc: Option<DateTime<Utc>>,
+  pub fin_utc: Option<DateTime<Utc>>,
+  pub marcaje_nuevo: Option<u32>,
+  pub pausa: Option<u32>,
+}
+
+impl From<HistorialMarcaje> for HistorialMarcajeDTO {
+  fn from(h: HistorialMarcaje) -> Self {
+    HistorialMarcajeDTO {
+      id: h.id,
+      marcaje: h.marcaje,
+      operacion: h.operacion as u8,
+      fecha: h.fecha,
+      autor: h.autor,
+      motivo: h.motivo,
+      hora_inicio_anterior: h.anterior.hora_inicio.map(|h| h.formato_corto()),
+      hora_fin_anterior: h.anterior.hora_fin.map(|h| h.formato_corto()),
+      inicio_utc_anterior: h.anterior.inicio_utc,
+      fin_utc_anterior: h.anterior.fin_utc,
+      hora_inicio: h.nuevo.hora_inicio.map(|h| h.formato_corto()),
+      hora_fin: h.nuevo.hora_fin.map(|h| h.formato_corto()),
+      inicio_utc: h.nuevo.inicio_utc,
+      fin_utc: h.nuevo.fin_utc,
+      marcaje_nuevo: h.marcaje_nuevo,
+      pausa: h.pausa,
     }
   }
 }
@@ -311,7 +457,6 @@ impl From<Marcaje> for MarcajeOutDTO {
 // Define la entidad de intercambio para el proceso de incidencias.
 #[derive(Deserialize)]
 pub(in crate::app) struct IncidenciaInProcesoDTO {
-  pub usuario_gestor: u32,
   pub param_filtro_inc: IncidenciasFiltroParams,
   pub incidencias: Vec<IncidenciaProcesoDTO>,
 }
@@ -356,7 +501,7 @@ pub(in crate::app) struct IncidenciaDTO {
   pub estado: u8,
   pub fecha_estado: Option<NaiveDateTime>,
   pub error: Option<String>,
-  pub usuario_creador: u32,
+  pub usuario_creador: Option<u32>,
   pub usuario_gestor: Option<u32>,
   pub motivo_solicitud: Option<String>,
   pub motivo_rechazo: Option<String>,
@@ -417,7 +562,6 @@ pub struct IncidenciaSolictudDTO {
   pub fecha_solicitud: NaiveDateTime,
   pub hora_inicio: Option<NaiveTime>,
   pub hora_fin: Option<NaiveTime>,
-  pub usuario_creador: u32,
 }
 
 impl From<IncidenciaSolictudDTO> for IncidenciaSolictud {
@@ -429,7 +573,40 @@ impl From<IncidenciaSolictudDTO> for IncidenciaSolictud {
       fecha_solicitud: inc.fecha_solicitud,
       hora_inicio: inc.hora_inicio,
       hora_fin: inc.hora_fin,
-      usuario_creador: inc.usuario_creador,
+      usuario_creador: 0, // Se asigna desde la sesión
+    }
+  }
+}
+
+// Define la entidad de intercambio para el centro de trabajo.
+#[derive(Serialize, Deserialize)]
+pub(in crate::app) struct CentroDTO {
+  pub id: u32,
+  pub nombre: String,
+  #[serde(default)]
+  pub direccion: String,
+  #[serde(default)]
+  pub calendarios: Vec<u32>,
+}
+
+impl From<Centro> for CentroDTO {
+  fn from(c: Centro) -> Self {
+    CentroDTO {
+      id: c.id,
+      nombre: c.nombre,
+      direccion: c.direccion,
+      calendarios: c.calendarios,
+    }
+  }
+}
+
+impl From<CentroDTO> for Centro {
+  fn from(dto: CentroDTO) -> Self {
+    Centro {
+      id: dto.id,
+      nombre: dto.nombre,
+      direccion: dto.direccion,
+      calendarios: dto.calendarios,
     }
   }
 }
@@ -501,7 +678,7 @@ pub struct CumplimientoHorarioDTO {
   pub fecha: NaiveDate,
   pub horas_trabajo_efectivo: f64,
   pub horas_trabajadas: f64,
-  pub horas_a_trabajar: u8,
+  pub horas_a_trabajar: f64,
   pub saldo: f64,
   pub nota: String,
 }
@@ -539,6 +716,486 @@ impl From<InformeCumplimiento> for InformeCumplimientoDTO {
   }
 }
 
+#[derive(Serialize)]
+pub struct CumplimientoMesDTO {
+  pub mes: u32,
+  pub horas_a_trabajar: f64,
+  pub horas_trabajadas: f64,
+  pub saldo: f64,
+  pub dias_vacaciones: u32,
+}
+
+impl From<CumplimientoMes> for CumplimientoMesDTO {
+  fn from(value: CumplimientoMes) -> Self {
+    Self {
+      mes: value.mes,
+      horas_a_trabajar: value.horas_a_trabajar,
+      horas_trabajadas: value.horas_trabajadas,
+      saldo: value.saldo,
+      dias_vacaciones: value.dias_vacaciones,
+    }
+  }
+}
+
+/// Define la entidad de salida del informe de cumplimiento anual
+#[derive(Serialize)]
+pub struct InformeCumplimientoAnualDTO {
+  pub anio: i32,
+  pub horas_convenio: f64,
+  pub meses: Vec<CumplimientoMesDTO>,
+  pub horas_a_trabajar: f64,
+  pub horas_trabajadas: f64,
+  pub saldo: f64,
+  pub horas_pendientes: f64,
+  pub proyeccion: f64,
+  pub diferencia_convenio: f64,
+  pub vacaciones_disfrutadas: u32,
+  pub vacaciones_pendientes: u32,
+}
+
+impl From<InformeCumplimientoAnual> for InformeCumplimientoAnualDTO {
+  fn from(value: InformeCumplimientoAnual) -> Self {
+    Self {
+      anio: value.anio,
+      horas_convenio: value.horas_convenio,
+      meses: vec_dominio_to_dtos(value.meses),
+      horas_a_trabajar: value.horas_a_trabajar,
+      horas_trabajadas: value.horas_trabajadas,
+      saldo: value.saldo,
+      horas_pendientes: value.horas_pendientes,
+      proyeccion: value.proyeccion,
+      diferencia_convenio: value.diferencia_convenio,
+      vacaciones_disfrutadas: value.vacaciones_disfrutadas,
+      vacaciones_pendientes: value.vacaciones_pendientes,
+    }
+  }
+}
+
+#[derive(Serialize)]
+pub struct CumplimientoEmpleadoDTO {
+  pub empleado: DescriptorUsuarioDTO,
+  pub horas_a_trabajar: f64,
+  pub horas_trabajadas: f64,
+  pub saldo: f64,
+  pub incidencias_abiertas: u32,
+  pub dias_sin_salida: Vec<NaiveDate>,
+}
+
+impl From<CumplimientoEmpleado> for CumplimientoEmpleadoDTO {
+  fn from(value: CumplimientoEmpleado) -> Self {
+    Self {
+      empleado: value.empleado.into(),
+      horas_a_trabajar: value.horas_a_trabajar,
+      horas_trabajadas: value.horas_trabajadas,
+      saldo: value.saldo,
+      incidencias_abiertas: value.incidencias_abiertas,
+      dias_sin_salida: value.dias_sin_salida,
+    }
+  }
+}
+
+/// Define la entidad de salida del informe de cumplimiento de un equipo
+#[derive(Serialize)]
+pub struct InformeEquipoDTO {
+  pub mes: u32,
+  pub anio: i32,
+  pub empleados: Vec<CumplimientoEmpleadoDTO>,
+  pub horas_a_trabajar: f64,
+  pub horas_trabajadas: f64,
+  pub saldo: f64,
+  pub incidencias_abiertas: u32,
+  pub dias_sin_salida: u32,
+}
+
+impl From<InformeEquipo> for InformeEquipoDTO {
+  fn from(value: InformeEquipo) -> Self {
+    Self {
+      mes: value.mes,
+      anio: value.anio,
+      empleados: vec_dominio_to_dtos(value.empleados),
+      horas_a_trabajar: value.horas_a_trabajar,
+      horas_trabajadas: value.horas_trabajadas,
+      saldo: value.saldo,
+      incidencias_abiertas: value.incidencias_abiertas,
+      dias_sin_salida: value.dias_sin_salida,
+    }
+  }
+}
+
+#[derive(Serialize)]
+pub struct LineaPuntualidadDTO {
+  pub fecha: NaiveDate,
+  pub tipo: String,
+  pub hora_prevista: Option<NaiveTime>,
+  pub hora_real: Option<NaiveTime>,
+  pub minutos: u32,
+}
+
+impl From<LineaPuntualidad> for LineaPuntualidadDTO {
+  fn from(value: LineaPuntualidad) -> Self {
+    Self {
+      fecha: value.fecha,
+      tipo: value.tipo.as_str().to_string(),
+      hora_prevista: value.hora_prevista,
+      hora_real: value.hora_real,
+      minutos: value.minutos,
+    }
+  }
+}
+
+/// Define la entidad de salida del informe de puntualidad
+#[derive(Serialize)]
+pub struct InformePuntualidadDTO {
+  pub empleado: u32,
+  pub nombre: String,
+  pub mes: u32,
+  pub anio: i32,
+  pub lineas: Vec<LineaPuntualidadDTO>,
+  pub retrasos: u32,
+  pub salidas_anticipadas: u32,
+  pub sin_salida: u32,
+}
+
+impl From<InformePuntualidad> for InformePuntualidadDTO {
+  fn from(value: InformePuntualidad) -> Self {
+    Self {
+      empleado: value.empleado.id,
+      nombre: value.empleado.nombre,
+      mes: value.mes,
+      anio: value.anio,
+      lineas: value
+        .lineas
+        .into_iter()
+        .map(LineaPuntualidadDTO::from)
+        .collect(),
+      retrasos: value.retrasos,
+      salidas_anticipadas: value.salidas_anticipadas,
+      sin_salida: value.sin_salida,
+    }
+  }
+}
+
+// Define la entidad de salida para un movimiento de la bolsa de horas.
+#[derive(Serialize)]
+pub(in crate::app) struct MovimientoBolsaDTO {
+  pub id: u32,
+  pub fecha: NaiveDate,
+  pub tipo: u8,
+  pub minutos: i32,
+  pub motivo: Option<String>,
+  pub autor: Option<u32>,
+  pub fecha_registro: NaiveDateTime,
+}
+
+impl From<MovimientoBolsa> for MovimientoBolsaDTO {
+  fn from(mov: MovimientoBolsa) -> Self {
+    MovimientoBolsaDTO {
+      id: mov.id,
+      fecha: mov.fecha,
+      tipo: mov.tipo as u8,
+      minutos: mov.minutos,
+      motivo: mov.motivo,
+      autor: mov.autor,
+      fecha_registro: mov.fecha_registro,
+    }
+  }
+}
+
+#[derive(Serialize)]
+pub(in crate::app) struct ResumenMesBolsaDTO {
+  pub mes: u32,
+  pub generado: i64,
+  pub ajustes: i64,
+  pub compensado: i64,
+  pub descartado: i64,
+  pub saldo: i64,
+}
+
+impl From<ResumenMesBolsa> for ResumenMesBolsaDTO {
+  fn from(resumen: ResumenMesBolsa) -> Self {
+    ResumenMesBolsaDTO {
+      mes: resumen.mes,
+      generado: resumen.generado,
+      ajustes: resumen.ajustes,
+      compensado: resumen.compensado,
+      descartado: resumen.descartado,
+      saldo: resumen.saldo,
+    }
+  }
+}
+
+// Define la entidad de salida de la vista anual de la bolsa de horas.
+// Los importes se expresan en minutos.
+#[derive(Serialize)]
+pub(in crate::app) struct ResumenAnualBolsaDTO {
+  pub usuario: u32,
+  pub anio: i32,
+  pub saldo_inicial: i64,
+  pub meses: Vec<ResumenMesBolsaDTO>,
+  pub saldo_final: i64,
+  pub movimientos: Vec<MovimientoBolsaDTO>,
+}
+
+impl From<ResumenAnualBolsa> for ResumenAnualBolsaDTO {
+  fn from(resumen: ResumenAnualBolsa) -> Self {
+    ResumenAnualBolsaDTO {
+      usuario: resumen.usuario,
+      anio: resumen.anio,
+      saldo_inicial: resumen.saldo_inicial,
+      meses: vec_dominio_to_dtos(resumen.meses),
+      saldo_final: resumen.saldo_final,
+      movimientos: vec_dominio_to_dtos(resumen.movimientos),
+    }
+  }
+}
+
+// Define la entidad de entrada para cerrar un mes de la bolsa de horas.
+#[derive(Deserialize)]
+pub(in crate::app) struct CierreBolsaDTO {
+  pub mes: u32,
+  pub anio: i32,
+}
+
+// Define la entidad de entrada para los ajustes y las
+// compensaciones de la bolsa de horas.
+#[derive(Deserialize)]
+pub(in crate::app) struct MovimientoBolsaInDTO {
+  pub fecha: NaiveDate,
+  pub minutos: i32,
+  pub motivo: Option<String>,
+}
+
+// Define la entidad de salida de un registro de horas extraordinarias.
+#[derive(Serialize)]
+pub(in crate::app) struct HoraExtraDTO {
+  pub id: u32,
+  pub fecha: NaiveDate,
+  pub minutos: u32,
+  pub causa: u8,
+  pub compensacion: u8,
+  pub computa_tope: bool,
+  pub motivo: Option<String>,
+  pub autor: u32,
+  pub fecha_registro: NaiveDateTime,
+}
+
+impl From<HoraExtra> for HoraExtraDTO {
+  fn from(hora: HoraExtra) -> Self {
+    HoraExtraDTO {
+      id: hora.id,
+      fecha: hora.fecha,
+      minutos: hora.minutos,
+      causa: hora.causa as u8,
+      compensacion: hora.compensacion as u8,
+      computa_tope: hora.computa_tope(),
+      motivo: hora.motivo,
+      autor: hora.autor,
+      fecha_registro: hora.fecha_registro,
+    }
+  }
+}
+
+// Define la entidad de entrada para registrar horas extraordinarias.
+// El autor es el usuario de la sesión.
+#[derive(Deserialize)]
+pub(in crate::app) struct HoraExtraInDTO {
+  pub fecha: NaiveDate,
+  pub minutos: u32,
+  pub causa: u8,
+  pub compensacion: u8,
+  pub motivo: Option<String>,
+}
+
+impl HoraExtraInDTO {
+  pub fn into_dominio(self, usuario: u32, autor: u32) -> HoraExtra {
+    HoraExtra {
+      id: 0,
+      usuario,
+      fecha: self.fecha,
+      minutos: self.minutos,
+      causa: self.causa.into(),
+      compensacion: self.compensacion.into(),
+      motivo: self.motivo,
+      autor,
+      fecha_registro: NaiveDateTime::default(),
+    }
+  }
+}
+
+// Define la entidad de salida al verificar la firma de un documento.
+#[derive(Serialize)]
+pub(in crate::app) struct VerificacionFirmaDTO {
+  pub firma: Option<String>,
+  pub valida: bool,
+}
+
+// Define la entidad de salida al registrar horas extraordinarias.
+#[derive(Serialize)]
+pub(in crate::app) struct RegistroHoraExtraDTO {
+  pub id: u32,
+  pub avisos: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub(in crate::app) struct ExcesoJornadaDTO {
+  pub fecha: NaiveDate,
+  pub minutos: u32,
+  pub registrados: u32,
+}
+
+impl From<ExcesoJornada> for ExcesoJornadaDTO {
+  fn from(exceso: ExcesoJornada) -> Self {
+    ExcesoJornadaDTO {
+      fecha: exceso.fecha,
+      minutos: exceso.minutos,
+      registrados: exceso.registrados,
+    }
+  }
+}
+
+// Define la entidad de salida del resumen mensual de horas
+// extraordinarias. Los totales se expresan en minutos.
+#[derive(Serialize)]
+pub(in crate::app) struct ResumenHorasExtraDTO {
+  pub empleado: u32,
+  pub nombre: String,
+  pub mes: u32,
+  pub anio: i32,
+  pub horas_extra: Vec<HoraExtraDTO>,
+  pub pendientes: Vec<ExcesoJornadaDTO>,
+  pub total: u32,
+  pub estructurales: u32,
+  pub fuerza_mayor: u32,
+  pub retribuidas: u32,
+  pub descanso: u32,
+  pub computables_anio: u32,
+  pub avisos: Vec<String>,
+}
+
+impl From<ResumenHorasExtra> for ResumenHorasExtraDTO {
+  fn from(resumen: ResumenHorasExtra) -> Self {
+    ResumenHorasExtraDTO {
+      empleado: resumen.empleado.id,
+      nombre: resumen.empleado.nombre,
+      mes: resumen.mes,
+      anio: resumen.anio,
+      horas_extra: vec_dominio_to_dtos(resumen.horas_extra),
+      pendientes: vec_dominio_to_dtos(resumen.pendientes),
+      total: resumen.total,
+      estructurales: resumen.estructurales,
+      fuerza_mayor: resumen.fuerza_mayor,
+      retribuidas: resumen.retribuidas,
+      descanso: resumen.descanso,
+      computables_anio: resumen.computables_anio,
+      avisos: resumen.avisos,
+    }
+  }
+}
+
+// Define la entidad de salida para las sesiones activas.
+#[derive(Serialize)]
+pub(in crate::app) struct SesionDTO {
+  pub id: String,
+  pub creada_en: DateTime<Utc>,
+  pub caduca_en: DateTime<Utc>,
+}
+
+impl From<Sesion> for SesionDTO {
+  fn from(sesion: Sesion) -> Self {
+    SesionDTO {
+      id: sesion.id,
+      creada_en: sesion.creada_en,
+      caduca_en: sesion.caduca_en,
+    }
+  }
+}
+
+// Define la entidad de salida para las trazas.
+#[derive(Serialize)]
+pub(in crate::app) struct TrazaDTO {
+  pub id: u32,
+  pub autor: Option<u32>,
+  pub tipo: u8,
+  pub entidad: u8,
+  pub entidad_id: u32,
+  pub fecha: NaiveDateTime,
+  pub motivo: Option<String>,
+}
+
+impl From<Traza> for TrazaDTO {
+  fn from(traza: Traza) -> Self {
+    TrazaDTO {
+      id: traza.id,
+      autor: traza.autor,
+      tipo: traza.tipo as u8,
+      entidad: traza.entidad as u8,
+      entidad_id: traza.entidad_id,
+      fecha: traza.fecha,
+      motivo: traza.motivo,
+    }
+  }
+}
+
+// Define la entidad de salida para una fila alterada.
+#[derive(Serialize)]
+pub(in crate::app) struct AlteracionDTO {
+  pub tabla: &'static str,
+  pub clave: String,
+  pub id: Option<u32>,
+  pub motivo: &'static str,
+}
+
+impl From<Alteracion> for AlteracionDTO {
+  fn from(alteracion: Alteracion) -> Self {
+    AlteracionDTO {
+      tabla: alteracion.tabla.nombre(),
+      clave: alteracion.clave,
+      id: alteracion.id,
+      motivo: alteracion.motivo.as_str(),
+    }
+  }
+}
+
+// Define la entidad de salida para la verificación de integridad.
+#[derive(Serialize)]
+pub(in crate::app) struct InformeIntegridadDTO {
+  pub integro: bool,
+  pub verificadas: u64,
+  pub sin_sellar: u64,
+  pub alteraciones: Vec<AlteracionDTO>,
+}
+
+impl From<InformeIntegridad> for InformeIntegridadDTO {
+  fn from(informe: InformeIntegridad) -> Self {
+    InformeIntegridadDTO {
+      integro: informe.integro(),
+      verificadas: informe.verificadas,
+      sin_sellar: informe.sin_sellar,
+      alteraciones: vec_dominio_to_dtos(informe.alteraciones),
+    }
+  }
+}
+
+// Define la entidad de salida para una página de trazas.
+#[derive(Serialize)]
+pub(in crate::app) struct PaginaTrazasDTO {
+  pub trazas: DominiosWithCacheUsuarioDTO<TrazaDTO>,
+  pub pagina: u32,
+  pub tamanio: u32,
+  pub total: u64,
+}
+
+impl From<PaginaTrazas> for PaginaTrazasDTO {
+  fn from(pagina: PaginaTrazas) -> Self {
+    PaginaTrazasDTO {
+      trazas: pagina.trazas.into(),
+      pagina: pagina.pagina,
+      tamanio: pagina.tamanio,
+      total: pagina.total,
+    }
+  }
+}
+
 // DTO genérico para DominiosWithCacheUsuario
 #[derive(Serialize)]
 pub(in crate::app) struct DominiosWithCacheUsuarioDTO<T> {
@@ -569,3 +1226,73 @@ where
 {
   entidad.into_iter().map(U::from).collect()
 }
+
+#[derive(Serialize)]
+pub(in crate::app) struct SolicitudAusenciaDTO {
+  pub id: u32,
+  pub usuario: u32,
+  pub tipo: u8,
+  pub fecha_inicio: NaiveDate,
+  pub fecha_fin: NaiveDate,
+  pub motivo: Option<String>,
+  pub estado: u8,
+  pub fecha_solicitud: NaiveDateTime,
+  pub usuario_gestor: Option<u32>,
+  pub fecha_estado: Option<NaiveDateTime>,
+  pub motivo_rechazo: Option<String>,
+  pub calendario_fecha: Option<u32>,
+}
+
+impl From<SolicitudAusencia> for SolicitudAusenciaDTO {
+  fn from(solicitud: SolicitudAusencia) -> Self {
+    SolicitudAusenciaDTO {
+      id: solicitud.id,
+      usuario: solicitud.usuario,
+      tipo: solicitud.tipo.into(),
+      fecha_inicio: solicitud.fecha_inicio,
+      fecha_fin: solicitud.fecha_fin,
+      motivo: solicitud.motivo,
+      estado: solicitud.estado as u8,
+      fecha_solicitud: solicitud.fecha_solicitud,
+      usuario_gestor: solicitud.usuario_gestor,
+      fecha_estado: solicitud.fecha_estado,
+      motivo_rechazo: solicitud.motivo_rechazo,
+      calendario_fecha: solicitud.calendario_fecha,
+    }
+  }
+}
+
+// Define la entidad de entrada para solicitar una ausencia.
+// El usuario es el de la sesión.
+#[derive(Deserialize)]
+pub(in crate::app) struct SolicitudAusenciaInDTO {
+  pub tipo: u8,
+  pub fecha_inicio: NaiveDate,
+  pub fecha_fin: NaiveDate,
+  pub motivo: Option<String>,
+}
+
+impl SolicitudAusenciaInDTO {
+  pub fn into_dominio(self, usuario: u32) -> SolicitudAusencia {
+    SolicitudAusencia {
+      id: 0,
+      usuario,
+      tipo: TipoCalendarioFecha::from(self.tipo),
+      fecha_inicio: self.fecha_inicio,
+      fecha_fin: self.fecha_fin,
+      motivo: self.motivo,
+      estado: EstadoAusencia::Solicitud,
+      fecha_solicitud: NaiveDateTime::default(),
+      usuario_gestor: None,
+      fecha_estado: None,
+      motivo_rechazo: None,
+      calendario_fecha: None,
+    }
+  }
+}
+
+// Define la entidad de entrada para rechazar una solicitud de ausencia.
+#[derive(Deserialize)]
+pub(in crate::app) struct RechazoAusenciaDTO {
+  pub motivo: Option<String>,
+}
@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use axum::{
+  Router,
+  extract::{Json, Path, Query, State},
+  http::StatusCode,
+  response::IntoResponse,
+  routing::get,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::{
+  app::{
+    AppState,
+    api::PaginaParams,
+    dto::{
+      DescriptorUsuarioDTO, DominiosWithCacheUsuarioDTO, HistorialMarcajeDTO,
+      IncidenciaDTO, MarcajeOutDTO, PaginaTrazasDTO, vec_dominio_to_dtos,
+    },
+  },
+  infra::{
+    ServicioError,
+    middleware::{DatosSesion, permitir},
+  },
+  traza::FiltroTrazas,
+  usuarios::Rol,
+};
+
+/// Solo la inspección de trabajo accede a estas rutas
+const INSPECCION: &[Rol] = &[Rol::Inspector];
+
+#[derive(Deserialize)]
+struct RangoFechasParams {
+  fecha_inicio: NaiveDate,
+  fecha_fin: NaiveDate,
+}
+
+#[derive(Deserialize)]
+struct CentroParams {
+  centro: Option<u32>,
+}
+
+/// Define las rutas de consulta para la inspección de trabajo.
+///
+/// Todas las rutas son de solo lectura y cada consulta
+/// queda registrada en las trazas antes de devolver los datos.
+pub(in crate::app) fn rutas() -> Router<Arc<AppState>> {
+  Router::new()
+    .route("/empleados", permitir(INSPECCION, get(empleados)))
+    .route(
+      "/empleados/{id}/marcajes",
+      permitir(INSPECCION, get(marcajes)),
+    )
+    .route(
+      "/empleados/{id}/marcajes/{marcaje}/historial",
+      permitir(INSPECCION, get(historial_marcaje)),
+    )
+    .route(
+      "/empleados/{id}/incidencias",
+      permitir(INSPECCION, get(incidencias)),
+    )
+    .route("/empleados/{id}/trazas", permitir(INSPECCION, get(trazas)))
+}
+
+/// Registra la consulta realizada por el inspector.
+///
+/// Si no se puede registrar no se devuelven los datos.
+async fn registrar_consulta(
+  state: &AppState,
+  sesion: &DatosSesion,
+  empleado: u32,
+  motivo: String,
+) -> Result<(), (StatusCode, String)> {
+  state
+    .traza_servicio
+    .registrar_inspeccion(sesion.usuario, empleado, motivo)
+    .await
+    .map(|_| ())
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+}
+
+/// Valida que el rango de fechas sea correcto
+fn validar_rango(
+  params: &RangoFechasParams,
+) -> Result<(), (StatusCode, String)> {
+  if params.fecha_inicio > params.fecha_fin {
+    return Err((
+      StatusCode::BAD_REQUEST,
+      ServicioError::Validacion(
+        "La fecha de inicio no puede ser posterior a la fecha fin".to_string(),
+      )
+      .mensaje_usuario(),
+    ));
+  }
+
+  Ok(())
+}
+
+/// Api para obtener los empleados
+///
+/// Permite filtrar por centro de trabajo.
+async fn empleados(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  axum::extract::Query(params): axum::extract::Query<CentroParams>,
+) -> impl IntoResponse {
+  let motivo = match params.centro {
+    Some(centro) => format!("Consulta de empleados del centro {}", centro),
+    None => "Consulta de empleados".to_string(),
+  };
+  registrar_consulta(&state, &sesion, 0, motivo).await?;
+
+  state
+    .usuario_servicio
+    .usuarios_por_rol(Rol::Empleado, params.centro)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|usrs| Json(vec_dominio_to_dtos::<_, DescriptorUsuarioDTO>(usrs)))
+}
+
+/// Api para obtener los marcajes de un empleado entre fechas
+async fn marcajes(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+  Query(params): Query<RangoFechasParams>,
+) -> impl IntoResponse {
+  validar_rango(&params)?;
+  registrar_consulta(
+    &state,
+    &sesion,
+    id,
+    format!(
+      "Consulta de marcajes del {} al {}",
+      params.fecha_inicio, params.fecha_fin
+    ),
+  )
+  .await?;
+
+  state
+    .marcaje_servicio
+    .marcajes_entre_fechas_reg(
+      id,
+      Some(params.fecha_inicio),
+      Some(params.fecha_fin),
+      None,
+    )
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|regs| Json(DominiosWithCacheUsuarioDTO::<MarcajeOutDTO>::from(regs)))
+}
+
+/// Api para obtener las incidencias de un empleado entre fechas
+async fn incidencias(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+  Query(params): Query<RangoFechasParams>,
+) -> impl IntoResponse {
+  validar_rango(&params)?;
+  registrar_consulta(
+    &state,
+    &sesion,
+    id,
+    format!(
+      "Consulta de incidencias del {} al {}",
+      params.fecha_inicio, params.fecha_fin
+    ),
+  )
+  .await?;
+
+  state
+    .inc_servicio
+    .incidencias_usuario(id, params.fecha_inicio, params.fecha_fin)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|regs| Json(DominiosWithCacheUsuarioDTO::<IncidenciaDTO>::from(regs)))
+}
+
+/// Api para obtener todas las versiones de un marcaje de un empleado
+async fn historial_marcaje(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path((id, marcaje)): Path<(u32, u32)>,
+) -> impl IntoResponse {
+  registrar_consulta(
+    &state,
+    &sesion,
+    id,
+    format!("Consulta del historial del marcaje {}", marcaje),
+  )
+  .await?;
+
+  state
+    .marcaje_servicio
+    .historial(marcaje, Some(id))
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|h| Json(DominiosWithCacheUsuarioDTO::<HistorialMarcajeDTO>::from(h)))
+}
+
+/// Api para obtener las trazas de un empleado entre fechas paginadas
+async fn trazas(
+  State(state): State<Arc<AppState>>,
+  sesion: DatosSesion,
+  Path(id): Path<u32>,
+  Query(params): Query<RangoFechasParams>,
+  Query(pagina): Query<PaginaParams>,
+) -> impl IntoResponse {
+  validar_rango(&params)?;
+  registrar_consulta(
+    &state,
+    &sesion,
+    id,
+    format!(
+      "Consulta de trazas del {} al {}",
+      params.fecha_inicio, params.fecha_fin
+    ),
+  )
+  .await?;
+
+  let filtro = FiltroTrazas {
+    usuario: Some(id),
+    fecha_inicio: Some(params.fecha_inicio),
+    fecha_fin: Some(params.fecha_fin),
+    pagina: pagina.pagina,
+    ..Default::default()
+  };
+
+  state
+    .traza_servicio
+    .trazas(&filtro)
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.mensaje_usuario()))
+    .map(|pagina| Json(PaginaTrazasDTO::from(pagina)))
+}
@@ -4,18 +4,29 @@
 mod api;
 /// Entidades de intercambio con la aplicación.
 mod dto;
+/// Rutas de solo lectura para la inspección de trabajo.
+mod inspeccion;
 
-use std::{sync::Arc, time::Duration};
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use chrono::{NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
 
 pub use api::*;
 
 use crate::horario::{HorarioRepo, HorarioServicio};
 use crate::{
-  config::{Config, ConfigTrabajo},
+  ausencias::{AusenciaRepo, AusenciaServicio},
+  bolsa::{BolsaRepo, BolsaServicio},
+  centros::{CentroRepo, CentroServicio},
+  config::{CierreMarcajesConfig, Config, ConfigTrabajo},
+  extras::{HoraExtraRepo, HoraExtraServicio},
   inc::{IncidenciaRepo, IncidenciaServicio},
   informes::{InformeRepo, InformeServicio},
   infra::{PoolConexion, middleware},
+  integridad::{IntegridadRepo, IntegridadServicio},
   marcaje::{MarcajeRepo, MarcajeServicio},
+  sesiones::{SesionRepo, SesionServicio},
   traza::{TrazaRepo, TrazaServicio},
   usuarios::{UsuarioRepo, UsuarioServicio},
 };
@@ -23,11 +34,21 @@ use crate::{
 /// Estructura principal de la aplicación que contiene los servicios.
 pub struct AppState {
   pub manejador_sesion: Arc<middleware::ManejadorSesion>,
+  pub sesion_servicio: Arc<SesionServicio>,
   pub marcaje_servicio: MarcajeServicio,
   pub usuario_servicio: UsuarioServicio,
   pub horario_servicio: HorarioServicio,
+  pub centro_servicio: CentroServicio,
+  pub bolsa_servicio: BolsaServicio,
+  pub hora_extra_servicio: HoraExtraServicio,
+  pub ausencia_servicio: AusenciaServicio,
   pub inc_servicio: IncidenciaServicio,
   pub informe_servicio: InformeServicio,
+  pub traza_servicio: TrazaServicio,
+  pub integridad_servicio: IntegridadServicio,
+  /// Proxies inversos de los que se aceptan las cabeceras con la IP
+  /// del cliente
+  pub proxies_confiables: Vec<IpAddr>,
 }
 
 impl AppState {
@@ -36,49 +57,112 @@ impl AppState {
     // Aunque se realizan varias clonaciones de los servicios,
     // estos son ligeros y no suponen un gran coste.
     // Además, solo se hacen al iniciar la aplicación.
+    let sesion_servicio = Arc::new(SesionServicio::new(
+      cnfg
+        .persistir_sesiones
+        .then(|| SesionRepo::new(pool.clone())),
+    ));
+
+    // Todos los servicios que insertan filas selladas
+    // necesitan el servicio de integridad.
+    let integridad = || {
+      IntegridadServicio::new(cnfg.clone(), IntegridadRepo::new(pool.clone()))
+    };
+    let traza = || {
+      TrazaServicio::new(
+        cnfg.clone(),
+        TrazaRepo::new(pool.clone()),
+        integridad(),
+      )
+    };
+    let marcaje = || {
+      MarcajeServicio::new(
+        cnfg.clone(),
+        MarcajeRepo::new(pool.clone()),
+        HorarioServicio::new(cnfg.clone(), HorarioRepo::new(pool.clone())),
+        integridad(),
+      )
+    };
+
     AppState {
       manejador_sesion: Arc::new(middleware::ManejadorSesion::new(
         cnfg.secreto.clone(),
         Duration::from_secs(cnfg.caducidad_sesion),
+        Duration::from_secs(cnfg.caducidad_maxima_sesion),
         cnfg.produccion,
       )),
       usuario_servicio: UsuarioServicio::new(
         cnfg.clone(),
         UsuarioRepo::new(pool.clone()),
-        TrazaServicio::new(TrazaRepo::new()),
+        traza(),
+        sesion_servicio.clone(),
       ),
       horario_servicio: HorarioServicio::new(
         cnfg.clone(),
         HorarioRepo::new(pool.clone()),
       ),
-      marcaje_servicio: MarcajeServicio::new(
+      centro_servicio: CentroServicio::new(CentroRepo::new(pool.clone())),
+      bolsa_servicio: BolsaServicio::new(
         cnfg.clone(),
-        MarcajeRepo::new(pool.clone()),
+        BolsaRepo::new(pool.clone()),
+        InformeServicio::new(cnfg.clone(), InformeRepo::new(pool.clone())),
+      ),
+      marcaje_servicio: marcaje(),
+      hora_extra_servicio: HoraExtraServicio::new(
+        cnfg.clone(),
+        HoraExtraRepo::new(pool.clone()),
+        InformeServicio::new(cnfg.clone(), InformeRepo::new(pool.clone())),
+      ),
+      ausencia_servicio: AusenciaServicio::new(
+        cnfg.clone(),
+        AusenciaRepo::new(pool.clone()),
         HorarioServicio::new(cnfg.clone(), HorarioRepo::new(pool.clone())),
       ),
       inc_servicio: IncidenciaServicio::new(
         cnfg.clone(),
         IncidenciaRepo::new(pool.clone()),
-        TrazaServicio::new(TrazaRepo::new()),
-        MarcajeServicio::new(
-          cnfg.clone(),
-          MarcajeRepo::new(pool.clone()),
-          HorarioServicio::new(cnfg.clone(), HorarioRepo::new(pool.clone())),
-        ),
+        traza(),
+        marcaje(),
+        integridad(),
       ),
       informe_servicio: InformeServicio::new(
         cnfg.clone(),
         InformeRepo::new(pool.clone()),
       ),
+      traza_servicio: traza(),
+      integridad_servicio: integridad(),
+      sesion_servicio,
+      proxies_confiables: cnfg.proxies_confiables.clone(),
     }
   }
 }
 
 /// Lanza los procesos de inicio de la aplicación
 ///
-/// Intenta crear el usuario administrador inicial
-/// si se encuentra configurado para ello.
-pub async fn lanzar_procesos_inicio(config: &Config, app: &AppState) {
+/// Carga las sesiones persistidas y las zonas horarias de los
+/// usuarios, e intenta crear el usuario
+/// administrador inicial si se encuentra configurado para ello.
+/// Si está activo, programa el tratamiento diario de los marcajes
+/// olvidados sin hora de salida.
+pub async fn lanzar_procesos_inicio(config: &Config, app: &Arc<AppState>) {
+  drop(
+    app
+      .sesion_servicio
+      .cargar()
+      .await
+      .inspect_err(|err| tracing::error!(error = %err, "Cargando sesiones")),
+  );
+
+  drop(
+    app
+      .usuario_servicio
+      .cargar_zonas_horarias()
+      .await
+      .inspect_err(|err| {
+        tracing::error!(error = %err, "Cargando zonas horarias de usuarios")
+      }),
+  );
+
   if config.boot_admin.crear {
     drop(
       app
@@ -90,4 +174,57 @@ pub async fn lanzar_procesos_inicio(config: &Config, app: &AppState) {
         ),
     );
   }
+
+  if config.cierre_marcajes.activo {
+    programar_cierre_marcajes(
+      config.cierre_marcajes,
+      config.zona_horaria,
+      app.clone(),
+    );
+  }
+}
+
+/// Lanza en segundo plano el tratamiento de los marcajes olvidados
+/// todos los días a la hora configurada.
+fn programar_cierre_marcajes(
+  cierre: CierreMarcajesConfig,
+  tz: Tz,
+  app: Arc<AppState>,
+) {
+  tokio::spawn(async move {
+    loop {
+      tokio::time::sleep(espera_hasta(cierre.hora, &tz)).await;
+
+      drop(
+        app
+          .inc_servicio
+          .tratar_marcajes_olvidados(cierre.modo.into())
+          .await
+          .inspect_err(
+            |err| tracing::error!(error = %err, "Tratando marcajes olvidados"),
+          ),
+      );
+    }
+  });
+}
+
+/// Tiempo que falta para la próxima vez que sea la hora dada
+/// en la zona horaria.
+///
+/// Si en un cambio de hora la hora no existe se salta a
+/// la del día siguiente.
+fn espera_hasta(hora: NaiveTime, tz: &Tz) -> Duration {
+  let ahora = Utc::now().with_timezone(tz);
+  let mut fecha = ahora.date_naive();
+
+  loop {
+    if let Some(siguiente) =
+      tz.from_local_datetime(&fecha.and_time(hora)).earliest()
+      && siguiente > ahora
+    {
+      return (siguiente - ahora).to_std().unwrap_or_default();
+    }
+
+    fecha = fecha.succ_opt().expect("Fecha fuera de rango");
+  }
 }
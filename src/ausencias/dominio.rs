@@ -0,0 +1,166 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::horario::{CalendarioFecha, TipoCalendarioFecha};
+
+/// Estados de una solicitud de ausencia.
+///
+/// Solo se pueden procesar las solicitudes pendientes. Una vez
+/// aprobada, rechazada o cancelada la solicitud no cambia.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EstadoAusencia {
+  Solicitud = 1,
+  Aprobada = 2,
+  Rechazada = 3,
+  /// La cancela el propio empleado antes de procesarla
+  Cancelada = 4,
+}
+
+impl TryFrom<u8> for EstadoAusencia {
+  type Error = u8;
+
+  /// Devuelve el valor recibido si no corresponde a ningún estado.
+  fn try_from(value: u8) -> Result<Self, Self::Error> {
+    match value {
+      1 => Ok(EstadoAusencia::Solicitud),
+      2 => Ok(EstadoAusencia::Aprobada),
+      3 => Ok(EstadoAusencia::Rechazada),
+      4 => Ok(EstadoAusencia::Cancelada),
+      _ => Err(value),
+    }
+  }
+}
+
+impl EstadoAusencia {
+  /// Indica si se puede pasar de este estado al estado destino.
+  pub fn permite(&self, destino: EstadoAusencia) -> bool {
+    matches!(
+      (self, destino),
+      (
+        EstadoAusencia::Solicitud,
+        EstadoAusencia::Aprobada
+          | EstadoAusencia::Rechazada
+          | EstadoAusencia::Cancelada
+      )
+    )
+  }
+}
+
+/// Solicitud de vacaciones, días propios o permiso de un empleado.
+#[derive(Debug, Clone)]
+pub struct SolicitudAusencia {
+  pub id: u32,
+  pub usuario: u32,
+  pub tipo: TipoCalendarioFecha,
+  pub fecha_inicio: NaiveDate,
+  pub fecha_fin: NaiveDate,
+  pub motivo: Option<String>,
+  pub estado: EstadoAusencia,
+  pub fecha_solicitud: NaiveDateTime,
+  /// Gestor que aprueba o rechaza la solicitud
+  pub usuario_gestor: Option<u32>,
+  pub fecha_estado: Option<NaiveDateTime>,
+  pub motivo_rechazo: Option<String>,
+  /// Fecha del calendario personal creada al aprobar la solicitud
+  pub calendario_fecha: Option<u32>,
+}
+
+impl SolicitudAusencia {
+  /// Valida el tipo y las fechas de una nueva solicitud.
+  ///
+  /// Los empleados solo pueden solicitar vacaciones, días propios
+  /// o permisos.
+  pub fn validar(&self) -> Result<(), &'static str> {
+    if !matches!(
+      self.tipo,
+      TipoCalendarioFecha::Vacaciones
+        | TipoCalendarioFecha::DiasPropios
+        | TipoCalendarioFecha::Permiso
+    ) {
+      return Err(
+        "Solo se pueden solicitar vacaciones, días propios o permisos",
+      );
+    }
+
+    if self.fecha_inicio > self.fecha_fin {
+      return Err("La fecha de inicio no puede ser posterior a la de fin");
+    }
+
+    Ok(())
+  }
+
+  /// Fecha del calendario personal que materializa la solicitud.
+  pub fn calendario_fecha(&self, calendario: u32) -> CalendarioFecha {
+    CalendarioFecha {
+      id: 0,
+      calendario,
+      fecha_inicio: self.fecha_inicio,
+      fecha_fin: self.fecha_fin,
+      tipo: self.tipo,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn solicitud(
+    tipo: TipoCalendarioFecha,
+    inicio: u32,
+    fin: u32,
+  ) -> SolicitudAusencia {
+    let fecha = |dia| NaiveDate::from_ymd_opt(2025, 8, dia).unwrap();
+
+    SolicitudAusencia {
+      id: 0,
+      usuario: 1,
+      tipo,
+      fecha_inicio: fecha(inicio),
+      fecha_fin: fecha(fin),
+      motivo: None,
+      estado: EstadoAusencia::Solicitud,
+      fecha_solicitud: fecha(1).and_hms_opt(9, 0, 0).unwrap(),
+      usuario_gestor: None,
+      fecha_estado: None,
+      motivo_rechazo: None,
+      calendario_fecha: None,
+    }
+  }
+
+  #[test]
+  fn test_estado_ausencia_transiciones() {
+    use EstadoAusencia::*;
+
+    assert!(Solicitud.permite(Aprobada));
+    assert!(Solicitud.permite(Rechazada));
+    assert!(Solicitud.permite(Cancelada));
+    assert!(!Solicitud.permite(Solicitud));
+
+    for estado in [Aprobada, Rechazada, Cancelada] {
+      for destino in [Solicitud, Aprobada, Rechazada, Cancelada] {
+        assert!(!estado.permite(destino), "{estado:?} -> {destino:?}");
+      }
+    }
+  }
+
+  #[test]
+  fn test_estado_ausencia_desde_valor() {
+    assert_eq!(EstadoAusencia::try_from(1), Ok(EstadoAusencia::Solicitud));
+    assert_eq!(EstadoAusencia::try_from(4), Ok(EstadoAusencia::Cancelada));
+    assert_eq!(EstadoAusencia::try_from(0), Err(0));
+    assert_eq!(EstadoAusencia::try_from(9), Err(9));
+  }
+
+  #[test]
+  fn test_validar_solicitud_ausencia() {
+    let valida = |tipo, inicio, fin| solicitud(tipo, inicio, fin).validar();
+
+    assert!(valida(TipoCalendarioFecha::Vacaciones, 4, 15).is_ok());
+    assert!(valida(TipoCalendarioFecha::DiasPropios, 4, 4).is_ok());
+    assert!(valida(TipoCalendarioFecha::Permiso, 4, 5).is_ok());
+    assert!(valida(TipoCalendarioFecha::Festivo, 4, 4).is_err());
+    assert!(valida(TipoCalendarioFecha::Baja, 4, 4).is_err());
+    assert!(valida(TipoCalendarioFecha::Vacaciones, 5, 4).is_err());
+  }
+}
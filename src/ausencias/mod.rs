@@ -0,0 +1,20 @@
+//! Solicitudes de ausencia de los empleados.
+//!
+//! Los empleados solicitan vacaciones, días propios o permisos para un
+//! rango de fechas y un gestor las aprueba o las rechaza. El empleado
+//! puede cancelar sus solicitudes mientras estén pendientes.
+//!
+//! Al aprobar una solicitud se crea la fecha en el calendario personal
+//! del empleado, que se crea y se le asigna la primera vez. Antes se
+//! comprueba que el empleado no tenga marcajes en esas fechas, igual
+//! que al crear cualquier fecha de un calendario.
+mod repo;
+
+/// Módulo que define el dominio de las solicitudes de ausencia
+mod dominio;
+/// Módulo con los servicios de las solicitudes de ausencia
+mod servicio;
+
+pub use dominio::*;
+pub use repo::*;
+pub use servicio::*;
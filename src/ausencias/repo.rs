@@ -0,0 +1,195 @@
+use chrono::NaiveDateTime;
+use sqlx::{Row, mysql::MySqlRow};
+
+use crate::{
+  ausencias::{EstadoAusencia, SolicitudAusencia},
+  infra::{DBError, PoolConexion, Transaccion},
+};
+
+/// Implementación del repositorio de las solicitudes de ausencia.
+pub struct AusenciaRepo {
+  pool: PoolConexion,
+}
+
+impl AusenciaRepo {
+  pub fn new(pool: PoolConexion) -> Self {
+    AusenciaRepo { pool }
+  }
+
+  pub(in crate::ausencias) fn conexion(&self) -> &PoolConexion {
+    &self.pool
+  }
+
+  /// Devuelve las solicitudes de ausencia ordenadas por fecha de inicio
+  /// descendente.
+  ///
+  /// Permite filtrar por usuario, por estado y por el centro
+  /// de trabajo del usuario.
+  pub(in crate::ausencias) async fn solicitudes(
+    &self,
+    usuario: Option<u32>,
+    estado: Option<EstadoAusencia>,
+    centro: Option<u32>,
+  ) -> Result<Vec<SolicitudAusencia>, DBError> {
+    let mut qb = sqlx::QueryBuilder::<sqlx::MySql>::new(
+      "SELECT s.id, s.usuario, s.tipo, s.fecha_inicio, s.fecha_fin,
+        s.motivo, s.estado, s.fecha_solicitud, s.usuario_gestor,
+        s.fecha_estado, s.motivo_rechazo, s.calendario_fecha
+      FROM solicitudes_ausencia s
+      JOIN usuarios u ON u.id = s.usuario
+      WHERE 1 = 1",
+    );
+
+    if let Some(usuario) = usuario {
+      qb.push(" AND s.usuario = ");
+      qb.push_bind(usuario);
+    }
+
+    if let Some(estado) = estado {
+      qb.push(" AND s.estado = ");
+      qb.push_bind(estado as u8);
+    }
+
+    if let Some(centro) = centro {
+      qb.push(" AND u.centro = ");
+      qb.push_bind(centro);
+    }
+
+    qb.push(" ORDER BY s.fecha_inicio DESC, s.id DESC");
+
+    let rows = qb
+      .build()
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(rows.iter().map(solicitud_from_row).collect())
+  }
+
+  /// Devuelve una solicitud de ausencia por su id.
+  pub(in crate::ausencias) async fn solicitud(
+    &self,
+    id: u32,
+  ) -> Result<SolicitudAusencia, DBError> {
+    const QUERY: &str = "SELECT id, usuario, tipo, fecha_inicio, fecha_fin,
+      motivo, estado, fecha_solicitud, usuario_gestor, fecha_estado,
+      motivo_rechazo, calendario_fecha
+      FROM solicitudes_ausencia WHERE id = ?";
+
+    let row = sqlx::query(QUERY)
+      .bind(id)
+      .fetch_optional(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    row.map(|r| solicitud_from_row(&r)).ok_or_else(|| {
+      DBError::registro_vacio(format!(
+        "Solicitud de ausencia no encontrada: {}",
+        id
+      ))
+    })
+  }
+
+  /// Indica si el usuario tiene solicitudes pendientes o aprobadas
+  /// que se solapan con el rango de fechas.
+  pub(in crate::ausencias) async fn solapada(
+    &self,
+    solicitud: &SolicitudAusencia,
+  ) -> Result<bool, DBError> {
+    const QUERY: &str = "SELECT CAST(COUNT(id) AS UNSIGNED)
+      FROM solicitudes_ausencia
+      WHERE usuario = ? AND estado IN (?, ?)
+      AND fecha_inicio <= ? AND fecha_fin >= ?";
+
+    let solapadas: u32 = sqlx::query_scalar(QUERY)
+      .bind(solicitud.usuario)
+      .bind(EstadoAusencia::Solicitud as u8)
+      .bind(EstadoAusencia::Aprobada as u8)
+      .bind(solicitud.fecha_fin)
+      .bind(solicitud.fecha_inicio)
+      .fetch_one(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(solapadas > 0)
+  }
+
+  /// Registra una nueva solicitud de ausencia.
+  ///
+  /// Devuelve el ID de la solicitud creada.
+  pub(in crate::ausencias) async fn agregar(
+    &self,
+    solicitud: &SolicitudAusencia,
+  ) -> Result<u32, DBError> {
+    const QUERY: &str = "INSERT INTO solicitudes_ausencia
+      (usuario, tipo, fecha_inicio, fecha_fin, motivo, estado,
+      fecha_solicitud)
+      VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+    let res = sqlx::query(QUERY)
+      .bind(solicitud.usuario)
+      .bind(solicitud.tipo as u8)
+      .bind(solicitud.fecha_inicio)
+      .bind(solicitud.fecha_fin)
+      .bind(&solicitud.motivo)
+      .bind(EstadoAusencia::Solicitud as u8)
+      .bind(solicitud.fecha_solicitud)
+      .execute(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(res.last_insert_id() as u32)
+  }
+
+  /// Cambia el estado de una solicitud pendiente.
+  ///
+  /// Devuelve falso si la solicitud ya no estaba pendiente.
+  #[allow(clippy::too_many_arguments)]
+  pub(in crate::ausencias) async fn cambiar_estado(
+    &self,
+    trans: &mut Transaccion<'_>,
+    id: u32,
+    estado: EstadoAusencia,
+    usuario_gestor: Option<u32>,
+    fecha_estado: NaiveDateTime,
+    motivo_rechazo: Option<&str>,
+    calendario_fecha: Option<u32>,
+  ) -> Result<bool, DBError> {
+    const QUERY: &str = "UPDATE solicitudes_ausencia
+      SET estado = ?, usuario_gestor = ?, fecha_estado = ?,
+        motivo_rechazo = ?, calendario_fecha = ?
+      WHERE id = ? AND estado = ?";
+
+    let res = sqlx::query(QUERY)
+      .bind(estado as u8)
+      .bind(usuario_gestor)
+      .bind(fecha_estado)
+      .bind(motivo_rechazo)
+      .bind(calendario_fecha)
+      .bind(id)
+      .bind(EstadoAusencia::Solicitud as u8)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(res.rows_affected() > 0)
+  }
+}
+
+fn solicitud_from_row(row: &MySqlRow) -> SolicitudAusencia {
+  SolicitudAusencia {
+    id: row.get("id"),
+    usuario: row.get("usuario"),
+    tipo: (row.get::<u16, _>("tipo") as u8).into(),
+    fecha_inicio: row.get("fecha_inicio"),
+    fecha_fin: row.get("fecha_fin"),
+    motivo: row.get("motivo"),
+    estado: EstadoAusencia::try_from(row.get::<u8, _>("estado"))
+      .expect("Valor de estado de ausencia no válido"),
+    fecha_solicitud: row.get("fecha_solicitud"),
+    usuario_gestor: row.get("usuario_gestor"),
+    fecha_estado: row.get("fecha_estado"),
+    motivo_rechazo: row.get("motivo_rechazo"),
+    calendario_fecha: row.get("calendario_fecha"),
+  }
+}
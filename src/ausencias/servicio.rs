@@ -0,0 +1,323 @@
+use crate::ausencias::{AusenciaRepo, EstadoAusencia, SolicitudAusencia};
+use crate::config::ConfigTrabajo;
+use crate::horario::HorarioServicio;
+use crate::infra::{ServicioError, Transaccion};
+
+/// Servicio para gestionar las solicitudes de ausencia de los empleados.
+pub struct AusenciaServicio {
+  cnfg: ConfigTrabajo,
+  repo: AusenciaRepo,
+  srv_horario: HorarioServicio,
+}
+
+impl AusenciaServicio {
+  pub fn new(
+    cnfg: ConfigTrabajo,
+    repo: AusenciaRepo,
+    srv_horario: HorarioServicio,
+  ) -> Self {
+    AusenciaServicio {
+      cnfg,
+      repo,
+      srv_horario,
+    }
+  }
+}
+
+impl AusenciaServicio {
+  /// Devuelve las solicitudes de ausencia filtradas por usuario, estado
+  /// y centro de trabajo del usuario.
+  pub async fn solicitudes(
+    &self,
+    usuario: Option<u32>,
+    estado: Option<EstadoAusencia>,
+    centro: Option<u32>,
+  ) -> Result<Vec<SolicitudAusencia>, ServicioError> {
+    tracing::debug!(
+      usuario = ?usuario,
+      estado = ?estado,
+      centro = ?centro,
+      "Obteniendo solicitudes de ausencia"
+    );
+
+    self
+      .repo
+      .solicitudes(usuario, estado, centro)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = ?usuario, estado = ?estado, centro = ?centro,
+          error = %err, "Obteniendo solicitudes de ausencia");
+        ServicioError::from(err)
+      })
+  }
+
+  /// Devuelve una solicitud de ausencia por su identificador.
+  pub async fn solicitud(
+    &self,
+    id: u32,
+  ) -> Result<SolicitudAusencia, ServicioError> {
+    self.repo.solicitud(id).await.map_err(|err| {
+      tracing::error!(
+        id = id, error = %err, "Obteniendo solicitud de ausencia");
+      ServicioError::from(err)
+    })
+  }
+
+  /// Registra una nueva solicitud de ausencia pendiente de aprobar.
+  ///
+  /// No se permiten solicitudes que se solapen con otras pendientes
+  /// o aprobadas del mismo usuario.
+  pub async fn solicitar(
+    &self,
+    solicitud: &mut SolicitudAusencia,
+  ) -> Result<u32, ServicioError> {
+    tracing::info!(
+      solicitud = ?solicitud,
+      "Iniciando registro de solicitud de ausencia"
+    );
+
+    solicitud
+      .validar()
+      .map_err(|err| ServicioError::Validacion(err.to_string()))?;
+
+    let solapada = self.repo.solapada(solicitud).await.map_err(|err| {
+      tracing::error!(
+        solicitud = ?solicitud, error = %err,
+        "Comprobando solapes de la solicitud de ausencia");
+      ServicioError::from(err)
+    })?;
+
+    if solapada {
+      return Err(ServicioError::Usuario(
+        "Ya existe una solicitud pendiente o aprobada en esas fechas"
+          .to_string(),
+      ));
+    }
+
+    solicitud.estado = EstadoAusencia::Solicitud;
+    solicitud.fecha_solicitud = self.cnfg.ahora();
+
+    let id = self.repo.agregar(solicitud).await.map_err(|err| {
+      tracing::error!(
+        solicitud = ?solicitud, error = %err,
+        "Registrando solicitud de ausencia");
+      ServicioError::from(err)
+    })?;
+
+    tracing::debug!(id = id, "Solicitud de ausencia registrada con éxito");
+
+    Ok(id)
+  }
+
+  /// Aprueba una solicitud de ausencia pendiente.
+  ///
+  /// Crea la fecha en el calendario personal del empleado validando
+  /// antes que no tenga marcajes en esas fechas. La fecha y el cambio
+  /// de estado se guardan en la misma transacción, de modo que si la
+  /// solicitud se ha procesado mientras tanto no se crea la fecha.
+  pub async fn aprobar(
+    &self,
+    usuario_gestor: u32,
+    id: u32,
+  ) -> Result<(), ServicioError> {
+    tracing::info!(
+      id = id,
+      gestor = usuario_gestor,
+      "Iniciando aprobación de solicitud de ausencia"
+    );
+
+    let solicitud = self.pendiente(id, EstadoAusencia::Aprobada).await?;
+
+    let calendario = self
+      .srv_horario
+      .calendario_personal(solicitud.usuario)
+      .await?;
+
+    let mut tr = self.transaccion(id).await?;
+
+    let fecha = self
+      .srv_horario
+      .agregar_calendario_fecha(
+        &mut tr,
+        &solicitud.calendario_fecha(calendario),
+      )
+      .await?;
+
+    self
+      .cambiar_estado(
+        &mut tr,
+        id,
+        EstadoAusencia::Aprobada,
+        Some(usuario_gestor),
+        None,
+        Some(fecha),
+      )
+      .await?;
+
+    self.commit(tr, id).await
+  }
+
+  /// Rechaza una solicitud de ausencia pendiente indicando el motivo.
+  pub async fn rechazar(
+    &self,
+    usuario_gestor: u32,
+    id: u32,
+    motivo: Option<&str>,
+  ) -> Result<(), ServicioError> {
+    tracing::info!(
+      id = id,
+      gestor = usuario_gestor,
+      "Iniciando rechazo de solicitud de ausencia"
+    );
+
+    self.pendiente(id, EstadoAusencia::Rechazada).await?;
+
+    self
+      .procesar(id, EstadoAusencia::Rechazada, Some(usuario_gestor), motivo)
+      .await
+  }
+
+  /// Cancela una solicitud de ausencia pendiente de su autor.
+  pub async fn cancelar(
+    &self,
+    usuario: u32,
+    id: u32,
+  ) -> Result<(), ServicioError> {
+    tracing::info!(
+      id = id,
+      usuario = usuario,
+      "Iniciando cancelación de solicitud de ausencia"
+    );
+
+    let solicitud = self.pendiente(id, EstadoAusencia::Cancelada).await?;
+
+    if solicitud.usuario != usuario {
+      return Err(ServicioError::Usuario(
+        "Solo el autor puede cancelar la solicitud de ausencia".to_string(),
+      ));
+    }
+
+    self
+      .procesar(id, EstadoAusencia::Cancelada, None, None)
+      .await
+  }
+
+  /// Obtiene una solicitud comprobando que puede pasar al estado destino.
+  async fn pendiente(
+    &self,
+    id: u32,
+    destino: EstadoAusencia,
+  ) -> Result<SolicitudAusencia, ServicioError> {
+    let solicitud = self.solicitud(id).await?;
+
+    if !solicitud.estado.permite(destino) {
+      return Err(ServicioError::Usuario(
+        "La solicitud de ausencia ya ha sido procesada".to_string(),
+      ));
+    }
+
+    Ok(solicitud)
+  }
+
+  /// Cambia el estado de una solicitud que no crea fechas en calendario.
+  async fn procesar(
+    &self,
+    id: u32,
+    estado: EstadoAusencia,
+    usuario_gestor: Option<u32>,
+    motivo_rechazo: Option<&str>,
+  ) -> Result<(), ServicioError> {
+    let mut tr = self.transaccion(id).await?;
+
+    self
+      .cambiar_estado(&mut tr, id, estado, usuario_gestor, motivo_rechazo, None)
+      .await?;
+
+    self.commit(tr, id).await
+  }
+
+  /// Cambia el estado de una solicitud pendiente dentro de la transacción.
+  ///
+  /// Falla si la solicitud se ha procesado mientras tanto.
+  async fn cambiar_estado(
+    &self,
+    trans: &mut Transaccion<'_>,
+    id: u32,
+    estado: EstadoAusencia,
+    usuario_gestor: Option<u32>,
+    motivo_rechazo: Option<&str>,
+    calendario_fecha: Option<u32>,
+  ) -> Result<(), ServicioError> {
+    let cambiado = self
+      .repo
+      .cambiar_estado(
+        trans,
+        id,
+        estado,
+        usuario_gestor,
+        self.cnfg.ahora(),
+        motivo_rechazo,
+        calendario_fecha,
+      )
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          id = id, estado = ?estado, error = %err,
+          "Cambiando estado de solicitud de ausencia");
+        ServicioError::from(err)
+      })?;
+
+    if !cambiado {
+      tracing::warn!(
+        id = id,
+        estado = ?estado,
+        "No se ha podido cambiar el estado de la solicitud de ausencia, \
+        posiblemente ya estaba procesada"
+      );
+      return Err(ServicioError::Usuario(
+        "La solicitud de ausencia ya ha sido procesada".to_string(),
+      ));
+    }
+
+    tracing::debug!(
+      id = id,
+      estado = ?estado,
+      "Estado de solicitud de ausencia cambiado con éxito"
+    );
+
+    Ok(())
+  }
+
+  /// Inicia la transacción para procesar una solicitud.
+  async fn transaccion(
+    &self,
+    id: u32,
+  ) -> Result<Transaccion<'_>, ServicioError> {
+    self
+      .repo
+      .conexion()
+      .empezar_transaccion()
+      .await
+      .map_err(|err| {
+        tracing::error!(
+        id = id, error = %err,
+        "Iniciando transacción para procesar solicitud de ausencia");
+        ServicioError::from(err)
+      })
+  }
+
+  /// Confirma la transacción de una solicitud procesada.
+  async fn commit(
+    &self,
+    tr: Transaccion<'_>,
+    id: u32,
+  ) -> Result<(), ServicioError> {
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        id = id, error = %err,
+        "Commit transacción para procesar solicitud de ausencia");
+      ServicioError::from(err)
+    })
+  }
+}
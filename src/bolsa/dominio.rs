@@ -0,0 +1,295 @@
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime, TimeDelta};
+
+/// Tipo de movimiento de la bolsa de horas.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TipoMovimientoBolsa {
+  /// Saldo del informe de cumplimiento horario de un mes
+  SaldoMensual = 1,
+  /// Corrección manual de un gestor
+  Ajuste = 2,
+  /// Tiempo libre disfrutado a cambio del saldo a favor
+  Compensacion = 3,
+  /// Minutos a favor no consumidos en el plazo configurado
+  Caducidad = 4,
+  /// Exceso sobre los topes configurados
+  Tope = 5,
+}
+
+impl From<u8> for TipoMovimientoBolsa {
+  fn from(value: u8) -> Self {
+    match value {
+      1 => TipoMovimientoBolsa::SaldoMensual,
+      2 => TipoMovimientoBolsa::Ajuste,
+      3 => TipoMovimientoBolsa::Compensacion,
+      4 => TipoMovimientoBolsa::Caducidad,
+      5 => TipoMovimientoBolsa::Tope,
+      _ => panic!("Valor de tipo de movimiento de bolsa no válido"),
+    }
+  }
+}
+
+/// Movimiento de la bolsa de horas de un empleado.
+#[derive(Debug)]
+pub struct MovimientoBolsa {
+  pub id: u32,
+  pub usuario: u32,
+  /// Fecha a la que se imputa el movimiento. Los saldos mensuales
+  /// se imputan al último día del mes
+  pub fecha: NaiveDate,
+  pub tipo: TipoMovimientoBolsa,
+  /// Minutos a favor (positivo) o en contra (negativo) del empleado
+  pub minutos: i32,
+  pub motivo: Option<String>,
+  /// Usuario que registra el movimiento. None si lo registra el sistema
+  pub autor: Option<u32>,
+  pub fecha_registro: NaiveDateTime,
+}
+
+/// Reglas de la bolsa de horas. Los valores a None no se aplican.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReglasBolsa {
+  /// Minutos máximos a favor del empleado
+  pub maximo: Option<u32>,
+  /// Minutos máximos en contra del empleado
+  pub deficit_maximo: Option<u32>,
+  /// Meses tras los que caducan los minutos a favor no consumidos
+  pub caducidad_meses: Option<u32>,
+}
+
+impl ReglasBolsa {
+  /// Minutos a anotar para que el saldo quede dentro de los topes.
+  ///
+  /// Devuelve 0 si el saldo está dentro de los topes.
+  pub fn ajuste_tope(&self, saldo: i64) -> i64 {
+    match (self.maximo, self.deficit_maximo) {
+      (Some(maximo), _) if saldo > maximo as i64 => maximo as i64 - saldo,
+      (_, Some(deficit)) if saldo < -(deficit as i64) => {
+        -(deficit as i64) - saldo
+      }
+      _ => 0,
+    }
+  }
+
+  /// Fecha a partir de la cual los minutos a favor siguen vigentes
+  /// al cerrar el mes que termina en la fecha dada.
+  ///
+  /// Los minutos imputados antes de esta fecha caducan.
+  pub fn limite_caducidad(&self, fin_mes: NaiveDate) -> Option<NaiveDate> {
+    self.caducidad_meses.and_then(|meses| {
+      (fin_mes + TimeDelta::days(1)).checked_sub_months(Months::new(meses))
+    })
+  }
+}
+
+/// Saldo en minutos de los movimientos.
+pub fn saldo_bolsa(movimientos: &[MovimientoBolsa]) -> i64 {
+  movimientos.iter().map(|m| m.minutos as i64).sum()
+}
+
+/// Minutos a favor imputados antes del límite que aún no se han consumido.
+///
+/// Los movimientos negativos consumen primero los minutos más antiguos,
+/// por lo que solo caduca lo que queda de ellos.
+pub fn minutos_caducados(
+  movimientos: &[MovimientoBolsa],
+  limite: NaiveDate,
+) -> i64 {
+  let antiguos: i64 = movimientos
+    .iter()
+    .filter(|m| m.minutos > 0 && m.fecha < limite)
+    .map(|m| m.minutos as i64)
+    .sum();
+
+  let consumidos: i64 = movimientos
+    .iter()
+    .filter(|m| m.minutos < 0)
+    .map(|m| -(m.minutos as i64))
+    .sum();
+
+  (antiguos - consumidos).max(0)
+}
+
+/// Resumen de la bolsa de horas de un mes.
+///
+/// Los minutos conservan el signo de los movimientos.
+#[derive(Debug, Default)]
+pub struct ResumenMesBolsa {
+  pub mes: u32,
+  /// Saldo del informe de cumplimiento horario
+  pub generado: i64,
+  pub ajustes: i64,
+  pub compensado: i64,
+  /// Minutos descartados por caducidad o por los topes
+  pub descartado: i64,
+  /// Saldo acumulado al final del mes
+  pub saldo: i64,
+}
+
+/// Vista consolidada de la bolsa de horas de un empleado para un año.
+#[derive(Debug)]
+pub struct ResumenAnualBolsa {
+  pub usuario: u32,
+  pub anio: i32,
+  /// Saldo arrastrado de los años anteriores
+  pub saldo_inicial: i64,
+  pub meses: Vec<ResumenMesBolsa>,
+  pub saldo_final: i64,
+  pub movimientos: Vec<MovimientoBolsa>,
+}
+
+impl ResumenAnualBolsa {
+  /// Compone el resumen a partir del saldo de los años anteriores
+  /// y los movimientos del año.
+  pub fn new(
+    usuario: u32,
+    anio: i32,
+    saldo_inicial: i64,
+    movimientos: Vec<MovimientoBolsa>,
+  ) -> Self {
+    let mut meses: Vec<ResumenMesBolsa> = (1..=12)
+      .map(|mes| ResumenMesBolsa {
+        mes,
+        ..Default::default()
+      })
+      .collect();
+
+    for m in movimientos.iter().filter(|m| m.fecha.year() == anio) {
+      let resumen = &mut meses[m.fecha.month0() as usize];
+      let minutos = m.minutos as i64;
+
+      match m.tipo {
+        TipoMovimientoBolsa::SaldoMensual => resumen.generado += minutos,
+        TipoMovimientoBolsa::Ajuste => resumen.ajustes += minutos,
+        TipoMovimientoBolsa::Compensacion => resumen.compensado += minutos,
+        TipoMovimientoBolsa::Caducidad | TipoMovimientoBolsa::Tope => {
+          resumen.descartado += minutos
+        }
+      }
+    }
+
+    let mut saldo = saldo_inicial;
+    for resumen in meses.iter_mut() {
+      saldo += resumen.generado
+        + resumen.ajustes
+        + resumen.compensado
+        + resumen.descartado;
+      resumen.saldo = saldo;
+    }
+
+    ResumenAnualBolsa {
+      usuario,
+      anio,
+      saldo_inicial,
+      meses,
+      saldo_final: saldo,
+      movimientos,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fecha(anio: i32, mes: u32, dia: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(anio, mes, dia).unwrap()
+  }
+
+  fn movimiento(
+    fecha: NaiveDate,
+    tipo: TipoMovimientoBolsa,
+    minutos: i32,
+  ) -> MovimientoBolsa {
+    MovimientoBolsa {
+      id: 0,
+      usuario: 1,
+      fecha,
+      tipo,
+      minutos,
+      motivo: None,
+      autor: None,
+      fecha_registro: fecha.and_hms_opt(0, 0, 0).unwrap(),
+    }
+  }
+
+  #[test]
+  fn test_ajuste_tope() {
+    let reglas = ReglasBolsa {
+      maximo: Some(600),
+      deficit_maximo: Some(120),
+      caducidad_meses: None,
+    };
+
+    assert_eq!(reglas.ajuste_tope(500), 0);
+    assert_eq!(reglas.ajuste_tope(700), -100);
+    assert_eq!(reglas.ajuste_tope(-120), 0);
+    assert_eq!(reglas.ajuste_tope(-200), 80);
+    assert_eq!(ReglasBolsa::default().ajuste_tope(10_000), 0);
+  }
+
+  #[test]
+  fn test_limite_caducidad() {
+    let reglas = ReglasBolsa {
+      caducidad_meses: Some(12),
+      ..Default::default()
+    };
+
+    assert_eq!(
+      reglas.limite_caducidad(fecha(2026, 1, 31)),
+      Some(fecha(2025, 2, 1))
+    );
+    assert_eq!(
+      ReglasBolsa::default().limite_caducidad(fecha(2026, 1, 31)),
+      None
+    );
+  }
+
+  #[test]
+  fn test_minutos_caducados_consume_los_mas_antiguos() {
+    use TipoMovimientoBolsa::*;
+
+    let movimientos = vec![
+      movimiento(fecha(2025, 1, 31), SaldoMensual, 300),
+      movimiento(fecha(2025, 2, 28), SaldoMensual, 200),
+      movimiento(fecha(2025, 3, 10), Compensacion, -240),
+      movimiento(fecha(2025, 3, 31), SaldoMensual, -30),
+    ];
+
+    // Enero: 300 - 270 consumidos
+    assert_eq!(minutos_caducados(&movimientos, fecha(2025, 2, 1)), 30);
+    // Enero y febrero: 500 - 270 consumidos
+    assert_eq!(minutos_caducados(&movimientos, fecha(2025, 3, 1)), 230);
+    assert_eq!(saldo_bolsa(&movimientos), 230);
+    // Sin minutos a favor anteriores al límite
+    assert_eq!(minutos_caducados(&movimientos, fecha(2025, 1, 1)), 0);
+  }
+
+  #[test]
+  fn test_resumen_anual() {
+    use TipoMovimientoBolsa::*;
+
+    let resumen = ResumenAnualBolsa::new(
+      1,
+      2025,
+      60,
+      vec![
+        movimiento(fecha(2025, 1, 31), SaldoMensual, 300),
+        movimiento(fecha(2025, 2, 14), Ajuste, 45),
+        movimiento(fecha(2025, 2, 20), Compensacion, -240),
+        movimiento(fecha(2025, 2, 28), SaldoMensual, -30),
+        movimiento(fecha(2025, 2, 28), Caducidad, -15),
+      ],
+    );
+
+    assert_eq!(resumen.meses.len(), 12);
+    assert_eq!(resumen.meses[0].generado, 300);
+    assert_eq!(resumen.meses[0].saldo, 360);
+    assert_eq!(resumen.meses[1].ajustes, 45);
+    assert_eq!(resumen.meses[1].compensado, -240);
+    assert_eq!(resumen.meses[1].descartado, -15);
+    assert_eq!(resumen.meses[1].saldo, 120);
+    assert_eq!(resumen.meses[11].saldo, 120);
+    assert_eq!(resumen.saldo_final, 120);
+  }
+}
@@ -0,0 +1,28 @@
+//! Gestiona la bolsa de horas de los empleados.
+//!
+//! La bolsa es un libro de movimientos en minutos que nunca se
+//! modifican ni eliminan. Los movimientos positivos suman horas a
+//! favor del empleado y los negativos las restan:
+//!
+//! - Saldo mensual: Al cerrar un mes se anota el saldo total del
+//!   informe de cumplimiento horario. Cada mes se cierra una sola vez.
+//! - Ajuste: Corrección manual de un gestor, siempre con un motivo.
+//! - Compensación: Tiempo libre que disfruta el empleado a cambio
+//!   del saldo a su favor. No puede superar el saldo disponible.
+//! - Caducidad: Minutos a favor que no se han consumido en los meses
+//!   configurados. Los consumos gastan primero los minutos más antiguos.
+//! - Tope: Exceso sobre el máximo a favor o el déficit máximo
+//!   configurados que se descarta al cerrar el mes.
+//!
+//! La caducidad y los topes se aplican al cerrar cada mes, de forma que
+//! el saldo se arrastra de un mes a otro y de un año al siguiente.
+mod repo;
+
+/// Módulo que define el dominio de la bolsa de horas
+mod dominio;
+/// Módulo con los servicios de la bolsa de horas
+mod servicio;
+
+pub use dominio::*;
+pub use repo::*;
+pub use servicio::*;
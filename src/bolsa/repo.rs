@@ -0,0 +1,171 @@
+use chrono::NaiveDate;
+use sqlx::{Row, mysql::MySqlRow};
+
+use crate::{
+  bolsa::{MovimientoBolsa, TipoMovimientoBolsa},
+  infra::{DBError, PoolConexion, Transaccion},
+};
+
+/// Implementación del repositorio de la bolsa de horas.
+pub struct BolsaRepo {
+  pool: PoolConexion,
+}
+
+impl BolsaRepo {
+  pub fn new(pool: PoolConexion) -> Self {
+    BolsaRepo { pool }
+  }
+
+  pub(in crate::bolsa) fn conexion(&self) -> &PoolConexion {
+    &self.pool
+  }
+
+  /// Devuelve los movimientos de un usuario hasta la fecha dada
+  /// ordenados por fecha y orden de registro.
+  pub(in crate::bolsa) async fn movimientos(
+    &self,
+    usuario: u32,
+    hasta: NaiveDate,
+  ) -> Result<Vec<MovimientoBolsa>, DBError> {
+    const QUERY: &str = "SELECT id, usuario, fecha, tipo, minutos,
+      motivo, autor, fecha_registro
+      FROM bolsa_horas
+      WHERE usuario = ? AND fecha <= ?
+      ORDER BY fecha, id";
+
+    let rows = sqlx::query(QUERY)
+      .bind(usuario)
+      .bind(hasta)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(rows.iter().map(movimiento_from_row).collect())
+  }
+
+  /// Devuelve los movimientos de un usuario en un año
+  /// ordenados por fecha y orden de registro.
+  pub(in crate::bolsa) async fn movimientos_anio(
+    &self,
+    usuario: u32,
+    anio: i32,
+  ) -> Result<Vec<MovimientoBolsa>, DBError> {
+    const QUERY: &str = "SELECT id, usuario, fecha, tipo, minutos,
+      motivo, autor, fecha_registro
+      FROM bolsa_horas
+      WHERE usuario = ? AND YEAR(fecha) = ?
+      ORDER BY fecha, id";
+
+    let rows = sqlx::query(QUERY)
+      .bind(usuario)
+      .bind(anio)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(rows.iter().map(movimiento_from_row).collect())
+  }
+
+  /// Saldo en minutos de los movimientos de un usuario anteriores
+  /// a la fecha dada. Si la fecha es None se suman todos.
+  pub(in crate::bolsa) async fn saldo(
+    &self,
+    usuario: u32,
+    antes_de: Option<NaiveDate>,
+  ) -> Result<i64, DBError> {
+    const QUERY: &str = "SELECT CAST(COALESCE(SUM(minutos), 0) AS SIGNED)
+      FROM bolsa_horas
+      WHERE usuario = ? AND (? IS NULL OR fecha < ?)";
+
+    sqlx::query_scalar(QUERY)
+      .bind(usuario)
+      .bind(antes_de)
+      .bind(antes_de)
+      .fetch_one(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)
+  }
+
+  /// Saldo en minutos de todos los movimientos de un usuario.
+  ///
+  /// Bloquea los movimientos del usuario hasta que termine la
+  /// transacción, de modo que no se puedan anotar otros entretanto.
+  pub(in crate::bolsa) async fn saldo_bloqueado(
+    &self,
+    trans: &mut Transaccion<'_>,
+    usuario: u32,
+  ) -> Result<i64, DBError> {
+    const QUERY: &str = "SELECT CAST(COALESCE(SUM(minutos), 0) AS SIGNED)
+      FROM bolsa_horas
+      WHERE usuario = ?
+      FOR UPDATE";
+
+    sqlx::query_scalar(QUERY)
+      .bind(usuario)
+      .fetch_one(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)
+  }
+
+  /// Indica si ya se ha anotado el saldo mensual del mes
+  /// que termina en la fecha dada.
+  pub(in crate::bolsa) async fn existe_cierre(
+    &self,
+    usuario: u32,
+    fin_mes: NaiveDate,
+  ) -> Result<bool, DBError> {
+    const QUERY: &str = "SELECT CAST(COUNT(id) AS UNSIGNED)
+      FROM bolsa_horas
+      WHERE usuario = ? AND fecha = ? AND tipo = ?";
+
+    let count: u32 = sqlx::query_scalar(QUERY)
+      .bind(usuario)
+      .bind(fin_mes)
+      .bind(TipoMovimientoBolsa::SaldoMensual as u8)
+      .fetch_one(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(count > 0)
+  }
+
+  /// Anota un movimiento en la bolsa de horas.
+  ///
+  /// Devuelve el ID del movimiento creado.
+  pub(in crate::bolsa) async fn agregar(
+    &self,
+    trans: &mut Transaccion<'_>,
+    mov: &MovimientoBolsa,
+  ) -> Result<u32, DBError> {
+    const QUERY: &str = "INSERT INTO bolsa_horas
+      (usuario, fecha, tipo, minutos, motivo, autor, fecha_registro)
+      VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+    let res = sqlx::query(QUERY)
+      .bind(mov.usuario)
+      .bind(mov.fecha)
+      .bind(mov.tipo as u8)
+      .bind(mov.minutos)
+      .bind(&mov.motivo)
+      .bind(mov.autor)
+      .bind(mov.fecha_registro)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(res.last_insert_id() as u32)
+  }
+}
+
+fn movimiento_from_row(row: &MySqlRow) -> MovimientoBolsa {
+  MovimientoBolsa {
+    id: row.get("id"),
+    usuario: row.get("usuario"),
+    fecha: row.get("fecha"),
+    tipo: row.get::<u8, _>("tipo").into(),
+    minutos: row.get("minutos"),
+    motivo: row.get("motivo"),
+    autor: row.get("autor"),
+    fecha_registro: row.get("fecha_registro"),
+  }
+}
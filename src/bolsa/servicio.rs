@@ -0,0 +1,402 @@
+use chrono::{Months, NaiveDate, Utc};
+
+use crate::bolsa::{
+  BolsaRepo, MovimientoBolsa, ResumenAnualBolsa, TipoMovimientoBolsa,
+  minutos_caducados, saldo_bolsa,
+};
+use crate::config::ConfigTrabajo;
+use crate::informes::InformeServicio;
+use crate::infra::{DBError, ServicioError};
+
+/// Servicio para gestionar la bolsa de horas de los empleados.
+pub struct BolsaServicio {
+  cnfg: ConfigTrabajo,
+  repo: BolsaRepo,
+  srv_informe: InformeServicio,
+}
+
+impl BolsaServicio {
+  pub fn new(
+    cnfg: ConfigTrabajo,
+    repo: BolsaRepo,
+    srv_informe: InformeServicio,
+  ) -> Self {
+    BolsaServicio {
+      cnfg,
+      repo,
+      srv_informe,
+    }
+  }
+}
+
+impl BolsaServicio {
+  /// Vista consolidada de la bolsa de horas de un usuario para un año.
+  pub async fn resumen_anual(
+    &self,
+    usuario: u32,
+    anio: i32,
+  ) -> Result<ResumenAnualBolsa, ServicioError> {
+    tracing::debug!(
+      usuario = usuario,
+      anio = anio,
+      "Obteniendo resumen anual de la bolsa de horas"
+    );
+
+    let inicio_anio = NaiveDate::from_ymd_opt(anio, 1, 1)
+      .ok_or(DBError::Parametros("Año inválido"))?;
+
+    let saldo_inicial = self
+      .repo
+      .saldo(usuario, Some(inicio_anio))
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = usuario, anio = anio, error = %err,
+          "Obteniendo saldo inicial de la bolsa de horas");
+        ServicioError::from(err)
+      })?;
+
+    let movimientos =
+      self
+        .repo
+        .movimientos_anio(usuario, anio)
+        .await
+        .map_err(|err| {
+          tracing::error!(
+          usuario = usuario, anio = anio, error = %err,
+          "Obteniendo movimientos anuales de la bolsa de horas");
+          ServicioError::from(err)
+        })?;
+
+    Ok(ResumenAnualBolsa::new(
+      usuario,
+      anio,
+      saldo_inicial,
+      movimientos,
+    ))
+  }
+
+  /// Cierra un mes finalizado de la bolsa de horas de un usuario.
+  ///
+  /// Anota el saldo total del informe de cumplimiento horario del mes,
+  /// los minutos caducados y el exceso sobre los topes configurados.
+  /// Cada mes solo se puede cerrar una vez.
+  ///
+  /// Devuelve los movimientos anotados.
+  pub async fn cerrar_mes(
+    &self,
+    usuario: u32,
+    mes: u32,
+    anio: i32,
+    autor: u32,
+  ) -> Result<Vec<MovimientoBolsa>, ServicioError> {
+    tracing::info!(
+      usuario = usuario,
+      mes = mes,
+      anio = anio,
+      autor = autor,
+      "Cerrando mes de la bolsa de horas"
+    );
+
+    let fin_mes = fin_mes(mes, anio)
+      .ok_or(ServicioError::Validacion("Mes o año inválido".to_string()))?;
+
+    let hoy = Utc::now()
+      .with_timezone(&self.cnfg.zona_usuario(usuario))
+      .naive_local()
+      .date();
+
+    if fin_mes >= hoy {
+      return Err(ServicioError::Usuario(
+        "Solo se pueden cerrar en la bolsa de horas los meses finalizados"
+          .to_string(),
+      ));
+    }
+
+    let cerrado =
+      self
+        .repo
+        .existe_cierre(usuario, fin_mes)
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            usuario = usuario, mes = mes, anio = anio, error = %err,
+            "Comprobando cierre de la bolsa de horas");
+          ServicioError::from(err)
+        })?;
+
+    if cerrado {
+      return Err(ServicioError::Usuario(format!(
+        "El mes {}/{} ya está cerrado en la bolsa de horas",
+        mes, anio
+      )));
+    }
+
+    let informe = self
+      .srv_informe
+      .cumplimiento_horario(usuario, mes, anio)
+      .await?;
+
+    let mut movimientos = self
+      .repo
+      .movimientos(usuario, fin_mes)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = usuario, mes = mes, anio = anio, error = %err,
+          "Obteniendo movimientos de la bolsa de horas");
+        ServicioError::from(err)
+      })?;
+    let anteriores = movimientos.len();
+
+    movimientos.push(self.movimiento(
+      usuario,
+      fin_mes,
+      TipoMovimientoBolsa::SaldoMensual,
+      (informe.total_saldo * 60.0).round() as i32,
+      None,
+      Some(autor),
+    ));
+
+    let reglas = self.cnfg.bolsa;
+
+    if let Some(limite) = reglas.limite_caducidad(fin_mes) {
+      let caducados = minutos_caducados(&movimientos, limite);
+      if caducados > 0 {
+        movimientos.push(self.movimiento(
+          usuario,
+          fin_mes,
+          TipoMovimientoBolsa::Caducidad,
+          -(caducados as i32),
+          Some(format!("Minutos anteriores al {} no consumidos", limite)),
+          None,
+        ));
+      }
+    }
+
+    let tope = reglas.ajuste_tope(saldo_bolsa(&movimientos));
+    if tope != 0 {
+      movimientos.push(self.movimiento(
+        usuario,
+        fin_mes,
+        TipoMovimientoBolsa::Tope,
+        tope as i32,
+        Some("Exceso sobre los topes de la bolsa de horas".to_string()),
+        None,
+      ));
+    }
+
+    let mut nuevos = movimientos.split_off(anteriores);
+    self.anotar(&mut nuevos).await?;
+
+    tracing::debug!(
+      usuario = usuario,
+      mes = mes,
+      anio = anio,
+      "Mes de la bolsa de horas cerrado con éxito"
+    );
+
+    Ok(nuevos)
+  }
+
+  /// Anota un ajuste manual en la bolsa de horas de un usuario.
+  ///
+  /// Devuelve el ID del movimiento.
+  pub async fn ajustar(
+    &self,
+    usuario: u32,
+    fecha: NaiveDate,
+    minutos: i32,
+    motivo: &str,
+    autor: u32,
+  ) -> Result<u32, ServicioError> {
+    tracing::info!(
+      usuario = usuario,
+      fecha = %fecha,
+      minutos = minutos,
+      autor = autor,
+      "Ajustando la bolsa de horas"
+    );
+
+    if minutos == 0 {
+      return Err(ServicioError::Validacion(
+        "Los minutos del ajuste no pueden ser cero".to_string(),
+      ));
+    }
+
+    if motivo.trim().is_empty() {
+      return Err(ServicioError::Validacion(
+        "El motivo del ajuste es obligatorio".to_string(),
+      ));
+    }
+
+    let mut movimientos = [self.movimiento(
+      usuario,
+      fecha,
+      TipoMovimientoBolsa::Ajuste,
+      minutos,
+      Some(motivo.trim().to_string()),
+      Some(autor),
+    )];
+    self.anotar(&mut movimientos).await?;
+
+    Ok(movimientos[0].id)
+  }
+
+  /// Compensa con tiempo libre el saldo a favor de un usuario.
+  ///
+  /// Los minutos no pueden superar el saldo actual de la bolsa.
+  /// El saldo se comprueba en la misma transacción que anota la
+  /// compensación, bloqueando los movimientos del usuario, para que
+  /// dos compensaciones simultáneas no dejen la bolsa en negativo.
+  ///
+  /// Devuelve el ID del movimiento.
+  pub async fn compensar(
+    &self,
+    usuario: u32,
+    fecha: NaiveDate,
+    minutos: u32,
+    motivo: Option<&str>,
+    autor: u32,
+  ) -> Result<u32, ServicioError> {
+    tracing::info!(
+      usuario = usuario,
+      fecha = %fecha,
+      minutos = minutos,
+      autor = autor,
+      "Compensando la bolsa de horas"
+    );
+
+    if minutos == 0 {
+      return Err(ServicioError::Validacion(
+        "Los minutos a compensar no pueden ser cero".to_string(),
+      ));
+    }
+
+    let mut tr =
+      self
+        .repo
+        .conexion()
+        .empezar_transaccion()
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            usuario = usuario, error = %err,
+            "Iniciando transacción para compensar la bolsa de horas");
+          ServicioError::from(err)
+        })?;
+
+    let saldo =
+      self
+        .repo
+        .saldo_bloqueado(&mut tr, usuario)
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            usuario = usuario, error = %err,
+            "Obteniendo saldo de la bolsa de horas");
+          ServicioError::from(err)
+        })?;
+
+    if (minutos as i64) > saldo {
+      return Err(ServicioError::Usuario(format!(
+        "Saldo insuficiente en la bolsa de horas: {} minutos disponibles",
+        saldo.max(0)
+      )));
+    }
+
+    let mov = self.movimiento(
+      usuario,
+      fecha,
+      TipoMovimientoBolsa::Compensacion,
+      -(minutos as i32),
+      motivo
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .map(str::to_string),
+      Some(autor),
+    );
+
+    let id = self.repo.agregar(&mut tr, &mov).await.map_err(|err| {
+      tracing::error!(
+        movimiento = ?mov, error = %err,
+        "Anotando compensación en la bolsa de horas");
+      ServicioError::from(err)
+    })?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        usuario = usuario, error = %err,
+        "Commit transacción para compensar la bolsa de horas");
+      ServicioError::from(err)
+    })?;
+
+    Ok(id)
+  }
+
+  fn movimiento(
+    &self,
+    usuario: u32,
+    fecha: NaiveDate,
+    tipo: TipoMovimientoBolsa,
+    minutos: i32,
+    motivo: Option<String>,
+    autor: Option<u32>,
+  ) -> MovimientoBolsa {
+    MovimientoBolsa {
+      id: 0,
+      usuario,
+      fecha,
+      tipo,
+      minutos,
+      motivo,
+      autor,
+      fecha_registro: self.cnfg.ahora(),
+    }
+  }
+
+  /// Anota los movimientos en una única transacción y
+  /// les asigna el ID creado.
+  async fn anotar(
+    &self,
+    movimientos: &mut [MovimientoBolsa],
+  ) -> Result<(), ServicioError> {
+    let mut tr =
+      self
+        .repo
+        .conexion()
+        .empezar_transaccion()
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            error = %err,
+            "Iniciando transacción para anotar en la bolsa de horas");
+          ServicioError::from(err)
+        })?;
+
+    for mov in movimientos.iter_mut() {
+      mov.id = self.repo.agregar(&mut tr, mov).await.map_err(|err| {
+        tracing::error!(
+          movimiento = ?mov, error = %err,
+          "Anotando movimiento en la bolsa de horas");
+        ServicioError::from(err)
+      })?;
+    }
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        error = %err,
+        "Commit transacción para anotar en la bolsa de horas");
+      ServicioError::from(err)
+    })?;
+
+    Ok(())
+  }
+}
+
+/// Último día del mes.
+fn fin_mes(mes: u32, anio: i32) -> Option<NaiveDate> {
+  NaiveDate::from_ymd_opt(anio, mes, 1)?
+    .checked_add_months(Months::new(1))?
+    .pred_opt()
+}
@@ -0,0 +1,72 @@
+/// Centro de trabajo de la compañia.
+#[derive(Debug)]
+pub struct Centro {
+  pub id: u32,
+  pub nombre: String,
+  pub direccion: String,
+  /// Calendarios por defecto de los empleados del centro
+  pub calendarios: Vec<u32>,
+}
+
+impl Centro {
+  /// Valida los datos del centro antes de guardarlo.
+  pub fn validar(&self) -> Result<(), String> {
+    if self.nombre.trim().is_empty() {
+      return Err("El nombre del centro de trabajo es obligatorio".to_string());
+    }
+
+    if let Some((_, cal)) = self
+      .calendarios
+      .iter()
+      .enumerate()
+      .find(|(i, cal)| self.calendarios[..*i].contains(cal))
+    {
+      return Err(format!(
+        "El calendario {} está repetido en el centro de trabajo",
+        cal
+      ));
+    }
+
+    Ok(())
+  }
+
+  /// Convierte la lista de calendarios separados por comas
+  /// que devuelven las consultas de centros.
+  pub fn parsear_calendarios(calendarios: &str) -> Vec<u32> {
+    calendarios
+      .split(',')
+      .filter_map(|c| c.trim().parse().ok())
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn centro(nombre: &str, calendarios: Vec<u32>) -> Centro {
+    Centro {
+      id: 1,
+      nombre: nombre.to_string(),
+      direccion: String::new(),
+      calendarios,
+    }
+  }
+
+  #[test]
+  fn test_validar_centro() {
+    assert!(centro("Sede central", vec![1, 2]).validar().is_ok());
+    assert!(centro("Sede central", vec![]).validar().is_ok());
+    assert!(centro("  ", vec![1]).validar().is_err());
+
+    let repetido = centro("Las Palmas", vec![1, 3, 1]).validar();
+    assert!(repetido.unwrap_err().contains("calendario 1"));
+  }
+
+  #[test]
+  fn test_parsear_calendarios() {
+    assert_eq!(Centro::parsear_calendarios("3,1,12"), vec![3, 1, 12]);
+    assert_eq!(Centro::parsear_calendarios("4, x,5"), vec![4, 5]);
+    assert!(Centro::parsear_calendarios("").is_empty());
+  }
+}
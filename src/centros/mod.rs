@@ -0,0 +1,24 @@
+//! Gestiona los centros de trabajo de la compañia.
+//!
+//! Cada empleado puede pertenecer a un centro de trabajo. Los
+//! centros definen los calendarios por defecto de sus empleados,
+//! normalmente con las fiestas locales de la oficina.
+//!
+//! Al asignar un empleado a un centro se le añaden los calendarios
+//! del centro que aún no tenga asignados. Los cambios posteriores en
+//! los calendarios del centro no se propagan a los empleados ya
+//! asignados; el administrador los mantiene desde el usuario.
+//!
+//! Un centro con empleados asignados no se puede eliminar.
+
+/// Módulo que gestiona el repositorio de los centros de trabajo.
+mod repo;
+
+/// Módulo que define el dominio de los centros de trabajo.
+mod dominio;
+/// Módulo con los servicios de los centros de trabajo.
+mod servicio;
+
+pub use dominio::*;
+pub use repo::*;
+pub use servicio::*;
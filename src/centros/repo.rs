@@ -0,0 +1,186 @@
+use sqlx::{Row, mysql::MySqlRow};
+
+use crate::{
+  centros::Centro,
+  infra::{DBError, PoolConexion, Transaccion},
+};
+
+/// Implementación del repositorio de los centros de trabajo.
+pub struct CentroRepo {
+  pool: PoolConexion,
+}
+
+impl CentroRepo {
+  pub fn new(pool: PoolConexion) -> Self {
+    CentroRepo { pool }
+  }
+
+  pub(in crate::centros) fn conexion(&self) -> &PoolConexion {
+    &self.pool
+  }
+
+  /// Devuelve todos los centros de trabajo ordenados por nombre.
+  pub(in crate::centros) async fn centros(
+    &self,
+  ) -> Result<Vec<Centro>, DBError> {
+    const QUERY: &str = "SELECT c.id, c.nombre, c.direccion,
+      (SELECT CAST(GROUP_CONCAT(cc.calendario) AS CHAR)
+        FROM centros_calendarios cc WHERE cc.centro = c.id) AS calendarios
+      FROM centros c
+      ORDER BY c.nombre";
+
+    let rows = sqlx::query(QUERY)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(rows.iter().map(centro_from_row).collect())
+  }
+
+  /// Devuelve un centro de trabajo por su id.
+  pub(in crate::centros) async fn centro(
+    &self,
+    id: u32,
+  ) -> Result<Centro, DBError> {
+    const QUERY: &str = "SELECT c.id, c.nombre, c.direccion,
+      (SELECT CAST(GROUP_CONCAT(cc.calendario) AS CHAR)
+        FROM centros_calendarios cc WHERE cc.centro = c.id) AS calendarios
+      FROM centros c
+      WHERE c.id = ?";
+
+    let row = sqlx::query(QUERY)
+      .bind(id)
+      .fetch_optional(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    row.map(|r| centro_from_row(&r)).ok_or_else(|| {
+      DBError::registro_vacio(format!("Centro no encontrado: {}", id))
+    })
+  }
+
+  /// Crea un nuevo centro de trabajo.
+  ///
+  /// Devuelve el ID del centro creado.
+  pub(in crate::centros) async fn crear_centro(
+    &self,
+    trans: &mut Transaccion<'_>,
+    centro: &Centro,
+  ) -> Result<u32, DBError> {
+    const QUERY: &str = "INSERT INTO centros (nombre, direccion) VALUES (?, ?)";
+
+    let res = sqlx::query(QUERY)
+      .bind(&centro.nombre)
+      .bind(&centro.direccion)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(res.last_insert_id() as u32)
+  }
+
+  /// Actualiza el nombre y la dirección de un centro de trabajo.
+  pub(in crate::centros) async fn actualizar_centro(
+    &self,
+    trans: &mut Transaccion<'_>,
+    centro: &Centro,
+  ) -> Result<(), DBError> {
+    const QUERY: &str =
+      "UPDATE centros SET nombre = ?, direccion = ? WHERE id = ?";
+
+    let res = sqlx::query(QUERY)
+      .bind(&centro.nombre)
+      .bind(&centro.direccion)
+      .bind(centro.id)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    if res.rows_affected() == 0 {
+      Err(DBError::registro_vacio("Actualizando centro".to_string()))
+    } else {
+      Ok(())
+    }
+  }
+
+  /// Reemplaza los calendarios por defecto de un centro de trabajo.
+  pub(in crate::centros) async fn asignar_calendarios(
+    &self,
+    trans: &mut Transaccion<'_>,
+    centro: u32,
+    calendarios: &[u32],
+  ) -> Result<(), DBError> {
+    const DELETE_QUERY: &str = "DELETE FROM centros_calendarios
+       WHERE centro = ?;";
+
+    sqlx::query(DELETE_QUERY)
+      .bind(centro)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    const QUERY: &str = "INSERT INTO centros_calendarios (centro, calendario)
+       VALUES (?, ?);";
+
+    for calendario in calendarios {
+      sqlx::query(QUERY)
+        .bind(centro)
+        .bind(calendario)
+        .execute(&mut **trans.deref_mut())
+        .await
+        .map_err(DBError::from_sqlx)?;
+    }
+    Ok(())
+  }
+
+  /// Número de usuarios asignados a un centro de trabajo.
+  pub(in crate::centros) async fn num_usuarios(
+    &self,
+    centro: u32,
+  ) -> Result<u32, DBError> {
+    const QUERY: &str = "SELECT CAST(COUNT(id) AS UNSIGNED)
+      FROM usuarios
+      WHERE centro = ?";
+
+    sqlx::query_scalar(QUERY)
+      .bind(centro)
+      .fetch_one(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)
+  }
+
+  /// Elimina un centro de trabajo y sus calendarios por defecto.
+  pub(in crate::centros) async fn eliminar_centro(
+    &self,
+    trans: &mut Transaccion<'_>,
+    id: u32,
+  ) -> Result<(), DBError> {
+    self.asignar_calendarios(trans, id, &[]).await?;
+
+    const QUERY: &str = "DELETE FROM centros WHERE id = ?";
+
+    let res = sqlx::query(QUERY)
+      .bind(id)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    if res.rows_affected() == 0 {
+      Err(DBError::registro_vacio("Eliminando centro".to_string()))
+    } else {
+      Ok(())
+    }
+  }
+}
+
+fn centro_from_row(row: &MySqlRow) -> Centro {
+  Centro {
+    id: row.get("id"),
+    nombre: row.get("nombre"),
+    direccion: row.get("direccion"),
+    calendarios: row
+      .get::<Option<String>, _>("calendarios")
+      .map(|cals| Centro::parsear_calendarios(&cals))
+      .unwrap_or_default(),
+  }
+}
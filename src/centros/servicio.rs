@@ -0,0 +1,207 @@
+use crate::{
+  centros::{Centro, CentroRepo},
+  infra::ServicioError,
+};
+
+/// Servicio para gestionar los centros de trabajo.
+pub struct CentroServicio {
+  repo: CentroRepo,
+}
+
+impl CentroServicio {
+  pub fn new(repo: CentroRepo) -> Self {
+    CentroServicio { repo }
+  }
+}
+
+impl CentroServicio {
+  /// Devuelve la lista de todos los centros de trabajo.
+  pub async fn centros(&self) -> Result<Vec<Centro>, ServicioError> {
+    tracing::debug!("Obteniendo lista de centros de trabajo");
+
+    self.repo.centros().await.map_err(|err| {
+      tracing::error!(error = %err, "Obteniendo lista de centros de trabajo");
+      ServicioError::from(err)
+    })
+  }
+
+  /// Devuelve un centro de trabajo por su identificador.
+  pub async fn centro(&self, id: u32) -> Result<Centro, ServicioError> {
+    tracing::debug!(id = id, "Obteniendo centro de trabajo por id");
+
+    self.repo.centro(id).await.map_err(|err| {
+      tracing::error!(
+        id = id, error = %err, "Obteniendo centro de trabajo por id");
+      ServicioError::from(err)
+    })
+  }
+
+  /// Crea un nuevo centro de trabajo con sus calendarios por defecto.
+  pub async fn crear_centro(
+    &self,
+    centro: &Centro,
+  ) -> Result<u32, ServicioError> {
+    tracing::info!(centro = ?centro, "Iniciando creación de centro de trabajo");
+
+    centro.validar().map_err(ServicioError::Validacion)?;
+
+    let mut tr =
+      self
+        .repo
+        .conexion()
+        .empezar_transaccion()
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            centro = ?centro, error = %err,
+            "Iniciando transacción para crear centro de trabajo");
+          ServicioError::from(err)
+        })?;
+
+    let id = self
+      .repo
+      .crear_centro(&mut tr, centro)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+            centro = ?centro, error = %err, "Creando centro de trabajo");
+        ServicioError::from(err)
+      })?;
+
+    self
+      .repo
+      .asignar_calendarios(&mut tr, id, &centro.calendarios)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          centro = ?centro, error = %err,
+          "Asignando calendarios al centro de trabajo");
+        ServicioError::from(err)
+      })?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        centro = ?centro, error = %err,
+        "Commit transacción para crear centro de trabajo");
+      ServicioError::from(err)
+    })?;
+
+    tracing::debug!(id = id, "Centro de trabajo creado con éxito");
+
+    Ok(id)
+  }
+
+  /// Actualiza un centro de trabajo y sus calendarios por defecto.
+  ///
+  /// Los calendarios de los empleados ya asignados al centro
+  /// no se modifican.
+  pub async fn actualizar_centro(
+    &self,
+    centro: &Centro,
+  ) -> Result<(), ServicioError> {
+    tracing::info!(
+      centro = ?centro, "Iniciando actualización de centro de trabajo");
+
+    centro.validar().map_err(ServicioError::Validacion)?;
+
+    let mut tr =
+      self
+        .repo
+        .conexion()
+        .empezar_transaccion()
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            centro = ?centro, error = %err,
+            "Iniciando transacción para actualizar centro de trabajo");
+          ServicioError::from(err)
+        })?;
+
+    self
+      .repo
+      .actualizar_centro(&mut tr, centro)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          centro = ?centro, error = %err, "Actualizando centro de trabajo");
+        ServicioError::from(err)
+      })?;
+
+    self
+      .repo
+      .asignar_calendarios(&mut tr, centro.id, &centro.calendarios)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          centro = ?centro, error = %err,
+          "Asignando calendarios al centro de trabajo");
+        ServicioError::from(err)
+      })?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        centro = ?centro, error = %err,
+        "Commit transacción para actualizar centro de trabajo");
+      ServicioError::from(err)
+    })?;
+
+    tracing::debug!(id = centro.id, "Centro de trabajo actualizado con éxito");
+
+    Ok(())
+  }
+
+  /// Elimina un centro de trabajo por su identificador.
+  ///
+  /// No se puede eliminar si tiene empleados asignados.
+  pub async fn eliminar_centro(&self, id: u32) -> Result<(), ServicioError> {
+    tracing::info!(id = id, "Iniciando eliminación de centro de trabajo");
+
+    let usuarios = self.repo.num_usuarios(id).await.map_err(|err| {
+      tracing::error!(
+        id = id, error = %err,
+        "Obteniendo los usuarios asignados al centro de trabajo");
+      ServicioError::from(err)
+    })?;
+
+    if usuarios > 0 {
+      return Err(ServicioError::Usuario(format!(
+        "No se puede eliminar el centro de trabajo porque tiene {} \
+        usuarios asignados",
+        usuarios
+      )));
+    }
+
+    let mut tr =
+      self
+        .repo
+        .conexion()
+        .empezar_transaccion()
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            id = id, error = %err,
+            "Iniciando transacción para eliminar centro de trabajo");
+          ServicioError::from(err)
+        })?;
+
+    self
+      .repo
+      .eliminar_centro(&mut tr, id)
+      .await
+      .map_err(|err| {
+        tracing::error!(id = id, error = %err, "Eliminando centro de trabajo");
+        ServicioError::from(err)
+      })?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        id = id, error = %err,
+        "Commit transacción para eliminar centro de trabajo");
+      ServicioError::from(err)
+    })?;
+
+    tracing::debug!(id = id, "Centro de trabajo eliminado con éxito");
+
+    Ok(())
+  }
+}
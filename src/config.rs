@@ -1,10 +1,17 @@
+use chrono::{NaiveDateTime, NaiveTime, Utc};
 use chrono_tz::Tz;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::BufReader;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
+use crate::bolsa::ReglasBolsa;
+use crate::inc::CierreMarcajeOlvidado;
 use crate::infra::PasswordLimites;
+use crate::usuarios::PoliticaBloqueo;
 
 #[derive(Deserialize)]
 /// Representa la configuración inicial de la aplicación.
@@ -50,6 +57,111 @@ impl From<PasswordConfig> for PasswordLimites {
   }
 }
 
+/// Representa la política de bloqueo por intentos fallidos de login.
+///
+/// Se aplica por usuario (DNI) y por IP.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct BloqueoLoginConfig {
+  /// Número de fallos consecutivos a partir del cual se bloquea
+  pub max_fallos: u32,
+  /// Segundos tras los cuales se olvidan los fallos previos
+  pub ventana: u64,
+  /// Segundos de bloqueo inicial. Se duplica en cada fallo posterior
+  pub espera_base: u64,
+  /// Segundos máximos de bloqueo
+  pub espera_maxima: u64,
+}
+
+impl Default for BloqueoLoginConfig {
+  fn default() -> Self {
+    BloqueoLoginConfig {
+      max_fallos: 5,
+      ventana: 900,
+      espera_base: 60,
+      espera_maxima: 3600,
+    }
+  }
+}
+
+impl From<BloqueoLoginConfig> for PoliticaBloqueo {
+  fn from(bloqueo: BloqueoLoginConfig) -> Self {
+    PoliticaBloqueo {
+      max_fallos: bloqueo.max_fallos,
+      ventana: bloqueo.ventana,
+      espera_base: bloqueo.espera_base,
+      espera_maxima: bloqueo.espera_maxima,
+    }
+  }
+}
+
+/// Representa los topes y la caducidad de la bolsa de horas.
+///
+/// Los valores a null no se aplican.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(default)]
+pub struct BolsaHorasConfig {
+  /// Minutos máximos a favor del empleado
+  pub maximo_minutos: Option<u32>,
+  /// Minutos máximos en contra del empleado
+  pub deficit_maximo_minutos: Option<u32>,
+  /// Meses tras los que caducan los minutos a favor no consumidos
+  pub caducidad_meses: Option<u32>,
+}
+
+impl From<BolsaHorasConfig> for ReglasBolsa {
+  fn from(bolsa: BolsaHorasConfig) -> Self {
+    ReglasBolsa {
+      maximo: bolsa.maximo_minutos,
+      deficit_maximo: bolsa.deficit_maximo_minutos,
+      caducidad_meses: bolsa.caducidad_meses,
+    }
+  }
+}
+
+/// Tratamiento de los marcajes olvidados sin hora de salida.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ModoCierreConfig {
+  /// Registra como salida la hora de fin del horario
+  Cerrar,
+  /// Abre una incidencia para que el empleado confirme la salida
+  Incidencia,
+}
+
+impl From<ModoCierreConfig> for CierreMarcajeOlvidado {
+  fn from(modo: ModoCierreConfig) -> Self {
+    match modo {
+      ModoCierreConfig::Cerrar => CierreMarcajeOlvidado::Cerrar,
+      ModoCierreConfig::Incidencia => CierreMarcajeOlvidado::Incidencia,
+    }
+  }
+}
+
+/// Representa el proceso diario que trata los marcajes
+/// que se quedaron sin hora de salida.
+///
+/// Si no se configura el proceso no se lanza.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct CierreMarcajesConfig {
+  /// Configurar a true para lanzar el proceso
+  pub activo: bool,
+  /// Hora del día en la zona horaria configurada a la que se lanza
+  pub hora: NaiveTime,
+  pub modo: ModoCierreConfig,
+}
+
+impl Default for CierreMarcajesConfig {
+  fn default() -> Self {
+    CierreMarcajesConfig {
+      activo: false,
+      hora: NaiveTime::from_hms_opt(23, 30, 0).unwrap(),
+      modo: ModoCierreConfig::Incidencia,
+    }
+  }
+}
+
 /// Representa los límites del número de registros
 /// que se pueden obtener en las consultas.
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -62,6 +174,8 @@ pub struct Limites {
   pub marcajes_registrados: u8,
   /// Número máximo de incidencias a mostrar
   pub incidencias: u8,
+  /// Número de trazas por página
+  pub trazas: u8,
 }
 
 #[derive(Deserialize)]
@@ -110,6 +224,10 @@ pub struct Servidor {
   pub produccion: bool,
   /// Es el código que identifica cada aplicación en el tenat
   pub app: String,
+  /// IPs de los proxies inversos de confianza. Solo se aceptan
+  /// las cabeceras con la IP del cliente si la conexión llega de uno
+  #[serde(default)]
+  pub proxies_confiables: Vec<IpAddr>,
 }
 
 /// Representa la configuración de la aplicación.
@@ -122,11 +240,30 @@ pub struct Config {
   pub log: Log,
   pub servidor: Servidor,
   pub password: PasswordConfig,
+  #[serde(default)]
+  pub bloqueo_login: BloqueoLoginConfig,
   pub boot_admin: BootAdmin,
+  #[serde(default)]
+  pub cierre_marcajes: CierreMarcajesConfig,
+  #[serde(default)]
+  pub bolsa_horas: BolsaHorasConfig,
   pub zona_horaria: Tz,
   pub secreto: String,
-  // Duración en segundos de la sesión cuando un usuario autentica
+  // Duración en segundos de la sesión cuando un usuario autentica.
+  // La sesión se renueva al consumir la mitad de su duración
   pub caducidad_sesion: u64,
+  // Duración máxima en segundos de la sesión aunque se renueve
+  pub caducidad_maxima_sesion: u64,
+  // Persiste las sesiones activas en la base de datos
+  pub persistir_sesiones: bool,
+  // Horas anuales de trabajo efectivo del convenio colectivo
+  #[serde(default = "horas_anuales_convenio_defecto")]
+  pub horas_anuales_convenio: f64,
+}
+
+/// Horas anuales del convenio si no se configuran.
+fn horas_anuales_convenio_defecto() -> f64 {
+  1780.0
 }
 
 impl std::fmt::Debug for Config {
@@ -136,10 +273,16 @@ impl std::fmt::Debug for Config {
       .field("log", &self.log)
       .field("servidor", &self.servidor)
       .field("password", &self.password)
+      .field("bloqueo_login", &self.bloqueo_login)
       .field("boot_admin", &self.boot_admin)
+      .field("cierre_marcajes", &self.cierre_marcajes)
+      .field("bolsa_horas", &self.bolsa_horas)
       .field("zona_horaria", &self.zona_horaria)
       .field("secreto", &"[OCULTO]")
       .field("caducidad_sesion", &self.caducidad_sesion)
+      .field("caducidad_maxima_sesion", &self.caducidad_maxima_sesion)
+      .field("persistir_sesiones", &self.persistir_sesiones)
+      .field("horas_anuales_convenio", &self.horas_anuales_convenio)
       .finish()
   }
 }
@@ -149,12 +292,61 @@ impl std::fmt::Debug for Config {
 /// Se propaga a través de la aplicación
 #[derive(Clone)]
 pub struct ConfigTrabajo {
+  /// Zona horaria por defecto de la aplicación
   pub zona_horaria: Tz,
+  /// Zonas horarias propias de los usuarios. Se comparte entre
+  /// todas las copias de la configuración
+  pub zonas_usuarios: Arc<RwLock<HashMap<u32, Tz>>>,
   pub secreto: String,
   pub limites: Limites,
   pub passw: PasswordLimites,
+  pub bloqueo: PoliticaBloqueo,
+  pub bolsa: ReglasBolsa,
   pub caducidad_sesion: u64,
+  pub caducidad_maxima_sesion: u64,
+  pub persistir_sesiones: bool,
+  pub horas_anuales_convenio: f64,
   pub produccion: bool,
+  pub proxies_confiables: Vec<IpAddr>,
+}
+
+impl ConfigTrabajo {
+  /// Zona horaria de un usuario.
+  ///
+  /// Si el usuario no tiene zona horaria propia se usa
+  /// la zona horaria por defecto.
+  pub fn zona_usuario(&self, usuario: u32) -> Tz {
+    self
+      .zonas_usuarios
+      .read()
+      .expect("Almacén de zonas horarias envenenado")
+      .get(&usuario)
+      .copied()
+      .unwrap_or(self.zona_horaria)
+  }
+
+  /// Fecha y hora actual en la zona horaria de la aplicación.
+  ///
+  /// Es la que se usa en las fechas de auditoría (trazas, historial,
+  /// solicitudes, movimientos...), de modo que todas quedan en la misma
+  /// zona sea cual sea la del autor. Las fechas de jornada de un empleado
+  /// se calculan con [`ConfigTrabajo::zona_usuario`].
+  pub fn ahora(&self) -> NaiveDateTime {
+    Utc::now().with_timezone(&self.zona_horaria).naive_local()
+  }
+
+  /// Asigna o elimina la zona horaria propia de un usuario.
+  pub fn asignar_zona_usuario(&self, usuario: u32, zona: Option<Tz>) {
+    let mut zonas = self
+      .zonas_usuarios
+      .write()
+      .expect("Almacén de zonas horarias envenenado");
+
+    match zona {
+      Some(zona) => zonas.insert(usuario, zona),
+      None => zonas.remove(&usuario),
+    };
+  }
 }
 
 impl Config {
@@ -187,10 +379,17 @@ impl Config {
     ConfigTrabajo {
       secreto: self.secreto.clone(),
       zona_horaria: self.zona_horaria,
+      zonas_usuarios: Arc::new(RwLock::new(HashMap::new())),
       limites: self.db.limites,
       passw: self.password.into(),
+      bloqueo: self.bloqueo_login.into(),
+      bolsa: self.bolsa_horas.into(),
       caducidad_sesion: self.caducidad_sesion,
+      caducidad_maxima_sesion: self.caducidad_maxima_sesion,
+      persistir_sesiones: self.persistir_sesiones,
+      horas_anuales_convenio: self.horas_anuales_convenio,
       produccion: self.servidor.produccion,
+      proxies_confiables: self.servidor.proxies_confiables.clone(),
     }
   }
 }
@@ -217,3 +416,56 @@ impl Secreto {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use chrono::{TimeDelta, Utc};
+  use chrono_tz::{Atlantic::Canary, Europe::Madrid};
+
+  use super::{Config, ConfigTrabajo};
+
+  fn config_trabajo() -> ConfigTrabajo {
+    // Se sustituyen los valores sin comillas de la plantilla de despliegue
+    let plantilla = include_str!("../config/config-api.json")
+      .replace("@DB_MAX_CONN", "5")
+      .replace("@SRV_PUERTO", "8080")
+      .replace("@SRV_PROD", "false")
+      .replace("@BOOT_ADMIN_CREAR", "false");
+    let config: Config = serde_json::from_str(&plantilla).unwrap();
+    config.config_trabajo()
+  }
+
+  #[test]
+  fn test_zona_usuario() {
+    let cnfg = config_trabajo();
+    let copia = cnfg.clone();
+
+    // Sin zona propia se usa la de la aplicación
+    assert_eq!(cnfg.zona_usuario(1), Madrid);
+
+    // Las copias de la configuración comparten las zonas
+    copia.asignar_zona_usuario(1, Some(Canary));
+    assert_eq!(cnfg.zona_usuario(1), Canary);
+    assert_eq!(cnfg.zona_usuario(2), Madrid);
+
+    cnfg.asignar_zona_usuario(1, None);
+    assert_eq!(copia.zona_usuario(1), Madrid);
+  }
+
+  #[test]
+  fn test_ahora_en_zona_de_la_aplicacion() {
+    let cnfg = config_trabajo();
+    assert_eq!(cnfg.zona_horaria, Madrid);
+
+    // La zona propia del usuario no afecta a las fechas de auditoría
+    cnfg.asignar_zona_usuario(1, Some(Canary));
+    assert_eq!(cnfg.zona_usuario(1), Canary);
+
+    let ahora = cnfg.ahora();
+    let madrid = Utc::now().with_timezone(&Madrid).naive_local();
+    let canarias = Utc::now().with_timezone(&Canary).naive_local();
+
+    assert!((madrid - ahora).abs() < TimeDelta::seconds(5));
+    assert!((canarias - ahora).abs() > TimeDelta::minutes(30));
+  }
+}
@@ -0,0 +1,333 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::informes::{EmpleadoRegistro, JornadaEfectiva};
+
+/// Tope legal de horas extraordinarias computables al año en minutos.
+pub const TOPE_ANUAL_MINUTOS: u32 = 80 * 60;
+/// Porcentaje del tope anual a partir del cual se avisa.
+pub const AVISO_TOPE_PORCENTAJE: u32 = 90;
+
+/// Causa de las horas extraordinarias.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CausaHoraExtra {
+  Estructural = 1,
+  /// Prevenir o reparar siniestros u otros daños extraordinarios
+  /// y urgentes. No computan para el tope anual
+  FuerzaMayor = 2,
+}
+
+impl From<u8> for CausaHoraExtra {
+  fn from(value: u8) -> Self {
+    match value {
+      2 => CausaHoraExtra::FuerzaMayor,
+      _ => CausaHoraExtra::Estructural,
+    }
+  }
+}
+
+impl CausaHoraExtra {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CausaHoraExtra::Estructural => "Estructural",
+      CausaHoraExtra::FuerzaMayor => "Fuerza mayor",
+    }
+  }
+}
+
+/// Forma de compensar las horas extraordinarias.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompensacionHoraExtra {
+  Retribuida = 1,
+  /// Compensada con tiempo de descanso. No computa para el tope anual
+  Descanso = 2,
+}
+
+impl From<u8> for CompensacionHoraExtra {
+  fn from(value: u8) -> Self {
+    match value {
+      2 => CompensacionHoraExtra::Descanso,
+      _ => CompensacionHoraExtra::Retribuida,
+    }
+  }
+}
+
+impl CompensacionHoraExtra {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CompensacionHoraExtra::Retribuida => "Retribuida",
+      CompensacionHoraExtra::Descanso => "Descanso",
+    }
+  }
+}
+
+/// Registro de horas extraordinarias de un empleado en un día.
+#[derive(Debug)]
+pub struct HoraExtra {
+  pub id: u32,
+  pub usuario: u32,
+  pub fecha: NaiveDate,
+  pub minutos: u32,
+  pub causa: CausaHoraExtra,
+  pub compensacion: CompensacionHoraExtra,
+  pub motivo: Option<String>,
+  /// Gestor que registra las horas
+  pub autor: u32,
+  pub fecha_registro: NaiveDateTime,
+}
+
+impl HoraExtra {
+  /// Indica si las horas computan para el tope legal anual.
+  pub fn computa_tope(&self) -> bool {
+    self.causa == CausaHoraExtra::Estructural
+      && self.compensacion == CompensacionHoraExtra::Retribuida
+  }
+}
+
+/// Día en el que el empleado trabajó más que la jornada de su horario.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExcesoJornada {
+  pub fecha: NaiveDate,
+  /// Minutos trabajados por encima de la jornada
+  pub minutos: u32,
+  /// Minutos ya registrados como horas extraordinarias
+  pub registrados: u32,
+}
+
+impl ExcesoJornada {
+  /// Minutos de exceso pendientes de registrar.
+  pub fn pendientes(&self) -> u32 {
+    self.minutos.saturating_sub(self.registrados)
+  }
+}
+
+/// Días con exceso de jornada, con los minutos ya registrados
+/// de cada día.
+pub fn excesos_jornada(
+  jornadas: &[JornadaEfectiva],
+  horas_extra: &[HoraExtra],
+) -> Vec<ExcesoJornada> {
+  jornadas
+    .iter()
+    .map(|j| ExcesoJornada {
+      fecha: j.fecha,
+      minutos: j.minutos_exceso(),
+      registrados: horas_extra
+        .iter()
+        .filter(|h| h.fecha == j.fecha)
+        .map(|h| h.minutos)
+        .sum(),
+    })
+    .filter(|e| e.minutos > 0)
+    .collect()
+}
+
+/// Avisos sobre el tope legal anual para los minutos computables.
+pub fn avisos_tope(computables: u32) -> Vec<String> {
+  let horas = computables as f64 / 60.0;
+
+  if computables > TOPE_ANUAL_MINUTOS {
+    vec![format!(
+      "Se ha superado el tope legal de {} horas extraordinarias anuales: \
+      {:.2} horas computables",
+      TOPE_ANUAL_MINUTOS / 60,
+      horas
+    )]
+  } else if computables * 100 >= TOPE_ANUAL_MINUTOS * AVISO_TOPE_PORCENTAJE {
+    vec![format!(
+      "Se ha alcanzado el {}% del tope legal de {} horas extraordinarias \
+      anuales: {:.2} horas computables",
+      AVISO_TOPE_PORCENTAJE,
+      TOPE_ANUAL_MINUTOS / 60,
+      horas
+    )]
+  } else {
+    Vec::new()
+  }
+}
+
+/// Resumen mensual de las horas extraordinarias de un empleado.
+///
+/// Los totales se expresan en minutos.
+#[derive(Debug)]
+pub struct ResumenHorasExtra {
+  pub empleado: EmpleadoRegistro,
+  pub mes: u32,
+  pub anio: i32,
+  pub horas_extra: Vec<HoraExtra>,
+  /// Días del mes con exceso de jornada sin registrar
+  pub pendientes: Vec<ExcesoJornada>,
+  pub total: u32,
+  pub estructurales: u32,
+  pub fuerza_mayor: u32,
+  pub retribuidas: u32,
+  pub descanso: u32,
+  /// Minutos computables para el tope desde el inicio del año
+  /// hasta el final del mes
+  pub computables_anio: u32,
+  pub avisos: Vec<String>,
+}
+
+impl ResumenHorasExtra {
+  pub fn new(
+    empleado: EmpleadoRegistro,
+    mes: u32,
+    anio: i32,
+    horas_extra: Vec<HoraExtra>,
+    excesos: Vec<ExcesoJornada>,
+    computables_anio: u32,
+  ) -> Self {
+    let suma = |filtro: &dyn Fn(&HoraExtra) -> bool| {
+      horas_extra
+        .iter()
+        .filter(|h| filtro(h))
+        .map(|h| h.minutos)
+        .sum()
+    };
+
+    ResumenHorasExtra {
+      total: suma(&|_| true),
+      estructurales: suma(&|h| h.causa == CausaHoraExtra::Estructural),
+      fuerza_mayor: suma(&|h| h.causa == CausaHoraExtra::FuerzaMayor),
+      retribuidas: suma(&|h| {
+        h.compensacion == CompensacionHoraExtra::Retribuida
+      }),
+      descanso: suma(&|h| h.compensacion == CompensacionHoraExtra::Descanso),
+      pendientes: excesos.into_iter().filter(|e| e.pendientes() > 0).collect(),
+      avisos: avisos_tope(computables_anio),
+      empleado,
+      mes,
+      anio,
+      horas_extra,
+      computables_anio,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fecha(dia: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 3, dia).unwrap()
+  }
+
+  fn hora_extra(
+    dia: u32,
+    minutos: u32,
+    causa: CausaHoraExtra,
+    compensacion: CompensacionHoraExtra,
+  ) -> HoraExtra {
+    HoraExtra {
+      id: 0,
+      usuario: 1,
+      fecha: fecha(dia),
+      minutos,
+      causa,
+      compensacion,
+      motivo: None,
+      autor: 2,
+      fecha_registro: fecha(31).and_hms_opt(10, 0, 0).unwrap(),
+    }
+  }
+
+  fn jornada(dia: u32, saldo: f64) -> JornadaEfectiva {
+    JornadaEfectiva {
+      fecha: fecha(dia),
+      horas_efectivas: 8.0 + saldo,
+      horas_a_trabajar: 8.0,
+    }
+  }
+
+  #[test]
+  fn test_computa_tope() {
+    use CausaHoraExtra::*;
+    use CompensacionHoraExtra::*;
+
+    assert!(hora_extra(3, 60, Estructural, Retribuida).computa_tope());
+    assert!(!hora_extra(3, 60, Estructural, Descanso).computa_tope());
+    assert!(!hora_extra(3, 60, FuerzaMayor, Retribuida).computa_tope());
+  }
+
+  #[test]
+  fn test_excesos_jornada() {
+    let registradas = vec![hora_extra(
+      3,
+      30,
+      CausaHoraExtra::Estructural,
+      CompensacionHoraExtra::Retribuida,
+    )];
+    let excesos = excesos_jornada(
+      &[jornada(3, 1.5), jornada(4, -0.5), jornada(5, 0.25)],
+      &registradas,
+    );
+
+    assert_eq!(
+      excesos,
+      vec![
+        ExcesoJornada {
+          fecha: fecha(3),
+          minutos: 90,
+          registrados: 30
+        },
+        ExcesoJornada {
+          fecha: fecha(5),
+          minutos: 15,
+          registrados: 0
+        },
+      ]
+    );
+    assert_eq!(excesos[0].pendientes(), 60);
+  }
+
+  #[test]
+  fn test_avisos_tope() {
+    assert!(avisos_tope(60 * 60).is_empty());
+    assert_eq!(avisos_tope(72 * 60).len(), 1);
+    assert!(avisos_tope(81 * 60)[0].contains("superado"));
+  }
+
+  #[test]
+  fn test_resumen_horas_extra() {
+    use CausaHoraExtra::*;
+    use CompensacionHoraExtra::*;
+
+    let resumen = ResumenHorasExtra::new(
+      EmpleadoRegistro {
+        id: 1,
+        dni: "12345678Z".to_string(),
+        nombre: "Ana".to_string(),
+      },
+      3,
+      2025,
+      vec![
+        hora_extra(3, 60, Estructural, Retribuida),
+        hora_extra(3, 30, FuerzaMayor, Descanso),
+        hora_extra(5, 15, Estructural, Descanso),
+      ],
+      vec![
+        ExcesoJornada {
+          fecha: fecha(3),
+          minutos: 90,
+          registrados: 90,
+        },
+        ExcesoJornada {
+          fecha: fecha(6),
+          minutos: 45,
+          registrados: 0,
+        },
+      ],
+      60,
+    );
+
+    assert_eq!(resumen.total, 105);
+    assert_eq!(resumen.estructurales, 75);
+    assert_eq!(resumen.fuerza_mayor, 30);
+    assert_eq!(resumen.retribuidas, 60);
+    assert_eq!(resumen.descanso, 45);
+    assert_eq!(resumen.pendientes.len(), 1);
+    assert_eq!(resumen.pendientes[0].fecha, fecha(6));
+    assert!(resumen.avisos.is_empty());
+  }
+}
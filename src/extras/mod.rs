@@ -0,0 +1,26 @@
+//! Registro de horas extraordinarias de los empleados.
+//!
+//! Las horas que superan la jornada diaria del horario se detectan
+//! con las horas efectivas de los marcajes. En los días sin horario y
+//! en los inhábiles todas las horas trabajadas exceden la jornada.
+//! Un gestor las registra como horas extraordinarias indicando su
+//! causa (estructurales o de fuerza mayor) y cómo se compensan
+//! (retribuidas o con descanso). Un mismo día puede repartirse en
+//! varios registros, pero nunca superar el exceso de jornada de ese día.
+//!
+//! Las horas extraordinarias se totalizan mensualmente y el empleado
+//! puede descargar una copia del resumen en CSV o en PDF firmado.
+//!
+//! Para el tope legal de 80 horas anuales no computan las horas de
+//! fuerza mayor ni las compensadas con descanso. Al registrar horas y
+//! en el resumen mensual se avisa al acercarse o superar el tope.
+mod repo;
+
+/// Módulo que define el dominio de las horas extraordinarias
+mod dominio;
+/// Módulo con los servicios de las horas extraordinarias
+mod servicio;
+
+pub use dominio::*;
+pub use repo::*;
+pub use servicio::*;
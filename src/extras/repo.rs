@@ -0,0 +1,136 @@
+use chrono::NaiveDate;
+use sqlx::{Row, mysql::MySqlRow};
+
+use crate::{
+  extras::{CausaHoraExtra, CompensacionHoraExtra, HoraExtra},
+  infra::{DBError, PoolConexion, Transaccion},
+};
+
+/// Implementación del repositorio de las horas extraordinarias.
+pub struct HoraExtraRepo {
+  pool: PoolConexion,
+}
+
+impl HoraExtraRepo {
+  pub fn new(pool: PoolConexion) -> Self {
+    HoraExtraRepo { pool }
+  }
+
+  pub(in crate::extras) fn conexion(&self) -> &PoolConexion {
+    &self.pool
+  }
+
+  /// Devuelve las horas extraordinarias de un usuario entre dos fechas
+  /// ordenadas por fecha y orden de registro.
+  pub(in crate::extras) async fn horas_extra(
+    &self,
+    usuario: u32,
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<Vec<HoraExtra>, DBError> {
+    const QUERY: &str = "SELECT id, usuario, fecha, minutos, causa,
+      compensacion, motivo, autor, fecha_registro
+      FROM horas_extra
+      WHERE usuario = ? AND fecha BETWEEN ? AND ?
+      ORDER BY fecha, id";
+
+    let rows = sqlx::query(QUERY)
+      .bind(usuario)
+      .bind(fecha_inicio)
+      .bind(fecha_fin)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(rows.iter().map(hora_extra_from_row).collect())
+  }
+
+  /// Minutos de horas extraordinarias de un usuario entre dos fechas
+  /// que computan para el tope legal anual.
+  pub(in crate::extras) async fn minutos_computables(
+    &self,
+    usuario: u32,
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<u32, DBError> {
+    const QUERY: &str = "SELECT CAST(COALESCE(SUM(minutos), 0) AS UNSIGNED)
+      FROM horas_extra
+      WHERE usuario = ? AND fecha BETWEEN ? AND ?
+      AND causa = ? AND compensacion = ?";
+
+    sqlx::query_scalar(QUERY)
+      .bind(usuario)
+      .bind(fecha_inicio)
+      .bind(fecha_fin)
+      .bind(CausaHoraExtra::Estructural as u8)
+      .bind(CompensacionHoraExtra::Retribuida as u8)
+      .fetch_one(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)
+  }
+
+  /// Minutos de horas extraordinarias registrados de un usuario en
+  /// un día, bloqueando los registros del día hasta el final de la
+  /// transacción.
+  pub(in crate::extras) async fn minutos_registrados_bloqueados(
+    &self,
+    trans: &mut Transaccion<'_>,
+    usuario: u32,
+    fecha: NaiveDate,
+  ) -> Result<u32, DBError> {
+    const QUERY: &str = "SELECT CAST(COALESCE(SUM(minutos), 0) AS UNSIGNED)
+      FROM horas_extra
+      WHERE usuario = ? AND fecha = ?
+      FOR UPDATE";
+
+    sqlx::query_scalar(QUERY)
+      .bind(usuario)
+      .bind(fecha)
+      .fetch_one(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)
+  }
+
+  /// Registra horas extraordinarias.
+  ///
+  /// Devuelve el ID del registro creado.
+  pub(in crate::extras) async fn agregar(
+    &self,
+    trans: &mut Transaccion<'_>,
+    hora: &HoraExtra,
+  ) -> Result<u32, DBError> {
+    const QUERY: &str = "INSERT INTO horas_extra
+      (usuario, fecha, minutos, causa, compensacion, motivo, autor,
+      fecha_registro)
+      VALUES (?, ?, ?, ?, ?, ?, ?, ?)";
+
+    let res = sqlx::query(QUERY)
+      .bind(hora.usuario)
+      .bind(hora.fecha)
+      .bind(hora.minutos)
+      .bind(hora.causa as u8)
+      .bind(hora.compensacion as u8)
+      .bind(&hora.motivo)
+      .bind(hora.autor)
+      .bind(hora.fecha_registro)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(res.last_insert_id() as u32)
+  }
+}
+
+fn hora_extra_from_row(row: &MySqlRow) -> HoraExtra {
+  HoraExtra {
+    id: row.get("id"),
+    usuario: row.get("usuario"),
+    fecha: row.get("fecha"),
+    minutos: row.get("minutos"),
+    causa: row.get::<u8, _>("causa").into(),
+    compensacion: row.get::<u8, _>("compensacion").into(),
+    motivo: row.get("motivo"),
+    autor: row.get("autor"),
+    fecha_registro: row.get("fecha_registro"),
+  }
+}
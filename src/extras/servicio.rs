@@ -0,0 +1,267 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::config::ConfigTrabajo;
+use crate::extras::{
+  ExcesoJornada, HoraExtra, HoraExtraRepo, ResumenHorasExtra, avisos_tope,
+  excesos_jornada,
+};
+use crate::informes::{
+  Documento, EmpleadoRegistro, FormatoExportacion, InformeServicio,
+  firma_documento, horas_extra_csv, horas_extra_pdf,
+};
+use crate::infra::{DBError, ServicioError, ShortDateTimeFormat};
+
+/// Servicio para gestionar las horas extraordinarias de los empleados.
+pub struct HoraExtraServicio {
+  cnfg: ConfigTrabajo,
+  repo: HoraExtraRepo,
+  srv_informe: InformeServicio,
+}
+
+impl HoraExtraServicio {
+  pub fn new(
+    cnfg: ConfigTrabajo,
+    repo: HoraExtraRepo,
+    srv_informe: InformeServicio,
+  ) -> Self {
+    HoraExtraServicio {
+      cnfg,
+      repo,
+      srv_informe,
+    }
+  }
+}
+
+impl HoraExtraServicio {
+  /// Resumen mensual de las horas extraordinarias de un empleado.
+  ///
+  /// Incluye los días del mes con exceso de jornada pendiente de
+  /// registrar y los avisos sobre el tope legal anual.
+  pub async fn resumen_mensual(
+    &self,
+    empleado: EmpleadoRegistro,
+    mes: u32,
+    anio: i32,
+  ) -> Result<ResumenHorasExtra, ServicioError> {
+    tracing::info!(
+      usuario = empleado.id,
+      mes = mes,
+      anio = anio,
+      "Generando el resumen mensual de horas extraordinarias"
+    );
+
+    let fecha_inicio = NaiveDate::from_ymd_opt(anio, mes, 1)
+      .ok_or(DBError::Parametros("Fecha inicio inválida"))?;
+    let fecha_fin = fin_de_mes(fecha_inicio)?;
+
+    let jornadas = self
+      .srv_informe
+      .jornadas_efectivas(empleado.id, mes, anio)
+      .await?;
+
+    let horas_extra = self
+      .repo
+      .horas_extra(empleado.id, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = empleado.id, mes = mes, anio = anio, error = %err,
+          "Obteniendo horas extraordinarias del mes");
+        ServicioError::from(err)
+      })?;
+
+    let computables = self
+      .repo
+      .minutos_computables(
+        empleado.id,
+        fecha_inicio.with_month(1).unwrap(),
+        fecha_fin,
+      )
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = empleado.id, mes = mes, anio = anio, error = %err,
+          "Obteniendo horas extraordinarias computables del año");
+        ServicioError::from(err)
+      })?;
+
+    let excesos = excesos_jornada(&jornadas, &horas_extra);
+
+    Ok(ResumenHorasExtra::new(
+      empleado,
+      mes,
+      anio,
+      horas_extra,
+      excesos,
+      computables,
+    ))
+  }
+
+  /// Registra horas extraordinarias de un empleado.
+  ///
+  /// Los minutos no pueden superar el exceso de jornada del día
+  /// pendiente de registrar. Los minutos ya registrados se leen
+  /// bloqueados dentro de la transacción del registro para que dos
+  /// registros simultáneos no superen el exceso. Si se alcanza el
+  /// tope legal anual se registran igualmente y se devuelven los avisos.
+  ///
+  /// Devuelve el ID del registro y los avisos.
+  pub async fn registrar(
+    &self,
+    mut hora: HoraExtra,
+  ) -> Result<(u32, Vec<String>), ServicioError> {
+    tracing::info!(hora = ?hora, "Registrando horas extraordinarias");
+
+    if hora.minutos == 0 {
+      return Err(ServicioError::Validacion(
+        "Los minutos de horas extraordinarias no pueden ser cero".to_string(),
+      ));
+    }
+
+    let exceso = self
+      .srv_informe
+      .jornadas_efectivas(hora.usuario, hora.fecha.month(), hora.fecha.year())
+      .await?
+      .iter()
+      .find(|j| j.fecha == hora.fecha)
+      .map(|j| j.minutos_exceso())
+      .unwrap_or(0);
+
+    hora.motivo = hora
+      .motivo
+      .take()
+      .map(|m| m.trim().to_string())
+      .filter(|m| !m.is_empty());
+    hora.fecha_registro = self.cnfg.ahora();
+
+    let mut tr =
+      self
+        .repo
+        .conexion()
+        .empezar_transaccion()
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            hora = ?hora, error = %err,
+            "Iniciando transacción para registrar horas extraordinarias");
+          ServicioError::from(err)
+        })?;
+
+    let registrados = self
+      .repo
+      .minutos_registrados_bloqueados(&mut tr, hora.usuario, hora.fecha)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          hora = ?hora, error = %err,
+          "Obteniendo horas extraordinarias del día");
+        ServicioError::from(err)
+      })?;
+
+    let pendientes = ExcesoJornada {
+      fecha: hora.fecha,
+      minutos: exceso,
+      registrados,
+    }
+    .pendientes();
+
+    if hora.minutos > pendientes {
+      return Err(ServicioError::Usuario(format!(
+        "El {} solo tiene {} minutos de exceso de jornada sin registrar",
+        hora.fecha.formato_corto(),
+        pendientes
+      )));
+    }
+
+    let id = self.repo.agregar(&mut tr, &hora).await.map_err(|err| {
+      tracing::error!(
+        hora = ?hora, error = %err, "Registrando horas extraordinarias");
+      ServicioError::from(err)
+    })?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        hora = ?hora, error = %err,
+        "Commit transacción para registrar horas extraordinarias");
+      ServicioError::from(err)
+    })?;
+
+    let inicio_anio = hora.fecha.with_ordinal(1).unwrap();
+    let fin_anio = NaiveDate::from_ymd_opt(hora.fecha.year(), 12, 31).unwrap();
+
+    let computables = self
+      .repo
+      .minutos_computables(hora.usuario, inicio_anio, fin_anio)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          hora = ?hora, error = %err,
+          "Obteniendo horas extraordinarias computables del año");
+        ServicioError::from(err)
+      })?;
+
+    let avisos = avisos_tope(computables);
+    for aviso in &avisos {
+      tracing::warn!(usuario = hora.usuario, aviso = aviso.as_str());
+    }
+
+    tracing::debug!(id = id, "Horas extraordinarias registradas con éxito");
+
+    Ok((id, avisos))
+  }
+
+  /// Exporta el resumen mensual de horas extraordinarias de un empleado.
+  ///
+  /// Es la copia que se entrega al empleado. El PDF se firma
+  /// igual que el registro diario de jornada.
+  pub async fn exportar(
+    &self,
+    empleado: EmpleadoRegistro,
+    mes: u32,
+    anio: i32,
+    formato: FormatoExportacion,
+  ) -> Result<Documento, ServicioError> {
+    let resumen = self.resumen_mensual(empleado, mes, anio).await?;
+
+    let contenido = match formato {
+      FormatoExportacion::Csv => horas_extra_csv(&resumen).map_err(|err| {
+        tracing::error!(
+          usuario = resumen.empleado.id,
+          error = %err,
+          "Generando CSV de horas extraordinarias"
+        );
+        ServicioError::Interno(
+          "No se ha podido generar el registro de horas extraordinarias"
+            .to_string(),
+        )
+      })?,
+      FormatoExportacion::Pdf => {
+        let generado = self.cnfg.ahora().formato_corto();
+
+        horas_extra_pdf(&resumen, &generado, &self.cnfg.secreto)
+      }
+    };
+
+    Ok(Documento {
+      nombre: format!(
+        "horas_extra_{}{:02}_{}.{}",
+        resumen.anio,
+        resumen.mes,
+        resumen.empleado.dni,
+        formato.extension()
+      ),
+      tipo_mime: formato.tipo_mime(),
+      firma: firma_documento(&contenido, formato, &self.cnfg.secreto),
+      contenido,
+    })
+  }
+}
+
+/// Último día del mes de la fecha dada.
+fn fin_de_mes(fecha: NaiveDate) -> Result<NaiveDate, DBError> {
+  fecha
+    .checked_add_months(chrono::Months::new(1))
+    .and_then(|f| f.with_day(1))
+    .and_then(|f| f.pred_opt())
+    .ok_or(DBError::Parametros("Fecha fin inválida"))
+}
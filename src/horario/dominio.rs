@@ -1,4 +1,4 @@
-use chrono::{NaiveDate, Weekday};
+use chrono::{NaiveDate, NaiveTime, Weekday};
 
 #[derive(Debug)]
 pub enum Dia {
@@ -54,11 +54,72 @@ impl From<Weekday> for Dia {
   }
 }
 
+/// Tramo del día en el que se debe trabajar.
+///
+/// Un día puede tener varias franjas (jornada partida).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FranjaHoraria {
+  pub hora_inicio: NaiveTime,
+  pub hora_fin: NaiveTime,
+}
+
+impl FranjaHoraria {
+  /// Minutos que abarca la franja.
+  pub fn minutos(&self) -> u32 {
+    (self.hora_fin - self.hora_inicio).num_minutes().max(0) as u32
+  }
+
+  /// Convierte la lista de franjas con formato `HH:MM-HH:MM,...`
+  /// que devuelven las consultas de horarios.
+  pub fn parsear_lista(franjas: &str) -> Vec<FranjaHoraria> {
+    franjas
+      .split(',')
+      .filter_map(|franja| {
+        let (ini, fin) = franja.split_once('-')?;
+        Some(FranjaHoraria {
+          hora_inicio: NaiveTime::parse_from_str(ini.trim(), "%H:%M").ok()?,
+          hora_fin: NaiveTime::parse_from_str(fin.trim(), "%H:%M").ok()?,
+        })
+      })
+      .collect()
+  }
+}
+
+/// Minutos a trabajar según las franjas, o según los minutos
+/// del horario si no tiene franjas.
+fn minutos_franjas(franjas: &[FranjaHoraria], minutos: u16) -> u32 {
+  if franjas.is_empty() {
+    minutos as u32
+  } else {
+    franjas.iter().map(FranjaHoraria::minutos).sum()
+  }
+}
+
+/// Reglas de las pausas de un horario.
+///
+/// Indica los minutos de cada tipo de pausa que se retribuyen al día.
+/// El tiempo de pausa que los supera no cuenta como trabajado.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReglasPausa {
+  pub comida_retribuida: u16,
+  pub personal_retribuida: u16,
+}
+
 #[derive(Debug)]
 pub struct DescriptorHorario {
   pub id: u32,
   pub dia: Dia,
-  pub horas: u8,
+  /// Minutos a trabajar en el día
+  pub minutos: u16,
+  pub franjas: Vec<FranjaHoraria>,
+  pub pausas: ReglasPausa,
+}
+
+impl DescriptorHorario {
+  /// Minutos a trabajar en el día.
+  pub fn minutos_a_trabajar(&self) -> u32 {
+    minutos_franjas(&self.franjas, self.minutos)
+  }
 }
 
 #[derive(Debug)]
@@ -67,12 +128,103 @@ pub struct ConfigHorario {
   pub usuario: u32,
   pub fecha_creacion: NaiveDate,
   pub dia: Dia,
-  pub horas: u8,
+  /// Minutos a trabajar en el día
+  pub minutos: u16,
+  pub franjas: Vec<FranjaHoraria>,
+  pub pausas: ReglasPausa,
   pub caducidad_fecha_ini: Option<NaiveDate>,
   pub caducidad_fecha_fin: Option<NaiveDate>,
   pub cortesia: u8,
 }
 
+impl ConfigHorario {
+  /// Minutos a trabajar en el día.
+  ///
+  /// Los horarios anteriores a las franjas solo tienen minutos.
+  pub fn minutos_a_trabajar(&self) -> u32 {
+    minutos_franjas(&self.franjas, self.minutos)
+  }
+
+  /// Indica si la fecha está dentro del periodo de caducidad del
+  /// horario. Sin fechas de caducidad el horario es permanente.
+  pub fn vigente(&self, fecha: NaiveDate) -> bool {
+    self.caducidad_fecha_ini.is_none_or(|ini| fecha >= ini)
+      && self.caducidad_fecha_fin.is_none_or(|fin| fecha <= fin)
+  }
+
+  /// Indica si el horario solo se aplica en un periodo de caducidad.
+  pub fn temporal(&self) -> bool {
+    self.caducidad_fecha_ini.is_some() || self.caducidad_fecha_fin.is_some()
+  }
+
+  /// Minutos que se guardan en el horario.
+  ///
+  /// Si el horario tiene franjas se guarda su suma.
+  pub fn minutos_guardados(&self) -> u16 {
+    self.minutos_a_trabajar().min(u16::MAX as u32) as u16
+  }
+
+  /// Valida que las franjas estén ordenadas y no se solapen.
+  pub fn validar_franjas(&self) -> Result<(), String> {
+    let mut anterior: Option<&FranjaHoraria> = None;
+
+    for franja in &self.franjas {
+      if franja.hora_fin <= franja.hora_inicio {
+        return Err(format!(
+          "La franja {} - {} termina antes de empezar",
+          franja.hora_inicio.format("%H:%M"),
+          franja.hora_fin.format("%H:%M")
+        ));
+      }
+
+      if let Some(ant) = anterior
+        && franja.hora_inicio < ant.hora_fin
+      {
+        return Err(format!(
+          "La franja {} - {} se solapa con la anterior o no está ordenada",
+          franja.hora_inicio.format("%H:%M"),
+          franja.hora_fin.format("%H:%M")
+        ));
+      }
+
+      anterior = Some(franja);
+    }
+
+    Ok(())
+  }
+
+  /// Valida que los minutos retribuidos de las pausas no superen
+  /// los minutos a trabajar en el día.
+  pub fn validar_pausas(&self) -> Result<(), String> {
+    let minutos = self.minutos_a_trabajar();
+    let reglas = [
+      ("comida", self.pausas.comida_retribuida),
+      ("personal", self.pausas.personal_retribuida),
+    ];
+
+    for (tipo, retribuidos) in reglas {
+      if retribuidos as u32 > minutos {
+        return Err(format!(
+          "Los minutos retribuidos de la pausa {}: {} superan los \
+          minutos a trabajar en el día: {}",
+          tipo, retribuidos, minutos
+        ));
+      }
+    }
+
+    let total = reglas.iter().map(|(_, m)| *m as u32).sum::<u32>();
+    if total > minutos {
+      return Err(format!(
+        "Los minutos retribuidos de las pausas: {} superan los \
+        minutos a trabajar en el día: {}",
+        total, minutos
+      ));
+    }
+
+    Ok(())
+  }
+}
+
 #[derive(Debug)]
 pub struct Calendario {
   pub id: u32,
@@ -135,3 +287,102 @@ pub struct CalendarioFecha {
   pub fecha_fin: NaiveDate,
   pub tipo: TipoCalendarioFecha,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn franja(ini: (u32, u32), fin: (u32, u32)) -> FranjaHoraria {
+    FranjaHoraria {
+      hora_inicio: NaiveTime::from_hms_opt(ini.0, ini.1, 0).unwrap(),
+      hora_fin: NaiveTime::from_hms_opt(fin.0, fin.1, 0).unwrap(),
+    }
+  }
+
+  fn config(minutos: u16, franjas: Vec<FranjaHoraria>) -> ConfigHorario {
+    ConfigHorario {
+      id: 1,
+      usuario: 1,
+      fecha_creacion: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+      dia: Dia::Lunes,
+      minutos,
+      franjas,
+      pausas: ReglasPausa::default(),
+      caducidad_fecha_ini: None,
+      caducidad_fecha_fin: None,
+      cortesia: 0,
+    }
+  }
+
+  #[test]
+  fn test_minutos_a_trabajar_jornada_partida() {
+    let partida =
+      config(0, vec![franja((8, 0), (14, 0)), franja((16, 0), (17, 30))]);
+    assert_eq!(partida.minutos_a_trabajar(), 450);
+    assert_eq!(partida.minutos_guardados(), 450);
+
+    // Contrato de 6h45
+    let sin_franjas = config(405, vec![]);
+    assert_eq!(sin_franjas.minutos_a_trabajar(), 405);
+  }
+
+  #[test]
+  fn test_validar_franjas() {
+    assert!(config(0, vec![]).validar_franjas().is_ok());
+    assert!(
+      config(0, vec![franja((8, 0), (14, 0)), franja((14, 0), (15, 0))])
+        .validar_franjas()
+        .is_ok()
+    );
+    assert!(
+      config(0, vec![franja((14, 0), (8, 0))])
+        .validar_franjas()
+        .is_err()
+    );
+    assert!(
+      config(0, vec![franja((8, 0), (14, 0)), franja((13, 0), (15, 0))])
+        .validar_franjas()
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn test_validar_pausas() {
+    let con_pausas = |comida, personal| ConfigHorario {
+      pausas: ReglasPausa {
+        comida_retribuida: comida,
+        personal_retribuida: personal,
+      },
+      ..config(0, vec![franja((8, 0), (12, 0))])
+    };
+
+    assert!(con_pausas(0, 0).validar_pausas().is_ok());
+    assert!(con_pausas(60, 20).validar_pausas().is_ok());
+    assert!(con_pausas(240, 0).validar_pausas().is_ok());
+    assert!(con_pausas(241, 0).validar_pausas().is_err());
+    assert!(con_pausas(0, 300).validar_pausas().is_err());
+    // Cada regla cabe en el día pero la suma no
+    assert!(con_pausas(200, 60).validar_pausas().is_err());
+    // Sin franjas se comparan con los minutos del horario
+    assert!(
+      ConfigHorario {
+        pausas: ReglasPausa {
+          comida_retribuida: 30,
+          personal_retribuida: 0,
+        },
+        ..config(20, vec![])
+      }
+      .validar_pausas()
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn test_parsear_lista_franjas() {
+    assert_eq!(
+      FranjaHoraria::parsear_lista("08:00-14:00,16:00-17:30"),
+      vec![franja((8, 0), (14, 0)), franja((16, 0), (17, 30))]
+    );
+    assert!(FranjaHoraria::parsear_lista("").is_empty());
+  }
+}
@@ -14,6 +14,12 @@
 //! El efecto de los horarios para un marcaje se aplica desde la
 //! fecha de creación del grupo.
 //!
+//! La jornada de cada día se guarda en minutos para admitir contratos
+//! como 7,5 horas o 6h45. Cada horario puede definir además una o varias
+//! franjas con la hora de inicio y fin (jornada partida). El tiempo a
+//! trabajar se calcula con las franjas; los horarios sin franjas usan
+//! los minutos del día.
+//!
 //! Los horarios a parte de ser asignados a un usuario para una
 //! determinada fecha, permiten caducidad. Esto es útil cuando un
 //! empleado debe realizar un horario extra a su horario normal.
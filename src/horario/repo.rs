@@ -6,13 +6,33 @@ use sqlx::{Row, mysql::MySqlRow};
 use crate::{
   horario::{
     Calendario, CalendarioFecha, ConfigHorario, DescriptorHorario, Dia,
-    TipoCalendarioFecha,
+    FranjaHoraria, ReglasPausa, TipoCalendarioFecha,
   },
   infra::{
     DBError, DateOptional, NONE_DATE, PoolConexion, ShortDateTimeFormat,
+    Transaccion,
   },
 };
 
+/// Subconsulta que devuelve las franjas de un horario como
+/// `HH:MM-HH:MM,...` dado el alias de la tabla `horarios`.
+///
+/// Se convierte con [`FranjaHoraria::parsear_lista`].
+#[macro_export]
+macro_rules! franjas_horario {
+  ($alias:literal) => {
+    concat!(
+      "(SELECT CAST(GROUP_CONCAT(CONCAT(
+          TIME_FORMAT(f.hora_inicio, '%H:%i'), '-',
+          TIME_FORMAT(f.hora_fin, '%H:%i'))
+        ORDER BY f.hora_inicio SEPARATOR ',') AS CHAR)
+      FROM horarios_franjas f WHERE f.horario = ",
+      $alias,
+      ".id)"
+    )
+  };
+}
+
 /// Implementación del repositorio de los horarios.
 pub struct HorarioRepo {
   pool: PoolConexion,
@@ -22,10 +42,17 @@ impl HorarioRepo {
   pub fn new(pool: PoolConexion) -> Self {
     HorarioRepo { pool }
   }
+
+  pub(in crate::horario) fn conexion(&self) -> &PoolConexion {
+    &self.pool
+  }
 }
 
 impl HorarioRepo {
   /// Obtiene el horario dada una fecha para un usuario.
+  ///
+  /// Solo se consideran los horarios cuyo periodo de caducidad contiene
+  /// la fecha, prevaleciendo los temporales sobre los permanentes.
   pub(in crate::horario) async fn horario_cercano(
     &self,
     usuario: u32,
@@ -40,18 +67,24 @@ impl HorarioRepo {
       "Buscando el horario más cercano del usuario"
     );
 
-    const QUERY: &str = "SELECT id, dia, horas, cortesia
-        FROM horarios
-        WHERE usuario = ?
-          AND dia = ?
-          AND fecha_creacion = (
+    const QUERY: &str = concat!(
+      "SELECT h.id, h.dia, h.minutos, h.cortesia,
+        h.pausa_comida_retribuida, h.pausa_personal_retribuida, ",
+      franjas_horario!("h"),
+      " AS franjas
+        FROM horarios h
+        WHERE h.usuario = ?
+          AND h.dia = ?
+          AND h.fecha_creacion = (
             SELECT MAX(fecha_creacion)
             FROM horarios
             WHERE usuario = ? AND fecha_creacion <= ?
           )
-          AND (caducidad_fecha_fin IS NULL OR ? >= caducidad_fecha_ini)
-          AND (caducidad_fecha_fin IS NULL OR ? <= caducidad_fecha_fin)
-        LIMIT 1";
+          AND ? >= h.caducidad_fecha_ini
+          AND (h.caducidad_fecha_fin IS NULL OR ? <= h.caducidad_fecha_fin)
+        ORDER BY h.caducidad_fecha_fin IS NULL, h.caducidad_fecha_ini DESC
+        LIMIT 1"
+    );
 
     let row = sqlx::query(QUERY)
       .bind(usuario)
@@ -68,7 +101,9 @@ impl HorarioRepo {
       Ok(DescriptorHorario {
         id: row.get("id"),
         dia: Dia::from(row.get::<String, _>("dia").as_str()),
-        horas: row.get("horas"),
+        minutos: row.get("minutos"),
+        franjas: franjas_from_row(&row),
+        pausas: reglas_pausa_from_row(&row),
       })
     } else {
       Err(DBError::registro_vacio(format!(
@@ -85,25 +120,57 @@ impl HorarioRepo {
     config: &ConfigHorario,
   ) -> Result<u32, DBError> {
     const QUERY: &str = "INSERT INTO horarios
-      (usuario, fecha_creacion, dia, horas,
-      caducidad_fecha_ini, caducidad_fecha_fin, cortesia)
-      VALUES (?, ?, ?, ?, ?, ?, ?);";
+      (usuario, fecha_creacion, dia, minutos,
+      caducidad_fecha_ini, caducidad_fecha_fin, cortesia,
+      pausa_comida_retribuida, pausa_personal_retribuida)
+      VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?);";
 
     let cad_fecha_ini = config.caducidad_fecha_ini.convert_to_date();
 
+    let mut tr = self.pool.empezar_transaccion().await?;
+
     let res = sqlx::query(QUERY)
       .bind(config.usuario)
       .bind(config.fecha_creacion)
       .bind(config.dia.letra())
-      .bind(config.horas)
+      .bind(config.minutos_guardados())
       .bind(cad_fecha_ini)
       .bind(config.caducidad_fecha_fin)
       .bind(config.cortesia)
-      .execute(self.pool.conexion())
+      .bind(config.pausas.comida_retribuida)
+      .bind(config.pausas.personal_retribuida)
+      .execute(&mut **tr.deref_mut())
       .await
       .map_err(DBError::from_sqlx)?;
 
-    Ok(res.last_insert_id() as u32)
+    let id = res.last_insert_id() as u32;
+    Self::agregar_franjas(&mut tr, id, &config.franjas).await?;
+    tr.commit().await?;
+
+    Ok(id)
+  }
+
+  /// Añade las franjas de un horario.
+  async fn agregar_franjas(
+    trans: &mut Transaccion<'_>,
+    horario: u32,
+    franjas: &[FranjaHoraria],
+  ) -> Result<(), DBError> {
+    const QUERY: &str = "INSERT INTO horarios_franjas
+      (horario, hora_inicio, hora_fin)
+      VALUES (?, ?, ?);";
+
+    for franja in franjas {
+      sqlx::query(QUERY)
+        .bind(horario)
+        .bind(franja.hora_inicio)
+        .bind(franja.hora_fin)
+        .execute(&mut **trans.deref_mut())
+        .await
+        .map_err(DBError::from_sqlx)?;
+    }
+
+    Ok(())
   }
 
   /// Modifica una configuración de horario para un usuario.
@@ -112,29 +179,42 @@ impl HorarioRepo {
     config: &ConfigHorario,
   ) -> Result<(), DBError> {
     const QUERY: &str = "UPDATE horarios SET
-        dia = ?, horas = ?, caducidad_fecha_ini = ?, caducidad_fecha_fin = ?, cortesia = ?
+        dia = ?, minutos = ?, caducidad_fecha_ini = ?, caducidad_fecha_fin = ?, cortesia = ?,
+        pausa_comida_retribuida = ?, pausa_personal_retribuida = ?
         WHERE id = ?;";
 
     let cad_fecha_ini = config.caducidad_fecha_ini.convert_to_date();
 
+    let mut tr = self.pool.empezar_transaccion().await?;
+
     let res = sqlx::query(QUERY)
       .bind(config.dia.letra())
-      .bind(config.horas)
+      .bind(config.minutos_guardados())
       .bind(cad_fecha_ini)
       .bind(config.caducidad_fecha_fin)
       .bind(config.cortesia)
+      .bind(config.pausas.comida_retribuida)
+      .bind(config.pausas.personal_retribuida)
       .bind(config.id)
-      .execute(self.pool.conexion())
+      .execute(&mut **tr.deref_mut())
       .await
       .map_err(DBError::from_sqlx)?;
 
     if res.rows_affected() == 0 {
-      Err(DBError::registro_vacio(
+      return Err(DBError::registro_vacio(
         "Modificando configuración de horario".to_string(),
-      ))
-    } else {
-      Ok(())
+      ));
     }
+
+    sqlx::query("DELETE FROM horarios_franjas WHERE horario = ?;")
+      .bind(config.id)
+      .execute(&mut **tr.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Self::agregar_franjas(&mut tr, config.id, &config.franjas).await?;
+
+    tr.commit().await
   }
 
   /// Elimina una configuración de horario para un usuario.
@@ -165,30 +245,66 @@ impl HorarioRepo {
     usuario: u32,
     nueva_fecha_creacion: NaiveDate,
   ) -> Result<(), DBError> {
+    const QUERY_ORIGEN: &str = "SELECT MAX(fecha_creacion)
+      FROM horarios
+      WHERE usuario = ? AND fecha_creacion <= ?;";
+
     const QUERY: &str = "INSERT INTO horarios
-      (usuario, fecha_creacion, dia, horas,
-       caducidad_fecha_ini, caducidad_fecha_fin, cortesia)
-      SELECT usuario, ?, dia, horas, caducidad_fecha_ini, caducidad_fecha_fin, cortesia
+      (usuario, fecha_creacion, dia, minutos,
+       caducidad_fecha_ini, caducidad_fecha_fin, cortesia,
+       pausa_comida_retribuida, pausa_personal_retribuida)
+      SELECT usuario, ?, dia, minutos, caducidad_fecha_ini, caducidad_fecha_fin, cortesia,
+        pausa_comida_retribuida, pausa_personal_retribuida
       FROM horarios
-      WHERE usuario = ? AND fecha_creacion = (
-        SELECT MAX(fecha_creacion)
-        FROM horarios
-        WHERE usuario = ? AND fecha_creacion <= ?
-      )
+      WHERE usuario = ? AND fecha_creacion = ?
       AND caducidad_fecha_fin IS NULL;";
 
+    // Las franjas se copian emparejando los horarios por día, que es
+    // único dentro de un grupo para los horarios sin caducidad.
+    const QUERY_FRANJAS: &str = "INSERT INTO horarios_franjas
+      (horario, hora_inicio, hora_fin)
+      SELECT n.id, f.hora_inicio, f.hora_fin
+      FROM horarios_franjas f
+      JOIN horarios o ON o.id = f.horario
+      JOIN horarios n ON n.usuario = o.usuario AND n.dia = o.dia
+        AND n.caducidad_fecha_ini = o.caducidad_fecha_ini
+        AND n.caducidad_fecha_fin IS NULL
+      WHERE o.usuario = ? AND o.fecha_creacion = ?
+        AND o.caducidad_fecha_fin IS NULL
+        AND n.fecha_creacion = ?;";
+
     let fecha_limite = nueva_fecha_creacion.add(chrono::Duration::days(1));
 
+    let mut tr = self.pool.empezar_transaccion().await?;
+
+    let origen: Option<NaiveDate> = sqlx::query_scalar(QUERY_ORIGEN)
+      .bind(usuario)
+      .bind(fecha_limite)
+      .fetch_one(&mut **tr.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let Some(origen) = origen else {
+      return Ok(());
+    };
+
     sqlx::query(QUERY)
       .bind(nueva_fecha_creacion)
       .bind(usuario)
+      .bind(origen)
+      .execute(&mut **tr.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    sqlx::query(QUERY_FRANJAS)
       .bind(usuario)
-      .bind(fecha_limite)
-      .execute(self.pool.conexion())
+      .bind(origen)
+      .bind(nueva_fecha_creacion)
+      .execute(&mut **tr.deref_mut())
       .await
       .map_err(DBError::from_sqlx)?;
 
-    Ok(())
+    tr.commit().await
   }
 
   /// Obtiene un horario configurado dado el id.
@@ -196,10 +312,15 @@ impl HorarioRepo {
     &self,
     id: u32,
   ) -> Result<ConfigHorario, DBError> {
-    const QUERY: &str = "SELECT id, usuario, fecha_creacion, dia, horas,
-        caducidad_fecha_ini, caducidad_fecha_fin, cortesia
-      FROM horarios
-      WHERE id = ?";
+    const QUERY: &str = concat!(
+      "SELECT h.id, h.usuario, h.fecha_creacion, h.dia, h.minutos,
+        h.caducidad_fecha_ini, h.caducidad_fecha_fin, h.cortesia,
+        h.pausa_comida_retribuida, h.pausa_personal_retribuida, ",
+      franjas_horario!("h"),
+      " AS franjas
+      FROM horarios h
+      WHERE h.id = ?"
+    );
 
     let row = sqlx::query(QUERY)
       .bind(id)
@@ -223,15 +344,20 @@ impl HorarioRepo {
     usuario: u32,
     fecha_actual: NaiveDate,
   ) -> Result<Vec<ConfigHorario>, DBError> {
-    const QUERY: &str = "SELECT id, usuario, fecha_creacion, dia, horas,
-        caducidad_fecha_ini, caducidad_fecha_fin, cortesia
-      FROM horarios
-      WHERE usuario = ? AND fecha_creacion = (
+    const QUERY: &str = concat!(
+      "SELECT h.id, h.usuario, h.fecha_creacion, h.dia, h.minutos,
+        h.caducidad_fecha_ini, h.caducidad_fecha_fin, h.cortesia,
+        h.pausa_comida_retribuida, h.pausa_personal_retribuida, ",
+      franjas_horario!("h"),
+      " AS franjas
+      FROM horarios h
+      WHERE h.usuario = ? AND h.fecha_creacion = (
         SELECT MAX(fecha_creacion)
         FROM horarios
         WHERE usuario = ? AND fecha_creacion <= ?
       )
-      ORDER BY dia;";
+      ORDER BY h.dia;"
+    );
 
     let fecha_limite = fecha_actual.add(chrono::Duration::days(1));
 
@@ -247,6 +373,9 @@ impl HorarioRepo {
   }
 
   /// Verifica que una configuración no se solape con otras para el mismo día.
+  ///
+  /// Un horario temporal puede convivir con el permanente del mismo día,
+  /// pero no con otro temporal cuyo periodo de caducidad se cruce.
   pub(in crate::horario) async fn config_horario_solape(
     &self,
     config_horario: &ConfigHorario,
@@ -258,8 +387,7 @@ impl HorarioRepo {
       AND id <> ?
       AND dia = ?
       AND (
-        caducidad_fecha_fin IS NULL 
-        OR ? IS NULL 
+        (caducidad_fecha_fin IS NULL AND ? IS NULL)
         OR (caducidad_fecha_ini <= ? AND caducidad_fecha_fin >= ?)
       );";
 
@@ -306,7 +434,8 @@ pub(crate) fn config_horario_from_row(row: &MySqlRow) -> ConfigHorario {
     usuario: row.get("usuario"),
     fecha_creacion: row.get("fecha_creacion"),
     dia: Dia::from(row.get::<String, _>("dia").as_str()),
-    horas: row.get("horas"),
+    minutos: row.get("minutos"),
+    franjas: franjas_from_row(row),
     caducidad_fecha_ini: {
       // 01/01/1900 es equivalente a nulo, pero no se utiliza
       // nulo porque se encuentra en un índice
@@ -319,9 +448,28 @@ pub(crate) fn config_horario_from_row(row: &MySqlRow) -> ConfigHorario {
     },
     caducidad_fecha_fin: row.get("caducidad_fecha_fin"),
     cortesia: row.get("cortesia"),
+    pausas: reglas_pausa_from_row(row),
   }
 }
 
+/// Obtiene las reglas de las pausas de las columnas del horario.
+pub(crate) fn reglas_pausa_from_row(row: &MySqlRow) -> ReglasPausa {
+  ReglasPausa {
+    comida_retribuida: row.get("pausa_comida_retribuida"),
+    personal_retribuida: row.get("pausa_personal_retribuida"),
+  }
+}
+
+/// Obtiene las franjas de la columna `franjas` de la consulta.
+///
+/// La columna se obtiene con la subconsulta [`franjas_horario!`].
+pub(crate) fn franjas_from_row(row: &MySqlRow) -> Vec<FranjaHoraria> {
+  row
+    .get::<Option<String>, _>("franjas")
+    .map(|franjas| FranjaHoraria::parsear_lista(&franjas))
+    .unwrap_or_default()
+}
+
 impl HorarioRepo {
   /// Devuelve todos los calendarios ordenados por nombre.
   pub(in crate::horario) async fn calendarios(
@@ -420,6 +568,60 @@ impl HorarioRepo {
     }
   }
 
+  /// Devuelve el calendario personal de un usuario si lo tiene.
+  pub(in crate::horario) async fn calendario_personal(
+    &self,
+    usuario: u32,
+  ) -> Result<Option<u32>, DBError> {
+    const QUERY: &str =
+      "SELECT calendario FROM calendarios_personales WHERE usuario = ?";
+
+    sqlx::query_scalar(QUERY)
+      .bind(usuario)
+      .fetch_optional(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)
+  }
+
+  /// Crea el calendario personal de un usuario y se lo asigna.
+  pub(in crate::horario) async fn crear_calendario_personal(
+    &self,
+    usuario: u32,
+    calendario: &Calendario,
+  ) -> Result<u32, DBError> {
+    const QUERY_CALENDARIO: &str =
+      "INSERT INTO calendarios (nombre, descripcion) VALUES (?, ?)";
+    const QUERY_PERSONAL: &str =
+      "INSERT INTO calendarios_personales (usuario, calendario) VALUES (?, ?)";
+    const QUERY_USUARIO: &str =
+      "INSERT IGNORE INTO calendarios_usuario (usuario, calendario)
+       VALUES (?, ?)";
+
+    let mut tr = self.pool.empezar_transaccion().await?;
+
+    let res = sqlx::query(QUERY_CALENDARIO)
+      .bind(&calendario.nombre)
+      .bind(&calendario.descripcion)
+      .execute(&mut **tr.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let id = res.last_insert_id() as u32;
+
+    for query in [QUERY_PERSONAL, QUERY_USUARIO] {
+      sqlx::query(query)
+        .bind(usuario)
+        .bind(id)
+        .execute(&mut **tr.deref_mut())
+        .await
+        .map_err(DBError::from_sqlx)?;
+    }
+
+    tr.commit().await?;
+
+    Ok(id)
+  }
+
   /// Devuelve las fechas de un calendario filtradas por rango.
   pub(in crate::horario) async fn calendario_fechas(
     &self,
@@ -485,6 +687,7 @@ impl HorarioRepo {
   /// Crea una nueva fecha en el calendario.
   pub(in crate::horario) async fn crear_calendario_fecha(
     &self,
+    trans: &mut Transaccion<'_>,
     fecha: &CalendarioFecha,
   ) -> Result<u32, DBError> {
     const QUERY: &str = "INSERT INTO calendario_fechas 
@@ -495,7 +698,7 @@ impl HorarioRepo {
       .bind(fecha.fecha_inicio)
       .bind(fecha.fecha_fin)
       .bind(fecha.tipo as u8)
-      .execute(self.pool.conexion())
+      .execute(&mut **trans.deref_mut())
       .await
       .map_err(DBError::from_sqlx)?;
 
@@ -5,7 +5,7 @@ use crate::{
   horario::{
     Calendario, CalendarioFecha, ConfigHorario, DescriptorHorario, HorarioRepo,
   },
-  infra::{DBError, ServicioError, ShortDateTimeFormat},
+  infra::{DBError, ServicioError, ShortDateTimeFormat, Transaccion},
 };
 
 /// Servicio para manejar operaciones relacionadas con horarios.
@@ -70,7 +70,7 @@ impl HorarioServicio {
     );
 
     let fecha_actual = Utc::now()
-      .with_timezone(&self.cnfg.zona_horaria)
+      .with_timezone(&self.cnfg.zona_usuario(usuario))
       .naive_local()
       .date();
 
@@ -137,7 +137,8 @@ impl HorarioServicio {
 
   /// Añade una nueva configuración de horario.
   ///
-  /// Verifica que no exista solapamiento con otros horarios.
+  /// Verifica que las franjas y las pausas sean válidas y que no exista
+  /// solapamiento con otros horarios.
   /// Si existe solapamiento se envía un error al usuario.
   /// Por último se crea la configuración del horario.
   pub async fn agregar_config_horario(
@@ -149,6 +150,13 @@ impl HorarioServicio {
       fecha = %config_horario.fecha_creacion,
       "Se ha iniciado el servicio para agregar una configuración de horario");
 
+    config_horario
+      .validar_franjas()
+      .map_err(ServicioError::Validacion)?;
+    config_horario
+      .validar_pausas()
+      .map_err(ServicioError::Validacion)?;
+
     if self
       .repo
       .config_horario_solape(config_horario)
@@ -194,6 +202,7 @@ impl HorarioServicio {
 
   /// Modifica una configuración de horario.
   ///
+  /// Verifica que las franjas y las pausas sean válidas.
   /// Verifica que no esté referenciada en un marcaje.
   /// Verifica que no exista solapamiento con otros horarios.
   pub async fn modificar_config_horario(
@@ -204,6 +213,13 @@ impl HorarioServicio {
       config_horario = ?config_horario,
       "Se ha iniciado el servicio para modificar una configuración de horario");
 
+    config_horario
+      .validar_franjas()
+      .map_err(ServicioError::Validacion)?;
+    config_horario
+      .validar_pausas()
+      .map_err(ServicioError::Validacion)?;
+
     if self
       .repo
       .esta_horario_en_marcaje(config_horario.id)
@@ -401,6 +417,49 @@ impl HorarioServicio {
     Ok(())
   }
 
+  /// Devuelve el calendario personal de un usuario.
+  ///
+  /// Si el usuario no lo tiene se crea y se le asigna.
+  pub async fn calendario_personal(
+    &self,
+    usuario: u32,
+  ) -> Result<u32, ServicioError> {
+    let personal =
+      self
+        .repo
+        .calendario_personal(usuario)
+        .await
+        .map_err(|err| {
+          tracing::error!(
+          usuario = usuario, error = %err,
+          "Obteniendo calendario personal");
+          ServicioError::from(err)
+        })?;
+
+    if let Some(id) = personal {
+      return Ok(id);
+    }
+
+    tracing::info!(usuario = usuario, "Creando calendario personal");
+
+    let calendario = Calendario {
+      id: 0,
+      nombre: format!("Personal {}", usuario),
+      descripcion: "Ausencias aprobadas del usuario".to_string(),
+    };
+
+    self
+      .repo
+      .crear_calendario_personal(usuario, &calendario)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = usuario, error = %err,
+          "Creando calendario personal");
+        ServicioError::from(err)
+      })
+  }
+
   /// Devuelve las fechas señaladas de un calendario.
   ///
   /// Permite filtrar por un rango de fechas (inicio y fin).
@@ -462,6 +521,42 @@ impl HorarioServicio {
       "Iniciando creación de fecha de calendario"
     );
 
+    let mut tr =
+      self
+        .repo
+        .conexion()
+        .empezar_transaccion()
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            fecha = ?fecha, error = %err,
+            "Iniciando transacción para crear fecha de calendario");
+          ServicioError::from(err)
+        })?;
+
+    let id = self.agregar_calendario_fecha(&mut tr, fecha).await?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        fecha = ?fecha, error = %err,
+        "Commit transacción para crear fecha de calendario");
+      ServicioError::from(err)
+    })?;
+
+    tracing::debug!(id = id, "Fecha de calendario creada con éxito");
+
+    Ok(id)
+  }
+
+  /// Agrega una fecha señalada a un calendario dentro de una transacción.
+  ///
+  /// Permite crear la fecha junto con otros datos que dependen de ella,
+  /// como la aprobación de una solicitud de ausencia.
+  pub async fn agregar_calendario_fecha(
+    &self,
+    trans: &mut Transaccion<'_>,
+    fecha: &CalendarioFecha,
+  ) -> Result<u32, ServicioError> {
     self
       .validar_conflicto_marcajes_calendario_fechas(
         fecha.calendario,
@@ -472,18 +567,14 @@ impl HorarioServicio {
 
     self
       .repo
-      .crear_calendario_fecha(fecha)
+      .crear_calendario_fecha(trans, fecha)
       .await
       .map_err(|err| {
         tracing::error!(
           fecha = ?fecha, error = %err,
           "Creando fecha de calendario");
         ServicioError::from(err)
-      })?;
-
-    tracing::debug!(id = fecha.id, "Fecha de calendario creada con éxito");
-
-    Ok(fecha.id)
+      })
   }
 
   /// Actualiza una fecha señalada existente.
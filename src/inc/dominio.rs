@@ -62,6 +62,16 @@ impl From<u8> for TipoIncidencia {
   }
 }
 
+/// Tratamiento de los marcajes olvidados sin hora de salida.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CierreMarcajeOlvidado {
+  /// Se registra como salida la hora de fin del horario
+  Cerrar,
+  /// Se abre una incidencia de corrección de salida en conflicto
+  /// para que el empleado confirme la hora de salida
+  Incidencia,
+}
+
 #[derive(Debug)]
 pub struct Incidencia {
   pub id: u32,
@@ -80,7 +90,8 @@ pub struct Incidencia {
   // La fecha solo para estados intermedios
   pub fecha_estado: Option<NaiveDateTime>,
   pub error: Option<String>,
-  pub usuario_creador: u32,
+  /// Nulo si la incidencia la crea el sistema
+  pub usuario_creador: Option<u32>,
   pub usuario_gestor: Option<u32>,
   pub motivo_solicitud: Option<String>,
   pub motivo_rechazo: Option<String>,
@@ -107,7 +118,8 @@ pub struct IncidenciaMarcaje {
   pub hora_inicio: Option<NaiveTime>,
   pub hora_fin: Option<NaiveTime>,
   pub marcaje: Option<DescriptorMarcaje>,
-  pub usuario_creador: u32,
+  pub usuario_creador: Option<u32>,
+  pub motivo_solicitud: Option<String>,
 }
 
 /// Define la entidad mínima necesaria para trazas.
@@ -119,7 +131,7 @@ pub struct IncidenciaTraza {
   pub hora_fin: Option<NaiveTime>,
   pub motivo_rechazo: Option<String>,
   pub fecha_estado: Option<NaiveDateTime>,
-  pub usuario_creador: u32,
+  pub usuario_creador: Option<u32>,
   pub usuario_gestor: Option<u32>,
   pub error: Option<String>,
 }
@@ -50,6 +50,12 @@
 //!    difiere del campo creador de la incidencia
 //!  - Si la incidencia no esta en estado resuelta,
 //!    el usuario puede cancelarla.
+//!  - Un proceso diario trata los marcajes que se quedaron sin
+//!    hora de salida tras terminar la jornada del horario. Según
+//!    la configuración, los cierra con la hora de salida del
+//!    horario o abre una incidencia de corrección de salida en
+//!    conflicto, con esa hora propuesta, para que el empleado la
+//!    confirme volviendo a solicitarla.
 //!
 //! Diagrama de estados:
 //!   Solicitud -> Resolver, Rechazar, Cancelada
@@ -31,6 +31,7 @@ impl IncidenciaRepo {
   /// Añade una incidencia
   pub(in crate::inc) async fn agregar(
     &self,
+    trans: &mut Transaccion<'_>,
     reg: &Incidencia,
   ) -> Result<u32, DBError> {
     const QUERY: &str = "INSERT INTO incidencias
@@ -55,7 +56,7 @@ impl IncidenciaRepo {
       .bind(reg.fecha_resolucion)
       .bind(reg.fecha_estado)
       .bind(reg.usuario)
-      .execute(self.pool.conexion())
+      .execute(&mut **trans.deref_mut())
       .await
       .map_err(DBError::from_sqlx)?;
 
@@ -262,7 +263,7 @@ impl IncidenciaRepo {
   ) -> Result<IncidenciaMarcaje, DBError> {
     const QUERY: &str = "SELECT
       i.tipo, i.usuario, i.fecha, i.hora_inicio,
-      i.hora_fin, i.marcaje, i.estado, i.usuario_creador,
+      i.hora_fin, i.marcaje, i.estado, i.usuario_creador, i.motivo_solicitud,
       m.hora_inicio AS m_hora_inicio, m.hora_fin AS m_hora_fin      
       FROM incidencias i
       LEFT JOIN marcajes m ON i.marcaje = m.id
@@ -289,6 +290,7 @@ impl IncidenciaRepo {
           }
         }),
         usuario_creador: row.get("usuario_creador"),
+        motivo_solicitud: row.get("motivo_solicitud"),
       })
     } else {
       Err(DBError::registro_vacio(format!(
@@ -316,30 +318,8 @@ impl IncidenciaRepo {
     usuario: Option<u32>,
     limit: u8,
   ) -> Result<DominioWithCacheUsuario<Incidencia>, DBError> {
-    let mut qb = sqlx::QueryBuilder::<sqlx::MySql>::new(
-      r"SELECT
-      i.id, i.tipo, i.fecha_solicitud,
-      i.fecha, i.hora_inicio, i.hora_fin, 
-      i.marcaje, i.estado, i.error,
-      i.motivo_solicitud, i.motivo_rechazo,
-      i.fecha_resolucion, i.fecha_estado,
-      u.id AS u_id, u.nombre AS u_nombre,
-      u.primer_apellido AS u_primer_apellido,
-      u.segundo_apellido AS u_segundo_apellido,
-      uc.id AS uc_id, uc.nombre AS uc_nombre,
-      uc.primer_apellido AS uc_primer_apellido,
-      uc.segundo_apellido AS uc_segundo_apellido,
-      ug.id AS ug_id, ug.nombre AS ug_nombre,
-      ug.primer_apellido AS ug_primer_apellido,
-      ug.segundo_apellido AS ug_segundo_apellido,
-      m.hora_inicio AS m_hora_inicio, m.hora_fin AS m_hora_fin
-      FROM incidencias i
-      JOIN usuarios u ON i.usuario = u.id      
-      JOIN usuarios uc ON i.usuario_creador = uc.id
-      LEFT JOIN usuarios ug ON i.usuario_gestor = ug.id
-      LEFT JOIN marcajes m ON i.marcaje = m.id
-      WHERE ",
-    );
+    let mut qb = sqlx::QueryBuilder::<sqlx::MySql>::new(SELECT_INCIDENCIAS);
+    qb.push(" WHERE ");
 
     if let Some(id_incidencia) = id {
       // Si hay ID, buscar solo por ID
@@ -371,7 +351,9 @@ impl IncidenciaRepo {
             .push_bind(u)
             .push(")");
         } else {
-          qb.push("i.usuario_creador = ").push_bind(u);
+          // Las incidencias creadas por el sistema son del empleado
+          qb.push("IFNULL(i.usuario_creador, i.usuario) = ")
+            .push_bind(u);
         }
       }
 
@@ -389,61 +371,121 @@ impl IncidenciaRepo {
       .await
       .map_err(DBError::from_sqlx)?;
 
-    let capacidad = rows.len();
-    let mut resultado = DominioWithCacheUsuario::<Incidencia>::new(capacidad);
+    Ok(incidencias_from_rows(rows))
+  }
 
-    for row in rows {
-      resultado.push_usuario(DescriptorUsuario {
-        id: row.get("u_id"),
-        nombre: row.get("u_nombre"),
-        primer_apellido: row.get("u_primer_apellido"),
-        segundo_apellido: row.get("u_segundo_apellido"),
-      });
+  /// Lista las incidencias de un empleado entre fechas.
+  ///
+  /// Se filtra por la fecha de la incidencia, no por la de solicitud,
+  /// y se incluyen todos los estados.
+  pub(in crate::inc) async fn incidencias_usuario(
+    &self,
+    usuario: u32,
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<DominioWithCacheUsuario<Incidencia>, DBError> {
+    let mut qb = sqlx::QueryBuilder::<sqlx::MySql>::new(SELECT_INCIDENCIAS);
+    qb.push(" WHERE i.usuario = ")
+      .push_bind(usuario)
+      .push(" AND i.fecha BETWEEN ")
+      .push_bind(fecha_inicio)
+      .push(" AND ")
+      .push_bind(fecha_fin)
+      .push(" ORDER BY i.fecha ASC, i.fecha_solicitud ASC");
 
+    let rows = qb
+      .build()
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(incidencias_from_rows(rows))
+  }
+}
+
+/// Consulta base de las incidencias con los usuarios relacionados
+const SELECT_INCIDENCIAS: &str = r"SELECT
+      i.id, i.tipo, i.fecha_solicitud,
+      i.fecha, i.hora_inicio, i.hora_fin, 
+      i.marcaje, i.estado, i.error,
+      i.motivo_solicitud, i.motivo_rechazo,
+      i.fecha_resolucion, i.fecha_estado,
+      u.id AS u_id, u.nombre AS u_nombre,
+      u.primer_apellido AS u_primer_apellido,
+      u.segundo_apellido AS u_segundo_apellido,
+      uc.id AS uc_id, uc.nombre AS uc_nombre,
+      uc.primer_apellido AS uc_primer_apellido,
+      uc.segundo_apellido AS uc_segundo_apellido,
+      ug.id AS ug_id, ug.nombre AS ug_nombre,
+      ug.primer_apellido AS ug_primer_apellido,
+      ug.segundo_apellido AS ug_segundo_apellido,
+      m.hora_inicio AS m_hora_inicio, m.hora_fin AS m_hora_fin
+      FROM incidencias i
+      JOIN usuarios u ON i.usuario = u.id
+      LEFT JOIN usuarios uc ON i.usuario_creador = uc.id
+      LEFT JOIN usuarios ug ON i.usuario_gestor = ug.id
+      LEFT JOIN marcajes m ON i.marcaje = m.id";
+
+/// Convierte las filas de [`SELECT_INCIDENCIAS`] en incidencias
+fn incidencias_from_rows(
+  rows: Vec<sqlx::mysql::MySqlRow>,
+) -> DominioWithCacheUsuario<Incidencia> {
+  let capacidad = rows.len();
+  let mut resultado = DominioWithCacheUsuario::<Incidencia>::new(capacidad);
+
+  for row in rows {
+    resultado.push_usuario(DescriptorUsuario {
+      id: row.get("u_id"),
+      nombre: row.get("u_nombre"),
+      primer_apellido: row.get("u_primer_apellido"),
+      segundo_apellido: row.get("u_segundo_apellido"),
+    });
+
+    if let Ok(uc_id) = row.try_get::<u32, _>("uc_id") {
       resultado.push_usuario(DescriptorUsuario {
-        id: row.get("uc_id"),
+        id: uc_id,
         nombre: row.get("uc_nombre"),
         primer_apellido: row.get("uc_primer_apellido"),
         segundo_apellido: row.get("uc_segundo_apellido"),
       });
+    }
 
-      if let Ok(ug_id) = row.try_get::<u32, _>("ug_id") {
-        resultado.push_usuario(DescriptorUsuario {
-          id: ug_id,
-          nombre: row.get("ug_nombre"),
-          primer_apellido: row.get("ug_primer_apellido"),
-          segundo_apellido: row.get("ug_segundo_apellido"),
-        });
-      }
-
-      let incidencia = Incidencia {
-        id: row.get("id"),
-        tipo: row.get::<u8, _>("tipo").into(),
-        fecha_solicitud: row.get("fecha_solicitud"),
-        fecha_resolucion: row.try_get("fecha_resolucion").ok(),
-        fecha: row.get("fecha"),
-        usuario: row.get("u_id"),
-        hora_inicio: row.get("hora_inicio"),
-        hora_fin: row.get("hora_fin"),
-        marcaje: row.try_get::<u32, _>("marcaje").ok().map(|m_id| {
-          DescriptorMarcaje {
-            id: m_id,
-            hora_inicio: row.get("m_hora_inicio"),
-            hora_fin: row.get("m_hora_fin"),
-          }
-        }),
-        estado: row.get::<u8, _>("estado").into(),
-        fecha_estado: row.try_get("fecha_estado").ok(),
-        error: row.get("error"),
-        usuario_creador: row.get("uc_id"),
-        usuario_gestor: row.try_get::<u32, _>("ug_id").ok(),
-        motivo_solicitud: row.get("motivo_solicitud"),
-        motivo_rechazo: row.get("motivo_rechazo"),
-      };
-
-      resultado.push_entidad(incidencia);
+    if let Ok(ug_id) = row.try_get::<u32, _>("ug_id") {
+      resultado.push_usuario(DescriptorUsuario {
+        id: ug_id,
+        nombre: row.get("ug_nombre"),
+        primer_apellido: row.get("ug_primer_apellido"),
+        segundo_apellido: row.get("ug_segundo_apellido"),
+      });
     }
 
-    Ok(resultado)
+    let incidencia = Incidencia {
+      id: row.get("id"),
+      tipo: row.get::<u8, _>("tipo").into(),
+      fecha_solicitud: row.get("fecha_solicitud"),
+      fecha_resolucion: row.try_get("fecha_resolucion").ok(),
+      fecha: row.get("fecha"),
+      usuario: row.get("u_id"),
+      hora_inicio: row.get("hora_inicio"),
+      hora_fin: row.get("hora_fin"),
+      marcaje: row.try_get::<u32, _>("marcaje").ok().map(|m_id| {
+        DescriptorMarcaje {
+          id: m_id,
+          hora_inicio: row.get("m_hora_inicio"),
+          hora_fin: row.get("m_hora_fin"),
+        }
+      }),
+      estado: row.get::<u8, _>("estado").into(),
+      fecha_estado: row.try_get("fecha_estado").ok(),
+      error: row.get("error"),
+      usuario_creador: row.try_get::<u32, _>("uc_id").ok(),
+      usuario_gestor: row.try_get::<u32, _>("ug_id").ok(),
+      motivo_solicitud: row.get("motivo_solicitud"),
+      motivo_rechazo: row.get("motivo_rechazo"),
+    };
+
+    resultado.push_entidad(incidencia);
   }
+
+  resultado
 }
@@ -1,14 +1,19 @@
-use chrono::{NaiveDate, NaiveDateTime, Utc};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
 
 use crate::{
   agregar_traza,
   config::ConfigTrabajo,
   inc::{
-    EstadoIncidencia, Incidencia, IncidenciaMarcaje, IncidenciaProceso,
-    IncidenciaRepo, IncidenciaSolictud, IncidenciaTraza, TipoIncidencia,
+    CierreMarcajeOlvidado, EstadoIncidencia, Incidencia, IncidenciaMarcaje,
+    IncidenciaProceso, IncidenciaRepo, IncidenciaSolictud, IncidenciaTraza,
+    TipoIncidencia,
   },
   infra::{DominioWithCacheUsuario, ServicioError, Transaccion},
-  marcaje::{Marcaje, MarcajeServicio},
+  integridad::{IntegridadServicio, TablaSellada},
+  marcaje::{
+    CambioMarcaje, DescriptorMarcaje, Marcaje, MarcajeOlvidado,
+    MarcajeServicio, TiempoPausas,
+  },
   traza::{TipoTraza, Traza, TrazaBuilder, TrazaServicio},
 };
 
@@ -18,6 +23,7 @@ pub struct IncidenciaServicio {
   repo: IncidenciaRepo,
   srv_traza: TrazaServicio,
   srv_marcaje: MarcajeServicio,
+  srv_integridad: IntegridadServicio,
 }
 
 impl IncidenciaServicio {
@@ -26,12 +32,14 @@ impl IncidenciaServicio {
     repo: IncidenciaRepo,
     srv_traza: TrazaServicio,
     srv_marcaje: MarcajeServicio,
+    srv_integridad: IntegridadServicio,
   ) -> Self {
     IncidenciaServicio {
       cnfg,
       repo,
       srv_traza,
       srv_marcaje,
+      srv_integridad,
     }
   }
 }
@@ -42,12 +50,29 @@ impl IncidenciaServicio {
   /// Si la incidencia ya existe devuelve un error
   /// gestionado por los propios constraint de la base
   /// de datos
+  ///
+  /// La incidencia se sella en la cadena de hashes del usuario.
   pub async fn agregar(&self, inc: &Incidencia) -> Result<u32, ServicioError> {
     tracing::info!(
       incidencia = ?inc,
       "Se ha iniciado el servicio para crear una incidencia de marcaje");
 
-    let id = match self.repo.agregar(inc).await {
+    let mut tr =
+      self
+        .repo
+        .conexion()
+        .empezar_transaccion()
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            incidencia = ?inc,
+            error = %err,
+            "Iniciando transacción para crear incidencia de marcaje"
+          );
+          ServicioError::from(err)
+        })?;
+
+    let id = match self.repo.agregar(&mut tr, inc).await {
       Ok(reg_id) => reg_id,
       Err(err) => {
         tracing::error!(
@@ -59,6 +84,20 @@ impl IncidenciaServicio {
       }
     };
 
+    self
+      .srv_integridad
+      .sellar(&mut tr, TablaSellada::Incidencias, id)
+      .await?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        incidencia = ?inc,
+        error = %err,
+        "Commit transacción para crear incidencia de marcaje"
+      );
+      ServicioError::from(err)
+    })?;
+
     tracing::debug!(
       incidencia = id,
       "Se ha completado satisfactoriamente la creación de la incidencia"
@@ -67,6 +106,110 @@ impl IncidenciaServicio {
     Ok(id)
   }
 
+  /// Trata los marcajes sin hora de salida cuya jornada ha terminado.
+  ///
+  /// Según el modo, el marcaje se cierra con la hora de salida del
+  /// horario o se abre una incidencia de corrección de salida en
+  /// estado conflicto con esa hora propuesta. El empleado la confirma
+  /// volviendo a solicitarla. Si el horario no permite deducir la hora
+  /// de salida siempre se abre la incidencia, sin hora propuesta.
+  ///
+  /// Los errores de cada marcaje se tracean y no detienen el proceso.
+  ///
+  /// Devuelve el número de marcajes tratados.
+  pub async fn tratar_marcajes_olvidados(
+    &self,
+    modo: CierreMarcajeOlvidado,
+  ) -> Result<usize, ServicioError> {
+    let ahora = Utc::now();
+
+    tracing::info!(
+      modo = ?modo,
+      ahora = %ahora,
+      "Se ha iniciado el servicio para tratar los marcajes olvidados"
+    );
+
+    let marcajes = self.srv_marcaje.marcajes_olvidados(ahora).await?;
+    let mut tratados = 0;
+
+    for marcaje in &marcajes {
+      let res = match (modo, marcaje.hora_salida()) {
+        (CierreMarcajeOlvidado::Cerrar, Some(hora_salida)) => self
+          .srv_marcaje
+          .cerrar_olvidado(marcaje, hora_salida)
+          .await
+          .map(|cerrado| cerrado as usize),
+        (_, hora_salida) => self
+          .incidencia_olvido(
+            marcaje,
+            hora_salida,
+            ahora.with_timezone(&self.cnfg.zona_horaria).naive_local(),
+          )
+          .await
+          .map(|_| 1),
+      };
+
+      match res {
+        Ok(n) => tratados += n,
+        Err(err) => tracing::error!(
+          marcaje = ?marcaje,
+          error = %err,
+          "Tratando el marcaje olvidado sin hora de salida"
+        ),
+      }
+    }
+
+    tracing::info!(
+      encontrados = marcajes.len(),
+      tratados = tratados,
+      "Se han tratado los marcajes olvidados"
+    );
+
+    Ok(tratados)
+  }
+
+  /// Abre la incidencia de corrección de salida de un marcaje
+  /// olvidado para que el empleado confirme la hora de salida.
+  async fn incidencia_olvido(
+    &self,
+    marcaje: &MarcajeOlvidado,
+    hora_salida: Option<NaiveTime>,
+    ahora: NaiveDateTime,
+  ) -> Result<u32, ServicioError> {
+    let error = match hora_salida {
+      Some(_) => {
+        "No se registró la hora de salida. Confirme la hora de salida \
+        propuesta según su horario"
+      }
+      None => "No se registró la hora de salida. Indique la hora de salida",
+    };
+
+    self
+      .agregar(&Incidencia {
+        id: 0,
+        tipo: TipoIncidencia::CorrecionSalida,
+        fecha_solicitud: ahora,
+        fecha_resolucion: None,
+        usuario: marcaje.usuario,
+        fecha: marcaje.fecha,
+        hora_inicio: None,
+        hora_fin: hora_salida,
+        marcaje: Some(DescriptorMarcaje {
+          id: marcaje.id,
+          hora_inicio: Some(marcaje.hora_inicio),
+          hora_fin: None,
+        }),
+        estado: EstadoIncidencia::Conflicto,
+        fecha_estado: Some(ahora),
+        error: Some(error.to_string()),
+        usuario_creador: None,
+        usuario_gestor: None,
+        motivo_solicitud: None,
+        motivo_rechazo: None,
+      })
+      .await
+  }
+
   /// Actualiza una incidencia cambiando la incidencia a solicitud
   ///
   /// Dependiendo del estado origen se cambian unos u otros campos
@@ -124,6 +267,11 @@ impl IncidenciaServicio {
       return Ok(());
     }
 
+    self
+      .srv_integridad
+      .resellar(&mut tr, TablaSellada::Incidencias, inc.id)
+      .await?;
+
     let traza = self.traza_cambio_estado(
       TipoTraza::IncReSolicitar,
       "Se vuelve a realizar la solicitud",
@@ -156,8 +304,11 @@ impl IncidenciaServicio {
   }
 
   /// Cancela una incidencia usando la transacción de la capa de datos.
+  ///
+  /// La incidencia es cancelada por un usuario autor.
   pub async fn cambiar_estado_a_cancelada(
     &self,
+    cancelada_por: u32,
     id: u32,
     fecha_cancelacion: NaiveDateTime,
   ) -> Result<(), ServicioError> {
@@ -209,11 +360,16 @@ impl IncidenciaServicio {
       return Ok(());
     }
 
+    self
+      .srv_integridad
+      .resellar(&mut tr, TablaSellada::Incidencias, id)
+      .await?;
+
     let traza = self.traza_cambio_estado(
       TipoTraza::IncCancelada,
       "Se canceló la incidencia",
       id,
-      incidencia_traza.usuario_creador,
+      cancelada_por,
       &incidencia_traza,
     );
 
@@ -274,9 +430,7 @@ impl IncidenciaServicio {
     usuario_gestor: u32,
     incidencias: &[IncidenciaProceso],
   ) -> Result<Vec<u32>, ServicioError> {
-    let fecha_actual = Utc::now()
-      .with_timezone(&self.cnfg.zona_horaria)
-      .naive_local();
+    let fecha_actual = self.cnfg.ahora();
 
     let mut panic_inc = Vec::with_capacity(incidencias.len());
 
@@ -341,8 +495,9 @@ impl IncidenciaServicio {
                         }
                       }
                       TipoIncidencia::EliminacionMarcaje => {
-                        if let Err(err) =
-                          self.eliminar_marcaje(&mut tr, incp, &inc).await
+                        if let Err(err) = self
+                          .eliminar_marcaje(&mut tr, usuario_gestor, incp, &inc)
+                          .await
                         {
                           error_message = Some(err);
                         }
@@ -457,6 +612,17 @@ impl IncidenciaServicio {
         }
       }
 
+      // Sella el estado final de la incidencia
+      if self
+        .srv_integridad
+        .resellar(&mut tr, TablaSellada::Incidencias, incp.id)
+        .await
+        .is_err()
+      {
+        panic_inc.push(incp.id);
+        continue;
+      }
+
       if let Err(err) = tr.commit().await {
         tracing::error!(
           incidencia = ?incp,
@@ -483,20 +649,21 @@ impl IncidenciaServicio {
     let marcaje = Marcaje {
       id: 0,
       usuario: inc.usuario,
-      usuario_reg: if inc.usuario == inc.usuario_creador {
-        None
-      } else {
-        Some(inc.usuario_creador)
-      },
+      usuario_reg: inc.usuario_creador.filter(|c| *c != inc.usuario),
       horario: None,
       fecha: inc.fecha,
       hora_inicio: inc.hora_inicio.unwrap(),
       hora_fin: inc.hora_fin,
+      inicio_utc: None,
+      fin_utc: None,
+      pausas: TiempoPausas::default(),
     };
 
+    let cambio = self.cambio_marcaje(usuario_gestor, incp, inc);
+
     match self
       .srv_marcaje
-      .agregar_with_trans(Some(tr), &marcaje, 0)
+      .agregar_with_trans(Some(tr), &marcaje, 0, &cambio)
       .await
     {
       Ok(marcaje_id) => {
@@ -561,26 +728,32 @@ impl IncidenciaServicio {
     let marcaje = Marcaje {
       id: 0,
       usuario: inc.usuario,
-      usuario_reg: if inc.usuario == inc.usuario_creador {
-        None
-      } else {
-        Some(inc.usuario_creador)
-      },
+      usuario_reg: inc.usuario_creador.filter(|c| *c != inc.usuario),
       horario: None,
       fecha: inc.fecha,
       hora_inicio,
       hora_fin,
+      inicio_utc: None,
+      fin_utc: None,
+      pausas: TiempoPausas::default(),
     };
 
+    let cambio = self.cambio_marcaje(usuario_gestor, incp, inc);
+
     match self
       .srv_marcaje
-      .agregar_with_trans(Some(tr), &marcaje, marcaje_asociado.id)
+      .agregar_with_trans(Some(tr), &marcaje, marcaje_asociado.id, &cambio)
       .await
     {
       Ok(marcaje_id) => {
         match self
           .srv_marcaje
-          .actualizar_modificado_por(tr, marcaje_asociado.id, marcaje_id)
+          .actualizar_modificado_por(
+            tr,
+            marcaje_asociado.id,
+            marcaje_id,
+            &cambio,
+          )
           .await
         {
           Ok(modificado) => {
@@ -628,6 +801,28 @@ impl IncidenciaServicio {
     }
   }
 
+  /// Crea el cambio que se anota en el historial de los marcajes
+  /// modificados al resolver la incidencia.
+  ///
+  /// El autor es el gestor y el motivo el de la solicitud.
+  #[inline]
+  fn cambio_marcaje(
+    &self,
+    usuario_gestor: u32,
+    incp: &IncidenciaProceso,
+    inc: &IncidenciaMarcaje,
+  ) -> CambioMarcaje {
+    CambioMarcaje::new(
+      Some(usuario_gestor),
+      Some(format!(
+        "Incidencia {}: {}",
+        incp.id,
+        inc.motivo_solicitud.as_deref().unwrap_or("")
+      )),
+      &self.cnfg.zona_horaria,
+    )
+  }
+
   /// Crea una traza motivada por el cambio de estado de la incidencia
   #[inline]
   fn traza_cambio_estado(
@@ -635,17 +830,17 @@ impl IncidenciaServicio {
     tipo_traza: TipoTraza,
     msg: &str,
     incidencia_id: u32,
-    usuario_creador: u32,
+    autor: u32,
     incidencia_traza: &IncidenciaTraza,
   ) -> Traza {
     TrazaBuilder::with_inc(tipo_traza, incidencia_id)
-      .autor(Some(usuario_creador))
+      .autor(Some(autor))
       .motivo(Some(format!(
         "{}. Registro previo: \
         (Fecha solicitud: {:?}, Motivo solictud: '{}' \
         Entrada: '{:?}' Salida: '{:?}' \
         Motivo rechazo: '{}' Fecha estado: '{:?}' \
-        Creador: {} Gestor: {:?} Error: '{}')",
+        Creador: {:?} Gestor: {:?} Error: '{}')",
         msg,
         incidencia_traza.fecha_solicitud,
         incidencia_traza.motivo_solicitud.as_deref().unwrap_or(""),
@@ -666,12 +861,15 @@ impl IncidenciaServicio {
   async fn eliminar_marcaje(
     &self,
     tr: &mut Transaccion<'_>,
+    usuario_gestor: u32,
     incp: &IncidenciaProceso,
     inc: &IncidenciaMarcaje,
   ) -> Result<(), &'static str> {
+    let cambio = self.cambio_marcaje(usuario_gestor, incp, inc);
+
     match self
       .srv_marcaje
-      .marcar_marcaje_eliminado(tr, inc.marcaje.as_ref().unwrap().id)
+      .marcar_marcaje_eliminado(tr, inc.marcaje.as_ref().unwrap().id, &cambio)
       .await
     {
       Ok(eliminado) => {
@@ -723,9 +921,7 @@ impl IncidenciaServicio {
     err: ServicioError,
     default_err: &'static str,
   ) -> Result<(), &'static str> {
-    let fecha_actual = Utc::now()
-      .with_timezone(&self.cnfg.zona_horaria)
-      .naive_local();
+    let fecha_actual = self.cnfg.ahora();
 
     let mensaje = err.mensaje();
 
@@ -773,6 +969,29 @@ impl IncidenciaServicio {
     Ok(())
   }
 
+  /// Lista todas las incidencias de un empleado entre fechas.
+  pub async fn incidencias_usuario(
+    &self,
+    usuario: u32,
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<DominioWithCacheUsuario<Incidencia>, ServicioError> {
+    self
+      .repo
+      .incidencias_usuario(usuario, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = usuario,
+          fecha_inicio = %fecha_inicio,
+          fecha_fin = %fecha_fin,
+          error = %err,
+          "Obteniendo incidencias del usuario"
+        );
+        ServicioError::from(err)
+      })
+  }
+
   /// Lista las incidencias que cumplen los filtros indicados.
   ///
   /// Si se indica ID solo se devuelve esa incidencia
@@ -1,7 +1,12 @@
 use std::collections::HashMap;
 
-use crate::horario::{CalendarioFecha, ConfigHorario};
-use chrono::{Datelike, NaiveDate};
+use crate::horario::{
+  CalendarioFecha, ConfigHorario, FranjaHoraria, ReglasPausa,
+  TipoCalendarioFecha,
+};
+use crate::marcaje::{TiempoPausas, duracion_real, fecha_hora_marcaje};
+use crate::usuarios::DescriptorUsuario;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeDelta, Utc};
 use smallvec::SmallVec;
 
 /// Representa una línea del informe de cumplimiento horario.
@@ -10,7 +15,7 @@ pub struct CumplimientoHorario {
   pub fecha: NaiveDate,
   pub horas_trabajo_efectivo: f64,
   pub horas_trabajadas: f64,
-  pub horas_a_trabajar: u8,
+  pub horas_a_trabajar: f64,
   pub saldo: f64,
   pub nota: String,
 }
@@ -21,11 +26,54 @@ impl CumplimientoHorario {
       fecha,
       horas_trabajo_efectivo: 0.0,
       horas_trabajadas: 0.0,
-      horas_a_trabajar: 0,
+      horas_a_trabajar: 0.0,
       saldo: 0.0,
       nota,
     }
   }
+
+  /// Calcula la línea de un día con horario a partir de las horas
+  /// efectivas de sus marcajes.
+  ///
+  /// La cortesía completa las horas trabajadas hasta la jornada
+  /// teórica. Los días inhábiles sin marcajes no tienen saldo.
+  pub fn new(
+    fecha: NaiveDate,
+    horario: &ConfigHorario,
+    horas_efectivas: f64,
+    inhabil: Option<&CalendarioFecha>,
+  ) -> Self {
+    let horas_a_trabajar = horario.minutos_a_trabajar() as f64 / 60.0;
+    let cortesia_en_horas = horario.cortesia as f64 / 60.0;
+
+    let horas_trabajadas = if horas_efectivas < horas_a_trabajar {
+      (horas_efectivas + cortesia_en_horas).min(horas_a_trabajar)
+    } else {
+      horas_efectivas
+    };
+
+    let nota = match inhabil {
+      Some(inhabil) if horas_efectivas > 0.0 => {
+        format!("Día inhábil con marcajes: {:?}", inhabil.tipo)
+      }
+      Some(inhabil) => {
+        return CumplimientoHorario::with_fecha_y_nota(
+          fecha,
+          format!("Día inhábil. Motivo: {:?}", inhabil.tipo),
+        );
+      }
+      None => String::new(),
+    };
+
+    CumplimientoHorario {
+      fecha,
+      horas_trabajo_efectivo: horas_efectivas,
+      horas_trabajadas,
+      horas_a_trabajar,
+      saldo: horas_trabajadas - horas_a_trabajar,
+      nota,
+    }
+  }
 }
 
 /// Entidad que representa el informe de cumplimiento horario de un usuario
@@ -36,18 +84,721 @@ pub struct InformeCumplimiento {
   pub total_saldo: f64,
 }
 
+impl InformeCumplimiento {
+  /// Calcula las líneas de los días con horario entre dos fechas
+  /// del mismo mes, sin pasar de la fecha actual.
+  pub fn new(
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+    hoy: NaiveDate,
+    horarios: &HorariosUsuario,
+    dias_inhabiles: &DiasInhabiles,
+    marcajes: &HorasEfectivasMarcajes,
+  ) -> Self {
+    let mut lineas = SmallVec::<[CumplimientoHorario; 31]>::new();
+    let mut total_saldo = 0.0;
+
+    let dias = fecha_inicio
+      .iter_days()
+      .take_while(|fecha| *fecha <= fecha_fin && *fecha <= hoy);
+
+    for fecha in dias {
+      let Some(h) = horarios.buscar(fecha) else {
+        continue;
+      };
+
+      let horas_efectivas = marcajes
+        .horas_descontando_pausas(fecha.day(), &h.pausas)
+        .unwrap_or(0.0);
+
+      let linea = CumplimientoHorario::new(
+        fecha,
+        h,
+        horas_efectivas,
+        dias_inhabiles.buscar(fecha),
+      );
+      total_saldo += linea.saldo;
+      lineas.push(linea);
+    }
+
+    InformeCumplimiento {
+      lineas,
+      total_saldo,
+    }
+  }
+}
+
+/// Horas efectivas de un día con marcajes frente a su jornada teórica.
+///
+/// A diferencia del informe de cumplimiento horario, incluye los días
+/// sin horario y los días inhábiles. Ninguno de ellos tiene jornada
+/// teórica, de modo que todas sus horas efectivas exceden la jornada.
+#[derive(Debug, PartialEq)]
+pub struct JornadaEfectiva {
+  pub fecha: NaiveDate,
+  pub horas_efectivas: f64,
+  pub horas_a_trabajar: f64,
+}
+
+impl JornadaEfectiva {
+  /// Calcula las jornadas de los días con marcajes entre dos fechas
+  /// del mismo mes, sin pasar de la fecha actual.
+  ///
+  /// En los días sin horario no se retribuye ninguna pausa.
+  pub fn dias(
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+    hoy: NaiveDate,
+    horarios: &HorariosUsuario,
+    dias_inhabiles: &DiasInhabiles,
+    marcajes: &HorasEfectivasMarcajes,
+  ) -> Vec<Self> {
+    fecha_inicio
+      .iter_days()
+      .take_while(|fecha| *fecha <= fecha_fin && *fecha <= hoy)
+      .filter_map(|fecha| {
+        let horario = horarios.buscar(fecha);
+        let reglas = horario.map(|h| h.pausas).unwrap_or_default();
+        let horas_efectivas =
+          marcajes.horas_descontando_pausas(fecha.day(), &reglas)?;
+
+        let horas_a_trabajar = match horario {
+          Some(h) if dias_inhabiles.buscar(fecha).is_none() => {
+            h.minutos_a_trabajar() as f64 / 60.0
+          }
+          _ => 0.0,
+        };
+
+        Some(JornadaEfectiva {
+          fecha,
+          horas_efectivas,
+          horas_a_trabajar,
+        })
+      })
+      .collect()
+  }
+
+  /// Minutos trabajados por encima de la jornada teórica.
+  pub fn minutos_exceso(&self) -> u32 {
+    ((self.horas_efectivas - self.horas_a_trabajar) * 60.0)
+      .round()
+      .max(0.0) as u32
+  }
+}
+
+/// Totales de un mes del informe de cumplimiento anual.
+#[derive(Debug, Default)]
+pub struct CumplimientoMes {
+  pub mes: u32,
+  pub horas_a_trabajar: f64,
+  pub horas_trabajadas: f64,
+  pub saldo: f64,
+  /// Días laborables de vacaciones o días propios disfrutados
+  pub dias_vacaciones: u32,
+}
+
+/// Informe de cumplimiento de la jornada anual de un empleado frente
+/// a las horas anuales del convenio colectivo.
+///
+/// Los días hasta la fecha actual se calculan como en el informe de
+/// cumplimiento horario. Para el resto del año se supone que el
+/// empleado cumplirá exactamente su horario.
+#[derive(Debug)]
+pub struct InformeCumplimientoAnual {
+  pub anio: i32,
+  /// Horas anuales de trabajo efectivo del convenio
+  pub horas_convenio: f64,
+  pub meses: Vec<CumplimientoMes>,
+  /// Jornada teórica hasta la fecha actual
+  pub horas_a_trabajar: f64,
+  pub horas_trabajadas: f64,
+  pub saldo: f64,
+  /// Jornada teórica desde mañana hasta fin de año
+  pub horas_pendientes: f64,
+  /// Horas trabajadas más las pendientes
+  pub proyeccion: f64,
+  /// Proyección menos las horas del convenio
+  pub diferencia_convenio: f64,
+  /// Días laborables de vacaciones o días propios disfrutados
+  pub vacaciones_disfrutadas: u32,
+  /// Días laborables de vacaciones o días propios planificados
+  pub vacaciones_pendientes: u32,
+}
+
+impl InformeCumplimientoAnual {
+  /// Recorre todos los días del año con horario.
+  ///
+  /// Las horas efectivas de los marcajes se indexan por mes.
+  pub fn new(
+    anio: i32,
+    horas_convenio: f64,
+    horarios: &HorariosUsuario,
+    dias_inhabiles: &DiasInhabiles,
+    marcajes: &HashMap<u32, HorasEfectivasMarcajes>,
+    hoy: NaiveDate,
+  ) -> Self {
+    let mut meses: Vec<CumplimientoMes> = (1..=12)
+      .map(|mes| CumplimientoMes {
+        mes,
+        ..Default::default()
+      })
+      .collect();
+    let mut horas_pendientes = 0.0;
+    let mut vacaciones_pendientes = 0;
+
+    let dias = NaiveDate::from_ymd_opt(anio, 1, 1)
+      .into_iter()
+      .flat_map(|inicio| inicio.iter_days())
+      .take_while(|fecha| fecha.year() == anio);
+
+    for fecha in dias {
+      let Some(horario) = horarios.buscar(fecha) else {
+        continue;
+      };
+
+      let inhabil = dias_inhabiles.buscar(fecha);
+      let vacaciones = inhabil.is_some_and(|i| {
+        matches!(
+          i.tipo,
+          TipoCalendarioFecha::Vacaciones | TipoCalendarioFecha::DiasPropios
+        )
+      });
+
+      if fecha > hoy {
+        if inhabil.is_none() {
+          horas_pendientes += horario.minutos_a_trabajar() as f64 / 60.0;
+        } else if vacaciones {
+          vacaciones_pendientes += 1;
+        }
+        continue;
+      }
+
+      let horas_efectivas = marcajes
+        .get(&fecha.month())
+        .and_then(|m| m.horas_descontando_pausas(fecha.day(), &horario.pausas))
+        .unwrap_or(0.0);
+
+      let linea =
+        CumplimientoHorario::new(fecha, horario, horas_efectivas, inhabil);
+
+      let mes = &mut meses[fecha.month0() as usize];
+      mes.horas_a_trabajar += linea.horas_a_trabajar;
+      mes.horas_trabajadas += linea.horas_trabajadas;
+      mes.saldo += linea.saldo;
+      if vacaciones {
+        mes.dias_vacaciones += 1;
+      }
+    }
+
+    let horas_a_trabajar = meses.iter().map(|m| m.horas_a_trabajar).sum();
+    let horas_trabajadas: f64 = meses.iter().map(|m| m.horas_trabajadas).sum();
+    let saldo = meses.iter().map(|m| m.saldo).sum();
+    let vacaciones_disfrutadas = meses.iter().map(|m| m.dias_vacaciones).sum();
+    let proyeccion = horas_trabajadas + horas_pendientes;
+
+    InformeCumplimientoAnual {
+      anio,
+      horas_convenio,
+      meses,
+      horas_a_trabajar,
+      horas_trabajadas,
+      saldo,
+      horas_pendientes,
+      proyeccion,
+      diferencia_convenio: proyeccion - horas_convenio,
+      vacaciones_disfrutadas,
+      vacaciones_pendientes,
+    }
+  }
+}
+
+/// Línea de un empleado del informe de cumplimiento de un equipo.
+#[derive(Debug)]
+pub struct CumplimientoEmpleado {
+  pub empleado: DescriptorUsuario,
+  pub horas_a_trabajar: f64,
+  pub horas_trabajadas: f64,
+  pub saldo: f64,
+  /// Incidencias del mes pendientes de resolver
+  pub incidencias_abiertas: u32,
+  /// Días anteriores a la fecha actual con marcajes sin salida
+  pub dias_sin_salida: Vec<NaiveDate>,
+}
+
+impl CumplimientoEmpleado {
+  /// Resume el informe de cumplimiento horario del mes de un empleado.
+  ///
+  /// Los marcajes sin salida de la fecha actual no se cuentan porque
+  /// el empleado puede estar trabajando.
+  pub fn new(
+    empleado: DescriptorUsuario,
+    informe: &InformeCumplimiento,
+    incidencias_abiertas: u32,
+    mut dias_sin_salida: Vec<NaiveDate>,
+    hoy: NaiveDate,
+  ) -> Self {
+    dias_sin_salida.retain(|fecha| *fecha < hoy);
+    dias_sin_salida.sort_unstable();
+
+    CumplimientoEmpleado {
+      empleado,
+      horas_a_trabajar: informe.lineas.iter().map(|l| l.horas_a_trabajar).sum(),
+      horas_trabajadas: informe.lineas.iter().map(|l| l.horas_trabajadas).sum(),
+      saldo: informe.total_saldo,
+      incidencias_abiertas,
+      dias_sin_salida,
+    }
+  }
+}
+
+/// Informe de cumplimiento horario agregado de un equipo de empleados
+/// para un mes concreto.
+#[derive(Debug)]
+pub struct InformeEquipo {
+  pub mes: u32,
+  pub anio: i32,
+  pub empleados: Vec<CumplimientoEmpleado>,
+  pub horas_a_trabajar: f64,
+  pub horas_trabajadas: f64,
+  pub saldo: f64,
+  pub incidencias_abiertas: u32,
+  pub dias_sin_salida: u32,
+}
+
+impl InformeEquipo {
+  pub fn new(
+    mes: u32,
+    anio: i32,
+    empleados: Vec<CumplimientoEmpleado>,
+  ) -> Self {
+    InformeEquipo {
+      mes,
+      anio,
+      horas_a_trabajar: empleados.iter().map(|e| e.horas_a_trabajar).sum(),
+      horas_trabajadas: empleados.iter().map(|e| e.horas_trabajadas).sum(),
+      saldo: empleados.iter().map(|e| e.saldo).sum(),
+      incidencias_abiertas: empleados
+        .iter()
+        .map(|e| e.incidencias_abiertas)
+        .sum(),
+      dias_sin_salida: empleados
+        .iter()
+        .map(|e| e.dias_sin_salida.len() as u32)
+        .sum(),
+      empleados,
+    }
+  }
+}
+
+/// Empleado al que pertenece el registro diario de jornada.
+#[derive(Debug, Clone)]
+pub struct EmpleadoRegistro {
+  pub id: u32,
+  pub dni: String,
+  pub nombre: String,
+}
+
+/// Situación de un marcaje dentro del registro diario de jornada.
+///
+/// Los marcajes nunca se borran físicamente, por lo que el registro
+/// muestra también los modificados y eliminados por incidencias.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EstadoRegistro {
+  Vigente,
+  Modificado,
+  Eliminado,
+}
+
+impl EstadoRegistro {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      EstadoRegistro::Vigente => "Vigente",
+      EstadoRegistro::Modificado => "Modificado",
+      EstadoRegistro::Eliminado => "Eliminado",
+    }
+  }
+}
+
+/// Marcaje del registro diario de jornada.
+#[derive(Debug)]
+pub struct MarcajeRegistro {
+  pub fecha: NaiveDate,
+  pub hora_inicio: NaiveTime,
+  pub hora_fin: Option<NaiveTime>,
+  /// Instantes UTC de la entrada y la salida, si se conocen
+  pub inicio_utc: Option<DateTime<Utc>>,
+  pub fin_utc: Option<DateTime<Utc>>,
+  pub estado: EstadoRegistro,
+  /// Registrador que realizó el marcaje en nombre del empleado
+  pub registrado_por: Option<DescriptorUsuario>,
+  /// Gestor que resolvió la incidencia que modificó o eliminó el marcaje
+  pub modificado_por: Option<DescriptorUsuario>,
+}
+
+/// Representa una línea del registro diario de jornada.
+///
+/// Las líneas sin hora de inicio son días sin marcajes que
+/// tienen una nota del calendario laboral.
+#[derive(Debug)]
+pub struct LineaRegistroJornada {
+  pub fecha: NaiveDate,
+  pub hora_inicio: Option<NaiveTime>,
+  pub hora_fin: Option<NaiveTime>,
+  pub horas: Option<f64>,
+  pub estado: Option<EstadoRegistro>,
+  pub registrado_por: Option<String>,
+  pub modificado_por: Option<String>,
+  pub nota: String,
+}
+
+/// Registro diario de jornada de un empleado para un mes concreto.
+#[derive(Debug)]
+pub struct RegistroJornada {
+  pub empleado: EmpleadoRegistro,
+  pub mes: u32,
+  pub anio: i32,
+  pub lineas: Vec<LineaRegistroJornada>,
+  /// Suma de las horas de los marcajes vigentes
+  pub total_horas: f64,
+}
+
+impl RegistroJornada {
+  /// Compone el registro a partir de los marcajes del mes
+  /// y los días inhábiles del calendario del empleado.
+  pub fn new(
+    empleado: EmpleadoRegistro,
+    mes: u32,
+    anio: i32,
+    marcajes: Vec<MarcajeRegistro>,
+    dias_inhabiles: &DiasInhabiles,
+    fecha_fin: NaiveDate,
+  ) -> Self {
+    let mut lineas = Vec::with_capacity(marcajes.len() + 4);
+    let mut total_horas = 0.0;
+    let mut marcajes = marcajes.into_iter().peekable();
+    let mut curr = NaiveDate::from_ymd_opt(anio, mes, 1).unwrap_or(fecha_fin);
+
+    while curr <= fecha_fin {
+      let nota = dias_inhabiles
+        .buscar(curr)
+        .map(|f| f.tipo.as_str().to_string())
+        .unwrap_or_default();
+
+      let mut con_marcajes = false;
+
+      while let Some(m) = marcajes.next_if(|m| m.fecha == curr) {
+        let horas = m.hora_fin.map(|fin| {
+          duracion_real(m.hora_inicio, fin, m.inicio_utc, m.fin_utc)
+            .num_milliseconds() as f64
+            / 3_600_000.0
+        });
+
+        if m.estado == EstadoRegistro::Vigente {
+          total_horas += horas.unwrap_or(0.0);
+        }
+
+        con_marcajes = true;
+        lineas.push(LineaRegistroJornada {
+          fecha: curr,
+          hora_inicio: Some(m.hora_inicio),
+          hora_fin: m.hora_fin,
+          horas,
+          estado: Some(m.estado),
+          registrado_por: m.registrado_por.map(|u| nombre_completo(&u)),
+          modificado_por: m.modificado_por.map(|u| nombre_completo(&u)),
+          nota: nota.clone(),
+        });
+      }
+
+      if !con_marcajes && !nota.is_empty() {
+        lineas.push(LineaRegistroJornada {
+          fecha: curr,
+          hora_inicio: None,
+          hora_fin: None,
+          horas: None,
+          estado: None,
+          registrado_por: None,
+          modificado_por: None,
+          nota,
+        });
+      }
+
+      match curr.succ_opt() {
+        Some(sig) => curr = sig,
+        None => break,
+      }
+    }
+
+    RegistroJornada {
+      empleado,
+      mes,
+      anio,
+      lineas,
+      total_horas,
+    }
+  }
+}
+
+fn nombre_completo(usuario: &DescriptorUsuario) -> String {
+  format!(
+    "{} {} {}",
+    usuario.nombre, usuario.primer_apellido, usuario.segundo_apellido
+  )
+  .trim_end()
+  .to_string()
+}
+
+/// Tipo de incumplimiento del informe de puntualidad.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TipoPuntualidad {
+  /// Entrada posterior a la hora prevista más la cortesía
+  Retraso,
+  /// Salida anterior a la hora prevista menos la cortesía
+  SalidaAnticipada,
+  /// Marcaje de un día pasado sin hora de fin
+  SinSalida,
+}
+
+impl TipoPuntualidad {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      TipoPuntualidad::Retraso => "Retraso",
+      TipoPuntualidad::SalidaAnticipada => "Salida anticipada",
+      TipoPuntualidad::SinSalida => "Sin salida",
+    }
+  }
+}
+
+/// Representa una línea del informe de puntualidad.
+#[derive(Debug)]
+pub struct LineaPuntualidad {
+  pub fecha: NaiveDate,
+  pub tipo: TipoPuntualidad,
+  pub hora_prevista: Option<NaiveTime>,
+  pub hora_real: Option<NaiveTime>,
+  /// Minutos de diferencia con la hora prevista, sin descontar la cortesía
+  pub minutos: u32,
+}
+
+/// Informe de retrasos, salidas anticipadas y marcajes sin salida
+/// de un empleado para un mes concreto.
+#[derive(Debug)]
+pub struct InformePuntualidad {
+  pub empleado: EmpleadoRegistro,
+  pub mes: u32,
+  pub anio: i32,
+  pub lineas: Vec<LineaPuntualidad>,
+  pub retrasos: u32,
+  pub salidas_anticipadas: u32,
+  pub sin_salida: u32,
+}
+
+impl InformePuntualidad {
+  /// Compara los marcajes vigentes de cada día con la hora de entrada
+  /// y salida previstas en cada franja del horario del día.
+  ///
+  /// Cada franja se compara con los marcajes que la solapan o, si un
+  /// marcaje no solapa ninguna, con el de la franja más cercana. La
+  /// entrada de la franja se compara con el primero de sus marcajes y
+  /// la salida con el último, de modo que en la jornada partida se
+  /// informan los retrasos y salidas anticipadas de cada tramo. Solo
+  /// se considera incumplimiento cuando la diferencia supera la
+  /// cortesía del horario. No se evalúan los días inhábiles ni los días
+  /// cuyo horario no tiene franjas. Los marcajes sin hora de fin del día
+  /// `hoy` y del anterior no se informan porque la jornada puede seguir
+  /// abierta. La salida de un marcaje que cruza la medianoche se compara
+  /// en el día siguiente.
+  pub fn new(
+    empleado: EmpleadoRegistro,
+    mes: u32,
+    anio: i32,
+    marcajes: Vec<MarcajeRegistro>,
+    horarios: &HorariosUsuario,
+    dias_inhabiles: &DiasInhabiles,
+    hoy: NaiveDate,
+  ) -> Self {
+    let mut lineas = Vec::new();
+    let mut marcajes = marcajes
+      .into_iter()
+      .filter(|m| m.estado == EstadoRegistro::Vigente)
+      .peekable();
+
+    while let Some(primero) = marcajes.next() {
+      let fecha = primero.fecha;
+      let mut dia = vec![primero];
+      while let Some(m) = marcajes.next_if(|m| m.fecha == fecha) {
+        dia.push(m);
+      }
+
+      // La jornada de ayer puede seguir abierta si cruza la medianoche
+      if fecha.succ_opt().is_some_and(|siguiente| siguiente < hoy) {
+        for m in dia.iter().filter(|m| m.hora_fin.is_none()) {
+          lineas.push(LineaPuntualidad {
+            fecha,
+            tipo: TipoPuntualidad::SinSalida,
+            hora_prevista: None,
+            hora_real: Some(m.hora_inicio),
+            minutos: 0,
+          });
+        }
+      }
+
+      if dias_inhabiles.buscar(fecha).is_some() {
+        continue;
+      }
+
+      let Some(horario) = horarios.buscar(fecha) else {
+        continue;
+      };
+
+      let cortesia = horario.cortesia as i64;
+
+      for (franja, asignados) in horario
+        .franjas
+        .iter()
+        .zip(marcajes_por_franja(fecha, &horario.franjas, &dia))
+      {
+        let (Some(primero), Some(ultimo)) =
+          (asignados.first(), asignados.last())
+        else {
+          continue;
+        };
+
+        let minutos = (primero.hora_inicio - franja.hora_inicio).num_minutes();
+        if minutos > cortesia {
+          lineas.push(LineaPuntualidad {
+            fecha,
+            tipo: TipoPuntualidad::Retraso,
+            hora_prevista: Some(franja.hora_inicio),
+            hora_real: Some(primero.hora_inicio),
+            minutos: minutos as u32,
+          });
+        }
+
+        if let Some(fin) = ultimo.hora_fin {
+          // El último marcaje puede terminar al día siguiente
+          let minutos = (fecha.and_time(franja.hora_fin)
+            - fecha_hora_marcaje(fecha, ultimo.hora_inicio, fin))
+          .num_minutes();
+          if minutos > cortesia {
+            lineas.push(LineaPuntualidad {
+              fecha,
+              tipo: TipoPuntualidad::SalidaAnticipada,
+              hora_prevista: Some(franja.hora_fin),
+              hora_real: Some(fin),
+              minutos: minutos as u32,
+            });
+          }
+        }
+      }
+    }
+
+    let contar = |tipo: TipoPuntualidad| {
+      lineas.iter().filter(|l| l.tipo == tipo).count() as u32
+    };
+
+    InformePuntualidad {
+      retrasos: contar(TipoPuntualidad::Retraso),
+      salidas_anticipadas: contar(TipoPuntualidad::SalidaAnticipada),
+      sin_salida: contar(TipoPuntualidad::SinSalida),
+      empleado,
+      mes,
+      anio,
+      lineas,
+    }
+  }
+}
+
+/// Reparte los marcajes de un día entre las franjas del horario.
+///
+/// Un marcaje se asigna a todas las franjas que solapa; si no solapa
+/// ninguna, a la más cercana. Los marcajes sin hora de fin solo ocupan
+/// su hora de inicio. Cada franja conserva el orden de los marcajes.
+fn marcajes_por_franja<'a>(
+  fecha: NaiveDate,
+  franjas: &[FranjaHoraria],
+  marcajes: &'a [MarcajeRegistro],
+) -> Vec<Vec<&'a MarcajeRegistro>> {
+  let mut asignados = vec![Vec::new(); franjas.len()];
+
+  for m in marcajes {
+    let inicio = fecha.and_time(m.hora_inicio);
+    let fin = m
+      .hora_fin
+      .map(|fin| fecha_hora_marcaje(fecha, m.hora_inicio, fin))
+      .unwrap_or(inicio);
+
+    // Distancia del marcaje a cada franja, cero si la solapa
+    let distancias: Vec<_> = franjas
+      .iter()
+      .map(|f| {
+        let (ini_franja, fin_franja) =
+          (fecha.and_time(f.hora_inicio), fecha.and_time(f.hora_fin));
+        if fin < ini_franja {
+          ini_franja - fin
+        } else if inicio > fin_franja {
+          inicio - fin_franja
+        } else {
+          TimeDelta::zero()
+        }
+      })
+      .collect();
+
+    let Some(minima) = distancias.iter().min() else {
+      continue;
+    };
+
+    if minima.is_zero() {
+      for (i, distancia) in distancias.iter().enumerate() {
+        if distancia.is_zero() {
+          asignados[i].push(m);
+        }
+      }
+    } else if let Some(i) = distancias.iter().position(|d| d == minima) {
+      asignados[i].push(m);
+    }
+  }
+
+  asignados
+}
+
 /// Entidad que almacena el resumen de horas efectivas trabajadas por día.
 ///
 /// Representa la suma total de horas que un usuario ha fichado realmente
 /// (diferencia entre entrada y salida) agrupadas por el día del mes.
+/// Los marcajes que cruzan la medianoche cuentan en el día en que
+/// empiezan.
 #[derive(Debug)]
 pub struct HorasEfectivasMarcajes {
   dias: HashMap<u32, f64>,
+  pausas: HashMap<u32, TiempoPausas>,
 }
 
 impl HorasEfectivasMarcajes {
-  pub fn new(dias: HashMap<u32, f64>) -> Self {
-    HorasEfectivasMarcajes { dias }
+  pub fn new(
+    dias: HashMap<u32, f64>,
+    pausas: HashMap<u32, TiempoPausas>,
+  ) -> Self {
+    HorasEfectivasMarcajes { dias, pausas }
+  }
+
+  /// Busca las horas trabajadas para un día descontando las pausas
+  /// que superan las retribuidas por las reglas del horario.
+  ///
+  /// Devuelve `None` si no existen registros para ese día.
+  pub fn horas_descontando_pausas(
+    &self,
+    dia: u32,
+    reglas: &ReglasPausa,
+  ) -> Option<f64> {
+    self.horas_efectivas(dia).map(|horas| {
+      let no_retribuidos = self
+        .pausas
+        .get(&dia)
+        .map(|p| p.minutos_no_retribuidos(reglas))
+        .unwrap_or(0);
+      (horas - no_retribuidos as f64 / 60.0).max(0.0)
+    })
   }
 
   /// Busca las horas trabajadas para un día específico del mes.
@@ -103,28 +854,27 @@ impl HorariosUsuario {
   /// (aquel cuya fecha de creación es la más reciente anterior a la
   /// fecha consultada).
   ///
-  /// Posteriormente, filtra por el día de la semana y verifica que el horario
-  /// específico no haya caducado para esa fecha.
+  /// Posteriormente, filtra por el día de la semana y descarta los horarios
+  /// cuyo periodo de caducidad no contiene la fecha. Si en el bloque hay un
+  /// horario temporal para la fecha, prevalece sobre el permanente.
   pub fn buscar(&self, fecha: NaiveDate) -> Option<&ConfigHorario> {
     let fecha_creacion = self
       .horarios
       .iter()
       .filter(|h| h.fecha_creacion < fecha)
       .map(|h| h.fecha_creacion)
-      .max();
-
-    if let Some(fecha_creacion) = fecha_creacion {
-      let dia_letra = crate::horario::Dia::from(fecha.weekday()).letra();
+      .max()?;
+    let dia_letra = crate::horario::Dia::from(fecha.weekday()).letra();
 
-      self.horarios.iter().find(|h| {
+    self
+      .horarios
+      .iter()
+      .filter(|h| {
         h.fecha_creacion == fecha_creacion
           && h.dia.letra() == dia_letra
-          && h.caducidad_fecha_ini.is_none_or(|ini| fecha >= ini)
-          && h.caducidad_fecha_fin.is_none_or(|fin| fecha <= fin)
+          && h.vigente(fecha)
       })
-    } else {
-      None
-    }
+      .max_by_key(|h| h.temporal())
   }
 }
 
@@ -160,6 +910,35 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_horas_descontando_pausas() {
+    let horas = HorasEfectivasMarcajes::new(
+      HashMap::from([(3, 9.0), (4, 8.0)]),
+      HashMap::from([
+        (
+          3,
+          TiempoPausas {
+            comida: 60,
+            personal: 20,
+          },
+        ),
+        (4, TiempoPausas::default()),
+      ]),
+    );
+    // 15 minutos de pausa personal retribuidos, la comida no se retribuye
+    let reglas = ReglasPausa {
+      comida_retribuida: 0,
+      personal_retribuida: 15,
+    };
+
+    assert_eq!(
+      horas.horas_descontando_pausas(3, &reglas),
+      Some(9.0 - 65.0 / 60.0)
+    );
+    assert_eq!(horas.horas_descontando_pausas(4, &reglas), Some(8.0));
+    assert_eq!(horas.horas_descontando_pausas(5, &reglas), None);
+  }
+
   #[test]
   fn test_horarios_usuario_calculo_horas() {
     struct TestCase {
@@ -177,7 +956,9 @@ mod tests {
       id: 1,
       usuario: 1,
       dia,
-      horas,
+      minutos: horas as u16 * 60,
+      franjas: vec![],
+      pausas: ReglasPausa::default(),
       fecha_creacion: creacion,
       caducidad_fecha_ini: ini,
       caducidad_fecha_fin: fin,
@@ -280,6 +1061,48 @@ mod tests {
         fecha_consulta: NaiveDate::from_ymd_opt(2023, 2, 6).unwrap(),
         horas_esperadas: 4,
       },
+      TestCase {
+        descripcion: "Prueba: Horario temporal y permanente en el mismo bloque. Se espera: Horas del temporal (6).",
+        configs: vec![
+          config(Dia::Lunes, 8, fecha_creacion, None, None),
+          config(
+            Dia::Lunes,
+            6,
+            fecha_creacion,
+            Some(NaiveDate::from_ymd_opt(2023, 7, 1).unwrap()),
+            Some(NaiveDate::from_ymd_opt(2023, 8, 31).unwrap()),
+          ),
+        ],
+        fecha_consulta: NaiveDate::from_ymd_opt(2023, 7, 3).unwrap(),
+        horas_esperadas: 6,
+      },
+      TestCase {
+        descripcion: "Prueba: Fuera del periodo del horario temporal. Se espera: Horas del permanente (8).",
+        configs: vec![
+          config(
+            Dia::Lunes,
+            6,
+            fecha_creacion,
+            Some(NaiveDate::from_ymd_opt(2023, 7, 1).unwrap()),
+            Some(NaiveDate::from_ymd_opt(2023, 8, 31).unwrap()),
+          ),
+          config(Dia::Lunes, 8, fecha_creacion, None, None),
+        ],
+        fecha_consulta: NaiveDate::from_ymd_opt(2023, 9, 4).unwrap(),
+        horas_esperadas: 8,
+      },
+      TestCase {
+        descripcion: "Prueba: Caducidad solo con fecha de inicio. Se espera: 0 horas antes del inicio.",
+        configs: vec![config(
+          Dia::Lunes,
+          8,
+          fecha_creacion,
+          Some(NaiveDate::from_ymd_opt(2023, 2, 1).unwrap()),
+          None,
+        )],
+        fecha_consulta: NaiveDate::from_ymd_opt(2023, 1, 30).unwrap(),
+        horas_esperadas: 0,
+      },
       TestCase {
         descripcion: "Prueba: Múltiples horarios para el mismo día (ahora una sola entrada). Se espera: 8 horas.",
         configs: vec![
@@ -296,12 +1119,519 @@ mod tests {
       assert_eq!(
         horarios_usuario
           .buscar(caso.fecha_consulta)
-          .map(|h| h.horas)
+          .map(|h| h.minutos_a_trabajar())
           .unwrap_or(0),
-        caso.horas_esperadas,
+        caso.horas_esperadas as u32 * 60,
         "Fallo en: {}",
         caso.descripcion
       );
     }
   }
+
+  #[test]
+  fn test_informe_cumplimiento_anual() {
+    let fecha = |mes, dia| NaiveDate::from_ymd_opt(2024, mes, dia).unwrap();
+    let vacaciones = |dia: NaiveDate| CalendarioFecha {
+      id: 1,
+      calendario: 1,
+      fecha_inicio: dia,
+      fecha_fin: dia,
+      tipo: TipoCalendarioFecha::Vacaciones,
+    };
+
+    // Solo los lunes, 8 horas. 2024 tiene 53 lunes
+    let horarios = HorariosUsuario::new(vec![ConfigHorario {
+      id: 1,
+      usuario: 1,
+      dia: Dia::Lunes,
+      minutos: 480,
+      franjas: vec![],
+      pausas: ReglasPausa::default(),
+      fecha_creacion: NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(),
+      caducidad_fecha_ini: None,
+      caducidad_fecha_fin: None,
+      cortesia: 0,
+    }]);
+    let dias_inhabiles = DiasInhabiles::new(vec![
+      vacaciones(fecha(1, 8)),
+      vacaciones(fecha(2, 5)),
+    ]);
+    let marcajes = HashMap::from([(
+      1,
+      HorasEfectivasMarcajes::new(
+        HashMap::from([(1, 9.0), (15, 8.0), (22, 7.5)]),
+        HashMap::new(),
+      ),
+    )]);
+
+    let informe = InformeCumplimientoAnual::new(
+      2024,
+      400.0,
+      &horarios,
+      &dias_inhabiles,
+      &marcajes,
+      fecha(1, 31),
+    );
+
+    // Enero: 4 lunes laborables y uno de vacaciones
+    assert_eq!(informe.meses[0].horas_a_trabajar, 32.0);
+    assert_eq!(informe.meses[0].horas_trabajadas, 24.5);
+    assert_eq!(informe.meses[0].saldo, -7.5);
+    assert_eq!(informe.meses[0].dias_vacaciones, 1);
+    assert_eq!(informe.meses[1].horas_a_trabajar, 0.0);
+    // 48 lunes pendientes menos uno de vacaciones
+    assert_eq!(informe.horas_pendientes, 376.0);
+    assert_eq!(informe.vacaciones_disfrutadas, 1);
+    assert_eq!(informe.vacaciones_pendientes, 1);
+    assert_eq!(informe.proyeccion, 400.5);
+    assert_eq!(informe.diferencia_convenio, 0.5);
+  }
+
+  #[test]
+  fn test_informe_cumplimiento_minutos() {
+    let fecha = |dia| NaiveDate::from_ymd_opt(2025, 1, dia).unwrap();
+    let lunes = |minutos, cortesia| {
+      HorariosUsuario::new(vec![ConfigHorario {
+        id: 1,
+        usuario: 1,
+        dia: Dia::Lunes,
+        minutos,
+        franjas: vec![],
+        pausas: ReglasPausa::default(),
+        fecha_creacion: NaiveDate::from_ymd_opt(2024, 12, 31).unwrap(),
+        caducidad_fecha_ini: None,
+        caducidad_fecha_fin: None,
+        cortesia,
+      }])
+    };
+    let marcajes = HorasEfectivasMarcajes::new(
+      HashMap::from([(6, 7.0), (13, 6.5), (20, 6.6)]),
+      HashMap::new(),
+    );
+    let informe = |horarios: &HorariosUsuario| {
+      InformeCumplimiento::new(
+        fecha(1),
+        fecha(31),
+        fecha(31),
+        horarios,
+        &DiasInhabiles::new(vec![]),
+        &marcajes,
+      )
+    };
+
+    // Contrato de 6h45 con 10 minutos de cortesía
+    let parcial = informe(&lunes(405, 10));
+    let saldos: Vec<f64> = parcial.lineas.iter().map(|l| l.saldo).collect();
+    assert!(parcial.lineas.iter().all(|l| l.horas_a_trabajar == 6.75));
+    assert_eq!(saldos.len(), 4);
+    assert!((saldos[0] - 0.25).abs() < 1e-9);
+    // 6,5 horas más la cortesía no llegan a la jornada
+    assert!((saldos[1] + 5.0 / 60.0).abs() < 1e-9);
+    // La cortesía completa hasta la jornada, sin superarla
+    assert_eq!(saldos[2], 0.0);
+    // Lunes 27 sin marcajes: solo cuenta la cortesía
+    assert!((saldos[3] - (10.0 / 60.0 - 6.75)).abs() < 1e-9);
+
+    // Un horario migrado de 8 horas enteras (8 * 60 minutos)
+    let migrado = informe(&lunes(8 * 60, 0));
+    assert!(migrado.lineas.iter().all(|l| l.horas_a_trabajar == 8.0));
+    assert!((migrado.total_saldo - (20.1 - 32.0)).abs() < 1e-9);
+  }
+
+  /// Horario de los lunes de 8 horas con 30 minutos de comida retribuidos
+  /// y marcajes del lunes 1, el sábado 6 y el lunes 8 de enero de 2024.
+  fn jornadas_enero(dias_inhabiles: &DiasInhabiles) -> Vec<JornadaEfectiva> {
+    let fecha = |dia| NaiveDate::from_ymd_opt(2024, 1, dia).unwrap();
+    let horarios = HorariosUsuario::new(vec![ConfigHorario {
+      id: 1,
+      usuario: 1,
+      dia: Dia::Lunes,
+      minutos: 480,
+      franjas: vec![],
+      pausas: ReglasPausa {
+        comida_retribuida: 30,
+        personal_retribuida: 0,
+      },
+      fecha_creacion: NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(),
+      caducidad_fecha_ini: None,
+      caducidad_fecha_fin: None,
+      cortesia: 0,
+    }]);
+    let comida = TiempoPausas {
+      comida: 45,
+      personal: 0,
+    };
+    let marcajes = HorasEfectivasMarcajes::new(
+      HashMap::from([(1, 9.0), (6, 4.0), (8, 9.5)]),
+      HashMap::from([(6, comida), (8, comida)]),
+    );
+
+    JornadaEfectiva::dias(
+      fecha(1),
+      fecha(31),
+      fecha(31),
+      &horarios,
+      dias_inhabiles,
+      &marcajes,
+    )
+  }
+
+  #[test]
+  fn test_jornadas_efectivas_dia_sin_horario() {
+    let jornadas = jornadas_enero(&DiasInhabiles::new(vec![]));
+
+    assert_eq!(jornadas.len(), 3);
+    // El sábado no tiene jornada y no se retribuye ninguna pausa
+    assert_eq!(jornadas[1].fecha.day(), 6);
+    assert_eq!(jornadas[1].horas_a_trabajar, 0.0);
+    assert_eq!(jornadas[1].horas_efectivas, 3.25);
+    assert_eq!(jornadas[1].minutos_exceso(), 195);
+    // El lunes se descuentan los 15 minutos de comida no retribuidos
+    assert_eq!(jornadas[2].minutos_exceso(), 75);
+    assert_eq!(jornadas[0].minutos_exceso(), 60);
+  }
+
+  #[test]
+  fn test_jornadas_efectivas_festivo() {
+    let festivo = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let jornadas = jornadas_enero(&DiasInhabiles::new(vec![CalendarioFecha {
+      id: 1,
+      calendario: 1,
+      fecha_inicio: festivo,
+      fecha_fin: festivo,
+      tipo: TipoCalendarioFecha::Festivo,
+    }]));
+
+    // Todas las horas del festivo exceden la jornada
+    assert_eq!(jornadas[0].fecha, festivo);
+    assert_eq!(jornadas[0].horas_a_trabajar, 0.0);
+    assert_eq!(jornadas[0].minutos_exceso(), 540);
+    assert_eq!(jornadas[2].minutos_exceso(), 75);
+  }
+
+  #[test]
+  fn test_informe_equipo() {
+    let fecha = |dia| NaiveDate::from_ymd_opt(2024, 1, dia).unwrap();
+    let empleado = |id| DescriptorUsuario {
+      id,
+      nombre: format!("Empleado {id}"),
+      primer_apellido: String::new(),
+      segundo_apellido: String::new(),
+    };
+
+    // Solo los lunes, 8 horas
+    let horarios = HorariosUsuario::new(vec![ConfigHorario {
+      id: 1,
+      usuario: 1,
+      dia: Dia::Lunes,
+      minutos: 480,
+      franjas: vec![],
+      pausas: ReglasPausa::default(),
+      fecha_creacion: NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(),
+      caducidad_fecha_ini: None,
+      caducidad_fecha_fin: None,
+      cortesia: 0,
+    }]);
+    let sin_inhabiles = DiasInhabiles::new(vec![]);
+    let marcajes = HorasEfectivasMarcajes::new(
+      HashMap::from([(1, 9.0), (8, 8.0)]),
+      HashMap::new(),
+    );
+    let sin_marcajes =
+      HorasEfectivasMarcajes::new(HashMap::new(), HashMap::new());
+    let hoy = fecha(10);
+
+    let informe = |marcajes| {
+      InformeCumplimiento::new(
+        fecha(1),
+        fecha(31),
+        hoy,
+        &horarios,
+        &sin_inhabiles,
+        marcajes,
+      )
+    };
+
+    let equipo = InformeEquipo::new(
+      1,
+      2024,
+      vec![
+        CumplimientoEmpleado::new(
+          empleado(1),
+          &informe(&marcajes),
+          0,
+          vec![fecha(10)],
+          hoy,
+        ),
+        CumplimientoEmpleado::new(
+          empleado(2),
+          &informe(&sin_marcajes),
+          2,
+          vec![fecha(8), fecha(1)],
+          hoy,
+        ),
+      ],
+    );
+
+    // Dos lunes hasta la fecha actual por empleado
+    assert_eq!(equipo.empleados[0].horas_a_trabajar, 16.0);
+    assert_eq!(equipo.empleados[0].horas_trabajadas, 17.0);
+    assert_eq!(equipo.empleados[0].saldo, 1.0);
+    // El marcaje sin salida de hoy no se cuenta
+    assert!(equipo.empleados[0].dias_sin_salida.is_empty());
+    assert_eq!(equipo.empleados[1].saldo, -16.0);
+    assert_eq!(
+      equipo.empleados[1].dias_sin_salida,
+      vec![fecha(1), fecha(8)]
+    );
+    assert_eq!(equipo.horas_a_trabajar, 32.0);
+    assert_eq!(equipo.horas_trabajadas, 17.0);
+    assert_eq!(equipo.saldo, -15.0);
+    assert_eq!(equipo.incidencias_abiertas, 2);
+    assert_eq!(equipo.dias_sin_salida, 2);
+  }
+
+  #[test]
+  fn test_registro_jornada_lineas() {
+    let fecha = |dia: u32| NaiveDate::from_ymd_opt(2025, 2, dia).unwrap();
+    let hora = |h: u32| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+    let marcaje = |dia: u32, ini: u32, fin: u32, estado| MarcajeRegistro {
+      fecha: fecha(dia),
+      hora_inicio: hora(ini),
+      hora_fin: Some(hora(fin)),
+      inicio_utc: None,
+      fin_utc: None,
+      estado,
+      registrado_por: None,
+      modificado_por: None,
+    };
+
+    let dias_inhabiles = DiasInhabiles::new(vec![CalendarioFecha {
+      id: 1,
+      calendario: 1,
+      fecha_inicio: fecha(10),
+      fecha_fin: fecha(11),
+      tipo: TipoCalendarioFecha::Festivo,
+    }]);
+
+    let registro = RegistroJornada::new(
+      EmpleadoRegistro {
+        id: 1,
+        dni: "12345678Z".to_string(),
+        nombre: "Empleado".to_string(),
+      },
+      2,
+      2025,
+      vec![
+        marcaje(3, 8, 12, EstadoRegistro::Modificado),
+        marcaje(3, 8, 15, EstadoRegistro::Vigente),
+        marcaje(10, 9, 11, EstadoRegistro::Vigente),
+      ],
+      &dias_inhabiles,
+      fecha(28),
+    );
+
+    // Dos marcajes del día 3, el marcaje en festivo y el festivo sin marcajes
+    assert_eq!(registro.lineas.len(), 4);
+    assert_eq!(registro.lineas[0].estado, Some(EstadoRegistro::Modificado));
+    assert_eq!(registro.lineas[2].nota, "Festivo");
+    assert_eq!(registro.lineas[3].fecha, fecha(11));
+    assert!(registro.lineas[3].hora_inicio.is_none());
+
+    // Solo suman los marcajes vigentes
+    assert_eq!(registro.total_horas, 9.0);
+  }
+
+  #[test]
+  fn test_registro_jornada_turno_noche() {
+    let fecha = |dia: u32| NaiveDate::from_ymd_opt(2025, 2, dia).unwrap();
+    let hora = |h: u32| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+
+    // El turno de noche del último día del mes cuenta en ese día
+    let registro = RegistroJornada::new(
+      EmpleadoRegistro {
+        id: 1,
+        dni: "12345678Z".to_string(),
+        nombre: "Empleado".to_string(),
+      },
+      2,
+      2025,
+      vec![MarcajeRegistro {
+        fecha: fecha(28),
+        hora_inicio: hora(22),
+        hora_fin: Some(hora(6)),
+        inicio_utc: None,
+        fin_utc: None,
+        estado: EstadoRegistro::Vigente,
+        registrado_por: None,
+        modificado_por: None,
+      }],
+      &DiasInhabiles::new(vec![]),
+      fecha(28),
+    );
+
+    assert_eq!(registro.lineas.len(), 1);
+    assert_eq!(registro.lineas[0].fecha, fecha(28));
+    assert_eq!(registro.lineas[0].horas, Some(8.0));
+    assert_eq!(registro.total_horas, 8.0);
+  }
+
+  #[test]
+  fn test_informe_puntualidad() {
+    let fecha = |dia: u32| NaiveDate::from_ymd_opt(2025, 2, dia).unwrap();
+    let hora = |h: u32, m: u32| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    let marcaje = |dia: u32, ini, fin| MarcajeRegistro {
+      fecha: fecha(dia),
+      hora_inicio: ini,
+      hora_fin: fin,
+      inicio_utc: None,
+      fin_utc: None,
+      estado: EstadoRegistro::Vigente,
+      registrado_por: None,
+      modificado_por: None,
+    };
+
+    // Jornada partida de lunes: 08:00-14:00 y 16:00-18:00, 10 min de cortesía
+    let horarios = HorariosUsuario::new(vec![ConfigHorario {
+      id: 1,
+      usuario: 1,
+      fecha_creacion: fecha(1),
+      dia: Dia::Lunes,
+      minutos: 480,
+      franjas: vec![
+        FranjaHoraria {
+          hora_inicio: hora(8, 0),
+          hora_fin: hora(14, 0),
+        },
+        FranjaHoraria {
+          hora_inicio: hora(16, 0),
+          hora_fin: hora(18, 0),
+        },
+      ],
+      pausas: ReglasPausa::default(),
+      caducidad_fecha_ini: None,
+      caducidad_fecha_fin: None,
+      cortesia: 10,
+    }]);
+
+    let informe = InformePuntualidad::new(
+      EmpleadoRegistro {
+        id: 1,
+        dni: "12345678Z".to_string(),
+        nombre: "Empleado".to_string(),
+      },
+      2,
+      2025,
+      vec![
+        // Lunes 3: dentro de la cortesía
+        marcaje(3, hora(8, 10), Some(hora(14, 0))),
+        marcaje(3, hora(16, 0), Some(hora(17, 50))),
+        // Lunes 10: retraso y salida anticipada
+        marcaje(10, hora(8, 25), Some(hora(14, 0))),
+        marcaje(10, hora(16, 0), Some(hora(17, 0))),
+        // Lunes 17: sin salida
+        marcaje(17, hora(8, 0), None),
+        // Lunes 24: jornada abierta del día actual
+        marcaje(24, hora(8, 0), None),
+      ],
+      &horarios,
+      &DiasInhabiles::new(vec![]),
+      fecha(24),
+    );
+
+    assert_eq!(informe.retrasos, 1);
+    assert_eq!(informe.salidas_anticipadas, 1);
+    assert_eq!(informe.sin_salida, 1);
+    assert_eq!(informe.lineas[0].fecha, fecha(10));
+    assert_eq!(informe.lineas[0].minutos, 25);
+    assert_eq!(informe.lineas[1].minutos, 60);
+    assert_eq!(informe.lineas[2].tipo, TipoPuntualidad::SinSalida);
+  }
+
+  #[test]
+  fn test_informe_puntualidad_jornada_partida() {
+    let fecha = |dia: u32| NaiveDate::from_ymd_opt(2025, 2, dia).unwrap();
+    let hora = |h: u32, m: u32| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    let marcaje = |dia: u32, ini, fin| MarcajeRegistro {
+      fecha: fecha(dia),
+      hora_inicio: ini,
+      hora_fin: Some(fin),
+      inicio_utc: None,
+      fin_utc: None,
+      estado: EstadoRegistro::Vigente,
+      registrado_por: None,
+      modificado_por: None,
+    };
+
+    // Jornada partida de lunes: 08:00-14:00 y 16:00-18:00, 5 min de cortesía
+    let horarios = HorariosUsuario::new(vec![ConfigHorario {
+      id: 1,
+      usuario: 1,
+      fecha_creacion: fecha(1),
+      dia: Dia::Lunes,
+      minutos: 480,
+      franjas: vec![
+        FranjaHoraria {
+          hora_inicio: hora(8, 0),
+          hora_fin: hora(14, 0),
+        },
+        FranjaHoraria {
+          hora_inicio: hora(16, 0),
+          hora_fin: hora(18, 0),
+        },
+      ],
+      pausas: ReglasPausa::default(),
+      caducidad_fecha_ini: None,
+      caducidad_fecha_fin: None,
+      cortesia: 5,
+    }]);
+
+    let informe = InformePuntualidad::new(
+      EmpleadoRegistro {
+        id: 1,
+        dni: "12345678Z".to_string(),
+        nombre: "Empleado".to_string(),
+      },
+      2,
+      2025,
+      vec![
+        // Lunes 3: retraso en la franja de tarde
+        marcaje(3, hora(8, 0), hora(14, 0)),
+        marcaje(3, hora(16, 40), hora(18, 0)),
+        // Lunes 10: salida anticipada en la franja de mañana
+        marcaje(10, hora(8, 0), hora(13, 0)),
+        marcaje(10, hora(16, 0), hora(18, 0)),
+        // Lunes 17: un único marcaje que cubre las dos franjas
+        marcaje(17, hora(8, 0), hora(18, 0)),
+        // Lunes 24: la tarde empieza después del fin de su franja
+        marcaje(24, hora(8, 0), hora(14, 0)),
+        marcaje(24, hora(18, 30), hora(20, 0)),
+      ],
+      &horarios,
+      &DiasInhabiles::new(vec![]),
+      fecha(28),
+    );
+
+    let lineas: Vec<_> = informe
+      .lineas
+      .iter()
+      .map(|l| (l.fecha, l.tipo, l.hora_prevista, l.minutos))
+      .collect();
+
+    assert_eq!(
+      lineas,
+      vec![
+        (fecha(3), TipoPuntualidad::Retraso, Some(hora(16, 0)), 40),
+        (
+          fecha(10),
+          TipoPuntualidad::SalidaAnticipada,
+          Some(hora(14, 0)),
+          60
+        ),
+        (fecha(24), TipoPuntualidad::Retraso, Some(hora(16, 0)), 150),
+      ]
+    );
+    assert_eq!(informe.retrasos, 2);
+    assert_eq!(informe.salidas_anticipadas, 1);
+  }
 }
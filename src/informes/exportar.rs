@@ -0,0 +1,708 @@
+use std::io::{Cursor, Write};
+
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::extras::ResumenHorasExtra;
+use crate::informes::RegistroJornada;
+use crate::infra::ShortDateTimeFormat;
+
+/// Formatos de exportación del registro diario de jornada.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FormatoExportacion {
+  Csv,
+  Pdf,
+}
+
+impl FormatoExportacion {
+  pub fn extension(&self) -> &'static str {
+    match self {
+      FormatoExportacion::Csv => "csv",
+      FormatoExportacion::Pdf => "pdf",
+    }
+  }
+
+  pub fn tipo_mime(&self) -> &'static str {
+    match self {
+      FormatoExportacion::Csv => "text/csv; charset=utf-8",
+      FormatoExportacion::Pdf => "application/pdf",
+    }
+  }
+}
+
+/// Documento generado listo para descargar.
+#[derive(Debug)]
+pub struct Documento {
+  pub nombre: String,
+  pub tipo_mime: &'static str,
+  pub contenido: Vec<u8>,
+  /// Firma HMAC-SHA256 del contenido entregado. Ver [`firma_documento`]
+  pub firma: String,
+}
+
+/// Longitud en hexadecimal de una firma HMAC-SHA256.
+const LONGITUD_FIRMA: usize = 64;
+
+/// Marca que ocupa el lugar de la firma en los PDF mientras se calcula.
+///
+/// Tiene la longitud de la firma para no alterar las posiciones
+/// de la tabla de referencias del PDF al sustituirla.
+const FIRMA_PENDIENTE: &str =
+  "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Prefijo de la firma en los metadatos de los PDF.
+const METADATO_FIRMA: &str = "firma-hmac-sha256:";
+
+/// Texto que precede a la firma en el pie de página de los PDF.
+const ETIQUETA_FIRMA: &str = "Firma HMAC-SHA256: ";
+
+const CABECERA_CSV: [&str; 9] = [
+  "Fecha",
+  "Entrada",
+  "Salida",
+  "Horas",
+  "Estado",
+  "Registrado por",
+  "Modificado por",
+  "Nota",
+  "DNI",
+];
+
+/// Genera el registro diario de jornada en formato CSV.
+///
+/// Se usa el punto y coma como separador para que las hojas de
+/// cálculo con configuración regional española lo abran directamente.
+pub fn registro_csv(
+  registro: &RegistroJornada,
+) -> Result<Vec<u8>, anyhow::Error> {
+  let mut wtr = csv::WriterBuilder::new()
+    .delimiter(b';')
+    .from_writer(Vec::new());
+
+  wtr.write_record(CABECERA_CSV)?;
+
+  for l in &registro.lineas {
+    wtr.write_record([
+      l.fecha.formato_corto(),
+      l.hora_inicio.map(|h| h.formato_corto()).unwrap_or_default(),
+      l.hora_fin.map(|h| h.formato_corto()).unwrap_or_default(),
+      l.horas.map(|h| format!("{:.2}", h)).unwrap_or_default(),
+      l.estado.map(|e| e.as_str().to_string()).unwrap_or_default(),
+      l.registrado_por.clone().unwrap_or_default(),
+      l.modificado_por.clone().unwrap_or_default(),
+      l.nota.clone(),
+      registro.empleado.dni.clone(),
+    ])?;
+  }
+
+  Ok(wtr.into_inner()?)
+}
+
+/// Firma el contenido con HMAC-SHA256 y devuelve la firma en hexadecimal.
+pub fn firmar(contenido: &[u8], secreto: &str) -> String {
+  hex::encode(mac(contenido, secreto).finalize().into_bytes())
+}
+
+/// Firma de un documento tal y como se entrega.
+///
+/// Los PDF llevan la firma impresa, calculada sobre el propio PDF
+/// con la marca de firma pendiente en su lugar. El resto de
+/// documentos se firman directamente.
+pub fn firma_documento(
+  contenido: &[u8],
+  formato: FormatoExportacion,
+  secreto: &str,
+) -> String {
+  match formato {
+    FormatoExportacion::Pdf => {
+      firma_incluida(contenido).unwrap_or_else(|| firmar(contenido, secreto))
+    }
+    FormatoExportacion::Csv => firmar(contenido, secreto),
+  }
+}
+
+/// Verifica la firma de un documento entregado.
+///
+/// Si la firma está en los metadatos del PDF se restaura la marca de
+/// firma pendiente en esa posición y en los pies de página antes de
+/// calcularla, igual que al generarlo. El resto del contenido
+/// se verifica tal cual.
+pub fn verificar_firma(contenido: &[u8], firma: &str, secreto: &str) -> bool {
+  let firma = firma.trim().to_ascii_lowercase();
+  let Ok(bytes) = hex::decode(&firma) else {
+    return false;
+  };
+  if firma.len() != LONGITUD_FIRMA {
+    return false;
+  }
+
+  let mut original = contenido.to_vec();
+  if let Some(inicio) = posicion_firma(contenido)
+    && contenido[inicio..inicio + LONGITUD_FIRMA]
+      .eq_ignore_ascii_case(firma.as_bytes())
+  {
+    let pie = format!("{}{}", ETIQUETA_FIRMA, firma);
+    let mut posiciones = vec![inicio];
+    posiciones.extend(
+      contenido
+        .windows(pie.len())
+        .enumerate()
+        .filter(|(_, w)| *w == pie.as_bytes())
+        .map(|(i, _)| i + ETIQUETA_FIRMA.len()),
+    );
+    escribir_firma(&mut original, &posiciones, FIRMA_PENDIENTE);
+  }
+
+  mac(&original, secreto).verify_slice(&bytes).is_ok()
+}
+
+/// Firma incluida en los metadatos de un PDF generado.
+pub fn firma_incluida(contenido: &[u8]) -> Option<String> {
+  let inicio = posicion_firma(contenido)?;
+  String::from_utf8(contenido[inicio..inicio + LONGITUD_FIRMA].to_vec()).ok()
+}
+
+/// Posición de la firma en los metadatos de un PDF generado.
+///
+/// Los metadatos preceden a las páginas, por lo que la primera
+/// aparición del prefijo es la de los metadatos.
+fn posicion_firma(contenido: &[u8]) -> Option<usize> {
+  let patron = METADATO_FIRMA.as_bytes();
+  let inicio =
+    contenido.windows(patron.len()).position(|w| w == patron)? + patron.len();
+
+  let firma = contenido.get(inicio..inicio + LONGITUD_FIRMA)?;
+  firma.iter().all(u8::is_ascii_hexdigit).then_some(inicio)
+}
+
+fn mac(contenido: &[u8], secreto: &str) -> Hmac<Sha256> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(secreto.as_bytes())
+    .expect("HMAC admite claves de cualquier longitud");
+  mac.update(contenido);
+  mac
+}
+
+/// Sustituye la marca de firma pendiente por la firma del PDF.
+///
+/// Solo se escribe en las posiciones donde el generador puso la
+/// marca, nunca en el texto de los registros.
+fn sellar_pdf(
+  (mut pdf, marcas): (Vec<u8>, Vec<usize>),
+  secreto: &str,
+) -> Vec<u8> {
+  let firma = firmar(&pdf, secreto);
+  escribir_firma(&mut pdf, &marcas, &firma);
+  pdf
+}
+
+/// Escribe la firma o la marca en las posiciones indicadas.
+fn escribir_firma(contenido: &mut [u8], posiciones: &[usize], valor: &str) {
+  debug_assert_eq!(valor.len(), LONGITUD_FIRMA);
+
+  for &i in posiciones {
+    contenido[i..i + LONGITUD_FIRMA].copy_from_slice(valor.as_bytes());
+  }
+}
+
+/// Genera el registro diario de jornada en formato PDF.
+///
+/// La firma se imprime al pie de cada página y se incluye en
+/// los metadatos del documento. Se calcula sobre el propio PDF,
+/// por lo que puede verificarse con el fichero entregado.
+pub fn registro_pdf(
+  registro: &RegistroJornada,
+  generado: &str,
+  secreto: &str,
+) -> Vec<u8> {
+  let titulo = format!(
+    "Registro diario de jornada {:02}/{}",
+    registro.mes, registro.anio
+  );
+
+  let mut texto = vec![
+    titulo.clone(),
+    format!(
+      "Empleado: {}   DNI: {}",
+      registro.empleado.nombre, registro.empleado.dni
+    ),
+    String::new(),
+    format!(
+      "{:<10} {:<7} {:<7} {:>6} {:<10} {:<26} {:<26} {}",
+      "Fecha",
+      "Entrada",
+      "Salida",
+      "Horas",
+      "Estado",
+      "Registrado por",
+      "Modificado por",
+      "Nota"
+    ),
+  ];
+
+  for l in &registro.lineas {
+    texto.push(format!(
+      "{:<10} {:<7} {:<7} {:>6} {:<10} {:<26} {:<26} {}",
+      l.fecha.formato_corto(),
+      l.hora_inicio.map(|h| h.formato_corto()).unwrap_or_default(),
+      l.hora_fin.map(|h| h.formato_corto()).unwrap_or_default(),
+      l.horas.map(|h| format!("{:.2}", h)).unwrap_or_default(),
+      l.estado.map(|e| e.as_str()).unwrap_or_default(),
+      recortar(l.registrado_por.as_deref().unwrap_or_default(), 26),
+      recortar(l.modificado_por.as_deref().unwrap_or_default(), 26),
+      l.nota
+    ));
+  }
+
+  texto.push(String::new());
+  texto.push(format!("Total horas vigentes: {:.2}", registro.total_horas));
+
+  let pie = format!("Generado: {}", generado);
+
+  sellar_pdf(pdf(&titulo, &texto, &pie), secreto)
+}
+
+const CABECERA_CSV_HORAS_EXTRA: [&str; 8] = [
+  "Fecha",
+  "Horas",
+  "Causa",
+  "Compensación",
+  "Computa tope",
+  "Motivo",
+  "Registrado",
+  "DNI",
+];
+
+/// Genera el resumen mensual de horas extraordinarias en formato CSV.
+pub fn horas_extra_csv(
+  resumen: &ResumenHorasExtra,
+) -> Result<Vec<u8>, anyhow::Error> {
+  let mut wtr = csv::WriterBuilder::new()
+    .delimiter(b';')
+    .from_writer(Vec::new());
+
+  wtr.write_record(CABECERA_CSV_HORAS_EXTRA)?;
+
+  for h in &resumen.horas_extra {
+    wtr.write_record([
+      h.fecha.formato_corto(),
+      horas(h.minutos),
+      h.causa.as_str().to_string(),
+      h.compensacion.as_str().to_string(),
+      if h.computa_tope() { "Sí" } else { "No" }.to_string(),
+      h.motivo.clone().unwrap_or_default(),
+      h.fecha_registro.formato_corto(),
+      resumen.empleado.dni.clone(),
+    ])?;
+  }
+
+  Ok(wtr.into_inner()?)
+}
+
+/// Genera el resumen mensual de horas extraordinarias en formato PDF.
+///
+/// Se firma igual que el registro diario de jornada.
+pub fn horas_extra_pdf(
+  resumen: &ResumenHorasExtra,
+  generado: &str,
+  secreto: &str,
+) -> Vec<u8> {
+  let titulo = format!(
+    "Registro de horas extraordinarias {:02}/{}",
+    resumen.mes, resumen.anio
+  );
+
+  let mut texto = vec![
+    titulo.clone(),
+    format!(
+      "Empleado: {}   DNI: {}",
+      resumen.empleado.nombre, resumen.empleado.dni
+    ),
+    String::new(),
+    format!(
+      "{:<10} {:>6} {:<12} {:<12} {:<6} {}",
+      "Fecha", "Horas", "Causa", "Compensación", "Tope", "Motivo"
+    ),
+  ];
+
+  for h in &resumen.horas_extra {
+    texto.push(format!(
+      "{:<10} {:>6} {:<12} {:<12} {:<6} {}",
+      h.fecha.formato_corto(),
+      horas(h.minutos),
+      h.causa.as_str(),
+      h.compensacion.as_str(),
+      if h.computa_tope() { "Sí" } else { "No" },
+      h.motivo.as_deref().unwrap_or_default()
+    ));
+  }
+
+  texto.push(String::new());
+  texto.push(format!(
+    "Total horas extraordinarias del mes: {}",
+    horas(resumen.total)
+  ));
+  texto.push(format!(
+    "Estructurales: {}   Fuerza mayor: {}",
+    horas(resumen.estructurales),
+    horas(resumen.fuerza_mayor)
+  ));
+  texto.push(format!(
+    "Retribuidas: {}   Compensadas con descanso: {}",
+    horas(resumen.retribuidas),
+    horas(resumen.descanso)
+  ));
+  texto.push(format!(
+    "Horas computables para el tope anual hasta el mes: {}",
+    horas(resumen.computables_anio)
+  ));
+  texto.extend(resumen.avisos.iter().cloned());
+
+  let pie = format!("Generado: {}", generado);
+
+  sellar_pdf(pdf(&titulo, &texto, &pie), secreto)
+}
+
+/// Minutos expresados en horas con dos decimales.
+fn horas(minutos: u32) -> String {
+  format!("{:.2}", minutos as f64 / 60.0)
+}
+
+/// Agrupa varios documentos en un fichero ZIP.
+pub fn zip(documentos: &[Documento]) -> Result<Vec<u8>, anyhow::Error> {
+  let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+  let opciones = SimpleFileOptions::default()
+    .compression_method(CompressionMethod::Deflated);
+
+  for doc in documentos {
+    zip.start_file(doc.nombre.as_str(), opciones)?;
+    zip.write_all(&doc.contenido)?;
+  }
+
+  Ok(zip.finish()?.into_inner())
+}
+
+fn recortar(valor: &str, max: usize) -> String {
+  valor.chars().take(max).collect()
+}
+
+// Dimensiones de la página A4 apaisada en puntos
+const ANCHO_PAGINA: u32 = 842;
+const ALTO_PAGINA: u32 = 595;
+const MARGEN: u32 = 40;
+const TAM_FUENTE: u32 = 8;
+const INTERLINEADO: u32 = 11;
+
+/// Genera un PDF de texto con fuente monoespaciada.
+///
+/// Es suficiente para documentos tabulares y evita depender
+/// de una librería de maquetación.
+///
+/// Incluye la marca de firma pendiente en los metadatos y en el pie
+/// de cada página. Devuelve el PDF y las posiciones de las marcas.
+fn pdf(titulo: &str, lineas: &[String], pie: &str) -> (Vec<u8>, Vec<usize>) {
+  let lineas_pagina =
+    ((ALTO_PAGINA - 2 * MARGEN - 2 * INTERLINEADO) / INTERLINEADO) as usize;
+  let paginas: Vec<&[String]> = if lineas.is_empty() {
+    vec![&[]]
+  } else {
+    lineas.chunks(lineas_pagina).collect()
+  };
+
+  // Objetos: 1 catálogo, 2 páginas, 3 fuente, 4 información
+  // y después página y contenido por cada página.
+  let mut objetos: Vec<Vec<u8>> = Vec::with_capacity(4 + paginas.len() * 2);
+  // Marcas de firma pendiente: objeto y posición dentro del objeto
+  let mut marcas: Vec<(usize, usize)> = Vec::with_capacity(1 + paginas.len());
+
+  let kids = (0..paginas.len())
+    .map(|i| format!("{} 0 R", 5 + i * 2))
+    .collect::<Vec<_>>()
+    .join(" ");
+
+  objetos.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+  objetos.push(
+    format!(
+      "<< /Type /Pages /Kids [{}] /Count {} >>",
+      kids,
+      paginas.len()
+    )
+    .into_bytes(),
+  );
+  objetos.push(
+    b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier \
+      /Encoding /WinAnsiEncoding >>"
+      .to_vec(),
+  );
+
+  let mut info = b"<< /Title ".to_vec();
+  info.extend(cadena_pdf(titulo));
+  info.extend(b" /Producer (controla) /Keywords (");
+  info.extend(METADATO_FIRMA.as_bytes());
+  marcas.push((objetos.len(), info.len()));
+  info.extend(FIRMA_PENDIENTE.as_bytes());
+  info.extend(b") >>");
+  objetos.push(info);
+
+  let total = paginas.len();
+  for (i, pagina) in paginas.iter().enumerate() {
+    let contenido_id = 6 + i * 2;
+    objetos.push(
+      format!(
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+         /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+        ANCHO_PAGINA, ALTO_PAGINA, contenido_id
+      )
+      .into_bytes(),
+    );
+
+    let mut flujo = format!(
+      "BT /F1 {} Tf {} TL {} {} Td\n",
+      TAM_FUENTE,
+      INTERLINEADO,
+      MARGEN,
+      ALTO_PAGINA - MARGEN
+    )
+    .into_bytes();
+
+    for linea in pagina.iter() {
+      flujo.extend(cadena_pdf(linea));
+      flujo.extend(b" Tj T*\n");
+    }
+    flujo.extend(b"ET\nBT /F1 6 Tf ");
+    flujo.extend(format!("{} {} Td ", MARGEN, MARGEN / 2).into_bytes());
+    flujo.push(b'(');
+    flujo.extend(texto_pdf(&format!("{}   {}", pie, ETIQUETA_FIRMA)));
+    let marca = flujo.len();
+    flujo.extend(FIRMA_PENDIENTE.as_bytes());
+    flujo.extend(texto_pdf(&format!("   Página {} de {}", i + 1, total)));
+    flujo.extend(b") Tj ET");
+
+    let mut obj =
+      format!("<< /Length {} >>\nstream\n", flujo.len()).into_bytes();
+    marcas.push((objetos.len(), obj.len() + marca));
+    obj.extend(flujo);
+    obj.extend(b"\nendstream");
+    objetos.push(obj);
+  }
+
+  let mut salida = b"%PDF-1.4\n".to_vec();
+  let mut posiciones = Vec::with_capacity(objetos.len());
+
+  for (i, obj) in objetos.iter().enumerate() {
+    posiciones.push(salida.len());
+    salida.extend(format!("{} 0 obj\n", i + 1).into_bytes());
+    for (_, marca) in marcas.iter_mut().filter(|(o, _)| *o == i) {
+      *marca += salida.len();
+    }
+    salida.extend(obj);
+    salida.extend(b"\nendobj\n");
+  }
+
+  let xref = salida.len();
+  salida.extend(format!("xref\n0 {}\n", objetos.len() + 1).into_bytes());
+  salida.extend(b"0000000000 65535 f \n");
+  for pos in posiciones {
+    salida.extend(format!("{:010} 00000 n \n", pos).into_bytes());
+  }
+  salida.extend(
+    format!(
+      "trailer\n<< /Size {} /Root 1 0 R /Info 4 0 R >>\nstartxref\n{}\n%%EOF\n",
+      objetos.len() + 1,
+      xref
+    )
+    .into_bytes(),
+  );
+
+  (salida, marcas.into_iter().map(|(_, marca)| marca).collect())
+}
+
+/// Convierte el texto a una cadena literal PDF en WinAnsiEncoding.
+///
+/// Los caracteres fuera de Latin-1 se sustituyen por '?'.
+fn cadena_pdf(texto: &str) -> Vec<u8> {
+  let mut salida = Vec::with_capacity(texto.len() + 2);
+  salida.push(b'(');
+  salida.extend(texto_pdf(texto));
+  salida.push(b')');
+  salida
+}
+
+/// Escapa el texto para incluirlo en una cadena literal PDF.
+fn texto_pdf(texto: &str) -> Vec<u8> {
+  let mut salida = Vec::with_capacity(texto.len());
+
+  for c in texto.chars() {
+    match c {
+      '(' | ')' | '\\' => {
+        salida.push(b'\\');
+        salida.push(c as u8);
+      }
+      c if (c as u32) < 0x100 => salida.push(c as u32 as u8),
+      _ => salida.push(b'?'),
+    }
+  }
+
+  salida
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{NaiveDate, NaiveTime};
+
+  use super::*;
+  use crate::informes::{
+    EmpleadoRegistro, EstadoRegistro, LineaRegistroJornada,
+  };
+
+  fn registro() -> RegistroJornada {
+    RegistroJornada {
+      empleado: EmpleadoRegistro {
+        id: 1,
+        dni: "12345678Z".to_string(),
+        nombre: "José Pérez".to_string(),
+      },
+      mes: 3,
+      anio: 2025,
+      lineas: vec![LineaRegistroJornada {
+        fecha: NaiveDate::from_ymd_opt(2025, 3, 3).unwrap(),
+        hora_inicio: NaiveTime::from_hms_opt(8, 0, 0),
+        hora_fin: NaiveTime::from_hms_opt(15, 30, 0),
+        horas: Some(7.5),
+        estado: Some(EstadoRegistro::Vigente),
+        registrado_por: Some("Ana (registradora)".to_string()),
+        modificado_por: None,
+        nota: "Nota; con separador".to_string(),
+      }],
+      total_horas: 7.5,
+    }
+  }
+
+  #[test]
+  fn test_registro_csv() {
+    let csv = String::from_utf8(registro_csv(&registro()).unwrap()).unwrap();
+    let lineas: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(lineas.len(), 2);
+    assert!(lineas[0].starts_with("Fecha;Entrada;Salida;Horas"));
+    assert!(lineas[1].contains(";7.50;Vigente;"));
+    assert!(lineas[1].contains("\"Nota; con separador\""));
+  }
+
+  #[test]
+  fn test_registro_pdf() {
+    let pdf = registro_pdf(&registro(), "01/04/2025 10:00", "secreto");
+    let firma = firma_incluida(&pdf).unwrap();
+
+    assert!(pdf.starts_with(b"%PDF-1.4"));
+    assert!(pdf.ends_with(b"%%EOF\n"));
+
+    let contiene =
+      |patron: &[u8]| pdf.windows(patron.len()).any(|w| w == patron);
+
+    // La posición de la tabla de referencias debe ser correcta
+    let inicio = pdf.windows(10).rposition(|w| w == b"startxref\n").unwrap();
+    let pos: usize = std::str::from_utf8(&pdf[inicio + 10..pdf.len() - 7])
+      .unwrap()
+      .parse()
+      .unwrap();
+    assert!(pdf[pos..].starts_with(b"xref"));
+
+    // Los caracteres especiales se escapan y los acentos en Latin-1
+    assert!(contiene(b"Ana \\(registradora\\)"));
+    assert!(contiene(b"Jos\xe9 P\xe9rez"));
+    assert!(contiene(firma.as_bytes()));
+  }
+
+  #[test]
+  fn test_horas_extra_csv() {
+    use crate::extras::{CausaHoraExtra, CompensacionHoraExtra, HoraExtra};
+
+    let fecha = NaiveDate::from_ymd_opt(2025, 3, 3).unwrap();
+    let resumen = ResumenHorasExtra::new(
+      EmpleadoRegistro {
+        id: 1,
+        dni: "12345678Z".to_string(),
+        nombre: "Ana".to_string(),
+      },
+      3,
+      2025,
+      vec![HoraExtra {
+        id: 1,
+        usuario: 1,
+        fecha,
+        minutos: 90,
+        causa: CausaHoraExtra::FuerzaMayor,
+        compensacion: CompensacionHoraExtra::Retribuida,
+        motivo: Some("Avería".to_string()),
+        autor: 2,
+        fecha_registro: fecha.and_hms_opt(20, 0, 0).unwrap(),
+      }],
+      vec![],
+      0,
+    );
+
+    let csv = String::from_utf8(horas_extra_csv(&resumen).unwrap()).unwrap();
+    let lineas: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(lineas.len(), 2);
+    assert!(lineas[0].starts_with("Fecha;Horas;Causa"));
+    assert!(lineas[1].contains(";1.50;Fuerza mayor;Retribuida;No;Avería;"));
+  }
+
+  #[test]
+  fn test_firmar() {
+    assert_eq!(firmar(b"a", "k"), firmar(b"a", "k"));
+    assert_ne!(firmar(b"a", "k"), firmar(b"b", "k"));
+    assert_ne!(firmar(b"a", "k"), firmar(b"a", "otra"));
+  }
+
+  #[test]
+  fn test_verificar_firma() {
+    let pdf = registro_pdf(&registro(), "01/04/2025 10:00", "secreto");
+    let firma = firma_documento(&pdf, FormatoExportacion::Pdf, "secreto");
+
+    assert_eq!(Some(firma.clone()), firma_incluida(&pdf));
+    assert!(verificar_firma(&pdf, &firma, "secreto"));
+    assert!(verificar_firma(&pdf, &firma.to_uppercase(), "secreto"));
+    assert!(!verificar_firma(&pdf, &firma, "otra"));
+
+    // Cualquier cambio en el PDF invalida la firma
+    let mut alterado = pdf.clone();
+    let pos = alterado.windows(4).position(|w| w == b"7.50").unwrap();
+    alterado[pos] = b'9';
+    assert!(!verificar_firma(&alterado, &firma, "secreto"));
+
+    let csv = registro_csv(&registro()).unwrap();
+    let firma = firma_documento(&csv, FormatoExportacion::Csv, "secreto");
+
+    assert_eq!(firma, firmar(&csv, "secreto"));
+    assert!(verificar_firma(&csv, &firma, "secreto"));
+    assert!(!verificar_firma(&csv[1..], &firma, "secreto"));
+    assert!(!verificar_firma(&csv, "no-es-una-firma", "secreto"));
+  }
+
+  #[test]
+  fn test_firma_solo_en_posiciones_del_generador() {
+    // Un registro con la marca de firma pendiente en la nota
+    let mut registro = registro();
+    registro.lineas[0].nota = format!("{}{}", ETIQUETA_FIRMA, FIRMA_PENDIENTE);
+
+    let pdf = registro_pdf(&registro, "01/04/2025 10:00", "secreto");
+    let firma = firma_documento(&pdf, FormatoExportacion::Pdf, "secreto");
+    let apariciones = |patron: &[u8]| {
+      pdf.windows(patron.len()).filter(|w| *w == patron).count()
+    };
+
+    // La nota se mantiene y la firma aparece en metadatos y pie
+    assert_eq!(apariciones(FIRMA_PENDIENTE.as_bytes()), 1);
+    assert_eq!(apariciones(firma.as_bytes()), 2);
+    assert!(verificar_firma(&pdf, &firma, "secreto"));
+
+    // Un CSV con una firma en los metadatos se firma entero
+    let csv = format!("{}{}", METADATO_FIRMA, firma).into_bytes();
+    let firma_csv = firma_documento(&csv, FormatoExportacion::Csv, "secreto");
+    assert_eq!(firma_csv, firmar(&csv, "secreto"));
+    assert_ne!(firma_csv, firma);
+  }
+}
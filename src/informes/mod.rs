@@ -5,13 +5,31 @@
 //! - Informe de cumplimiento horario: Tiene como objetivo principal generar
 //!   un balance mensual detallado que compara la jornada laboral teórica de
 //!   un empleado contra su jornada real registrada.
+//! - Informe de cumplimiento anual: Recorre todos los días del año con
+//!   las reglas del informe anterior y compara las horas trabajadas más
+//!   la jornada teórica pendiente hasta fin de año con las horas anuales
+//!   del convenio colectivo configuradas. Incluye los días de
+//!   vacaciones disfrutados y pendientes.
+//! - Informe de cumplimiento de equipo: Totales del informe de
+//!   cumplimiento horario de un mes para un conjunto de empleados,
+//!   con sus incidencias sin resolver y los días con marcajes sin
+//!   salida. Los datos se obtienen con una consulta por entidad.
+//! - Registro diario de jornada: Documento legal con todos los marcajes
+//!   de un empleado en un mes. Se exporta en CSV y en PDF firmado,
+//!   individualmente o para todos los empleados en un ZIP.
+//! - Informe de puntualidad: Retrasos, salidas anticipadas y marcajes
+//!   sin salida de un empleado en un mes, comparando los marcajes con
+//!   las franjas del horario y su cortesía.
 mod repo;
 
 /// Módulo que define el dominio para las entidades de los informes
 mod dominio;
+/// Módulo que genera los documentos exportables de los informes
+mod exportar;
 /// Módulo con los servicios para los informes
 mod servicio;
 
 pub use dominio::*;
+pub use exportar::*;
 pub use repo::*;
 pub use servicio::*;
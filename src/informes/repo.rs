@@ -1,10 +1,18 @@
+use std::collections::HashMap;
+
 use chrono::NaiveDate;
-use sqlx::{QueryBuilder, Row};
+use sqlx::{MySql, QueryBuilder, Row};
 
 use crate::{
-  horario::{CalendarioFecha, repo::config_horario_from_row},
-  informes::{DiasInhabiles, HorariosUsuario, HorasEfectivasMarcajes},
+  horario::{CalendarioFecha, ConfigHorario, repo::config_horario_from_row},
+  inc::EstadoIncidencia,
+  informes::{
+    DiasInhabiles, EstadoRegistro, HorariosUsuario, HorasEfectivasMarcajes,
+    MarcajeRegistro,
+  },
   infra::{DBError, PoolConexion},
+  marcaje::TiempoPausas,
+  usuarios::DescriptorUsuario,
 };
 
 /// Repositorio encargado de la persistencia y recuperación de datos para
@@ -18,7 +26,10 @@ impl InformeRepo {
     InformeRepo { pool }
   }
 
-  fn fin_de_mes(anio: i32, mes: u32) -> Result<NaiveDate, DBError> {
+  pub(in crate::informes) fn fin_de_mes(
+    anio: i32,
+    mes: u32,
+  ) -> Result<NaiveDate, DBError> {
     let (nuevo_anio, nuevo_mes) = if mes == 12 {
       (anio + 1, 1)
     } else {
@@ -36,7 +47,9 @@ impl InformeRepo {
   /// Recupera las horas efectivas trabajadas por un usuario en un mes y año.
   ///
   /// Agrupa los marcajes válidos (con hora de fin, no eliminados ni modificados)
-  /// por día y suma la duración total en horas.
+  /// por día y suma la duración total en horas y los minutos de pausa
+  /// de cada tipo. Los marcajes que cruzan la medianoche se agrupan
+  /// en el día en que empiezan.
   pub(in crate::informes) async fn marcajes_mes(
     &self,
     usuario: u32,
@@ -47,16 +60,42 @@ impl InformeRepo {
       .ok_or(DBError::Parametros("Fecha inicio inválida"))?;
     let fecha_fin = Self::fin_de_mes(anio, mes)?;
 
-    const QUERY: &str = "SELECT EXTRACT(DAY FROM fecha) as dia,
-      CAST(
-       SUM(
-        TIMESTAMPDIFF(
-          SECOND, hora_inicio, hora_fin)) AS DOUBLE) / 3600.0 as horas
-      FROM marcajes
-      WHERE usuario = ? AND fecha BETWEEN ? AND ?
-      AND hora_fin IS NOT NULL 
-      AND modificado_por IS NULL AND eliminado IS NULL
-      GROUP BY dia";
+    let mut meses = self
+      .marcajes_periodo(usuario, fecha_inicio, fecha_fin)
+      .await?;
+
+    Ok(meses.remove(&mes).unwrap_or_else(|| {
+      HorasEfectivasMarcajes::new(HashMap::new(), HashMap::new())
+    }))
+  }
+
+  /// Recupera las horas efectivas trabajadas por un usuario entre dos
+  /// fechas, agrupadas por mes.
+  ///
+  /// Los meses sin marcajes no se incluyen.
+  pub(in crate::informes) async fn marcajes_periodo(
+    &self,
+    usuario: u32,
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<HashMap<u32, HorasEfectivasMarcajes>, DBError> {
+    const QUERY: &str = concat!(
+      "SELECT EXTRACT(MONTH FROM m.fecha) as mes,
+      EXTRACT(DAY FROM m.fecha) as dia,
+      CAST(SUM(",
+      crate::segundos_reales_marcaje!("m"),
+      ") AS DOUBLE) / 3600.0 as horas,
+      CAST(COALESCE(SUM(p.comida), 0) AS UNSIGNED) AS pausa_comida,
+      CAST(COALESCE(SUM(p.personal), 0) AS UNSIGNED) AS pausa_personal
+      FROM marcajes m
+      LEFT JOIN ",
+      crate::tiempo_pausas!(),
+      " p ON p.marcaje = m.id
+      WHERE m.usuario = ? AND m.fecha BETWEEN ? AND ?
+      AND m.hora_fin IS NOT NULL
+      AND m.modificado_por IS NULL AND m.eliminado IS NULL
+      GROUP BY mes, dia"
+    );
 
     let rows = sqlx::query(QUERY)
       .bind(usuario)
@@ -66,12 +105,117 @@ impl InformeRepo {
       .await
       .map_err(DBError::from_sqlx)?;
 
-    let dias = rows
-      .into_iter()
-      .map(|row| (row.get::<i64, _>("dia") as u32, row.get("horas")))
-      .collect();
+    let mut meses: HashMap<u32, (HashMap<_, _>, HashMap<_, _>)> =
+      HashMap::new();
+
+    for row in rows {
+      let mes = row.get::<i64, _>("mes") as u32;
+      let dia = row.get::<i64, _>("dia") as u32;
+      let (dias, pausas) = meses.entry(mes).or_default();
+
+      dias.insert(dia, row.get("horas"));
+      pausas.insert(
+        dia,
+        TiempoPausas {
+          comida: row.get::<u64, _>("pausa_comida") as u32,
+          personal: row.get::<u64, _>("pausa_personal") as u32,
+        },
+      );
+    }
+
+    Ok(
+      meses
+        .into_iter()
+        .map(|(mes, (dias, pausas))| {
+          (mes, HorasEfectivasMarcajes::new(dias, pausas))
+        })
+        .collect(),
+    )
+  }
+
+  /// Recupera todos los marcajes de un usuario en un mes y año
+  /// para el registro diario de jornada.
+  ///
+  /// Incluye los marcajes modificados y eliminados, el registrador
+  /// que realizó cada marcaje y el gestor que resolvió la incidencia
+  /// que lo modificó o eliminó.
+  pub(in crate::informes) async fn marcajes_registro_mes(
+    &self,
+    usuario: u32,
+    mes: u32,
+    anio: i32,
+  ) -> Result<Vec<MarcajeRegistro>, DBError> {
+    let fecha_inicio = NaiveDate::from_ymd_opt(anio, mes, 1)
+      .ok_or(DBError::Parametros("Fecha inicio inválida"))?;
+    let fecha_fin = Self::fin_de_mes(anio, mes)?;
+
+    const QUERY: &str = "SELECT m.fecha, m.hora_inicio, m.hora_fin,
+      m.inicio_utc, m.fin_utc,
+      m.modificado_por, m.eliminado,
+      ur.id AS ur_id, ur.nombre AS ur_nombre,
+      ur.primer_apellido AS ur_primer_apellido,
+      ur.segundo_apellido AS ur_segundo_apellido,
+      ug.id AS ug_id, ug.nombre AS ug_nombre,
+      ug.primer_apellido AS ug_primer_apellido,
+      ug.segundo_apellido AS ug_segundo_apellido
+      FROM marcajes m
+      LEFT JOIN usuarios ur ON m.usuario_registrador = ur.id
+      LEFT JOIN usuarios ug ON ug.id = (
+        SELECT i.usuario_gestor FROM incidencias i
+        WHERE i.marcaje = m.id AND i.estado = ?
+        ORDER BY i.fecha_resolucion DESC LIMIT 1)
+      WHERE m.usuario = ? AND m.fecha BETWEEN ? AND ?
+      ORDER BY m.fecha, m.hora_inicio, m.id";
+
+    let rows = sqlx::query(QUERY)
+      .bind(EstadoIncidencia::Resuelta as u8)
+      .bind(usuario)
+      .bind(fecha_inicio)
+      .bind(fecha_fin)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let descriptor = |row: &sqlx::mysql::MySqlRow, prefijo: &str| {
+      row
+        .try_get::<u32, _>(format!("{}_id", prefijo).as_str())
+        .ok()
+        .map(|id| DescriptorUsuario {
+          id,
+          nombre: row.get(format!("{}_nombre", prefijo).as_str()),
+          primer_apellido: row
+            .get(format!("{}_primer_apellido", prefijo).as_str()),
+          segundo_apellido: row
+            .get(format!("{}_segundo_apellido", prefijo).as_str()),
+        })
+    };
 
-    Ok(HorasEfectivasMarcajes::new(dias))
+    Ok(
+      rows
+        .iter()
+        .map(|row| {
+          let estado = if row.get::<Option<bool>, _>("eliminado") == Some(true)
+          {
+            EstadoRegistro::Eliminado
+          } else if row.get::<Option<u32>, _>("modificado_por").is_some() {
+            EstadoRegistro::Modificado
+          } else {
+            EstadoRegistro::Vigente
+          };
+
+          MarcajeRegistro {
+            fecha: row.get("fecha"),
+            hora_inicio: row.get("hora_inicio"),
+            hora_fin: row.get("hora_fin"),
+            inicio_utc: row.get("inicio_utc"),
+            fin_utc: row.get("fin_utc"),
+            estado,
+            registrado_por: descriptor(row, "ur"),
+            modificado_por: descriptor(row, "ug"),
+          }
+        })
+        .collect(),
+    )
   }
 
   /// Obtiene los periodos inhábiles que afectan a un usuario durante un mes y año.
@@ -92,6 +236,19 @@ impl InformeRepo {
 
     let fecha_fin = Self::fin_de_mes(anio, mes)?;
 
+    self
+      .dias_inhabiles_periodo(usuario, fecha_inicio, fecha_fin)
+      .await
+  }
+
+  /// Obtiene los periodos inhábiles que afectan a un usuario entre
+  /// dos fechas.
+  pub(in crate::informes) async fn dias_inhabiles_periodo(
+    &self,
+    usuario: u32,
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<DiasInhabiles, DBError> {
     const QUERY: &str = "SELECT cf.id, cf.calendario, cf.fecha_inicio, 
       cf.fecha_fin, cf.tipo
       FROM calendario_fechas cf
@@ -142,6 +299,19 @@ impl InformeRepo {
 
     let fecha_fin = Self::fin_de_mes(anio, mes)?;
 
+    self
+      .horarios_usuario_periodo(usuario, fecha_inicio, fecha_fin)
+      .await
+  }
+
+  /// Recupera la configuración de horarios de un usuario vigente
+  /// entre dos fechas: la anterior al inicio y las creadas después.
+  pub(in crate::informes) async fn horarios_usuario_periodo(
+    &self,
+    usuario: u32,
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<HorariosUsuario, DBError> {
     const QUERY_PREV: &str = "SELECT MAX(fecha_creacion) FROM horarios 
       WHERE usuario = ? AND fecha_creacion < ?";
 
@@ -152,22 +322,25 @@ impl InformeRepo {
       .await
       .map_err(DBError::from_sqlx)?;
 
-    let mut qb = QueryBuilder::new(
-      "SELECT id, usuario, fecha_creacion, dia, horas, cortesia,
-        caducidad_fecha_ini, caducidad_fecha_fin
-      FROM horarios
-      WHERE usuario = ",
-    );
+    let mut qb = QueryBuilder::new(concat!(
+      "SELECT h.id, h.usuario, h.fecha_creacion, h.dia, h.minutos, h.cortesia,
+        h.pausa_comida_retribuida, h.pausa_personal_retribuida,
+        h.caducidad_fecha_ini, h.caducidad_fecha_fin, ",
+      crate::franjas_horario!("h"),
+      " AS franjas
+      FROM horarios h
+      WHERE h.usuario = ",
+    ));
     qb.push_bind(usuario);
     qb.push(" AND (");
 
     if let Some(prev) = fecha_prev {
-      qb.push("fecha_creacion = ");
+      qb.push("h.fecha_creacion = ");
       qb.push_bind(prev);
       qb.push(" OR ");
     }
 
-    qb.push("fecha_creacion BETWEEN ");
+    qb.push("h.fecha_creacion BETWEEN ");
     qb.push_bind(fecha_inicio);
     qb.push(" AND ");
     qb.push_bind(fecha_fin);
@@ -183,4 +356,269 @@ impl InformeRepo {
 
     Ok(HorariosUsuario::new(horarios))
   }
+
+  /// Recupera en una sola consulta las horas efectivas trabajadas
+  /// por varios usuarios en un mes, agrupadas por usuario.
+  ///
+  /// Los usuarios sin marcajes no se incluyen.
+  pub(in crate::informes) async fn marcajes_usuarios(
+    &self,
+    usuarios: &[u32],
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<HashMap<u32, HorasEfectivasMarcajes>, DBError> {
+    let mut qb = QueryBuilder::new(concat!(
+      "SELECT m.usuario, EXTRACT(DAY FROM m.fecha) as dia,
+      CAST(SUM(",
+      crate::segundos_reales_marcaje!("m"),
+      ") AS DOUBLE) / 3600.0 as horas,
+      CAST(COALESCE(SUM(p.comida), 0) AS UNSIGNED) AS pausa_comida,
+      CAST(COALESCE(SUM(p.personal), 0) AS UNSIGNED) AS pausa_personal
+      FROM marcajes m
+      LEFT JOIN ",
+      crate::tiempo_pausas!(),
+      " p ON p.marcaje = m.id
+      WHERE m.hora_fin IS NOT NULL
+      AND m.modificado_por IS NULL AND m.eliminado IS NULL
+      AND m.fecha BETWEEN "
+    ));
+    qb.push_bind(fecha_inicio);
+    qb.push(" AND ");
+    qb.push_bind(fecha_fin);
+    push_usuarios(&mut qb, "m.usuario", usuarios);
+    qb.push(" GROUP BY m.usuario, dia");
+
+    let rows = qb
+      .build()
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let mut por_usuario: HashMap<u32, (HashMap<_, _>, HashMap<_, _>)> =
+      HashMap::new();
+
+    for row in rows {
+      let dia = row.get::<i64, _>("dia") as u32;
+      let (dias, pausas) = por_usuario.entry(row.get("usuario")).or_default();
+
+      dias.insert(dia, row.get("horas"));
+      pausas.insert(
+        dia,
+        TiempoPausas {
+          comida: row.get::<u64, _>("pausa_comida") as u32,
+          personal: row.get::<u64, _>("pausa_personal") as u32,
+        },
+      );
+    }
+
+    Ok(
+      por_usuario
+        .into_iter()
+        .map(|(usuario, (dias, pausas))| {
+          (usuario, HorasEfectivasMarcajes::new(dias, pausas))
+        })
+        .collect(),
+    )
+  }
+
+  /// Obtiene en una sola consulta los periodos inhábiles de varios
+  /// usuarios entre dos fechas, agrupados por usuario.
+  pub(in crate::informes) async fn dias_inhabiles_usuarios(
+    &self,
+    usuarios: &[u32],
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<HashMap<u32, DiasInhabiles>, DBError> {
+    let mut qb = QueryBuilder::new(
+      "SELECT cu.usuario, cf.id, cf.calendario, cf.fecha_inicio,
+      cf.fecha_fin, cf.tipo
+      FROM calendario_fechas cf
+      JOIN calendarios_usuario cu ON cu.calendario = cf.calendario
+      WHERE cf.fecha_inicio <= ",
+    );
+    qb.push_bind(fecha_fin);
+    qb.push(" AND cf.fecha_fin >= ");
+    qb.push_bind(fecha_inicio);
+    push_usuarios(&mut qb, "cu.usuario", usuarios);
+
+    let rows = qb
+      .build()
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let mut por_usuario: HashMap<u32, Vec<CalendarioFecha>> = HashMap::new();
+
+    for row in rows {
+      por_usuario.entry(row.get("usuario")).or_default().push(
+        CalendarioFecha {
+          id: row.get("id"),
+          calendario: row.get("calendario"),
+          fecha_inicio: row.get("fecha_inicio"),
+          fecha_fin: row.get("fecha_fin"),
+          tipo: (row.get::<u16, _>("tipo") as u8).into(),
+        },
+      );
+    }
+
+    Ok(
+      por_usuario
+        .into_iter()
+        .map(|(usuario, fechas)| (usuario, DiasInhabiles::new(fechas)))
+        .collect(),
+    )
+  }
+
+  /// Recupera en una sola consulta la configuración de horarios de
+  /// varios usuarios vigente entre dos fechas, agrupada por usuario.
+  ///
+  /// Para cada usuario obtiene la configuración anterior al inicio
+  /// y las creadas después.
+  pub(in crate::informes) async fn horarios_usuarios(
+    &self,
+    usuarios: &[u32],
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<HashMap<u32, HorariosUsuario>, DBError> {
+    let mut qb = QueryBuilder::new(concat!(
+      "SELECT h.id, h.usuario, h.fecha_creacion, h.dia, h.minutos, h.cortesia,
+        h.pausa_comida_retribuida, h.pausa_personal_retribuida,
+        h.caducidad_fecha_ini, h.caducidad_fecha_fin, ",
+      crate::franjas_horario!("h"),
+      " AS franjas
+      FROM horarios h
+      WHERE (h.fecha_creacion = (
+        SELECT MAX(hp.fecha_creacion) FROM horarios hp
+        WHERE hp.usuario = h.usuario AND hp.fecha_creacion < ",
+    ));
+    qb.push_bind(fecha_inicio);
+    qb.push(") OR h.fecha_creacion BETWEEN ");
+    qb.push_bind(fecha_inicio);
+    qb.push(" AND ");
+    qb.push_bind(fecha_fin);
+    qb.push(")");
+    push_usuarios(&mut qb, "h.usuario", usuarios);
+
+    let rows = qb
+      .build()
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let mut por_usuario: HashMap<u32, Vec<ConfigHorario>> = HashMap::new();
+
+    for horario in rows.iter().map(config_horario_from_row) {
+      por_usuario
+        .entry(horario.usuario)
+        .or_default()
+        .push(horario);
+    }
+
+    Ok(
+      por_usuario
+        .into_iter()
+        .map(|(usuario, horarios)| (usuario, HorariosUsuario::new(horarios)))
+        .collect(),
+    )
+  }
+
+  /// Número de incidencias sin resolver de varios usuarios cuya
+  /// fecha está entre dos fechas, agrupado por usuario.
+  pub(in crate::informes) async fn incidencias_abiertas_usuarios(
+    &self,
+    usuarios: &[u32],
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<HashMap<u32, u32>, DBError> {
+    let mut qb = QueryBuilder::new(
+      "SELECT i.usuario, CAST(COUNT(i.id) AS UNSIGNED) AS abiertas
+      FROM incidencias i
+      WHERE i.estado IN (",
+    );
+    {
+      let mut separated = qb.separated(", ");
+      for estado in [
+        EstadoIncidencia::Solicitud,
+        EstadoIncidencia::Conflicto,
+        EstadoIncidencia::ErrorResolver,
+      ] {
+        separated.push_bind(estado as u8);
+      }
+    }
+    qb.push(") AND i.fecha BETWEEN ");
+    qb.push_bind(fecha_inicio);
+    qb.push(" AND ");
+    qb.push_bind(fecha_fin);
+    push_usuarios(&mut qb, "i.usuario", usuarios);
+    qb.push(" GROUP BY i.usuario");
+
+    let rows = qb
+      .build()
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(
+      rows
+        .iter()
+        .map(|row| (row.get("usuario"), row.get::<u64, _>("abiertas") as u32))
+        .collect(),
+    )
+  }
+
+  /// Fechas con marcajes vigentes sin hora de salida de varios usuarios
+  /// entre dos fechas, agrupadas por usuario.
+  pub(in crate::informes) async fn fechas_sin_salida_usuarios(
+    &self,
+    usuarios: &[u32],
+    fecha_inicio: NaiveDate,
+    fecha_fin: NaiveDate,
+  ) -> Result<HashMap<u32, Vec<NaiveDate>>, DBError> {
+    let mut qb = QueryBuilder::new(
+      "SELECT DISTINCT m.usuario, m.fecha
+      FROM marcajes m
+      WHERE m.hora_fin IS NULL
+      AND m.modificado_por IS NULL AND m.eliminado IS NULL
+      AND m.fecha BETWEEN ",
+    );
+    qb.push_bind(fecha_inicio);
+    qb.push(" AND ");
+    qb.push_bind(fecha_fin);
+    push_usuarios(&mut qb, "m.usuario", usuarios);
+
+    let rows = qb
+      .build()
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let mut por_usuario: HashMap<u32, Vec<NaiveDate>> = HashMap::new();
+
+    for row in rows {
+      por_usuario
+        .entry(row.get("usuario"))
+        .or_default()
+        .push(row.get("fecha"));
+    }
+
+    Ok(por_usuario)
+  }
+}
+
+/// Añade el filtro de los usuarios a la consulta.
+fn push_usuarios(
+  qb: &mut QueryBuilder<MySql>,
+  columna: &str,
+  usuarios: &[u32],
+) {
+  qb.push(" AND ");
+  qb.push(columna);
+  qb.push(" IN (");
+  {
+    let mut separated = qb.separated(", ");
+    for usuario in usuarios {
+      separated.push_bind(*usuario);
+    }
+  }
+  qb.push(")");
 }
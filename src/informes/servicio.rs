@@ -1,9 +1,16 @@
-use chrono::{Datelike, NaiveDate, Utc};
-use smallvec::SmallVec;
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, Utc};
 
 use crate::config::ConfigTrabajo;
-use crate::informes::{CumplimientoHorario, InformeCumplimiento, InformeRepo};
-use crate::infra::{DBError, ServicioError};
+use crate::informes::{
+  CumplimientoEmpleado, DiasInhabiles, Documento, EmpleadoRegistro,
+  FormatoExportacion, HorariosUsuario, HorasEfectivasMarcajes,
+  InformeCumplimiento, InformeCumplimientoAnual, InformeEquipo,
+  InformePuntualidad, InformeRepo, JornadaEfectiva, RegistroJornada, exportar,
+};
+use crate::infra::{DBError, ServicioError, ShortDateTimeFormat};
+use crate::usuarios::DescriptorUsuario;
 
 pub struct InformeServicio {
   cnfg: ConfigTrabajo,
@@ -32,7 +39,8 @@ impl InformeServicio {
   /// - **Horas a trabajar**: La jornada teórica que el usuario debía
   ///   cumplir según su horario asignado.
   /// - **Horas efectivas**: La suma total de horas trabajadas sin cortesía,
-  ///   calculada a partir de los marcajes de entrada y salida.
+  ///   calculada a partir de los marcajes de entrada y salida, descontando
+  ///   las pausas no retribuidas según las reglas del horario.
   /// - **Horas trabajadas**: La suma total de horas trabajadas más la
   ///   cortesía.
   /// - **Saldo diario**: La diferencia entre las horas efectivas
@@ -58,6 +66,80 @@ impl InformeServicio {
       "Generando el informe de cumplimiento de horario"
     );
 
+    let (dias_inhabiles, horas_efectivas_marcajes, horarios_usuario) =
+      self.datos_mes(usuario, mes, anio).await?;
+
+    let fecha_inicio = NaiveDate::from_ymd_opt(anio, mes, 1)
+      .ok_or(DBError::Parametros("Fecha inicio inválida"))?;
+    let fecha_fin = InformeRepo::fin_de_mes(anio, mes)?;
+
+    let fecha_actual = Utc::now()
+      .with_timezone(&self.cnfg.zona_usuario(usuario))
+      .naive_local()
+      .date();
+
+    Ok(InformeCumplimiento::new(
+      fecha_inicio,
+      fecha_fin,
+      fecha_actual,
+      &horarios_usuario,
+      &dias_inhabiles,
+      &horas_efectivas_marcajes,
+    ))
+  }
+
+  /// Horas efectivas de los días con marcajes de un usuario en un mes
+  /// frente a su jornada teórica.
+  ///
+  /// A diferencia del informe de cumplimiento horario, incluye los días
+  /// sin horario y los inhábiles. Se usa para obtener el exceso de
+  /// jornada de cada día.
+  pub async fn jornadas_efectivas(
+    &self,
+    usuario: u32,
+    mes: u32,
+    anio: i32,
+  ) -> Result<Vec<JornadaEfectiva>, ServicioError> {
+    tracing::debug!(
+      usuario = usuario,
+      mes = mes,
+      anio = anio,
+      "Obteniendo las jornadas efectivas del mes"
+    );
+
+    let (dias_inhabiles, horas_efectivas_marcajes, horarios_usuario) =
+      self.datos_mes(usuario, mes, anio).await?;
+
+    let fecha_inicio = NaiveDate::from_ymd_opt(anio, mes, 1)
+      .ok_or(DBError::Parametros("Fecha inicio inválida"))?;
+    let fecha_fin = InformeRepo::fin_de_mes(anio, mes)?;
+
+    let fecha_actual = Utc::now()
+      .with_timezone(&self.cnfg.zona_usuario(usuario))
+      .naive_local()
+      .date();
+
+    Ok(JornadaEfectiva::dias(
+      fecha_inicio,
+      fecha_fin,
+      fecha_actual,
+      &horarios_usuario,
+      &dias_inhabiles,
+      &horas_efectivas_marcajes,
+    ))
+  }
+
+  /// Obtiene los días inhábiles, las horas efectivas de los marcajes
+  /// y los horarios de un usuario en un mes.
+  async fn datos_mes(
+    &self,
+    usuario: u32,
+    mes: u32,
+    anio: i32,
+  ) -> Result<
+    (DiasInhabiles, HorasEfectivasMarcajes, HorariosUsuario),
+    ServicioError,
+  > {
     let dias_inhabiles = self
       .repo
       .dias_inhabiles_mes(usuario, mes, anio)
@@ -103,86 +185,471 @@ impl InformeServicio {
         ServicioError::from(err)
       })?;
 
+    Ok((dias_inhabiles, horas_efectivas_marcajes, horarios_usuario))
+  }
+
+  /// Genera el informe de cumplimiento de la jornada anual de un usuario
+  /// frente a las horas anuales del convenio colectivo.
+  ///
+  /// Recorre todos los días del año con las mismas reglas que el informe
+  /// de cumplimiento horario y proyecta hasta fin de año la jornada
+  /// teórica de los días pendientes.
+  pub async fn cumplimiento_anual(
+    &self,
+    usuario: u32,
+    anio: i32,
+  ) -> Result<InformeCumplimientoAnual, ServicioError> {
+    tracing::info!(
+      usuario = usuario,
+      anio = anio,
+      "Generando el informe de cumplimiento anual"
+    );
+
+    let fecha_inicio = NaiveDate::from_ymd_opt(anio, 1, 1)
+      .ok_or(DBError::Parametros("Fecha inicio inválida"))?;
+    let fecha_fin = InformeRepo::fin_de_mes(anio, 12)?;
+
+    let dias_inhabiles = self
+      .repo
+      .dias_inhabiles_periodo(usuario, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = usuario,
+          anio = anio,
+          error = %err,
+          "Obteniendo días inhábiles para informe anual"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let marcajes = self
+      .repo
+      .marcajes_periodo(usuario, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = usuario,
+          anio = anio,
+          error = %err,
+          "Obteniendo horas efectivas para informe anual"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let horarios_usuario = self
+      .repo
+      .horarios_usuario_periodo(usuario, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = usuario,
+          anio = anio,
+          error = %err,
+          "Obteniendo horarios usuario para informe anual"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let hoy = Utc::now()
+      .with_timezone(&self.cnfg.zona_usuario(usuario))
+      .naive_local()
+      .date();
+
+    Ok(InformeCumplimientoAnual::new(
+      anio,
+      self.cnfg.horas_anuales_convenio,
+      &horarios_usuario,
+      &dias_inhabiles,
+      &marcajes,
+      hoy,
+    ))
+  }
+
+  /// Genera el informe de cumplimiento horario agregado de un equipo
+  /// de empleados para un mes.
+  ///
+  /// Los datos de todos los empleados se obtienen con una consulta por
+  /// cada entidad y para cada empleado se aplican las mismas reglas que
+  /// en el informe de cumplimiento horario. Incluye las incidencias
+  /// del mes sin resolver y los días con marcajes sin salida.
+  pub async fn cumplimiento_equipo(
+    &self,
+    empleados: Vec<DescriptorUsuario>,
+    mes: u32,
+    anio: i32,
+  ) -> Result<InformeEquipo, ServicioError> {
+    tracing::info!(
+      empleados = empleados.len(),
+      mes = mes,
+      anio = anio,
+      "Generando el informe de cumplimiento de equipo"
+    );
+
+    if empleados.is_empty() {
+      return Ok(InformeEquipo::new(mes, anio, Vec::new()));
+    }
+
     let fecha_inicio = NaiveDate::from_ymd_opt(anio, mes, 1)
       .ok_or(DBError::Parametros("Fecha inicio inválida"))?;
+    let fecha_fin = InformeRepo::fin_de_mes(anio, mes)?;
+    let ids: Vec<u32> = empleados.iter().map(|e| e.id).collect();
 
-    let (nuevo_anio, nuevo_mes) = if mes == 12 {
-      (anio + 1, 1)
-    } else {
-      (anio, mes + 1)
-    };
+    let dias_inhabiles = self
+      .repo
+      .dias_inhabiles_usuarios(&ids, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo días inhábiles para informe de equipo"
+        );
+        ServicioError::from(err)
+      })?;
 
-    let fecha_fin = NaiveDate::from_ymd_opt(nuevo_anio, nuevo_mes, 1)
-      .ok_or(DBError::Parametros("Fecha fin inválida"))?
-      .pred_opt()
-      .ok_or(DBError::Parametros("Fecha fin inválida"))?;
+    let marcajes = self
+      .repo
+      .marcajes_usuarios(&ids, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo horas efectivas para informe de equipo"
+        );
+        ServicioError::from(err)
+      })?;
 
-    let mut lineas = SmallVec::<[CumplimientoHorario; 31]>::new();
-    let mut total_saldo = 0.0;
-    let mut curr = fecha_inicio;
+    let horarios = self
+      .repo
+      .horarios_usuarios(&ids, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo horarios para informe de equipo"
+        );
+        ServicioError::from(err)
+      })?;
 
-    let fecha_actual = Utc::now()
-      .with_timezone(&self.cnfg.zona_horaria)
+    let incidencias = self
+      .repo
+      .incidencias_abiertas_usuarios(&ids, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo incidencias abiertas para informe de equipo"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let mut sin_salida = self
+      .repo
+      .fechas_sin_salida_usuarios(&ids, fecha_inicio, fecha_fin)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo marcajes sin salida para informe de equipo"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let sin_horarios = HorariosUsuario::new(Vec::new());
+    let sin_inhabiles = DiasInhabiles::new(Vec::new());
+    let sin_marcajes =
+      HorasEfectivasMarcajes::new(HashMap::new(), HashMap::new());
+    let ahora = Utc::now();
+
+    let lineas = empleados
+      .into_iter()
+      .map(|empleado| {
+        let id = empleado.id;
+        let hoy = ahora
+          .with_timezone(&self.cnfg.zona_usuario(id))
+          .naive_local()
+          .date();
+
+        let informe = InformeCumplimiento::new(
+          fecha_inicio,
+          fecha_fin,
+          hoy,
+          horarios.get(&id).unwrap_or(&sin_horarios),
+          dias_inhabiles.get(&id).unwrap_or(&sin_inhabiles),
+          marcajes.get(&id).unwrap_or(&sin_marcajes),
+        );
+
+        CumplimientoEmpleado::new(
+          empleado,
+          &informe,
+          incidencias.get(&id).copied().unwrap_or(0),
+          sin_salida.remove(&id).unwrap_or_default(),
+          hoy,
+        )
+      })
+      .collect();
+
+    Ok(InformeEquipo::new(mes, anio, lineas))
+  }
+
+  /// Obtiene el registro diario de jornada de un empleado para un mes.
+  ///
+  /// Incluye todos los marcajes, también los modificados o eliminados
+  /// por incidencias, y las notas del calendario laboral.
+  pub async fn registro_jornada(
+    &self,
+    empleado: EmpleadoRegistro,
+    mes: u32,
+    anio: i32,
+  ) -> Result<RegistroJornada, ServicioError> {
+    tracing::info!(
+      usuario = empleado.id,
+      mes = mes,
+      anio = anio,
+      "Generando el registro diario de jornada"
+    );
+
+    let fecha_fin = InformeRepo::fin_de_mes(anio, mes)?;
+
+    let marcajes = self
+      .repo
+      .marcajes_registro_mes(empleado.id, mes, anio)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = empleado.id,
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo marcajes para registro de jornada"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let dias_inhabiles = self
+      .repo
+      .dias_inhabiles_mes(empleado.id, mes, anio)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = empleado.id,
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo días inhábiles para registro de jornada"
+        );
+        ServicioError::from(err)
+      })?;
+
+    Ok(RegistroJornada::new(
+      empleado,
+      mes,
+      anio,
+      marcajes,
+      &dias_inhabiles,
+      fecha_fin,
+    ))
+  }
+
+  /// Obtiene el informe de puntualidad de un empleado para un mes.
+  ///
+  /// Detecta los retrasos en la entrada, las salidas anticipadas y los
+  /// marcajes sin hora de fin comparando los marcajes vigentes con las
+  /// franjas del horario de cada día y su cortesía.
+  pub async fn puntualidad(
+    &self,
+    empleado: EmpleadoRegistro,
+    mes: u32,
+    anio: i32,
+  ) -> Result<InformePuntualidad, ServicioError> {
+    tracing::info!(
+      usuario = empleado.id,
+      mes = mes,
+      anio = anio,
+      "Generando el informe de puntualidad"
+    );
+
+    let marcajes = self
+      .repo
+      .marcajes_registro_mes(empleado.id, mes, anio)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = empleado.id,
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo marcajes para informe de puntualidad"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let dias_inhabiles = self
+      .repo
+      .dias_inhabiles_mes(empleado.id, mes, anio)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = empleado.id,
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo días inhábiles para informe de puntualidad"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let horarios_usuario = self
+      .repo
+      .horarios_usuario_mes(empleado.id, mes, anio)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = empleado.id,
+          mes = mes,
+          anio = anio,
+          error = %err,
+          "Obteniendo horarios usuario para informe de puntualidad"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let hoy = Utc::now()
+      .with_timezone(&self.cnfg.zona_usuario(empleado.id))
       .naive_local()
       .date();
 
-    while curr <= fecha_fin && curr <= fecha_actual {
-      let Some(h) = horarios_usuario.buscar(curr) else {
-        curr = curr.succ_opt().unwrap();
-        continue;
-      };
-
-      let horas_a_trabajar = h.horas as f64;
-      let cortesia_en_horas = h.cortesia as f64 / 60.0;
-
-      let horas_efectivas = horas_efectivas_marcajes
-        .horas_efectivas(curr.day())
-        .unwrap_or(0.0);
-
-      let horas_trabajadas = if horas_efectivas < horas_a_trabajar {
-        (horas_efectivas + cortesia_en_horas).min(horas_a_trabajar)
-      } else {
-        horas_efectivas
-      };
-
-      let saldo = horas_trabajadas - horas_a_trabajar;
-
-      let linea = match dias_inhabiles.buscar(curr) {
-        Some(inhabil) if horas_efectivas > 0.0 => {
-          total_saldo += saldo;
-          CumplimientoHorario {
-            fecha: curr,
-            horas_trabajo_efectivo: horas_efectivas,
-            horas_trabajadas,
-            horas_a_trabajar: h.horas,
-            saldo,
-            nota: format!("Día inhábil con marcajes: {:?}", inhabil.tipo),
-          }
-        }
-        Some(inhabil) => CumplimientoHorario::with_fecha_y_nota(
-          curr,
-          format!("Día inhábil. Motivo: {:?}", inhabil.tipo),
-        ),
-        None => {
-          total_saldo += saldo;
-          CumplimientoHorario {
-            fecha: curr,
-            horas_trabajo_efectivo: horas_efectivas,
-            horas_trabajadas,
-            horas_a_trabajar: h.horas,
-            saldo,
-            nota: String::new(),
-          }
-        }
-      };
-
-      lineas.push(linea);
-      curr = curr.succ_opt().unwrap_or(curr);
+    Ok(InformePuntualidad::new(
+      empleado,
+      mes,
+      anio,
+      marcajes,
+      &horarios_usuario,
+      &dias_inhabiles,
+      hoy,
+    ))
+  }
+
+  /// Exporta el registro diario de jornada de un empleado.
+  ///
+  /// El documento se firma con HMAC-SHA256 sobre el contenido entregado.
+  pub async fn exportar_registro(
+    &self,
+    empleado: EmpleadoRegistro,
+    mes: u32,
+    anio: i32,
+    formato: FormatoExportacion,
+  ) -> Result<Documento, ServicioError> {
+    let registro = self.registro_jornada(empleado, mes, anio).await?;
+    self.documento(&registro, formato)
+  }
+
+  /// Exporta el registro diario de jornada de varios empleados
+  /// en un fichero ZIP con un documento por empleado.
+  pub async fn exportar_registros(
+    &self,
+    empleados: Vec<EmpleadoRegistro>,
+    mes: u32,
+    anio: i32,
+    formato: FormatoExportacion,
+  ) -> Result<Documento, ServicioError> {
+    let mut documentos = Vec::with_capacity(empleados.len());
+
+    for empleado in empleados {
+      let registro = self.registro_jornada(empleado, mes, anio).await?;
+      documentos.push(self.documento(&registro, formato)?);
     }
 
-    Ok(InformeCumplimiento {
-      lineas,
-      total_saldo,
+    let contenido = exportar::zip(&documentos).map_err(|err| {
+      tracing::error!(
+        mes = mes,
+        anio = anio,
+        error = %err,
+        "Generando ZIP de registros de jornada"
+      );
+      ServicioError::Interno(
+        "No se ha podido generar el fichero comprimido".to_string(),
+      )
+    })?;
+
+    Ok(Documento {
+      nombre: format!("registros_jornada_{}{:02}.zip", anio, mes),
+      tipo_mime: "application/zip",
+      firma: exportar::firmar(&contenido, &self.cnfg.secreto),
+      contenido,
+    })
+  }
+
+  /// Verifica la firma de un documento exportado.
+  ///
+  /// Si no se indica la firma se usa la incluida en el documento.
+  /// Devuelve la firma comprobada y si es válida.
+  pub fn verificar_firma(
+    &self,
+    contenido: &[u8],
+    firma: Option<&str>,
+  ) -> (Option<String>, bool) {
+    let firma = firma
+      .map(str::to_string)
+      .or_else(|| exportar::firma_incluida(contenido));
+
+    let valida = firma.as_deref().is_some_and(|firma| {
+      exportar::verificar_firma(contenido, firma, &self.cnfg.secreto)
+    });
+
+    tracing::info!(
+      firma = ?firma, valida = valida, "Verificada firma de documento");
+
+    (firma, valida)
+  }
+
+  /// Genera el documento del registro en el formato indicado
+  fn documento(
+    &self,
+    registro: &RegistroJornada,
+    formato: FormatoExportacion,
+  ) -> Result<Documento, ServicioError> {
+    let contenido = match formato {
+      FormatoExportacion::Csv => {
+        exportar::registro_csv(registro).map_err(|err| {
+          tracing::error!(
+            usuario = registro.empleado.id,
+            error = %err,
+            "Generando CSV del registro de jornada"
+          );
+          ServicioError::Interno(
+            "No se ha podido generar el registro de jornada".to_string(),
+          )
+        })?
+      }
+      FormatoExportacion::Pdf => {
+        let generado = self.cnfg.ahora().formato_corto();
+
+        exportar::registro_pdf(registro, &generado, &self.cnfg.secreto)
+      }
+    };
+
+    Ok(Documento {
+      nombre: format!(
+        "registro_jornada_{}{:02}_{}.{}",
+        registro.anio,
+        registro.mes,
+        registro.empleado.dni,
+        formato.extension()
+      ),
+      tipo_mime: formato.tipo_mime(),
+      firma: exportar::firma_documento(&contenido, formato, &self.cnfg.secreto),
+      contenido,
     })
   }
 }
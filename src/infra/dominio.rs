@@ -6,7 +6,9 @@ use std::{fmt::Display, ops::Deref};
 use data_encoding::HEXLOWER;
 use sha2::{Digest, Sha256};
 
-use crate::infra::{desencriptar, encriptar};
+use crate::infra::{
+  desencriptar, encriptar, hash_password, verificar_hash_password,
+};
 use crate::usuarios::DescriptorUsuario;
 
 /// Estructura que representa un array de entidades
@@ -92,6 +94,27 @@ impl Crypto {
     encriptar(&self.0, clave)
   }
 
+  /// Genera un hash de password irreversible (PBKDF2) con salt aleatorio.
+  ///
+  /// El cálculo es costoso y se realiza en un hilo bloqueante
+  /// para no detener el runtime.
+  pub async fn hash_password(&self) -> Result<String, anyhow::Error> {
+    let valor = self.0.clone();
+
+    tokio::task::spawn_blocking(move || hash_password(&valor)).await?
+  }
+
+  /// Verifica en tiempo constante el valor contra un hash de password.
+  ///
+  /// Se realiza en un hilo bloqueante como el cálculo del hash.
+  pub async fn verificar_hash_password(&self, hash: &str) -> bool {
+    let (valor, hash) = (self.0.clone(), hash.to_string());
+
+    tokio::task::spawn_blocking(move || verificar_hash_password(&valor, &hash))
+      .await
+      .unwrap_or(false)
+  }
+
   /// Genera un hash con salt
   /// Misma entrada, misma salida (para detección de duplicados)
   pub fn hash_con_salt(&self, salt: &str) -> String {
@@ -393,4 +416,19 @@ mod tests {
     assert!(!result.es_valido);
     assert!(result.errors.iter().any(|e| e.contains("común")));
   }
+
+  #[tokio::test]
+  async fn test_hash_password() {
+    let passw = Password::new("Secure123!".to_string());
+    let hash = passw.hash_password().await.unwrap();
+
+    assert!(crate::infra::es_hash_password(&hash));
+    assert!(passw.verificar_hash_password(&hash).await);
+    assert!(
+      !Password::new("Secure123?".to_string())
+        .verificar_hash_password(&hash)
+        .await
+    );
+    assert_ne!(hash, passw.hash_password().await.unwrap()); // Salt aleatorio
+  }
 }
@@ -2,27 +2,78 @@ use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use axum::{
-  Extension, extract::Request, http::StatusCode, middleware::Next,
-  response::IntoResponse,
+  Extension,
+  extract::{FromRequestParts, Request, State},
+  http::{HeaderValue, StatusCode, header::SET_COOKIE, request::Parts},
+  middleware::Next,
+  response::{IntoResponse, Response},
+  routing::MethodRouter,
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
-use hmac::{Hmac, Mac, KeyInit};
+use hmac::{Hmac, KeyInit, Mac};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 
+use crate::{sesiones::SesionServicio, usuarios::Rol};
+
 const NOMBRE_COOKIE_SESION: &str = "token";
 
 /// Datos de la sesión que se almacenarán
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatosSesion {
   pub id: String,
   pub caduca_en: u64, // timestamp UNIX
+  /// Timestamp UNIX de inicio de la sesión. Se mantiene en las
+  /// renovaciones para limitar la duración máxima de la sesión
+  pub iniciada_en: u64,
+  /// Usuario autenticado. Es el único origen válido del usuario
+  /// que realiza las acciones (autor de las trazas)
+  pub usuario: u32,
+  /// Roles del usuario autenticado. Se firman junto al resto
+  /// de datos para que el cliente no pueda manipularlos
+  pub roles: Vec<u8>,
+}
+
+impl DatosSesion {
+  /// Devuelve true si la sesión tiene alguno de los roles indicados
+  pub fn tiene_algun_rol(&self, roles: &[Rol]) -> bool {
+    roles.iter().any(|r| self.roles.contains(&(*r as u8)))
+  }
+}
+
+/// Extractor para obtener los datos de la sesión en los handlers.
+///
+/// Los datos son añadidos por el middleware [`autenticacion`],
+/// por lo que solo están disponibles en las rutas privadas.
+impl<S> FromRequestParts<S> for DatosSesion
+where
+  S: Send + Sync,
+{
+  type Rejection = StatusCode;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    _state: &S,
+  ) -> Result<Self, Self::Rejection> {
+    parts
+      .extensions
+      .get::<DatosSesion>()
+      .cloned()
+      .ok_or_else(|| {
+        tracing::error!("Datos de sesión no disponibles en la petición");
+        StatusCode::UNAUTHORIZED
+      })
+  }
 }
 
 /// Manejador de sesiones con caducidad
+///
+/// La sesión se renueva cuando ha consumido la mitad de su duración,
+/// sin superar nunca la duración máxima desde su inicio.
 pub struct ManejadorSesion {
   clave_secreta: String,
   duracion_sesion: Duration,
+  duracion_maxima: Duration,
   produccion: bool,
 }
 
@@ -40,39 +91,88 @@ impl ManejadorSesion {
   pub fn new(
     clave_secreta: String,
     duracion_sesion: Duration,
+    duracion_maxima: Duration,
     produccion: bool,
   ) -> Self {
     Self {
       clave_secreta,
       duracion_sesion,
+      duracion_maxima,
       produccion,
     }
   }
 
   /// Crea un nuevo token de sesión firmado
-  pub fn crear_sesion(&self) -> Result<Cookie<'_>, ErrorSesion> {
-    let caduca_en =
-      self.obtener_timestamp_actual() + self.duracion_sesion.as_secs();
+  ///
+  /// El usuario y sus roles quedan firmados dentro del token
+  /// para poder identificarlo y autorizar cada petición.
+  ///
+  /// Devuelve los datos de la sesión para registrarla en el almacén
+  /// de sesiones junto a la cookie.
+  pub fn crear_sesion(
+    &self,
+    usuario: u32,
+    roles: &[Rol],
+  ) -> Result<(DatosSesion, Cookie<'_>), ErrorSesion> {
+    let ahora = self.obtener_timestamp_actual();
+    let caduca_en = (ahora + self.duracion_sesion.as_secs())
+      .min(ahora + self.duracion_maxima.as_secs());
 
     let datos_sesion = DatosSesion {
       id: uuid::Uuid::new_v4().to_string(),
       caduca_en,
+      iniciada_en: ahora,
+      usuario,
+      roles: roles.iter().map(|r| *r as u8).collect(),
     };
 
     let token = self.crear_token_hmac(&datos_sesion)?;
 
-    // Crear cookie segura
-    Ok(
-      Cookie::build((NOMBRE_COOKIE_SESION, token))
-        .path("/")
-        .http_only(true)
-        .secure(self.produccion)
-        .same_site(self.lax())
-        .max_age(time::Duration::seconds(
-          self.duracion_sesion.as_secs() as i64
-        ))
-        .build(),
-    )
+    Ok((datos_sesion, self.cookie(token, caduca_en - ahora)))
+  }
+
+  /// Renueva la sesión si ha consumido la mitad de su duración.
+  ///
+  /// La nueva caducidad nunca supera la duración máxima desde
+  /// el inicio de la sesión. Devuelve None si no procede renovar.
+  /// La sesión renovada mantiene el mismo identificador.
+  pub fn renovar_sesion(
+    &self,
+    datos: &DatosSesion,
+  ) -> Result<Option<(DatosSesion, Cookie<'_>)>, ErrorSesion> {
+    let ahora = self.obtener_timestamp_actual();
+    let restante = datos.caduca_en.saturating_sub(ahora);
+
+    if restante.saturating_mul(2) > self.duracion_sesion.as_secs() {
+      return Ok(None);
+    }
+
+    let caduca_en = (ahora + self.duracion_sesion.as_secs())
+      .min(datos.iniciada_en + self.duracion_maxima.as_secs());
+
+    if caduca_en <= datos.caduca_en {
+      return Ok(None);
+    }
+
+    let renovada = DatosSesion {
+      caduca_en,
+      ..datos.clone()
+    };
+
+    let token = self.crear_token_hmac(&renovada)?;
+
+    Ok(Some((renovada, self.cookie(token, caduca_en - ahora))))
+  }
+
+  /// Crea la cookie segura con el token de sesión
+  fn cookie(&self, token: String, duracion: u64) -> Cookie<'_> {
+    Cookie::build((NOMBRE_COOKIE_SESION, token))
+      .path("/")
+      .http_only(true)
+      .secure(self.produccion)
+      .same_site(self.lax())
+      .max_age(time::Duration::seconds(duracion as i64))
+      .build()
   }
 
   /// Elimina la cookie de sesión del cliente
@@ -95,14 +195,20 @@ impl ManejadorSesion {
   }
 
   /// Valida un token de sesión
+  ///
+  /// La sesión es inválida si ha caducado o si ha superado
+  /// la duración máxima desde su inicio.
   pub fn validar_sesion(
     &self,
     token: &str,
   ) -> Result<DatosSesion, ErrorSesion> {
     let datos_sesion = self.verificar_token_hmac(token)?;
+    let ahora = self.obtener_timestamp_actual();
 
     // Verificar caducidad
-    if datos_sesion.caduca_en < self.obtener_timestamp_actual() {
+    if datos_sesion.caduca_en < ahora
+      || datos_sesion.iniciada_en + self.duracion_maxima.as_secs() < ahora
+    {
       return Err(ErrorSesion::SesionExpirada);
     }
 
@@ -169,13 +275,20 @@ impl ManejadorSesion {
   }
 }
 
-/// Middleware que verifica si la sesión es válida y no ha expirado
+/// Middleware que verifica si la sesión es válida, no ha expirado
+/// y no ha sido revocada.
+///
+/// Si la sesión es válida, los datos de la sesión se añaden a las
+/// extensiones de la petición para los middlewares posteriores.
+/// Si procede renovar la sesión, se envía la nueva cookie en la
+/// respuesta, salvo que la respuesta ya establezca la cookie.
 pub async fn autenticacion(
   cookiejar: CookieJar,
   Extension(manejador_sesiones): Extension<Arc<ManejadorSesion>>,
-  solicitud: Request,
+  Extension(sesiones): Extension<Arc<SesionServicio>>,
+  mut solicitud: Request,
   siguiente: Next,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, StatusCode> {
   let token = cookiejar
     .get(NOMBRE_COOKIE_SESION)
     .map(|cookie| cookie.value().to_string())
@@ -185,10 +298,214 @@ pub async fn autenticacion(
     })?;
 
   match manejador_sesiones.validar_sesion(&token) {
-    Ok(_) => Ok(siguiente.run(solicitud).await),
+    Ok(datos_sesion) if !sesiones.activa(&datos_sesion.id) => {
+      tracing::warn!(
+        usuario = datos_sesion.usuario,
+        sesion = datos_sesion.id,
+        "Middleware de autenticación: sesión revocada"
+      );
+      Err(StatusCode::UNAUTHORIZED)
+    }
+    Ok(datos_sesion) => {
+      let renovacion = match manejador_sesiones.renovar_sesion(&datos_sesion) {
+        Ok(renovacion) => renovacion,
+        Err(err) => {
+          // La sesión sigue siendo válida aunque no se pueda renovar
+          tracing::error!(error = ?err, "Renovando la sesión");
+          None
+        }
+      };
+
+      let Some((renovada, cookie)) = renovacion else {
+        solicitud.extensions_mut().insert(datos_sesion);
+        return Ok(siguiente.run(solicitud).await);
+      };
+
+      tracing::debug!(
+        usuario = renovada.usuario,
+        sesion = renovada.id,
+        caduca_en = renovada.caduca_en,
+        "Renovando la sesión"
+      );
+
+      // Si falla la persistencia, la sesión ya está renovada en memoria
+      drop(sesiones.renovar(&renovada).await);
+
+      solicitud.extensions_mut().insert(renovada);
+      let mut respuesta = siguiente.run(solicitud).await;
+
+      if !respuesta.headers().contains_key(SET_COOKIE)
+        && let Ok(valor) = HeaderValue::from_str(&cookie.to_string())
+      {
+        respuesta.headers_mut().insert(SET_COOKIE, valor);
+      }
+
+      Ok(respuesta)
+    }
     Err(err) => {
       tracing::error!(error = ?err, "Middleware de autenticación");
       Err(StatusCode::UNAUTHORIZED)
     }
   }
 }
+
+/// Middleware que verifica que el usuario de la sesión tenga
+/// alguno de los roles permitidos para la ruta.
+///
+/// Debe ejecutarse después de [`autenticacion`], que es quien
+/// añade los datos de la sesión a la petición.
+pub async fn autorizacion(
+  State(permitidos): State<&'static [Rol]>,
+  datos_sesion: DatosSesion,
+  solicitud: Request,
+  siguiente: Next,
+) -> Result<impl IntoResponse, StatusCode> {
+  if datos_sesion.tiene_algun_rol(permitidos) {
+    Ok(siguiente.run(solicitud).await)
+  } else {
+    tracing::warn!(
+      usuario = datos_sesion.usuario,
+      ruta = %solicitud.uri(),
+      roles_sesion = ?datos_sesion.roles,
+      roles_permitidos = ?permitidos,
+      "Acceso denegado: el usuario no tiene ningún rol permitido"
+    );
+    Err(StatusCode::FORBIDDEN)
+  }
+}
+
+/// Restringe el acceso a una ruta a los roles indicados.
+///
+/// Si el usuario de la sesión no tiene ninguno de los roles
+/// la petición se rechaza con el estado: FORBIDDEN.
+pub fn permitir<S>(
+  roles: &'static [Rol],
+  ruta: MethodRouter<S>,
+) -> MethodRouter<S>
+where
+  S: Clone + Send + Sync + 'static,
+{
+  ruta.route_layer(axum::middleware::from_fn_with_state(roles, autorizacion))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DURACION: u64 = 3600;
+  const MAXIMA: u64 = 43200;
+
+  fn manejador() -> ManejadorSesion {
+    ManejadorSesion::new(
+      "clave-de-pruebas".to_string(),
+      Duration::from_secs(DURACION),
+      Duration::from_secs(MAXIMA),
+      false,
+    )
+  }
+
+  fn datos(iniciada_en: u64, caduca_en: u64) -> DatosSesion {
+    DatosSesion {
+      id: "sesion".to_string(),
+      caduca_en,
+      iniciada_en,
+      usuario: 1,
+      roles: vec![Rol::Empleado as u8],
+    }
+  }
+
+  #[test]
+  fn test_validar_sesion_valida() {
+    let manejador = manejador();
+    let (datos, cookie) = manejador.crear_sesion(1, &[Rol::Admin]).unwrap();
+
+    let validada = manejador.validar_sesion(cookie.value()).unwrap();
+    assert_eq!(validada.id, datos.id);
+    assert_eq!(validada.usuario, 1);
+    assert!(validada.tiene_algun_rol(&[Rol::Admin]));
+  }
+
+  #[test]
+  fn test_validar_sesion_token_manipulado() {
+    let manejador = manejador();
+    let (_, cookie) = manejador.crear_sesion(1, &[Rol::Empleado]).unwrap();
+    let token = cookie.value().replace("\"usuario\":1", "\"usuario\":2");
+
+    assert!(matches!(
+      manejador.validar_sesion(&token),
+      Err(ErrorSesion::TokenInvalido)
+    ));
+    assert!(matches!(
+      manejador.validar_sesion("sin-firma"),
+      Err(ErrorSesion::TokenInvalido)
+    ));
+  }
+
+  #[test]
+  fn test_validar_sesion_caducada() {
+    let manejador = manejador();
+    let ahora = manejador.obtener_timestamp_actual();
+    let token = manejador
+      .crear_token_hmac(&datos(ahora - DURACION, ahora - 1))
+      .unwrap();
+
+    assert!(matches!(
+      manejador.validar_sesion(&token),
+      Err(ErrorSesion::SesionExpirada)
+    ));
+  }
+
+  #[test]
+  fn test_validar_sesion_supera_duracion_maxima() {
+    let manejador = manejador();
+    let ahora = manejador.obtener_timestamp_actual();
+    let token = manejador
+      .crear_token_hmac(&datos(ahora - MAXIMA - 1, ahora + DURACION))
+      .unwrap();
+
+    assert!(matches!(
+      manejador.validar_sesion(&token),
+      Err(ErrorSesion::SesionExpirada)
+    ));
+  }
+
+  #[test]
+  fn test_renovar_sesion_antes_de_la_mitad() {
+    let manejador = manejador();
+    let ahora = manejador.obtener_timestamp_actual();
+    let sesion = datos(ahora - 60, ahora + DURACION - 60);
+
+    assert!(manejador.renovar_sesion(&sesion).unwrap().is_none());
+  }
+
+  #[test]
+  fn test_renovar_sesion_pasada_la_mitad() {
+    let manejador = manejador();
+    let ahora = manejador.obtener_timestamp_actual();
+    let sesion = datos(ahora - 2000, ahora + DURACION - 2000);
+
+    let (renovada, cookie) =
+      manejador.renovar_sesion(&sesion).unwrap().unwrap();
+    assert_eq!(renovada.id, sesion.id);
+    assert_eq!(renovada.iniciada_en, sesion.iniciada_en);
+    assert!(renovada.caduca_en >= ahora + DURACION);
+
+    let validada = manejador.validar_sesion(cookie.value()).unwrap();
+    assert_eq!(validada.caduca_en, renovada.caduca_en);
+  }
+
+  #[test]
+  fn test_renovar_sesion_limitada_por_duracion_maxima() {
+    let manejador = manejador();
+    let ahora = manejador.obtener_timestamp_actual();
+    let iniciada_en = ahora - MAXIMA + 600;
+    let sesion = datos(iniciada_en, ahora + 300);
+
+    let (renovada, _) = manejador.renovar_sesion(&sesion).unwrap().unwrap();
+    assert_eq!(renovada.caduca_en, iniciada_en + MAXIMA);
+
+    // Alcanzada la duración máxima ya no se renueva
+    let sesion = datos(ahora - MAXIMA + 300, ahora + 300);
+    assert!(manejador.renovar_sesion(&sesion).unwrap().is_none());
+  }
+}
@@ -1,7 +1,7 @@
 use base64::{Engine, engine::general_purpose::STANDARD};
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use ring::{
-  aead, hkdf,
+  aead, hkdf, pbkdf2,
   rand::{self, SecureRandom},
 };
 use thiserror::Error;
@@ -10,6 +10,13 @@ use crate::infra::DBError;
 
 pub const NONE_DATE: NaiveDate = NaiveDate::from_ymd_opt(1900, 1, 1).unwrap();
 
+/// Prefijo que identifica las password almacenadas como hash PBKDF2
+const PREFIJO_HASH_PASSWORD: &str = "pbkdf2-sha256";
+/// Iteraciones del PBKDF2 (recomendación OWASP para HMAC-SHA256)
+const ITERACIONES_HASH_PASSWORD: u32 = 600_000;
+const LONGITUD_SALT_PASSWORD: usize = 16;
+const LONGITUD_HASH_PASSWORD: usize = 32;
+
 #[derive(Debug, Error)]
 pub enum ServicioError {
   #[error("Error de acceso a la base de datos: {0}")]
@@ -18,6 +25,13 @@ pub enum ServicioError {
   Validacion(String),
   #[error("{0}")]
   Usuario(String),
+  /// El acceso está bloqueado temporalmente por exceso de intentos fallidos.
+  #[error("{0}")]
+  AccesoBloqueado(String),
+  /// Fallo interno que no depende de los datos del usuario.
+  /// El mensaje se muestra al usuario, sin detalles del fallo
+  #[error("Error interno: {0}")]
+  Interno(String),
 }
 
 impl ServicioError {
@@ -32,7 +46,9 @@ impl ServicioError {
   pub fn mensaje(&self) -> String {
     match self {
       ServicioError::Usuario(msg) => msg.to_owned(),
+      ServicioError::AccesoBloqueado(msg) => msg.to_owned(),
       ServicioError::Validacion(msg) => msg.to_owned(),
+      ServicioError::Interno(msg) => msg.to_owned(),
       ServicioError::DB(
         DBError::RegistroVacio(e) | DBError::ConstraintViolation(e),
       ) => e.to_owned(),
@@ -200,3 +216,73 @@ pub fn desencriptar(
   String::from_utf8(texto_plano.to_vec())
     .map_err(|_| anyhow::anyhow!("UTF-8 inválido".to_string()))
 }
+
+/// Genera un hash de password con PBKDF2-HMAC-SHA256 y salt aleatorio.
+///
+/// El resultado tiene el formato: `pbkdf2-sha256$iteraciones$salt$hash`,
+/// con el salt y el hash codificados en Base64.
+pub fn hash_password(cadena: &str) -> Result<String, anyhow::Error> {
+  let mut salt = [0u8; LONGITUD_SALT_PASSWORD];
+  rand::SystemRandom::new()
+    .fill(&mut salt)
+    .map_err(|e| anyhow::anyhow!(format!("Error generando salt: {}", e)))?;
+
+  let iteraciones = std::num::NonZeroU32::new(ITERACIONES_HASH_PASSWORD)
+    .expect("Las iteraciones deben ser mayores que cero");
+
+  let mut hash = [0u8; LONGITUD_HASH_PASSWORD];
+  pbkdf2::derive(
+    pbkdf2::PBKDF2_HMAC_SHA256,
+    iteraciones,
+    &salt,
+    cadena.as_bytes(),
+    &mut hash,
+  );
+
+  Ok(format!(
+    "{}${}${}${}",
+    PREFIJO_HASH_PASSWORD,
+    ITERACIONES_HASH_PASSWORD,
+    STANDARD.encode(salt),
+    STANDARD.encode(hash)
+  ))
+}
+
+/// Verifica en tiempo constante una password contra su hash PBKDF2.
+///
+/// Devuelve false si el hash no tiene un formato válido.
+pub fn verificar_hash_password(cadena: &str, hash: &str) -> bool {
+  let mut partes = hash.split('$');
+
+  if partes.next() != Some(PREFIJO_HASH_PASSWORD) {
+    return false;
+  }
+
+  let iteraciones = partes
+    .next()
+    .and_then(|i| i.parse::<u32>().ok())
+    .and_then(std::num::NonZeroU32::new);
+  let salt = partes.next().and_then(|s| STANDARD.decode(s).ok());
+  let esperado = partes.next().and_then(|h| STANDARD.decode(h).ok());
+
+  match (iteraciones, salt, esperado, partes.next()) {
+    (Some(iteraciones), Some(salt), Some(esperado), None) => pbkdf2::verify(
+      pbkdf2::PBKDF2_HMAC_SHA256,
+      iteraciones,
+      &salt,
+      cadena.as_bytes(),
+      &esperado,
+    )
+    .is_ok(),
+    _ => false,
+  }
+}
+
+/// Devuelve true si el valor almacenado es un hash de password.
+///
+/// Las password de versiones previas estaban encriptadas de
+/// forma reversible y no tienen el prefijo del hash.
+#[inline]
+pub fn es_hash_password(valor: &str) -> bool {
+  valor.starts_with(PREFIJO_HASH_PASSWORD)
+}
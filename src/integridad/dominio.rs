@@ -0,0 +1,541 @@
+use std::collections::HashMap;
+
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+
+/// Tablas cuyas filas se encadenan con hashes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TablaSellada {
+  Marcajes,
+  Incidencias,
+  Trazas,
+  Pausas,
+}
+
+impl TablaSellada {
+  /// Todas las tablas selladas
+  pub const TODAS: [TablaSellada; 4] = [
+    TablaSellada::Marcajes,
+    TablaSellada::Incidencias,
+    TablaSellada::Trazas,
+    TablaSellada::Pausas,
+  ];
+
+  pub fn nombre(&self) -> &'static str {
+    match self {
+      TablaSellada::Marcajes => "marcajes",
+      TablaSellada::Incidencias => "incidencias",
+      TablaSellada::Trazas => "trazas",
+      TablaSellada::Pausas => "pausas",
+    }
+  }
+
+  /// Expresión SQL con la clave de la cadena de cada fila.
+  pub(in crate::integridad) fn clave(&self) -> &'static str {
+    match self {
+      TablaSellada::Marcajes | TablaSellada::Incidencias => {
+        "CAST(usuario AS CHAR)"
+      }
+      TablaSellada::Trazas => "CAST(CONCAT(entidad, ':', entidad_id) AS CHAR)",
+      TablaSellada::Pausas => "CAST(marcaje AS CHAR)",
+    }
+  }
+
+  /// Expresión SQL con los campos que no cambian tras la inserción.
+  ///
+  /// Se calcula en la base de datos para que el contenido firmado
+  /// coincida exactamente con lo almacenado.
+  pub(in crate::integridad) fn datos(&self) -> &'static str {
+    match self {
+      TablaSellada::Marcajes => {
+        "CAST(CONCAT_WS('|', usuario, fecha, horario, hora_inicio,
+          IFNULL(usuario_registrador, ''), IFNULL(inicio_utc, '')) AS CHAR)"
+      }
+      TablaSellada::Incidencias => {
+        "CAST(CONCAT_WS('|', tipo, fecha, usuario,
+          IFNULL(marcaje, '')) AS CHAR)"
+      }
+      TablaSellada::Trazas => {
+        "CAST(CONCAT_WS('|', IFNULL(autor, ''), tipo, fecha, entidad,
+          entidad_id, IFNULL(motivo, '')) AS CHAR)"
+      }
+      TablaSellada::Pausas => {
+        "CAST(CONCAT_WS('|', marcaje, tipo, hora_inicio) AS CHAR)"
+      }
+    }
+  }
+
+  /// Expresión SQL con los campos que la aplicación puede cambiar.
+  pub(in crate::integridad) fn estado(&self) -> &'static str {
+    match self {
+      TablaSellada::Marcajes => {
+        "CAST(CONCAT_WS('|', IFNULL(hora_fin, ''),
+          IFNULL(modificado_por, ''), IFNULL(eliminado, ''),
+          IFNULL(fin_utc, '')) AS CHAR)"
+      }
+      TablaSellada::Incidencias => {
+        "CAST(CONCAT_WS('|', fecha_solicitud, IFNULL(hora_inicio, ''),
+          IFNULL(hora_fin, ''), estado, IFNULL(error, ''),
+          IFNULL(usuario_creador, ''),
+          IFNULL(usuario_gestor, ''), IFNULL(motivo_solicitud, ''),
+          IFNULL(motivo_rechazo, ''), IFNULL(fecha_resolucion, ''),
+          IFNULL(fecha_estado, '')) AS CHAR)"
+      }
+      // Las trazas no se modifican nunca
+      TablaSellada::Trazas => "''",
+      TablaSellada::Pausas => "CAST(IFNULL(hora_fin, '') AS CHAR)",
+    }
+  }
+}
+
+/// Fila de una tabla sellada preparada para verificar.
+#[derive(Debug)]
+pub struct FilaSellada {
+  pub id: u32,
+  pub clave: String,
+  pub eslabon: Option<u32>,
+  pub hash: Option<String>,
+  pub sello: Option<String>,
+  pub datos: String,
+  pub estado: String,
+}
+
+/// Cabecera de una cadena: último eslabón y su hash.
+#[derive(Debug, Clone)]
+pub struct CabezaCadena {
+  pub eslabones: u32,
+  pub hash: String,
+}
+
+/// Motivo por el que una fila no supera la verificación.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MotivoAlteracion {
+  /// Los campos inmutables o el hash no coinciden
+  DatosAlterados,
+  /// Los campos modificables no coinciden con el sello
+  EstadoAlterado,
+  /// Faltan eslabones anteriores a la fila
+  EslabonesEliminados,
+  /// La cabecera no coincide con el último eslabón.
+  /// Se han eliminado las últimas filas de la cadena
+  FinCadenaAlterado,
+  /// Fila sin sellar insertada después de iniciar la cadena
+  SinSellar,
+}
+
+impl MotivoAlteracion {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      MotivoAlteracion::DatosAlterados => "Datos alterados",
+      MotivoAlteracion::EstadoAlterado => "Estado alterado",
+      MotivoAlteracion::EslabonesEliminados => "Filas anteriores eliminadas",
+      MotivoAlteracion::FinCadenaAlterado => "Últimas filas eliminadas",
+      MotivoAlteracion::SinSellar => "Fila insertada fuera de la aplicación",
+    }
+  }
+}
+
+/// Fila que no supera la verificación.
+#[derive(Debug)]
+pub struct Alteracion {
+  pub tabla: TablaSellada,
+  pub clave: String,
+  /// Fila afectada, si la fila existe
+  pub id: Option<u32>,
+  pub motivo: MotivoAlteracion,
+}
+
+/// Resultado de verificar las cadenas de hashes.
+#[derive(Debug, Default)]
+pub struct InformeIntegridad {
+  /// Filas selladas verificadas
+  pub verificadas: u64,
+  /// Filas anteriores a la activación del sellado
+  pub sin_sellar: u64,
+  pub alteraciones: Vec<Alteracion>,
+}
+
+impl InformeIntegridad {
+  /// Devuelve true si no se ha detectado ninguna alteración
+  pub fn integro(&self) -> bool {
+    self.alteraciones.is_empty()
+  }
+
+  fn alterada(
+    &mut self,
+    tabla: TablaSellada,
+    clave: &str,
+    id: Option<u32>,
+    motivo: MotivoAlteracion,
+  ) {
+    self.alteraciones.push(Alteracion {
+      tabla,
+      clave: clave.to_string(),
+      id,
+      motivo,
+    });
+  }
+}
+
+/// Verificación de las cadenas de una tabla fila a fila.
+///
+/// Las filas se reciben ordenadas por clave, eslabón e id, con las
+/// filas sin sellar al final de su cadena. Así se pueden leer por
+/// lotes sin cargar la tabla completa en memoria.
+/// Tras una alteración se continúa la cadena con el hash almacenado
+/// para que una sola fila alterada no invalide el resto.
+pub struct VerificacionTabla<'a> {
+  secreto: &'a str,
+  tabla: TablaSellada,
+  cabezas: HashMap<String, CabezaCadena>,
+  cadena: Option<CadenaEnCurso>,
+}
+
+/// Estado de la cadena que se está verificando.
+struct CadenaEnCurso {
+  clave: String,
+  anterior: String,
+  esperado: u32,
+  /// Primera fila sellada de la cadena
+  primera: Option<u32>,
+  /// Última fila sellada de la cadena
+  ultima: Option<u32>,
+}
+
+impl<'a> VerificacionTabla<'a> {
+  pub fn new(
+    secreto: &'a str,
+    tabla: TablaSellada,
+    cabezas: HashMap<String, CabezaCadena>,
+  ) -> Self {
+    VerificacionTabla {
+      secreto,
+      tabla,
+      cabezas,
+      cadena: None,
+    }
+  }
+
+  /// Verifica la siguiente fila y añade el resultado al informe.
+  pub fn verificar(
+    &mut self,
+    informe: &mut InformeIntegridad,
+    fila: &FilaSellada,
+  ) {
+    if self.cadena.as_ref().is_none_or(|c| c.clave != fila.clave) {
+      self.cerrar_cadena(informe);
+      self.cadena = Some(CadenaEnCurso {
+        clave: fila.clave.clone(),
+        anterior: String::new(),
+        esperado: 1,
+        primera: None,
+        ultima: None,
+      });
+    }
+
+    let tabla = self.tabla;
+    let secreto = self.secreto;
+    let Some(cadena) = self.cadena.as_mut() else {
+      return;
+    };
+
+    let Some(eslabon) = fila.eslabon else {
+      // Las filas sin sellar posteriores a la primera sellada
+      // se han insertado fuera de la aplicación
+      match cadena.primera {
+        Some(p) if fila.id > p => informe.alterada(
+          tabla,
+          &cadena.clave,
+          Some(fila.id),
+          MotivoAlteracion::SinSellar,
+        ),
+        _ => informe.sin_sellar += 1,
+      }
+      return;
+    };
+
+    let hash = fila.hash.as_deref().unwrap_or_default();
+
+    if eslabon != cadena.esperado {
+      informe.alterada(
+        tabla,
+        &cadena.clave,
+        Some(fila.id),
+        MotivoAlteracion::EslabonesEliminados,
+      );
+    }
+
+    if hash_eslabon(secreto, &cadena.anterior, eslabon, &fila.datos) != hash {
+      informe.alterada(
+        tabla,
+        &cadena.clave,
+        Some(fila.id),
+        MotivoAlteracion::DatosAlterados,
+      );
+    }
+
+    if fila.sello.as_deref() != Some(&hash_sello(secreto, hash, &fila.estado)) {
+      informe.alterada(
+        tabla,
+        &cadena.clave,
+        Some(fila.id),
+        MotivoAlteracion::EstadoAlterado,
+      );
+    }
+
+    informe.verificadas += 1;
+    cadena.anterior = hash.to_string();
+    cadena.esperado = eslabon + 1;
+    cadena.primera = Some(cadena.primera.map_or(fila.id, |p| p.min(fila.id)));
+    cadena.ultima = Some(fila.id);
+  }
+
+  /// Termina la verificación de la tabla.
+  ///
+  /// Comprueba la cabecera de la última cadena y las cadenas
+  /// cuyas filas se han eliminado todas.
+  pub fn terminar(mut self, informe: &mut InformeIntegridad) {
+    self.cerrar_cadena(informe);
+
+    for (clave, _) in self.cabezas {
+      informe.alterada(
+        self.tabla,
+        &clave,
+        None,
+        MotivoAlteracion::FinCadenaAlterado,
+      );
+    }
+  }
+
+  /// Comprueba que la cabecera coincide con el último eslabón.
+  fn cerrar_cadena(&mut self, informe: &mut InformeIntegridad) {
+    let Some(cadena) = self.cadena.take() else {
+      return;
+    };
+
+    match self.cabezas.remove(&cadena.clave) {
+      Some(cabeza)
+        if cabeza.eslabones == cadena.esperado - 1
+          && cabeza.hash == cadena.anterior => {}
+      None if cadena.ultima.is_none() => {}
+      _ => informe.alterada(
+        self.tabla,
+        &cadena.clave,
+        cadena.ultima,
+        MotivoAlteracion::FinCadenaAlterado,
+      ),
+    }
+  }
+}
+
+/// Calcula el hash de un eslabón encadenado con el anterior.
+pub fn hash_eslabon(
+  secreto: &str,
+  anterior: &str,
+  eslabon: u32,
+  datos: &str,
+) -> String {
+  hmac(secreto, &[anterior, &eslabon.to_string(), datos])
+}
+
+/// Calcula el sello de los campos modificables de una fila.
+pub fn hash_sello(secreto: &str, hash: &str, estado: &str) -> String {
+  hmac(secreto, &[hash, estado])
+}
+
+fn hmac(secreto: &str, partes: &[&str]) -> String {
+  let mut mac = Hmac::<Sha256>::new_from_slice(secreto.as_bytes())
+    .expect("HMAC admite claves de cualquier longitud");
+
+  for (i, parte) in partes.iter().enumerate() {
+    if i > 0 {
+      mac.update(b"\n");
+    }
+    mac.update(parte.as_bytes());
+  }
+
+  hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SECRETO: &str = "secreto";
+
+  /// Genera una cadena íntegra y su cabecera
+  fn cadena(
+    datos: &[&str],
+  ) -> (Vec<FilaSellada>, HashMap<String, CabezaCadena>) {
+    let mut anterior = String::new();
+    let mut filas = Vec::new();
+
+    for (i, d) in datos.iter().enumerate() {
+      let eslabon = i as u32 + 1;
+      let hash = hash_eslabon(SECRETO, &anterior, eslabon, d);
+
+      filas.push(FilaSellada {
+        id: eslabon * 10,
+        clave: "1".to_string(),
+        eslabon: Some(eslabon),
+        sello: Some(hash_sello(SECRETO, &hash, "estado")),
+        hash: Some(hash.clone()),
+        datos: d.to_string(),
+        estado: "estado".to_string(),
+      });
+      anterior = hash;
+    }
+
+    let cabezas = HashMap::from([(
+      "1".to_string(),
+      CabezaCadena {
+        eslabones: datos.len() as u32,
+        hash: anterior,
+      },
+    )]);
+
+    (filas, cabezas)
+  }
+
+  /// Verifica las filas en el orden en que se leen de la base de datos
+  fn verificar(
+    mut filas: Vec<FilaSellada>,
+    cabezas: HashMap<String, CabezaCadena>,
+  ) -> InformeIntegridad {
+    filas
+      .sort_by_key(|f| (f.clave.clone(), f.eslabon.unwrap_or(u32::MAX), f.id));
+
+    let mut informe = InformeIntegridad::default();
+    let mut verificacion =
+      VerificacionTabla::new(SECRETO, TablaSellada::Marcajes, cabezas);
+    for fila in &filas {
+      verificacion.verificar(&mut informe, fila);
+    }
+    verificacion.terminar(&mut informe);
+    informe
+  }
+
+  fn motivos(
+    informe: &InformeIntegridad,
+  ) -> Vec<(Option<u32>, MotivoAlteracion)> {
+    informe
+      .alteraciones
+      .iter()
+      .map(|a| (a.id, a.motivo))
+      .collect()
+  }
+
+  #[test]
+  fn test_cadena_integra() {
+    let (filas, cabezas) = cadena(&["a", "b", "c"]);
+    let informe = verificar(filas, cabezas);
+
+    assert!(informe.integro());
+    assert_eq!(informe.verificadas, 3);
+  }
+
+  #[test]
+  fn test_datos_y_estado_alterados() {
+    let (mut filas, cabezas) = cadena(&["a", "b", "c"]);
+    filas[1].datos = "B".to_string();
+    filas[2].estado = "otro".to_string();
+
+    let informe = verificar(filas, cabezas);
+    assert_eq!(
+      motivos(&informe),
+      vec![
+        (Some(20), MotivoAlteracion::DatosAlterados),
+        (Some(30), MotivoAlteracion::EstadoAlterado),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_filas_eliminadas() {
+    // Eliminada una fila intermedia
+    let (mut filas, cabezas) = cadena(&["a", "b", "c"]);
+    filas.remove(1);
+    let informe = verificar(filas, cabezas);
+    assert_eq!(
+      motivos(&informe),
+      vec![
+        (Some(30), MotivoAlteracion::EslabonesEliminados),
+        (Some(30), MotivoAlteracion::DatosAlterados),
+      ]
+    );
+
+    // Eliminada la última fila
+    let (mut filas, cabezas) = cadena(&["a", "b", "c"]);
+    filas.pop();
+    let informe = verificar(filas, cabezas);
+    assert_eq!(
+      motivos(&informe),
+      vec![(Some(20), MotivoAlteracion::FinCadenaAlterado)]
+    );
+
+    // Eliminadas todas las filas
+    let (_, cabezas) = cadena(&["a"]);
+    let informe = verificar(vec![], cabezas);
+    assert_eq!(
+      motivos(&informe),
+      vec![(None, MotivoAlteracion::FinCadenaAlterado)]
+    );
+  }
+
+  #[test]
+  fn test_varias_cadenas() {
+    // Cada cadena se cierra al pasar a la siguiente clave
+    let (mut filas, mut cabezas) = cadena(&["a", "b"]);
+    let (otras, otras_cabezas) = cadena(&["c", "d", "e"]);
+    for mut fila in otras {
+      fila.clave = "2".to_string();
+      fila.id += 1;
+      filas.push(fila);
+    }
+    cabezas.insert("2".to_string(), otras_cabezas["1"].clone());
+
+    let informe = verificar(filas, cabezas.clone());
+    assert!(informe.integro());
+    assert_eq!(informe.verificadas, 5);
+
+    // Eliminada la última fila de la primera cadena
+    let (mut filas, _) = cadena(&["a", "b"]);
+    filas.pop();
+    let (otras, _) = cadena(&["c", "d", "e"]);
+    for mut fila in otras {
+      fila.clave = "2".to_string();
+      filas.push(fila);
+    }
+
+    let informe = verificar(filas, cabezas);
+    assert_eq!(
+      motivos(&informe),
+      vec![(Some(10), MotivoAlteracion::FinCadenaAlterado)]
+    );
+  }
+
+  #[test]
+  fn test_filas_sin_sellar() {
+    let (mut filas, cabezas) = cadena(&["a", "b"]);
+    let sin_sellar = |id| FilaSellada {
+      id,
+      clave: "1".to_string(),
+      eslabon: None,
+      hash: None,
+      sello: None,
+      datos: String::new(),
+      estado: String::new(),
+    };
+
+    // Anterior a la activación del sellado y posterior
+    filas.push(sin_sellar(1));
+    filas.push(sin_sellar(15));
+
+    let informe = verificar(filas, cabezas);
+    assert_eq!(informe.sin_sellar, 1);
+    assert_eq!(
+      motivos(&informe),
+      vec![(Some(15), MotivoAlteracion::SinSellar)]
+    );
+  }
+}
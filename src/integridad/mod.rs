@@ -0,0 +1,35 @@
+//! Módulo que garantiza la inmutabilidad de los registros.
+//!
+//! Cada fila insertada en las tablas selladas (marcajes, incidencias
+//! y trazas) se encadena con la fila anterior de la misma clave
+//! mediante un HMAC con el secreto de la aplicación:
+//!
+//! - **eslabon**: Posición de la fila dentro de su cadena.
+//! - **hash**: HMAC del hash anterior, la posición y los campos
+//!   que no cambian tras la inserción.
+//! - **sello**: HMAC del hash y los campos que la aplicación puede
+//!   cambiar (por ejemplo la hora de fin de un marcaje). Se recalcula
+//!   en cada cambio realizado a través de la aplicación.
+//!
+//! La cabecera de cada cadena (último eslabón y hash) se guarda en
+//! la tabla `cadenas_hash`, que se bloquea en cada inserción para
+//! serializar las inserciones de una misma clave.
+//!
+//! El verificador recorre las cadenas e informa de las filas alteradas,
+//! eliminadas o insertadas fuera de la aplicación. Las filas anteriores
+//! a la activación del sellado no se pueden verificar.
+//!
+//! Si se cambia el secreto de la aplicación las cadenas existentes
+//! dejan de ser verificables.
+
+/// Módulo que gestiona el repositorio de las cadenas de hashes.
+mod repo;
+
+/// Módulo que gestiona el dominio de las cadenas de hashes.
+mod dominio;
+/// Módulo que gestiona el servicio de las cadenas de hashes.
+mod servicio;
+
+pub use dominio::*;
+pub use repo::*;
+pub use servicio::*;
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use sqlx::{AssertSqlSafe, MySql, QueryBuilder, Row};
+
+use crate::{
+  infra::{DBError, PoolConexion, Transaccion},
+  integridad::{CabezaCadena, FilaSellada, TablaSellada},
+};
+
+/// Implementación del repositorio de las cadenas de hashes.
+///
+/// Las consultas se componen con el nombre y las expresiones
+/// constantes de [`TablaSellada`], nunca con datos de usuario.
+pub struct IntegridadRepo {
+  pool: PoolConexion,
+}
+
+impl IntegridadRepo {
+  pub fn new(pool: PoolConexion) -> Self {
+    IntegridadRepo { pool }
+  }
+}
+
+impl IntegridadRepo {
+  /// Obtiene la clave, los datos y el estado de una fila.
+  pub(in crate::integridad) async fn fila(
+    &self,
+    trans: &mut Transaccion<'_>,
+    tabla: TablaSellada,
+    id: u32,
+  ) -> Result<FilaSellada, DBError> {
+    let query = format!(
+      "SELECT id, {} AS clave, eslabon, hash, sello,
+      {} AS datos, {} AS estado
+      FROM {} WHERE id = ?;",
+      tabla.clave(),
+      tabla.datos(),
+      tabla.estado(),
+      tabla.nombre()
+    );
+
+    let row = sqlx::query(AssertSqlSafe(query))
+      .bind(id)
+      .fetch_optional(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?
+      .ok_or_else(|| {
+        DBError::registro_vacio(format!(
+          "No existe la fila {} en {}",
+          id,
+          tabla.nombre()
+        ))
+      })?;
+
+    Ok(Self::fila_from_row(&row))
+  }
+
+  /// Bloquea la cabecera de una cadena hasta el final de la transacción.
+  ///
+  /// Si la cadena no existe se crea vacía.
+  pub(in crate::integridad) async fn bloquear_cabeza(
+    &self,
+    trans: &mut Transaccion<'_>,
+    tabla: TablaSellada,
+    clave: &str,
+  ) -> Result<CabezaCadena, DBError> {
+    sqlx::query(
+      "INSERT INTO cadenas_hash (tabla, clave, eslabones, hash)
+      VALUES (?, ?, 0, '')
+      ON DUPLICATE KEY UPDATE eslabones = eslabones;",
+    )
+    .bind(tabla.nombre())
+    .bind(clave)
+    .execute(&mut **trans.deref_mut())
+    .await
+    .map_err(DBError::from_sqlx)?;
+
+    let row = sqlx::query(
+      "SELECT eslabones, hash FROM cadenas_hash
+      WHERE tabla = ? AND clave = ? FOR UPDATE;",
+    )
+    .bind(tabla.nombre())
+    .bind(clave)
+    .fetch_one(&mut **trans.deref_mut())
+    .await
+    .map_err(DBError::from_sqlx)?;
+
+    Ok(CabezaCadena {
+      eslabones: row.get("eslabones"),
+      hash: row.get("hash"),
+    })
+  }
+
+  /// Guarda el eslabón de una fila y avanza la cabecera de su cadena.
+  pub(in crate::integridad) async fn encadenar(
+    &self,
+    trans: &mut Transaccion<'_>,
+    tabla: TablaSellada,
+    fila: &FilaSellada,
+  ) -> Result<(), DBError> {
+    let query = format!(
+      "UPDATE {} SET eslabon = ?, hash = ?, sello = ? WHERE id = ?;",
+      tabla.nombre()
+    );
+
+    sqlx::query(AssertSqlSafe(query))
+      .bind(fila.eslabon)
+      .bind(&fila.hash)
+      .bind(&fila.sello)
+      .bind(fila.id)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    sqlx::query(
+      "UPDATE cadenas_hash SET eslabones = ?, hash = ?
+      WHERE tabla = ? AND clave = ?;",
+    )
+    .bind(fila.eslabon)
+    .bind(&fila.hash)
+    .bind(tabla.nombre())
+    .bind(&fila.clave)
+    .execute(&mut **trans.deref_mut())
+    .await
+    .map_err(DBError::from_sqlx)?;
+
+    Ok(())
+  }
+
+  /// Actualiza el sello de una fila.
+  pub(in crate::integridad) async fn actualizar_sello(
+    &self,
+    trans: &mut Transaccion<'_>,
+    tabla: TablaSellada,
+    id: u32,
+    sello: &str,
+  ) -> Result<(), DBError> {
+    let query =
+      format!("UPDATE {} SET sello = ? WHERE id = ?;", tabla.nombre());
+
+    sqlx::query(AssertSqlSafe(query))
+      .bind(sello)
+      .bind(id)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(())
+  }
+
+  /// Obtiene un lote de filas de una tabla sellada.
+  ///
+  /// Las filas se ordenan por clave, eslabón e id, con las filas sin
+  /// sellar al final de su cadena. Se paginan por clave: el lote
+  /// empieza tras la fila indicada.
+  pub(in crate::integridad) async fn filas(
+    &self,
+    tabla: TablaSellada,
+    desde: Option<&FilaSellada>,
+    limite: u32,
+  ) -> Result<Vec<FilaSellada>, DBError> {
+    let mut qb = QueryBuilder::<MySql>::new(format!(
+      "SELECT id, clave, eslabon, hash, sello, datos, estado
+      FROM (
+        SELECT id, {} AS clave, eslabon, IFNULL(eslabon, {}) AS orden,
+          hash, sello, {} AS datos, {} AS estado
+        FROM {}
+      ) f",
+      tabla.clave(),
+      u32::MAX,
+      tabla.datos(),
+      tabla.estado(),
+      tabla.nombre()
+    ));
+
+    if let Some(fila) = desde {
+      qb.push(" WHERE (clave, orden, id) > (");
+      qb.push_bind(fila.clave.clone());
+      qb.push(", ");
+      qb.push_bind(fila.eslabon.unwrap_or(u32::MAX));
+      qb.push(", ");
+      qb.push_bind(fila.id);
+      qb.push(")");
+    }
+
+    qb.push(" ORDER BY clave, orden, id LIMIT ");
+    qb.push_bind(limite);
+
+    let rows = qb
+      .build()
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(rows.iter().map(Self::fila_from_row).collect())
+  }
+
+  /// Obtiene las cabeceras de las cadenas de una tabla.
+  pub(in crate::integridad) async fn cabezas(
+    &self,
+    tabla: TablaSellada,
+  ) -> Result<HashMap<String, CabezaCadena>, DBError> {
+    let rows = sqlx::query(
+      "SELECT clave, eslabones, hash FROM cadenas_hash WHERE tabla = ?;",
+    )
+    .bind(tabla.nombre())
+    .fetch_all(self.pool.conexion())
+    .await
+    .map_err(DBError::from_sqlx)?;
+
+    Ok(
+      rows
+        .iter()
+        .map(|row| {
+          (
+            row.get("clave"),
+            CabezaCadena {
+              eslabones: row.get("eslabones"),
+              hash: row.get("hash"),
+            },
+          )
+        })
+        .collect(),
+    )
+  }
+
+  fn fila_from_row(row: &sqlx::mysql::MySqlRow) -> FilaSellada {
+    FilaSellada {
+      id: row.get("id"),
+      clave: row.get("clave"),
+      eslabon: row.get("eslabon"),
+      hash: row.get("hash"),
+      sello: row.get("sello"),
+      datos: row.get("datos"),
+      estado: row.get("estado"),
+    }
+  }
+}
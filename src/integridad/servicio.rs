@@ -0,0 +1,141 @@
+use crate::{
+  config::ConfigTrabajo,
+  infra::{ServicioError, Transaccion},
+  integridad::{
+    InformeIntegridad, IntegridadRepo, TablaSellada, VerificacionTabla,
+    hash_eslabon, hash_sello,
+  },
+};
+
+/// Número de filas que se leen en cada lote al verificar las cadenas.
+const LOTE_VERIFICACION: u32 = 1000;
+
+pub struct IntegridadServicio {
+  cnfg: ConfigTrabajo,
+  repo: IntegridadRepo,
+}
+
+impl IntegridadServicio {
+  pub fn new(cnfg: ConfigTrabajo, repo: IntegridadRepo) -> Self {
+    IntegridadServicio { cnfg, repo }
+  }
+}
+
+impl IntegridadServicio {
+  /// Encadena una fila recién insertada con la última de su cadena.
+  ///
+  /// Se debe llamar dentro de la transacción que inserta la fila.
+  /// La cabecera de la cadena queda bloqueada hasta el commit.
+  pub async fn sellar(
+    &self,
+    trans: &mut Transaccion<'_>,
+    tabla: TablaSellada,
+    id: u32,
+  ) -> Result<(), ServicioError> {
+    let resultado = async {
+      let mut fila = self.repo.fila(trans, tabla, id).await?;
+      let cabeza = self.repo.bloquear_cabeza(trans, tabla, &fila.clave).await?;
+
+      let eslabon = cabeza.eslabones + 1;
+      let hash =
+        hash_eslabon(&self.cnfg.secreto, &cabeza.hash, eslabon, &fila.datos);
+
+      fila.sello = Some(hash_sello(&self.cnfg.secreto, &hash, &fila.estado));
+      fila.eslabon = Some(eslabon);
+      fila.hash = Some(hash);
+
+      self.repo.encadenar(trans, tabla, &fila).await
+    }
+    .await;
+
+    resultado.map_err(|err| {
+      tracing::error!(
+        tabla = tabla.nombre(),
+        id = id,
+        error = %err,
+        "Sellando fila"
+      );
+      ServicioError::from(err)
+    })
+  }
+
+  /// Recalcula el sello de una fila tras un cambio en su estado.
+  ///
+  /// Las filas anteriores a la activación del sellado no se sellan.
+  pub async fn resellar(
+    &self,
+    trans: &mut Transaccion<'_>,
+    tabla: TablaSellada,
+    id: u32,
+  ) -> Result<(), ServicioError> {
+    let resultado = async {
+      let fila = self.repo.fila(trans, tabla, id).await?;
+
+      match fila.hash {
+        Some(hash) => {
+          let sello = hash_sello(&self.cnfg.secreto, &hash, &fila.estado);
+          self.repo.actualizar_sello(trans, tabla, id, &sello).await
+        }
+        None => Ok(()),
+      }
+    }
+    .await;
+
+    resultado.map_err(|err| {
+      tracing::error!(
+        tabla = tabla.nombre(),
+        id = id,
+        error = %err,
+        "Resellando fila"
+      );
+      ServicioError::from(err)
+    })
+  }
+
+  /// Verifica las cadenas de todas las tablas selladas.
+  ///
+  /// Las filas se leen por lotes para no cargar las tablas
+  /// completas en memoria.
+  pub async fn verificar(&self) -> Result<InformeIntegridad, ServicioError> {
+    let mut informe = InformeIntegridad::default();
+
+    for tabla in TablaSellada::TODAS {
+      let error = |err| {
+        tracing::error!(
+          tabla = tabla.nombre(),
+          error = %err,
+          "Obteniendo filas para verificar la integridad"
+        );
+        ServicioError::from(err)
+      };
+
+      let cabezas = self.repo.cabezas(tabla).await.map_err(error)?;
+      let mut verificacion =
+        VerificacionTabla::new(&self.cnfg.secreto, tabla, cabezas);
+      let mut ultima = None;
+
+      loop {
+        let filas = self
+          .repo
+          .filas(tabla, ultima.as_ref(), LOTE_VERIFICACION)
+          .await
+          .map_err(error)?;
+
+        let completo = filas.len() < LOTE_VERIFICACION as usize;
+
+        for fila in &filas {
+          verificacion.verificar(&mut informe, fila);
+        }
+        ultima = filas.into_iter().last().or(ultima);
+
+        if completo {
+          break;
+        }
+      }
+
+      verificacion.terminar(&mut informe);
+    }
+
+    Ok(informe)
+  }
+}
@@ -13,6 +13,7 @@
 //! - Administración de usuarios y empleados.
 //! - Gestión de horarios múltiples del empleado.
 //! - Gestión de los calendarios de los empleados.
+//! - Gestión de los centros de trabajo y sus calendarios por defecto.
 //! - Marcaje de entrada y salida por el empleado.
 //! - Marcaje manual por el registrador en nombre del empleado.
 //! - Consulta de los registros de marcajes.
@@ -40,16 +41,23 @@ mod app;
 mod config;
 mod infra;
 
+mod ausencias;
+mod bolsa;
+mod centros;
+mod extras;
 mod horario;
 mod inc;
 mod informes;
+mod integridad;
 mod marcaje;
+mod sesiones;
 mod traza;
 mod usuarios;
 
 use config::*;
 use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions};
 
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::{env, path::Path};
@@ -130,9 +138,13 @@ async fn main() {
     direccion.as_str()
   );
 
-  axum::serve(listener, rutas(&config.servidor.app, app))
-    .await
-    .unwrap();
+  axum::serve(
+    listener,
+    rutas(&config.servidor.app, app)
+      .into_make_service_with_connect_info::<SocketAddr>(),
+  )
+  .await
+  .unwrap();
 }
 
 fn obtener_nivel_log(config: &Config) -> LevelFilter {
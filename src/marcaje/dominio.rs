@@ -1,6 +1,10 @@
-use chrono::{NaiveDate, NaiveTime};
+use chrono::{
+  DateTime, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+  TimeDelta, TimeZone, Utc,
+};
+use chrono_tz::Tz;
 
-use crate::horario::DescriptorHorario;
+use crate::horario::{DescriptorHorario, ReglasPausa};
 
 #[derive(Debug)]
 pub struct DescriptorMarcaje {
@@ -18,14 +22,677 @@ pub struct Marcaje {
   pub fecha: NaiveDate,
   pub hora_inicio: NaiveTime,
   pub hora_fin: Option<NaiveTime>,
+  /// Instante UTC de la entrada. Es `None` en los marcajes
+  /// anteriores a su registro que no se pudieron migrar
+  pub inicio_utc: Option<DateTime<Utc>>,
+  /// Instante UTC de la salida
+  pub fin_utc: Option<DateTime<Utc>>,
+  /// Tiempo de las pausas finalizadas del marcaje
+  pub pausas: TiempoPausas,
 }
 
 impl Marcaje {
+  /// Horas trabajadas descontando las pausas no retribuidas
+  /// según las reglas del horario.
+  ///
+  /// Ver [`duracion_real`].
   #[inline]
   pub fn horas_trabajadas(&self) -> Option<f64> {
+    let reglas = self.horario.as_ref().map(|h| h.pausas).unwrap_or_default();
+    let no_retribuidos = self.pausas.minutos_no_retribuidos(&reglas);
+
     self.hora_fin.map(|fin| {
-      let diferencia = fin - self.hora_inicio;
+      let diferencia =
+        duracion_real(self.hora_inicio, fin, self.inicio_utc, self.fin_utc);
       diferencia.num_milliseconds() as f64 / 3_600_000.0
+        - no_retribuidos as f64 / 60.0
     })
   }
+
+  /// Instantes UTC de la entrada y la salida según la zona horaria.
+  ///
+  /// Ver [`instante_utc`].
+  pub fn instantes_utc(
+    &self,
+    tz: &Tz,
+  ) -> (DateTime<Utc>, Option<DateTime<Utc>>) {
+    let inicio = instante_utc(tz, self.fecha.and_time(self.hora_inicio), None);
+    let fin = self
+      .hora_fin
+      .map(|fin| instante_fin_utc(tz, self.fecha, self.hora_inicio, fin));
+
+    (inicio, fin)
+  }
+}
+
+/// Convierte una fecha y hora local de la zona horaria en un instante UTC.
+///
+/// En la hora que se repite al retrasar el reloj se elige la primera
+/// ocurrencia posterior a `despues_de` o, si no se indica, la primera.
+/// En la hora que no existe al adelantar el reloj se aplica el desfase
+/// anterior al cambio.
+pub fn instante_utc(
+  tz: &Tz,
+  local: NaiveDateTime,
+  despues_de: Option<DateTime<Utc>>,
+) -> DateTime<Utc> {
+  match tz.from_local_datetime(&local) {
+    LocalResult::Single(fecha) => fecha.to_utc(),
+    LocalResult::Ambiguous(primera, segunda) => {
+      let (primera, segunda) = (primera.to_utc(), segunda.to_utc());
+      match despues_de {
+        Some(despues_de) if primera <= despues_de => segunda,
+        _ => primera,
+      }
+    }
+    LocalResult::None => {
+      let desfase = tz
+        .offset_from_utc_datetime(&(local - TimeDelta::days(1)))
+        .fix()
+        .local_minus_utc();
+      Utc.from_utc_datetime(&(local - TimeDelta::seconds(desfase as i64)))
+    }
+  }
+}
+
+/// Instante UTC de una hora de salida de un marcaje.
+///
+/// La hora se sitúa en la fecha del marcaje o en la siguiente
+/// si cruza la medianoche (ver [`fecha_hora_marcaje`]) y, en la
+/// hora que se repite, se elige la primera ocurrencia posterior
+/// a la entrada.
+pub fn instante_fin_utc(
+  tz: &Tz,
+  fecha: NaiveDate,
+  inicio: NaiveTime,
+  fin: NaiveTime,
+) -> DateTime<Utc> {
+  let inicio_utc = instante_utc(tz, fecha.and_time(inicio), None);
+  instante_utc(tz, fecha_hora_marcaje(fecha, inicio, fin), Some(inicio_utc))
+}
+
+/// Tiempo real transcurrido en un marcaje finalizado.
+///
+/// Si se conocen los instantes UTC se usan, ya que tienen en cuenta
+/// los cambios de hora. Si no, se usa [`duracion_marcaje`].
+pub fn duracion_real(
+  inicio: NaiveTime,
+  fin: NaiveTime,
+  inicio_utc: Option<DateTime<Utc>>,
+  fin_utc: Option<DateTime<Utc>>,
+) -> TimeDelta {
+  match (inicio_utc, fin_utc) {
+    (Some(inicio_utc), Some(fin_utc)) => fin_utc - inicio_utc,
+    _ => duracion_marcaje(inicio, fin),
+  }
+}
+
+/// Duración entre la hora de inicio y una hora de un marcaje.
+///
+/// Si la hora es anterior a la de inicio el marcaje cruza la
+/// medianoche y la hora pertenece al día siguiente. Un marcaje
+/// nunca dura 24 horas o más.
+pub fn duracion_marcaje(inicio: NaiveTime, hora: NaiveTime) -> TimeDelta {
+  let diferencia = hora - inicio;
+  if hora < inicio {
+    diferencia + TimeDelta::days(1)
+  } else {
+    diferencia
+  }
+}
+
+/// Fecha y hora de una hora de un marcaje que empieza en la fecha
+/// y hora de inicio dadas.
+///
+/// Ver [`duracion_marcaje`].
+pub fn fecha_hora_marcaje(
+  fecha: NaiveDate,
+  inicio: NaiveTime,
+  hora: NaiveTime,
+) -> NaiveDateTime {
+  fecha.and_time(inicio) + duracion_marcaje(inicio, hora)
+}
+
+/// Marcaje sin hora de salida candidato al cierre automático.
+#[derive(Debug)]
+pub struct MarcajeOlvidado {
+  pub id: u32,
+  pub usuario: u32,
+  pub fecha: NaiveDate,
+  pub hora_inicio: NaiveTime,
+  pub horario: DescriptorHorario,
+}
+
+impl MarcajeOlvidado {
+  /// Hora de salida prevista por el horario del marcaje.
+  ///
+  /// Es el fin de la última franja o, si el horario no tiene
+  /// franjas, la hora de inicio más los minutos a trabajar, que
+  /// puede caer al día siguiente. Devuelve `None` si la salida de
+  /// la franja no es posterior a la entrada o el horario no tiene
+  /// minutos.
+  pub fn hora_salida(&self) -> Option<NaiveTime> {
+    match self.horario.franjas.last() {
+      Some(franja) => {
+        (franja.hora_fin > self.hora_inicio).then_some(franja.hora_fin)
+      }
+      None => (self.horario.minutos > 0
+        && (self.horario.minutos as i64) < TimeDelta::days(1).num_minutes())
+      .then(|| {
+        self.hora_inicio + TimeDelta::minutes(self.horario.minutos as i64)
+      }),
+    }
+  }
+
+  /// Indica si la jornada del marcaje ha terminado en el momento dado.
+  ///
+  /// Sin hora de salida prevista la jornada termina con el día.
+  pub fn jornada_terminada(&self, ahora: NaiveDateTime) -> bool {
+    match self.hora_salida() {
+      Some(salida) => {
+        fecha_hora_marcaje(self.fecha, self.hora_inicio, salida) <= ahora
+      }
+      None => self.fecha < ahora.date(),
+    }
+  }
+}
+
+/// Tipo de pausa dentro de un marcaje.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TipoPausa {
+  Comida = 1,
+  Personal = 2,
+}
+
+impl TipoPausa {
+  pub fn from_u8(value: u8) -> Option<Self> {
+    match value {
+      1 => Some(TipoPausa::Comida),
+      2 => Some(TipoPausa::Personal),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      TipoPausa::Comida => "Comida",
+      TipoPausa::Personal => "Personal",
+    }
+  }
+}
+
+impl From<u8> for TipoPausa {
+  fn from(value: u8) -> Self {
+    TipoPausa::from_u8(value).expect("Valor de tipo de pausa no válido")
+  }
+}
+
+/// Pausa registrada dentro de un marcaje abierto.
+#[derive(Debug, Clone)]
+pub struct Pausa {
+  pub id: u32,
+  pub marcaje: u32,
+  pub tipo: TipoPausa,
+  pub hora_inicio: NaiveTime,
+  pub hora_fin: Option<NaiveTime>,
+}
+
+impl Pausa {
+  /// Horas e instantes UTC de la pausa para el historial.
+  ///
+  /// Las horas se sitúan en el marcaje que empieza en la fecha y
+  /// hora de inicio dadas, por lo que pueden cruzar la medianoche
+  /// (ver [`instante_fin_utc`]).
+  pub fn version(
+    &self,
+    tz: &Tz,
+    fecha: NaiveDate,
+    inicio_marcaje: NaiveTime,
+  ) -> VersionMarcaje {
+    let instante = |hora| instante_fin_utc(tz, fecha, inicio_marcaje, hora);
+
+    VersionMarcaje {
+      hora_inicio: Some(self.hora_inicio),
+      hora_fin: self.hora_fin,
+      inicio_utc: Some(instante(self.hora_inicio)),
+      fin_utc: self.hora_fin.map(instante),
+    }
+  }
+}
+
+/// Minutos de pausas finalizadas por tipo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TiempoPausas {
+  pub comida: u32,
+  pub personal: u32,
+}
+
+impl TiempoPausas {
+  /// Minutos de pausa que superan los retribuidos por las reglas.
+  pub fn minutos_no_retribuidos(&self, reglas: &ReglasPausa) -> u32 {
+    self.comida.saturating_sub(reglas.comida_retribuida as u32)
+      + self
+        .personal
+        .saturating_sub(reglas.personal_retribuida as u32)
+  }
+}
+
+/// Operación que modifica un marcaje.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OperacionMarcaje {
+  Creacion = 1,
+  /// Registro de la hora de fin
+  Finalizacion = 2,
+  /// Sustitución por un marcaje con las horas corregidas
+  Correccion = 3,
+  Eliminacion = 4,
+  /// Inicio de una pausa del marcaje
+  InicioPausa = 5,
+  /// Fin de una pausa del marcaje
+  FinPausa = 6,
+}
+
+impl From<u8> for OperacionMarcaje {
+  fn from(value: u8) -> Self {
+    match value {
+      1 => OperacionMarcaje::Creacion,
+      2 => OperacionMarcaje::Finalizacion,
+      3 => OperacionMarcaje::Correccion,
+      4 => OperacionMarcaje::Eliminacion,
+      5 => OperacionMarcaje::InicioPausa,
+      6 => OperacionMarcaje::FinPausa,
+      _ => panic!("Valor de operación de marcaje no válido"),
+    }
+  }
+}
+
+/// Autor, motivo y fecha de un cambio en un marcaje.
+#[derive(Debug)]
+pub struct CambioMarcaje {
+  pub autor: Option<u32>,
+  pub motivo: Option<String>,
+  pub fecha: NaiveDateTime,
+}
+
+impl CambioMarcaje {
+  /// Crea un cambio con la fecha actual en la zona horaria indicada.
+  pub fn new(autor: Option<u32>, motivo: Option<String>, tz: &Tz) -> Self {
+    CambioMarcaje {
+      autor,
+      motivo,
+      fecha: Utc::now().with_timezone(tz).naive_local(),
+    }
+  }
+}
+
+/// Horas e instantes UTC de un marcaje o de una pausa antes
+/// o después de un cambio.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VersionMarcaje {
+  pub hora_inicio: Option<NaiveTime>,
+  pub hora_fin: Option<NaiveTime>,
+  pub inicio_utc: Option<DateTime<Utc>>,
+  pub fin_utc: Option<DateTime<Utc>>,
+}
+
+impl VersionMarcaje {
+  /// Versión de un marcaje recién creado con sus instantes UTC.
+  pub fn creacion(
+    reg: &Marcaje,
+    instantes: (DateTime<Utc>, Option<DateTime<Utc>>),
+  ) -> Self {
+    VersionMarcaje {
+      hora_inicio: Some(reg.hora_inicio),
+      hora_fin: reg.hora_fin,
+      inicio_utc: Some(instantes.0),
+      fin_utc: instantes.1,
+    }
+  }
+
+  /// Versión tras registrar la hora de fin. El inicio no cambia.
+  pub fn finalizada(
+    &self,
+    hora_fin: NaiveTime,
+    fin_utc: DateTime<Utc>,
+  ) -> Self {
+    VersionMarcaje {
+      hora_fin: Some(hora_fin),
+      fin_utc: Some(fin_utc),
+      ..*self
+    }
+  }
+}
+
+/// Versión de un marcaje con los valores anteriores y posteriores
+/// a un cambio.
+///
+/// En los cambios de las pausas los valores son los de la pausa.
+#[derive(Debug)]
+pub struct HistorialMarcaje {
+  pub id: u32,
+  pub marcaje: u32,
+  pub operacion: OperacionMarcaje,
+  pub fecha: NaiveDateTime,
+  pub autor: Option<u32>,
+  pub motivo: Option<String>,
+  pub anterior: VersionMarcaje,
+  pub nuevo: VersionMarcaje,
+  /// Marcaje que sustituye a este en una corrección
+  pub marcaje_nuevo: Option<u32>,
+  /// Pausa que se inicia o finaliza
+  pub pausa: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::horario::{Dia, FranjaHoraria};
+
+  fn hora(h: u32, m: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(h, m, 0).unwrap()
+  }
+
+  fn olvidado(
+    inicio: NaiveTime,
+    minutos: u16,
+    franjas: Vec<FranjaHoraria>,
+  ) -> MarcajeOlvidado {
+    MarcajeOlvidado {
+      id: 1,
+      usuario: 1,
+      fecha: NaiveDate::from_ymd_opt(2025, 3, 10).unwrap(),
+      hora_inicio: inicio,
+      horario: DescriptorHorario {
+        id: 1,
+        dia: Dia::Lunes,
+        minutos,
+        franjas,
+        pausas: ReglasPausa::default(),
+      },
+    }
+  }
+
+  #[test]
+  fn test_hora_salida_marcaje_olvidado() {
+    let franjas = vec![
+      FranjaHoraria {
+        hora_inicio: hora(8, 0),
+        hora_fin: hora(14, 0),
+      },
+      FranjaHoraria {
+        hora_inicio: hora(15, 0),
+        hora_fin: hora(17, 30),
+      },
+    ];
+
+    assert_eq!(
+      olvidado(hora(8, 5), 0, franjas.clone()).hora_salida(),
+      Some(hora(17, 30))
+    );
+    // Entrada posterior al fin de la última franja
+    assert_eq!(olvidado(hora(18, 0), 0, franjas).hora_salida(), None);
+    // Sin franjas se suman los minutos a la entrada
+    assert_eq!(
+      olvidado(hora(9, 0), 450, vec![]).hora_salida(),
+      Some(hora(16, 30))
+    );
+    // Turno de noche: la salida cae al día siguiente
+    assert_eq!(
+      olvidado(hora(20, 0), 300, vec![]).hora_salida(),
+      Some(hora(1, 0))
+    );
+    assert_eq!(olvidado(hora(9, 0), 0, vec![]).hora_salida(), None);
+  }
+
+  #[test]
+  fn test_jornada_terminada_marcaje_olvidado() {
+    let fecha = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+    let marcaje = olvidado(hora(9, 0), 480, vec![]);
+
+    assert!(!marcaje.jornada_terminada(fecha.and_time(hora(16, 59))));
+    assert!(marcaje.jornada_terminada(fecha.and_time(hora(17, 0))));
+
+    let noche = olvidado(hora(22, 0), 480, vec![]);
+    let siguiente = fecha.succ_opt().unwrap();
+
+    assert!(!noche.jornada_terminada(fecha.and_time(hora(23, 59))));
+    assert!(!noche.jornada_terminada(siguiente.and_time(hora(5, 59))));
+    assert!(noche.jornada_terminada(siguiente.and_time(hora(6, 0))));
+
+    let sin_salida = olvidado(hora(9, 0), 0, vec![]);
+
+    assert!(!sin_salida.jornada_terminada(fecha.and_time(hora(23, 59))));
+    assert!(sin_salida.jornada_terminada(siguiente.and_time(hora(0, 0))));
+  }
+
+  #[test]
+  fn test_duracion_marcaje_cruza_medianoche() {
+    let fecha = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+
+    assert_eq!(
+      duracion_marcaje(hora(8, 0), hora(15, 30)),
+      TimeDelta::minutes(450)
+    );
+    assert_eq!(
+      duracion_marcaje(hora(22, 0), hora(6, 0)),
+      TimeDelta::hours(8)
+    );
+    assert_eq!(
+      fecha_hora_marcaje(fecha, hora(22, 0), hora(1, 15)),
+      fecha.succ_opt().unwrap().and_time(hora(1, 15))
+    );
+    assert_eq!(
+      fecha_hora_marcaje(fecha, hora(22, 0), hora(23, 0)),
+      fecha.and_time(hora(23, 0))
+    );
+
+    let marcaje = Marcaje {
+      id: 1,
+      usuario: 1,
+      usuario_reg: None,
+      horario: None,
+      fecha,
+      hora_inicio: hora(22, 0),
+      hora_fin: Some(hora(6, 30)),
+      inicio_utc: None,
+      fin_utc: None,
+      pausas: TiempoPausas::default(),
+    };
+
+    assert_eq!(marcaje.horas_trabajadas(), Some(8.5));
+  }
+
+  fn marcaje_madrid(
+    fecha: NaiveDate,
+    inicio: NaiveTime,
+    fin: NaiveTime,
+  ) -> Marcaje {
+    let mut marcaje = Marcaje {
+      id: 1,
+      usuario: 1,
+      usuario_reg: None,
+      horario: None,
+      fecha,
+      hora_inicio: inicio,
+      hora_fin: Some(fin),
+      inicio_utc: None,
+      fin_utc: None,
+      pausas: TiempoPausas::default(),
+    };
+
+    (marcaje.inicio_utc, marcaje.fin_utc) = {
+      let (inicio, fin) = marcaje.instantes_utc(&chrono_tz::Europe::Madrid);
+      (Some(inicio), fin)
+    };
+
+    marcaje
+  }
+
+  fn utc(fecha: NaiveDate, h: u32, m: u32) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&fecha.and_time(hora(h, m)))
+  }
+
+  #[test]
+  fn test_horas_trabajadas_cambio_hora_octubre() {
+    // 26/10/2025: a las 03:00 CEST se vuelve a las 02:00 CET
+    let fecha = NaiveDate::from_ymd_opt(2025, 10, 26).unwrap();
+    let anterior = fecha.pred_opt().unwrap();
+
+    // Turno de noche que atraviesa la hora repetida
+    let noche = marcaje_madrid(anterior, hora(22, 0), hora(6, 0));
+    assert_eq!(noche.inicio_utc, Some(utc(anterior, 20, 0)));
+    assert_eq!(noche.fin_utc, Some(utc(fecha, 5, 0)));
+    assert_eq!(noche.horas_trabajadas(), Some(9.0));
+
+    // Salida en la hora repetida: primera ocurrencia tras la entrada
+    let repetida = marcaje_madrid(fecha, hora(1, 30), hora(2, 30));
+    assert_eq!(repetida.fin_utc, Some(utc(fecha, 0, 30)));
+    assert_eq!(repetida.horas_trabajadas(), Some(1.0));
+
+    // Entrada y salida en la hora repetida
+    let dentro = marcaje_madrid(fecha, hora(2, 10), hora(2, 50));
+    assert_eq!(dentro.inicio_utc, Some(utc(fecha, 0, 10)));
+    assert_eq!(dentro.horas_trabajadas(), Some(40.0 / 60.0));
+  }
+
+  #[test]
+  fn test_horas_trabajadas_cambio_hora_marzo() {
+    // 30/03/2025: a las 02:00 CET se adelanta a las 03:00 CEST
+    let fecha = NaiveDate::from_ymd_opt(2025, 3, 30).unwrap();
+
+    let madrugada = marcaje_madrid(fecha, hora(0, 0), hora(6, 0));
+    assert_eq!(
+      madrugada.inicio_utc,
+      Some(utc(fecha, 23, 0) - TimeDelta::days(1))
+    );
+    assert_eq!(madrugada.fin_utc, Some(utc(fecha, 4, 0)));
+    assert_eq!(madrugada.horas_trabajadas(), Some(5.0));
+
+    // La hora 02:30 no existe: se aplica el desfase de invierno
+    let hueco = marcaje_madrid(fecha, hora(2, 30), hora(4, 0));
+    assert_eq!(hueco.inicio_utc, Some(utc(fecha, 1, 30)));
+    assert_eq!(hueco.horas_trabajadas(), Some(0.5));
+  }
+
+  #[test]
+  fn test_instantes_utc_zona_usuario() {
+    // Las mismas horas locales en Canarias van una hora por detrás
+    let fecha = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+    let mut marcaje = marcaje_madrid(fecha, hora(8, 0), hora(15, 0));
+    let (inicio, fin) = marcaje.instantes_utc(&chrono_tz::Atlantic::Canary);
+
+    assert_eq!(marcaje.inicio_utc, Some(utc(fecha, 7, 0)));
+    assert_eq!(inicio, utc(fecha, 8, 0));
+    assert_eq!(fin, Some(utc(fecha, 15, 0)));
+
+    // Las horas trabajadas no dependen de la zona
+    (marcaje.inicio_utc, marcaje.fin_utc) = (Some(inicio), fin);
+    assert_eq!(marcaje.horas_trabajadas(), Some(7.0));
+  }
+
+  #[test]
+  fn test_horas_trabajadas_sin_instantes() {
+    // Los marcajes sin instantes UTC usan las horas locales
+    let fecha = NaiveDate::from_ymd_opt(2025, 10, 26).unwrap();
+
+    assert_eq!(
+      duracion_real(hora(0, 0), hora(6, 0), None, None),
+      TimeDelta::hours(6)
+    );
+    assert_eq!(
+      duracion_real(
+        hora(0, 0),
+        hora(6, 0),
+        Some(utc(fecha, 0, 0) - TimeDelta::hours(2)),
+        Some(utc(fecha, 5, 0))
+      ),
+      TimeDelta::hours(7)
+    );
+  }
+
+  #[test]
+  fn test_version_pausa_cruza_medianoche() {
+    let fecha = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+    let siguiente = fecha.succ_opt().unwrap();
+    let mut pausa = Pausa {
+      id: 1,
+      marcaje: 1,
+      tipo: TipoPausa::Comida,
+      hora_inicio: hora(23, 30),
+      hora_fin: None,
+    };
+
+    // Pausa abierta de un turno de noche iniciado a las 22:00 en Madrid
+    let abierta = pausa.version(&chrono_tz::Europe::Madrid, fecha, hora(22, 0));
+    assert_eq!(abierta.hora_inicio, Some(hora(23, 30)));
+    assert_eq!(abierta.inicio_utc, Some(utc(fecha, 22, 30)));
+    assert_eq!(abierta.hora_fin, None);
+    assert_eq!(abierta.fin_utc, None);
+
+    // El fin cae al día siguiente del marcaje
+    pausa.hora_fin = Some(hora(0, 15));
+    let cerrada = pausa.version(&chrono_tz::Europe::Madrid, fecha, hora(22, 0));
+    assert_eq!(cerrada.inicio_utc, abierta.inicio_utc);
+    assert_eq!(cerrada.hora_fin, Some(hora(0, 15)));
+    assert_eq!(cerrada.fin_utc, Some(utc(fecha, 23, 15)));
+
+    // Pausa completamente tras la medianoche
+    pausa.hora_inicio = hora(1, 0);
+    pausa.hora_fin = Some(hora(1, 30));
+    let madrugada =
+      pausa.version(&chrono_tz::Europe::Madrid, fecha, hora(22, 0));
+    assert_eq!(madrugada.inicio_utc, Some(utc(siguiente, 0, 0)));
+    assert_eq!(madrugada.fin_utc, Some(utc(siguiente, 0, 30)));
+  }
+
+  #[test]
+  fn test_version_marcaje_creacion_y_finalizacion() {
+    let fecha = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+    let mut marcaje = marcaje_madrid(fecha, hora(22, 0), hora(6, 0));
+    marcaje.hora_fin = None;
+
+    // Creación de un marcaje abierto: sin fin ni instante de fin
+    let creado =
+      VersionMarcaje::creacion(&marcaje, (marcaje.inicio_utc.unwrap(), None));
+    assert_eq!(creado.hora_inicio, Some(hora(22, 0)));
+    assert_eq!(creado.inicio_utc, Some(utc(fecha, 21, 0)));
+    assert_eq!(creado.hora_fin, None);
+    assert_eq!(creado.fin_utc, None);
+
+    // La finalización conserva el inicio y añade el fin del día siguiente
+    let fin_utc = utc(fecha.succ_opt().unwrap(), 5, 0);
+    let finalizado = creado.finalizada(hora(6, 0), fin_utc);
+    assert_eq!(finalizado.hora_inicio, creado.hora_inicio);
+    assert_eq!(finalizado.inicio_utc, creado.inicio_utc);
+    assert_eq!(finalizado.hora_fin, Some(hora(6, 0)));
+    assert_eq!(finalizado.fin_utc, Some(fin_utc));
+
+    // La eliminación deja todos los valores nuevos a nulo
+    assert_eq!(VersionMarcaje::default().hora_inicio, None);
+    assert_ne!(finalizado, VersionMarcaje::default());
+  }
+
+  #[test]
+  fn test_cambio_marcaje_fecha_en_zona() {
+    let cambio = CambioMarcaje::new(
+      Some(2),
+      Some("Olvido".to_string()),
+      &chrono_tz::Atlantic::Canary,
+    );
+    let canarias = Utc::now()
+      .with_timezone(&chrono_tz::Atlantic::Canary)
+      .naive_local();
+
+    assert_eq!(cambio.autor, Some(2));
+    assert!((canarias - cambio.fecha).abs() < TimeDelta::seconds(5));
+  }
+
+  #[test]
+  fn test_operacion_marcaje_pausas() {
+    assert_eq!(OperacionMarcaje::from(5), OperacionMarcaje::InicioPausa);
+    assert_eq!(OperacionMarcaje::from(6), OperacionMarcaje::FinPausa);
+    assert_eq!(OperacionMarcaje::FinPausa as u8, 6);
+  }
 }
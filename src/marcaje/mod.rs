@@ -23,6 +23,29 @@
 //! a nulo se pueden tener en cuenta para evaluar registros
 //! de cara a el empleado.
 //!
+//! Cada cambio en un marcaje (creación, finalización, corrección
+//! y eliminación) se anota en su historial dentro de la misma
+//! transacción, con las horas anteriores y posteriores, el autor
+//! y el motivo.
+//!
+//! Un marcaje cuya hora de fin es anterior a la de inicio cruza
+//! la medianoche y termina al día siguiente (turnos de noche).
+//! Pertenece a la fecha en que empieza y nunca dura 24 horas o más.
+//! Para finalizarlo se busca el marcaje abierto en la fecha y,
+//! si no existe, en el día anterior.
+//!
+//! Además de la fecha y las horas locales, cada marcaje guarda
+//! los instantes UTC de entrada y salida. Las horas trabajadas se
+//! calculan con ellos, por lo que un turno que atraviesa un cambio
+//! de hora cuenta el tiempo real transcurrido. En la hora repetida
+//! de octubre se toma la primera ocurrencia posterior a la entrada.
+//!
+//! Mientras un marcaje está abierto se pueden registrar pausas
+//! (comida o personal), de una en una y sin solaparse. Al finalizar
+//! el marcaje se cierra la pausa abierta. Las reglas del horario
+//! indican los minutos de cada tipo de pausa que se retribuyen al
+//! día; el resto se descuenta de las horas trabajadas.
+//!
 //! Si el marcaje se realiza como rol registrador, el marcaje
 //! se anota, no solo para el usuario para el que se realiza
 //! el marcaje, si no para el registrador.
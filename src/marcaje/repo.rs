@@ -1,17 +1,123 @@
-use chrono::{NaiveDate, NaiveTime};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 
 use sqlx::{QueryBuilder, Row};
 
 use crate::{
-  horario::DescriptorHorario,
+  horario::{
+    DescriptorHorario,
+    repo::{franjas_from_row, reglas_pausa_from_row},
+  },
   infra::{
     DBError, DominioWithCacheUsuario, PoolConexion, ShortDateTimeFormat,
     Transaccion,
   },
-  marcaje::{DescriptorMarcaje, Marcaje},
+  marcaje::{
+    CambioMarcaje, DescriptorMarcaje, HistorialMarcaje, Marcaje,
+    MarcajeOlvidado, OperacionMarcaje, Pausa, TiempoPausas, VersionMarcaje,
+  },
   usuarios::DescriptorUsuario,
 };
 
+/// Segundos entre la hora de inicio y de fin de la fila con el alias
+/// dado de marcajes o pausas.
+///
+/// Si la hora de fin es anterior a la de inicio la fila cruza la
+/// medianoche y termina al día siguiente.
+/// Ver [`crate::marcaje::duracion_marcaje`].
+#[macro_export]
+macro_rules! segundos_marcaje {
+  ($alias:literal) => {
+    concat!(
+      "MOD(TIME_TO_SEC(",
+      $alias,
+      ".hora_fin) - TIME_TO_SEC(",
+      $alias,
+      ".hora_inicio) + 86400, 86400)"
+    )
+  };
+}
+
+/// Segundos reales trabajados en el marcaje con el alias dado.
+///
+/// Usa los instantes UTC cuando existen, de modo que los cambios de
+/// hora se tienen en cuenta; si no, se calcula con las horas locales.
+/// Ver [`crate::marcaje::duracion_real`].
+#[macro_export]
+macro_rules! segundos_reales_marcaje {
+  ($alias:literal) => {
+    concat!(
+      "COALESCE(TIMESTAMPDIFF(SECOND, ",
+      $alias,
+      ".inicio_utc, ",
+      $alias,
+      ".fin_utc), ",
+      $crate::segundos_marcaje!($alias),
+      ")"
+    )
+  };
+}
+
+/// Fecha y hora de inicio del marcaje con el alias dado.
+macro_rules! inicio_marcaje {
+  ($alias:literal) => {
+    concat!("TIMESTAMP(", $alias, ".fecha, ", $alias, ".hora_inicio)")
+  };
+}
+
+/// Instante UTC de inicio del marcaje con el alias dado.
+///
+/// Los marcajes sin instante UTC usan la fecha y hora local.
+macro_rules! inicio_utc_marcaje {
+  ($alias:literal) => {
+    concat!(
+      "COALESCE(",
+      $alias,
+      ".inicio_utc, ",
+      inicio_marcaje!($alias),
+      ")"
+    )
+  };
+}
+
+/// Fecha y hora de fin del marcaje con el alias dado.
+///
+/// Es nula si el marcaje no tiene hora de fin.
+macro_rules! fin_marcaje {
+  ($alias:literal) => {
+    concat!(
+      "(",
+      inicio_marcaje!($alias),
+      " + INTERVAL ",
+      $crate::segundos_marcaje!($alias),
+      " SECOND)"
+    )
+  };
+}
+
+/// Tabla derivada con los minutos de pausas finalizadas por marcaje
+/// en las columnas `comida` y `personal`.
+///
+/// Se une a los marcajes con `LEFT JOIN ... p ON p.marcaje = <id>`.
+#[macro_export]
+macro_rules! tiempo_pausas {
+  () => {
+    concat!(
+      "(SELECT pa.marcaje,
+        SUM(CASE WHEN pa.tipo = 1
+          THEN ",
+      $crate::segundos_marcaje!("pa"),
+      " DIV 60 ELSE 0 END) AS comida,
+        SUM(CASE WHEN pa.tipo = 2
+          THEN ",
+      $crate::segundos_marcaje!("pa"),
+      " DIV 60 ELSE 0 END) AS personal
+      FROM pausas pa
+      WHERE pa.hora_fin IS NOT NULL
+      GROUP BY pa.marcaje)"
+    )
+  };
+}
+
 /// Implementación del repositorio de marcajes.
 pub struct MarcajeRepo {
   pool: PoolConexion,
@@ -21,56 +127,82 @@ impl MarcajeRepo {
   pub fn new(pool: PoolConexion) -> Self {
     MarcajeRepo { pool }
   }
+
+  pub(in crate::marcaje) fn conexion(&self) -> &PoolConexion {
+    &self.pool
+  }
 }
 
 impl MarcajeRepo {
   /// Agrega un nuevo marcaje a la base de datos.
   ///
+  /// Se guardan los instantes UTC de la entrada y la salida del marcaje.
+  /// La creación se anota en el historial del marcaje.
+  ///
   /// Devuelve el ID del marcaje creado.
   pub(in crate::marcaje) async fn agregar(
     &self,
-    tr: Option<&mut Transaccion<'_>>,
+    trans: &mut Transaccion<'_>,
     reg: &Marcaje,
     horario: u32,
+    instantes: (DateTime<Utc>, Option<DateTime<Utc>>),
+    cambio: &CambioMarcaje,
   ) -> Result<u32, DBError> {
     const QUERY: &str = "INSERT INTO marcajes
-      (usuario, fecha, horario, hora_inicio, hora_fin, usuario_registrador)
-      VALUES (?, ?, ?, ?, ?, ?)";
+      (usuario, fecha, horario, hora_inicio, hora_fin, usuario_registrador,
+       inicio_utc, fin_utc)
+      VALUES (?, ?, ?, ?, ?, ?, ?, ?)";
 
-    let query = sqlx::query(QUERY)
+    let result = sqlx::query(QUERY)
       .bind(reg.usuario)
       .bind(reg.fecha)
       .bind(horario)
       .bind(reg.hora_inicio)
       .bind(reg.hora_fin)
-      .bind(reg.usuario_reg);
+      .bind(reg.usuario_reg)
+      .bind(instantes.0)
+      .bind(instantes.1)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
 
-    let result = if let Some(tr) = tr {
-      query
-        .execute(&mut **tr.deref_mut())
-        .await
-        .map_err(DBError::from_sqlx)?
-    } else {
-      query
-        .execute(self.pool.conexion())
-        .await
-        .map_err(DBError::from_sqlx)?
-    };
+    let id = result.last_insert_id() as u32;
+
+    let nuevo = VersionMarcaje::creacion(reg, instantes);
 
-    Ok(result.last_insert_id() as u32)
+    self
+      .agregar_historial(
+        trans,
+        id,
+        OperacionMarcaje::Creacion,
+        cambio,
+        (&VersionMarcaje::default(), &nuevo),
+        None,
+        None,
+      )
+      .await?;
+
+    Ok(id)
   }
 
   /// Actualiza modificado_por
   ///
+  /// La corrección se anota en el historial del marcaje con
+  /// las horas del marcaje original y las del que lo sustituye.
+  ///
   /// Devuelve True si se actualizo
   pub(in crate::marcaje) async fn actualizar_modificado_por(
     &self,
     trans: &mut Transaccion<'_>,
     id: u32,
     modificar_por: u32,
+    cambio: &CambioMarcaje,
   ) -> Result<bool, DBError> {
     const QUERY: &str = "UPDATE marcajes SET modificado_por = ? WHERE id = ?";
 
+    let anterior = self.version_marcaje(trans, id).await?;
+    let nuevo = self.version_marcaje(trans, modificar_por).await?;
+
     let result = sqlx::query(QUERY)
       .bind(modificar_por)
       .bind(id)
@@ -78,47 +210,434 @@ impl MarcajeRepo {
       .await
       .map_err(DBError::from_sqlx)?;
 
-    Ok(result.rows_affected() > 0)
+    if result.rows_affected() == 0 {
+      return Ok(false);
+    }
+
+    self
+      .agregar_historial(
+        trans,
+        id,
+        OperacionMarcaje::Correccion,
+        cambio,
+        (&anterior, &nuevo),
+        Some(modificar_por),
+        None,
+      )
+      .await?;
+
+    Ok(true)
   }
 
   /// Marca un marcaje como eliminado
   ///
+  /// La eliminación se anota en el historial del marcaje.
+  ///
   /// Devuelve True si se actualizo
   pub(in crate::marcaje) async fn marcar_marcaje_eliminado(
     &self,
     trans: &mut Transaccion<'_>,
     id: u32,
+    cambio: &CambioMarcaje,
   ) -> Result<bool, DBError> {
     const QUERY: &str = "UPDATE marcajes SET eliminado = TRUE WHERE id = ?";
 
+    let anterior = self.version_marcaje(trans, id).await?;
+
     let result = sqlx::query(QUERY)
       .bind(id)
       .execute(&mut **trans.deref_mut())
       .await
       .map_err(DBError::from_sqlx)?;
 
-    Ok(result.rows_affected() > 0)
+    if result.rows_affected() == 0 {
+      return Ok(false);
+    }
+
+    self
+      .agregar_historial(
+        trans,
+        id,
+        OperacionMarcaje::Eliminacion,
+        cambio,
+        (&anterior, &VersionMarcaje::default()),
+        None,
+        None,
+      )
+      .await?;
+
+    Ok(true)
   }
 
+  /// Registra la hora de fin de un marcaje y su instante UTC
+  ///
+  /// Solo se actualiza si el marcaje sigue abierto. La finalización
+  /// se anota en el historial del marcaje.
+  ///
+  /// Devuelve True si se actualizo
   pub(in crate::marcaje) async fn actualizar_hora_fin(
     &self,
+    trans: &mut Transaccion<'_>,
     id: u32,
     hora_fin: NaiveTime,
+    fin_utc: DateTime<Utc>,
+    cambio: &CambioMarcaje,
   ) -> Result<bool, DBError> {
-    const QUERY: &str = "UPDATE marcajes SET hora_fin = ? WHERE id = ?";
+    const QUERY: &str = "UPDATE marcajes SET hora_fin = ?, fin_utc = ?
+      WHERE id = ? AND hora_fin IS NULL";
+
+    let anterior = self.version_marcaje(trans, id).await?;
 
     let result = sqlx::query(QUERY)
       .bind(hora_fin)
+      .bind(fin_utc)
       .bind(id)
-      .execute(self.pool.conexion())
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    if result.rows_affected() == 0 {
+      return Ok(false);
+    }
+
+    self
+      .agregar_historial(
+        trans,
+        id,
+        OperacionMarcaje::Finalizacion,
+        cambio,
+        (&anterior, &anterior.finalizada(hora_fin, fin_utc)),
+        None,
+        None,
+      )
+      .await?;
+
+    Ok(true)
+  }
+
+  /// Obtiene las horas e instantes UTC de un marcaje bloqueándolo
+  /// hasta el final de la transacción.
+  ///
+  /// Si el marcaje no existe los valores son nulos.
+  async fn version_marcaje(
+    &self,
+    trans: &mut Transaccion<'_>,
+    id: u32,
+  ) -> Result<VersionMarcaje, DBError> {
+    const QUERY: &str = "SELECT hora_inicio, hora_fin, inicio_utc, fin_utc
+      FROM marcajes WHERE id = ? FOR UPDATE";
+
+    let row = sqlx::query(QUERY)
+      .bind(id)
+      .fetch_optional(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(
+      row
+        .map(|row| VersionMarcaje {
+          hora_inicio: row.get("hora_inicio"),
+          hora_fin: row.get("hora_fin"),
+          inicio_utc: row.get("inicio_utc"),
+          fin_utc: row.get("fin_utc"),
+        })
+        .unwrap_or_default(),
+    )
+  }
+
+  /// Anota un cambio en el historial de un marcaje.
+  ///
+  /// Se indican las versiones anterior y posterior al cambio. En los
+  /// cambios de pausas son las de la pausa indicada.
+  #[allow(clippy::too_many_arguments)]
+  async fn agregar_historial(
+    &self,
+    trans: &mut Transaccion<'_>,
+    marcaje: u32,
+    operacion: OperacionMarcaje,
+    cambio: &CambioMarcaje,
+    (anterior, nuevo): (&VersionMarcaje, &VersionMarcaje),
+    marcaje_nuevo: Option<u32>,
+    pausa: Option<u32>,
+  ) -> Result<(), DBError> {
+    const QUERY: &str = "INSERT INTO marcajes_historial
+      (marcaje, operacion, fecha, autor, motivo,
+       hora_inicio_anterior, hora_fin_anterior,
+       inicio_utc_anterior, fin_utc_anterior,
+       hora_inicio, hora_fin, inicio_utc, fin_utc,
+       marcaje_nuevo, pausa)
+      VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+    sqlx::query(QUERY)
+      .bind(marcaje)
+      .bind(operacion as u8)
+      .bind(cambio.fecha)
+      .bind(cambio.autor)
+      .bind(&cambio.motivo)
+      .bind(anterior.hora_inicio)
+      .bind(anterior.hora_fin)
+      .bind(anterior.inicio_utc)
+      .bind(anterior.fin_utc)
+      .bind(nuevo.hora_inicio)
+      .bind(nuevo.hora_fin)
+      .bind(nuevo.inicio_utc)
+      .bind(nuevo.fin_utc)
+      .bind(marcaje_nuevo)
+      .bind(pausa)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(())
+  }
+
+  /// Obtiene el historial completo de un marcaje.
+  ///
+  /// Incluye los cambios de los marcajes a los que corrige y de los
+  /// que lo corrigen, de forma que se obtienen todas sus versiones.
+  ///
+  /// Si se indica el usuario solo se devuelve el historial si
+  /// el marcaje le pertenece.
+  pub(in crate::marcaje) async fn historial(
+    &self,
+    id: u32,
+    usuario: Option<u32>,
+  ) -> Result<DominioWithCacheUsuario<HistorialMarcaje>, DBError> {
+    const QUERY: &str = "WITH RECURSIVE
+      anteriores AS (
+        SELECT id FROM marcajes WHERE id = ?
+        UNION
+        SELECT m.id FROM marcajes m
+        JOIN anteriores a ON m.modificado_por = a.id),
+      posteriores AS (
+        SELECT id, modificado_por FROM marcajes WHERE id = ?
+        UNION
+        SELECT m.id, m.modificado_por FROM marcajes m
+        JOIN posteriores p ON m.id = p.modificado_por)
+      SELECT h.id, h.marcaje, h.operacion, h.fecha, h.autor, h.motivo,
+      h.hora_inicio_anterior, h.hora_fin_anterior,
+      h.inicio_utc_anterior, h.fin_utc_anterior,
+      h.hora_inicio, h.hora_fin, h.inicio_utc, h.fin_utc,
+      h.marcaje_nuevo, h.pausa,
+      u.nombre, u.primer_apellido, u.segundo_apellido
+      FROM marcajes_historial h
+      LEFT JOIN usuarios u ON h.autor = u.id
+      WHERE h.marcaje IN (
+        SELECT id FROM anteriores UNION SELECT id FROM posteriores)
+      AND (? IS NULL
+        OR EXISTS (SELECT 1 FROM marcajes m WHERE m.id = ? AND m.usuario = ?))
+      ORDER BY h.fecha ASC, h.id ASC";
+
+    let rows = sqlx::query(QUERY)
+      .bind(id)
+      .bind(id)
+      .bind(usuario)
+      .bind(id)
+      .bind(usuario)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let mut resultado = DominioWithCacheUsuario::new(rows.len());
+
+    for row in rows {
+      let autor = row.try_get::<u32, _>("autor").ok();
+
+      if let Some(id) = autor {
+        resultado.push_usuario(DescriptorUsuario {
+          id,
+          nombre: row.get("nombre"),
+          primer_apellido: row.get("primer_apellido"),
+          segundo_apellido: row.get("segundo_apellido"),
+        });
+      }
+
+      resultado.push_entidad(HistorialMarcaje {
+        id: row.get("id"),
+        marcaje: row.get("marcaje"),
+        operacion: row.get::<u8, _>("operacion").into(),
+        fecha: row.get("fecha"),
+        autor,
+        motivo: row.get("motivo"),
+        anterior: VersionMarcaje {
+          hora_inicio: row.get("hora_inicio_anterior"),
+          hora_fin: row.get("hora_fin_anterior"),
+          inicio_utc: row.get("inicio_utc_anterior"),
+          fin_utc: row.get("fin_utc_anterior"),
+        },
+        nuevo: VersionMarcaje {
+          hora_inicio: row.get("hora_inicio"),
+          hora_fin: row.get("hora_fin"),
+          inicio_utc: row.get("inicio_utc"),
+          fin_utc: row.get("fin_utc"),
+        },
+        marcaje_nuevo: row.get("marcaje_nuevo"),
+        pausa: row.get("pausa"),
+      });
+    }
+
+    Ok(resultado)
+  }
+
+  /// Obtiene la última pausa de un marcaje.
+  pub(in crate::marcaje) async fn ultima_pausa(
+    &self,
+    marcaje: u32,
+  ) -> Result<Option<Pausa>, DBError> {
+    const QUERY: &str = "SELECT id, marcaje, tipo, hora_inicio, hora_fin
+      FROM pausas
+      WHERE marcaje = ?
+      ORDER BY id DESC
+      LIMIT 1";
+
+    let row = sqlx::query(QUERY)
+      .bind(marcaje)
+      .fetch_optional(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(row.as_ref().map(pausa_from_row))
+  }
+
+  /// Agrega una pausa abierta a un marcaje.
+  ///
+  /// El inicio de la pausa se anota en el historial del marcaje.
+  ///
+  /// Devuelve el ID de la pausa creada.
+  pub(in crate::marcaje) async fn agregar_pausa(
+    &self,
+    trans: &mut Transaccion<'_>,
+    pausa: &Pausa,
+    version: &VersionMarcaje,
+    cambio: &CambioMarcaje,
+  ) -> Result<u32, DBError> {
+    const QUERY: &str = "INSERT INTO pausas (marcaje, tipo, hora_inicio)
+      VALUES (?, ?, ?)";
+
+    let result = sqlx::query(QUERY)
+      .bind(pausa.marcaje)
+      .bind(pausa.tipo as u8)
+      .bind(pausa.hora_inicio)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let id = result.last_insert_id() as u32;
+
+    self
+      .agregar_historial(
+        trans,
+        pausa.marcaje,
+        OperacionMarcaje::InicioPausa,
+        cambio,
+        (&VersionMarcaje::default(), version),
+        None,
+        Some(id),
+      )
+      .await?;
+
+    Ok(id)
+  }
+
+  /// Registra la hora de fin de una pausa abierta.
+  ///
+  /// La finalización se anota en el historial del marcaje con las
+  /// versiones de la pausa anterior y posterior.
+  ///
+  /// Devuelve True si se actualizo
+  pub(in crate::marcaje) async fn finalizar_pausa(
+    &self,
+    trans: &mut Transaccion<'_>,
+    pausa: &Pausa,
+    (anterior, nuevo): (&VersionMarcaje, &VersionMarcaje),
+    cambio: &CambioMarcaje,
+  ) -> Result<bool, DBError> {
+    const QUERY: &str =
+      "UPDATE pausas SET hora_fin = ? WHERE id = ? AND hora_fin IS NULL";
+
+    let result = sqlx::query(QUERY)
+      .bind(nuevo.hora_fin)
+      .bind(pausa.id)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    if result.rows_affected() == 0 {
+      return Ok(false);
+    }
+
+    self
+      .agregar_historial(
+        trans,
+        pausa.marcaje,
+        OperacionMarcaje::FinPausa,
+        cambio,
+        (anterior, nuevo),
+        None,
+        Some(pausa.id),
+      )
+      .await?;
+
+    Ok(true)
+  }
+
+  /// Obtiene la pausa abierta de un marcaje bloqueándola hasta el
+  /// final de la transacción.
+  pub(in crate::marcaje) async fn pausa_abierta(
+    &self,
+    trans: &mut Transaccion<'_>,
+    marcaje: u32,
+  ) -> Result<Option<Pausa>, DBError> {
+    const QUERY: &str = "SELECT id, marcaje, tipo, hora_inicio, hora_fin
+      FROM pausas
+      WHERE marcaje = ? AND hora_fin IS NULL
+      ORDER BY id DESC
+      LIMIT 1
+      FOR UPDATE";
+
+    let row = sqlx::query(QUERY)
+      .bind(marcaje)
+      .fetch_optional(&mut **trans.deref_mut())
       .await
       .map_err(DBError::from_sqlx)?;
 
-    Ok(result.rows_affected() > 0)
+    Ok(row.as_ref().map(pausa_from_row))
   }
 
-  /// Verifica si la hora de fin para cualquier marcaje
-  /// horario de un determinado usuario y fecha está vacía.
+  /// Obtiene las pausas de un marcaje.
+  ///
+  /// Si se indica el usuario solo se devuelven las pausas si
+  /// el marcaje le pertenece.
+  pub(in crate::marcaje) async fn pausas(
+    &self,
+    marcaje: u32,
+    usuario: Option<u32>,
+  ) -> Result<Vec<Pausa>, DBError> {
+    const QUERY: &str = "SELECT p.id, p.marcaje, p.tipo,
+      p.hora_inicio, p.hora_fin
+      FROM pausas p
+      JOIN marcajes m ON m.id = p.marcaje
+      WHERE p.marcaje = ? AND (? IS NULL OR m.usuario = ?)
+      ORDER BY p.id";
+
+    let rows = sqlx::query(QUERY)
+      .bind(marcaje)
+      .bind(usuario)
+      .bind(usuario)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(rows.iter().map(pausa_from_row).collect())
+  }
+
+  /// Verifica si existe un marcaje sin hora de fin de un usuario
+  /// que haya empezado en las 24 horas anteriores al fin del
+  /// intervalo UTC dado, sin ser posterior a él.
+  ///
+  /// Así un marcaje abierto que cruza la medianoche bloquea los
+  /// marcajes del día siguiente. La fecha local del intervalo
+  /// acota los marcajes a comparar.
   ///
   /// Se puede excluir un marcaje pasado como parámetro
   /// Si no quiere excluir ningún marcaje use 0
@@ -128,27 +647,42 @@ impl MarcajeRepo {
     &self,
     usuario: u32,
     fecha: NaiveDate,
+    (inicio, fin): (DateTime<Utc>, DateTime<Utc>),
     excluir_marcaje_id: u32,
   ) -> Result<bool, DBError> {
     tracing::debug!(
       usuario = usuario,
       fecha = ?fecha,
+      inicio = %inicio,
+      fin = %fin,
       excluir_marcaje_id = excluir_marcaje_id,
-      "Verificando si existe un marcaje sin hora fin para el usuario y fecha"
+      "Verificando si existe un marcaje sin hora fin para el usuario"
     );
 
-    const QUERY: &str = "SELECT id
-      FROM marcajes
-      WHERE usuario = ? AND fecha = ?
-      AND id <> ? AND modificado_por IS NULL AND eliminado IS NULL
-      AND hora_fin IS NULL
-      LIMIT 1;";
+    const QUERY: &str = concat!(
+      "SELECT r.id
+      FROM marcajes r
+      WHERE r.usuario = ?
+      AND r.fecha BETWEEN ? - INTERVAL 1 DAY AND ? + INTERVAL 1 DAY
+      AND r.id <> ? AND r.modificado_por IS NULL AND r.eliminado IS NULL
+      AND r.hora_fin IS NULL
+      AND ",
+      inicio_utc_marcaje!("r"),
+      " > ? - INTERVAL 1 DAY
+      AND ",
+      inicio_utc_marcaje!("r"),
+      " <= ?
+      LIMIT 1;"
+    );
 
     Ok(
       sqlx::query_scalar::<_, u32>(QUERY)
         .bind(usuario)
         .bind(fecha)
+        .bind(fecha)
         .bind(excluir_marcaje_id)
+        .bind(inicio)
+        .bind(fin)
         .fetch_optional(self.pool.conexion())
         .await
         .map_err(DBError::from_sqlx)?
@@ -156,8 +690,11 @@ impl MarcajeRepo {
     )
   }
 
-  /// Verifica si existen horas posteriores a la hora dada
-  /// de un marcaje horario para un usuario y fecha.
+  /// Verifica si existe un marcaje de un usuario que empiece en
+  /// el instante UTC dado o en las 24 horas siguientes.
+  ///
+  /// Así se detectan los marcajes posteriores aunque caigan en el
+  /// día siguiente por cruzar la medianoche.
   ///
   /// Se puede excluir un marcaje pasado como parámetro
   /// Si no quiere excluir ningún marcaje use 0
@@ -167,22 +704,32 @@ impl MarcajeRepo {
     &self,
     usuario: u32,
     fecha: NaiveDate,
-    hora: NaiveTime,
+    inicio: DateTime<Utc>,
     excluir_marcaje_id: u32,
   ) -> Result<bool, DBError> {
-    const QUERY: &str = "SELECT id
-        FROM marcajes
-        WHERE usuario = ? AND fecha = ?
-        AND id <> ? AND modificado_por IS NULL AND eliminado IS NULL
-        AND hora_inicio >= ?
-        LIMIT 1;";
+    const QUERY: &str = concat!(
+      "SELECT r.id
+        FROM marcajes r
+        WHERE r.usuario = ?
+        AND r.fecha BETWEEN ? - INTERVAL 1 DAY AND ? + INTERVAL 1 DAY
+        AND r.id <> ? AND r.modificado_por IS NULL AND r.eliminado IS NULL
+        AND ",
+      inicio_utc_marcaje!("r"),
+      " >= ?
+        AND ",
+      inicio_utc_marcaje!("r"),
+      " < ? + INTERVAL 1 DAY
+        LIMIT 1;"
+    );
 
     Ok(
       sqlx::query(QUERY)
         .bind(usuario)
         .bind(fecha)
+        .bind(fecha)
         .bind(excluir_marcaje_id)
-        .bind(hora)
+        .bind(inicio)
+        .bind(inicio)
         .fetch_optional(self.pool.conexion())
         .await
         .map_err(DBError::from_sqlx)?
@@ -190,8 +737,11 @@ impl MarcajeRepo {
     )
   }
 
-  /// Verifica si una hora se encuentra en el rango de horas
-  /// de un marcaje horario para un usuario y fecha.
+  /// Verifica si una fecha y hora se encuentra en el rango de horas
+  /// de un marcaje horario para un usuario.
+  ///
+  /// Se tienen en cuenta los marcajes del día anterior que
+  /// cruzan la medianoche.
   ///
   /// Se puede excluir un marcaje pasado como parámetro
   /// Si no quiere excluir ningún marcaje use 0
@@ -200,32 +750,41 @@ impl MarcajeRepo {
   pub(in crate::marcaje) async fn hora_asignada(
     &self,
     usuario: u32,
-    fecha: NaiveDate,
-    hora: NaiveTime,
+    fecha_hora: NaiveDateTime,
     excluir_marcaje_id: u32,
   ) -> Result<bool, DBError> {
-    const QUERY: &str = "SELECT id
-        FROM marcajes
-        WHERE usuario = ? AND fecha = ?
-        AND id <> ? AND modificado_por IS NULL AND eliminado IS NULL
-        AND ? BETWEEN hora_inicio AND hora_fin
-        LIMIT 1;";
+    const QUERY: &str = concat!(
+      "SELECT r.id
+        FROM marcajes r
+        WHERE r.usuario = ?
+        AND r.fecha BETWEEN ? - INTERVAL 1 DAY AND ?
+        AND r.id <> ? AND r.modificado_por IS NULL AND r.eliminado IS NULL
+        AND ? BETWEEN ",
+      inicio_marcaje!("r"),
+      " AND ",
+      fin_marcaje!("r"),
+      " LIMIT 1;"
+    );
 
     Ok(
       sqlx::query(QUERY)
         .bind(usuario)
-        .bind(fecha)
+        .bind(fecha_hora.date())
+        .bind(fecha_hora.date())
         .bind(excluir_marcaje_id)
-        .bind(hora)
+        .bind(fecha_hora)
         .fetch_optional(self.pool.conexion())
         .await
         .map_err(DBError::from_sqlx)?
         .is_some(),
     )
   }
-  /// Verifica si un rango de horas como parámetro se solapa
-  /// con otro rango de horas ya asignado a un usuario en un marcaje.
-  /// También verifica que la hora no se encuentre entre los rangos de horas.
+  /// Verifica si un rango de fechas y horas como parámetro se solapa
+  /// con otro rango ya asignado a un usuario en un marcaje.
+  /// También verifica que el fin no se encuentre entre los rangos.
+  ///
+  /// Los rangos pueden cruzar la medianoche, por lo que se comparan
+  /// los marcajes del día anterior al inicio hasta el día del fin.
   ///
   /// Se puede excluir un marcaje pasado como parámetro
   /// Si no quiere excluir ningún marcaje use 0
@@ -234,27 +793,38 @@ impl MarcajeRepo {
   pub(in crate::marcaje) async fn horas_solapadas(
     &self,
     usuario: u32,
-    fecha: NaiveDate,
-    hora_ini: NaiveTime,
-    hora_fin: NaiveTime,
+    inicio: NaiveDateTime,
+    fin: NaiveDateTime,
     excluir_marcaje_id: u32,
   ) -> Result<bool, DBError> {
-    const QUERY: &str = "SELECT id
-        FROM marcajes
-        WHERE usuario = ? AND fecha = ?
-        AND id <> ? AND modificado_por IS NULL AND eliminado IS NULL
-        AND ( hora_inicio < ? AND hora_fin > ?
-        OR ? BETWEEN hora_inicio AND hora_fin )
-        LIMIT 1;";
+    const QUERY: &str = concat!(
+      "SELECT r.id
+        FROM marcajes r
+        WHERE r.usuario = ?
+        AND r.fecha BETWEEN ? - INTERVAL 1 DAY AND ?
+        AND r.id <> ? AND r.modificado_por IS NULL AND r.eliminado IS NULL
+        AND ( ",
+      inicio_marcaje!("r"),
+      " < ? AND ",
+      fin_marcaje!("r"),
+      " > ?
+        OR ? BETWEEN ",
+      inicio_marcaje!("r"),
+      " AND ",
+      fin_marcaje!("r"),
+      " )
+        LIMIT 1;"
+    );
 
     Ok(
       sqlx::query(QUERY)
         .bind(usuario)
-        .bind(fecha)
+        .bind(inicio.date())
+        .bind(fin.date())
         .bind(excluir_marcaje_id)
-        .bind(hora_fin)
-        .bind(hora_ini)
-        .bind(hora_fin)
+        .bind(fin)
+        .bind(inicio)
+        .bind(fin)
         .fetch_optional(self.pool.conexion())
         .await
         .map_err(DBError::from_sqlx)?
@@ -293,6 +863,56 @@ impl MarcajeRepo {
     }
   }
 
+  /// Obtiene los marcajes sin hora de fin hasta la fecha dada.
+  ///
+  /// Se excluyen los marcajes con una incidencia pendiente
+  /// (solicitud, conflicto o error al resolver).
+  pub(in crate::marcaje) async fn marcajes_olvidados(
+    &self,
+    hasta: NaiveDate,
+  ) -> Result<Vec<MarcajeOlvidado>, DBError> {
+    const QUERY: &str = concat!(
+      "SELECT r.id, r.usuario, r.fecha, r.hora_inicio,
+        h.id AS h_id, h.dia, h.minutos,
+        h.pausa_comida_retribuida, h.pausa_personal_retribuida, ",
+      crate::franjas_horario!("h"),
+      " AS franjas
+      FROM marcajes r
+      JOIN horarios h ON h.id = r.horario
+      WHERE r.fecha <= ? AND r.hora_fin IS NULL
+      AND r.modificado_por IS NULL AND r.eliminado IS NULL
+      AND NOT EXISTS (
+        SELECT 1 FROM incidencias i
+        WHERE i.marcaje = r.id AND i.estado IN (1, 2, 3))
+      ORDER BY r.fecha, r.usuario"
+    );
+
+    let rows = sqlx::query(QUERY)
+      .bind(hasta)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(
+      rows
+        .iter()
+        .map(|row| MarcajeOlvidado {
+          id: row.get("id"),
+          usuario: row.get("usuario"),
+          fecha: row.get("fecha"),
+          hora_inicio: row.get("hora_inicio"),
+          horario: DescriptorHorario {
+            id: row.get("h_id"),
+            dia: row.get::<String, _>("dia").as_str().into(),
+            minutos: row.get("minutos"),
+            franjas: franjas_from_row(row),
+            pausas: reglas_pausa_from_row(row),
+          },
+        })
+        .collect(),
+    )
+  }
+
   /// Obtiene los últimos marcajes horarios de un usuario.
   pub(in crate::marcaje) async fn ultimos_marcajes(
     &self,
@@ -437,19 +1057,29 @@ impl MarcajeRepo {
   where
     B: FnOnce(&mut QueryBuilder<sqlx::MySql>) -> Result<(), DBError>,
   {
-    const SELECT: &str = "SELECT r.id, r.fecha,
-        r.hora_inicio, r.hora_fin,
+    const SELECT: &str = concat!(
+      "SELECT r.id, r.fecha,
+        r.hora_inicio, r.hora_fin, r.inicio_utc, r.fin_utc,
         u.id AS u_id, u.nombre AS u_nombre,
         u.primer_apellido AS u_primer_apellido,
         u.segundo_apellido AS u_segundo_apellido,
         ur.id AS ur_id, ur.nombre AS ur_nombre,
         ur.primer_apellido AS ur_primer_apellido,
         ur.segundo_apellido AS ur_segundo_apellido,
-        h.id AS h_id, h.dia, h.horas
+        h.id AS h_id, h.dia, h.minutos,
+        h.pausa_comida_retribuida, h.pausa_personal_retribuida,
+        CAST(COALESCE(p.comida, 0) AS UNSIGNED) AS pausa_comida,
+        CAST(COALESCE(p.personal, 0) AS UNSIGNED) AS pausa_personal, ",
+      crate::franjas_horario!("h"),
+      " AS franjas
         FROM marcajes r
         JOIN horarios h ON h.id = r.horario
         JOIN usuarios u ON u.id = r.usuario
-        LEFT JOIN usuarios ur ON ur.id = r.usuario_registrador";
+        LEFT JOIN usuarios ur ON ur.id = r.usuario_registrador
+        LEFT JOIN ",
+      tiempo_pausas!(),
+      " p ON p.marcaje = r.id"
+    );
 
     let mut qb = QueryBuilder::new(SELECT);
 
@@ -500,11 +1130,19 @@ impl MarcajeRepo {
         horario: Some(DescriptorHorario {
           id: row.get("h_id"),
           dia: row.get::<String, _>("dia").as_str().into(),
-          horas: row.get("horas"),
+          minutos: row.get("minutos"),
+          franjas: franjas_from_row(&row),
+          pausas: reglas_pausa_from_row(&row),
         }),
         fecha: row.get("fecha"),
         hora_inicio: row.get("hora_inicio"),
         hora_fin: row.get("hora_fin"),
+        inicio_utc: row.get("inicio_utc"),
+        fin_utc: row.get("fin_utc"),
+        pausas: TiempoPausas {
+          comida: row.get::<u64, _>("pausa_comida") as u32,
+          personal: row.get::<u64, _>("pausa_personal") as u32,
+        },
       };
 
       resultado.push_entidad(marcaje);
@@ -513,3 +1151,13 @@ impl MarcajeRepo {
     Ok(resultado)
   }
 }
+
+fn pausa_from_row(row: &sqlx::mysql::MySqlRow) -> Pausa {
+  Pausa {
+    id: row.get("id"),
+    marcaje: row.get("marcaje"),
+    tipo: row.get::<u8, _>("tipo").into(),
+    hora_inicio: row.get("hora_inicio"),
+    hora_fin: row.get("hora_fin"),
+  }
+}
@@ -1,13 +1,18 @@
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
 
 use crate::{
   config::ConfigTrabajo,
   horario::HorarioServicio,
   infra::{
-    DominioWithCacheUsuario, ServicioError, ShortDateTimeFormat, TimeConvert,
-    Transaccion,
+    DBError, DominioWithCacheUsuario, ServicioError, ShortDateTimeFormat,
+    TimeConvert, Transaccion,
+  },
+  integridad::{IntegridadServicio, TablaSellada},
+  marcaje::{
+    CambioMarcaje, DescriptorMarcaje, HistorialMarcaje, Marcaje,
+    MarcajeOlvidado, MarcajeRepo, Pausa, TipoPausa, fecha_hora_marcaje,
+    instante_fin_utc, instante_utc,
   },
-  marcaje::{Marcaje, MarcajeRepo},
 };
 
 /// Servicio que gestiona los marcajes del usuario
@@ -15,6 +20,7 @@ pub struct MarcajeServicio {
   cnfg: ConfigTrabajo,
   repo: MarcajeRepo,
   horario_servicio: HorarioServicio,
+  srv_integridad: IntegridadServicio,
 }
 
 impl MarcajeServicio {
@@ -22,11 +28,13 @@ impl MarcajeServicio {
     cnfg: ConfigTrabajo,
     repo: MarcajeRepo,
     horario_servicio: HorarioServicio,
+    srv_integridad: IntegridadServicio,
   ) -> Self {
     MarcajeServicio {
       cnfg,
       repo,
       horario_servicio,
+      srv_integridad,
     }
   }
 }
@@ -37,7 +45,10 @@ impl MarcajeServicio {
   ///
   /// Para más detalles vea: [`Self::agregar_with_trans`].
   pub async fn agregar(&self, reg: &Marcaje) -> Result<u32, ServicioError> {
-    self.agregar_with_trans(None, reg, 0).await
+    let autor = reg.usuario_reg.unwrap_or(reg.usuario);
+    let cambio = CambioMarcaje::new(Some(autor), None, &self.cnfg.zona_horaria);
+
+    self.agregar_with_trans(None, reg, 0, &cambio).await
   }
   /// Añade un nuevo marcaje horario para el usuario.
   ///
@@ -56,12 +67,18 @@ impl MarcajeServicio {
   /// La exclusión puede ser muy útil cuando se quiere
   /// realizar una modificación de este marcaje
   ///
+  /// El marcaje se sella en la cadena de hashes del usuario. Si no se
+  /// proporciona una transacción se usa una propia.
+  ///
+  /// El cambio indica el autor y motivo que se anotan en el historial.
+  ///
   /// Devuelve el ID del marcaje creado.
   pub async fn agregar_with_trans(
     &self,
     tr: Option<&mut Transaccion<'_>>,
     reg: &Marcaje,
     excluir_marcaje_id: u32,
+    cambio: &CambioMarcaje,
   ) -> Result<u32, ServicioError> {
     tracing::info!(
       marcaje = ?reg,
@@ -87,17 +104,38 @@ impl MarcajeServicio {
       "Horario más cercano al marcaje horario del usuario"
     );
 
-    let id = match self.repo.agregar(tr, reg, horario_cercano.id).await {
-      Ok(reg_id) => reg_id,
-      Err(err) => {
-        tracing::error!(
-          marcaje = ?reg,
-          error = %err,
-          "Creando marcaje horario"
-        );
-        return Err(ServicioError::from(err));
-      }
-    };
+    let id =
+      match tr {
+        Some(tr) => self.insertar(tr, reg, horario_cercano.id, cambio).await?,
+        None => {
+          let mut tr =
+            self.repo.conexion().empezar_transaccion().await.map_err(
+              |err| {
+                tracing::error!(
+                  marcaje = ?reg,
+                  error = %err,
+                  "Iniciando transacción para crear marcaje horario"
+                );
+                ServicioError::from(err)
+              },
+            )?;
+
+          let id = self
+            .insertar(&mut tr, reg, horario_cercano.id, cambio)
+            .await?;
+
+          tr.commit().await.map_err(|err| {
+            tracing::error!(
+              marcaje = ?reg,
+              error = %err,
+              "Commit transacción para crear marcaje horario"
+            );
+            ServicioError::from(err)
+          })?;
+
+          id
+        }
+      };
 
     tracing::debug!(
       id_marcaje = id,
@@ -115,10 +153,11 @@ impl MarcajeServicio {
     trans: &mut Transaccion<'_>,
     id: u32,
     modificar_por: u32,
+    cambio: &CambioMarcaje,
   ) -> Result<bool, ServicioError> {
-    self
+    let actualizado = self
       .repo
-      .actualizar_modificado_por(trans, id, modificar_por)
+      .actualizar_modificado_por(trans, id, modificar_por, cambio)
       .await
       .map_err(|err| {
         tracing::error!(
@@ -128,7 +167,16 @@ impl MarcajeServicio {
           "Actualizando modificado_por del marcaje"
         );
         ServicioError::from(err)
-      })
+      })?;
+
+    if actualizado {
+      self
+        .srv_integridad
+        .resellar(trans, TablaSellada::Marcajes, id)
+        .await?;
+    }
+
+    Ok(actualizado)
   }
 
   /// Marca un marcaje como eliminado
@@ -138,10 +186,11 @@ impl MarcajeServicio {
     &self,
     trans: &mut Transaccion<'_>,
     id: u32,
+    cambio: &CambioMarcaje,
   ) -> Result<bool, ServicioError> {
-    self
+    let actualizado = self
       .repo
-      .marcar_marcaje_eliminado(trans, id)
+      .marcar_marcaje_eliminado(trans, id, cambio)
       .await
       .map_err(|err| {
         tracing::error!(
@@ -150,7 +199,16 @@ impl MarcajeServicio {
           "Marcando el marcaje como eliminado"
         );
         ServicioError::from(err)
-      })
+      })?;
+
+    if actualizado {
+      self
+        .srv_integridad
+        .resellar(trans, TablaSellada::Marcajes, id)
+        .await?;
+    }
+
+    Ok(actualizado)
   }
 
   /// Registrar una marcaje como finalizado.
@@ -158,11 +216,17 @@ impl MarcajeServicio {
   /// Verifica que la hora de inicio del marcaje,
   /// cuya hora fin sea nula, sea anterior a la hora fin
   /// que se quiere registrar.
+  /// Si no hay marcaje sin finalizar en la fecha se busca en
+  /// el día anterior, para los marcajes que cruzan la medianoche.
+  /// En ese caso el marcaje no puede durar 24 horas o más.
   /// Si el registro no existe devuelve un error.
+  ///
+  /// El autor es el usuario que registra la hora de fin.
   pub async fn finalizar_marcaje(
     &self,
     usuario: u32,
     fecha_hora_fin: chrono::NaiveDateTime,
+    autor: u32,
   ) -> Result<(), ServicioError> {
     let hora_fin = fecha_hora_fin.time().to_short_time();
 
@@ -173,25 +237,61 @@ impl MarcajeServicio {
     );
 
     match self
-      .repo
-      .marcaje_sin_hora_fin(usuario, fecha_hora_fin.date())
+      .buscar_marcaje_abierto(usuario, fecha_hora_fin.date())
       .await
     {
-      Ok(Some(marcaje)) => {
-        if marcaje.hora_inicio.unwrap() >= hora_fin {
+      Ok(Some((fecha, marcaje))) => {
+        let inicio = fecha.and_time(marcaje.hora_inicio.unwrap());
+        let fin = fecha_hora_fin.date().and_time(hora_fin);
+
+        if inicio >= fin {
           return Err(ServicioError::Usuario(format!(
             "La hora de fin: {} debe ser posterior a la hora de inicio: {} \
             del marcaje sin finalizar para el usuario: {} en la fecha: {}",
             hora_fin,
             marcaje.hora_inicio.unwrap(),
             usuario,
-            fecha_hora_fin.date().formato_corto()
+            fecha.formato_corto()
+          )));
+        }
+
+        if fin - inicio >= TimeDelta::days(1) {
+          return Err(ServicioError::Usuario(format!(
+            "El marcaje sin finalizar para el usuario: {} iniciado el {} \
+            a las {} no puede durar 24 horas o más",
+            usuario,
+            fecha.formato_corto(),
+            marcaje.hora_inicio.unwrap()
           )));
         }
 
+        let mut tr =
+          self
+            .repo
+            .conexion()
+            .empezar_transaccion()
+            .await
+            .map_err(|err| {
+              tracing::error!(
+                id_marcaje = marcaje.id,
+                error = %err,
+                "Iniciando transacción para finalizar el marcaje"
+              );
+              ServicioError::from(err)
+            })?;
+
+        let cambio =
+          CambioMarcaje::new(Some(autor), None, &self.cnfg.zona_horaria);
+        let fin_utc = instante_fin_utc(
+          &self.cnfg.zona_usuario(usuario),
+          fecha,
+          marcaje.hora_inicio.unwrap(),
+          hora_fin,
+        );
+
         if !self
           .repo
-          .actualizar_hora_fin(marcaje.id, hora_fin)
+          .actualizar_hora_fin(&mut tr, marcaje.id, hora_fin, fin_utc, &cambio)
           .await
           .map_err(|err| {
             tracing::error!(
@@ -211,6 +311,31 @@ impl MarcajeServicio {
           )));
         }
 
+        self
+          .cerrar_pausa_abierta(
+            &mut tr,
+            usuario,
+            (fecha, marcaje.hora_inicio.unwrap()),
+            marcaje.id,
+            hora_fin,
+            &cambio,
+          )
+          .await?;
+
+        self
+          .srv_integridad
+          .resellar(&mut tr, TablaSellada::Marcajes, marcaje.id)
+          .await?;
+
+        tr.commit().await.map_err(|err| {
+          tracing::error!(
+            id_marcaje = marcaje.id,
+            error = %err,
+            "Commit transacción para finalizar el marcaje"
+          );
+          ServicioError::from(err)
+        })?;
+
         tracing::debug!(
           id_marcaje = marcaje.id,
           hora_fin = %hora_fin,
@@ -237,6 +362,126 @@ impl MarcajeServicio {
     }
   }
 
+  /// Obtiene los marcajes sin hora de fin cuya jornada
+  /// ha terminado en el momento dado.
+  ///
+  /// El momento se evalúa en la zona horaria de cada usuario.
+  /// Ver [`MarcajeOlvidado::jornada_terminada`].
+  pub async fn marcajes_olvidados(
+    &self,
+    ahora: DateTime<Utc>,
+  ) -> Result<Vec<MarcajeOlvidado>, ServicioError> {
+    // Ninguna zona horaria va más de un día por delante de UTC
+    let hasta = ahora.date_naive() + TimeDelta::days(1);
+
+    let marcajes =
+      self.repo.marcajes_olvidados(hasta).await.map_err(|err| {
+        tracing::error!(
+          ahora = %ahora,
+          error = %err,
+          "Obteniendo los marcajes sin hora de fin"
+        );
+        ServicioError::from(err)
+      })?;
+
+    Ok(
+      marcajes
+        .into_iter()
+        .filter(|m| {
+          m.jornada_terminada(
+            ahora
+              .with_timezone(&self.cnfg.zona_usuario(m.usuario))
+              .naive_local(),
+          )
+        })
+        .collect(),
+    )
+  }
+
+  /// Cierra un marcaje olvidado con la hora de fin dada.
+  ///
+  /// El cierre lo realiza el sistema, por lo que el historial
+  /// se anota sin autor. También se cierra la pausa abierta.
+  ///
+  /// Devuelve False si el marcaje ya no estaba abierto.
+  pub async fn cerrar_olvidado(
+    &self,
+    marcaje: &MarcajeOlvidado,
+    hora_fin: NaiveTime,
+  ) -> Result<bool, ServicioError> {
+    let mut tr =
+      self
+        .repo
+        .conexion()
+        .empezar_transaccion()
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            id_marcaje = marcaje.id,
+            error = %err,
+            "Iniciando transacción para cerrar el marcaje olvidado"
+          );
+          ServicioError::from(err)
+        })?;
+
+    let cambio = CambioMarcaje::new(
+      None,
+      Some("Cierre automático con la hora de salida del horario".to_string()),
+      &self.cnfg.zona_horaria,
+    );
+
+    let fin_utc = instante_fin_utc(
+      &self.cnfg.zona_usuario(marcaje.usuario),
+      marcaje.fecha,
+      marcaje.hora_inicio,
+      hora_fin,
+    );
+
+    if !self
+      .repo
+      .actualizar_hora_fin(&mut tr, marcaje.id, hora_fin, fin_utc, &cambio)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          id_marcaje = marcaje.id,
+          hora_fin = %hora_fin,
+          error = %err,
+          "Registrando la hora fin del marcaje olvidado"
+        );
+        ServicioError::from(err)
+      })?
+    {
+      return Ok(false);
+    }
+
+    self
+      .cerrar_pausa_abierta(
+        &mut tr,
+        marcaje.usuario,
+        (marcaje.fecha, marcaje.hora_inicio),
+        marcaje.id,
+        hora_fin,
+        &cambio,
+      )
+      .await?;
+
+    self
+      .srv_integridad
+      .resellar(&mut tr, TablaSellada::Marcajes, marcaje.id)
+      .await?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        id_marcaje = marcaje.id,
+        error = %err,
+        "Commit transacción para cerrar el marcaje olvidado"
+      );
+      ServicioError::from(err)
+    })?;
+
+    Ok(true)
+  }
+
   /// Obtiene los marcajes entre fechas para un usuario.
   ///
   /// Dependiendo del valor de usuario_reg se añaden más filtros
@@ -314,16 +559,18 @@ impl MarcajeServicio {
       })
   }
 
-  // Determina si la hora fin esta vacía para un
-  // un usuario y fecha de marcaje
+  // Determina si el usuario tiene un marcaje sin hora fin
+  // iniciado en las 24 horas anteriores a la fecha y hora dada
   pub async fn hora_fin_vacia(
     &self,
     usuario: u32,
-    fecha: NaiveDate,
+    fecha: chrono::NaiveDateTime,
   ) -> Result<bool, ServicioError> {
+    let instante = instante_utc(&self.cnfg.zona_usuario(usuario), fecha, None);
+
     return self
       .repo
-      .hora_fin_vacia(usuario, fecha, 0)
+      .hora_fin_vacia(usuario, fecha.date(), (instante, instante), 0)
       .await
       .map_err(|err| {
         tracing::error!(
@@ -357,6 +604,412 @@ impl MarcajeServicio {
       })
   }
 
+  /// Obtiene todas las versiones de un marcaje con los
+  /// valores anteriores y posteriores de cada cambio.
+  ///
+  /// Si se indica el usuario solo se obtiene el historial
+  /// de sus marcajes.
+  pub async fn historial(
+    &self,
+    id: u32,
+    usuario: Option<u32>,
+  ) -> Result<DominioWithCacheUsuario<HistorialMarcaje>, ServicioError> {
+    self.repo.historial(id, usuario).await.map_err(|err| {
+      tracing::error!(
+        id_marcaje = id,
+        usuario = ?usuario,
+        error = %err,
+        "Obteniendo el historial del marcaje"
+      );
+      ServicioError::from(err)
+    })
+  }
+
+  /// Inicia una pausa en el marcaje sin finalizar del usuario.
+  ///
+  /// La pausa debe empezar después del inicio del marcaje y del fin
+  /// de la pausa anterior. No puede haber otra pausa abierta.
+  /// Las horas se comparan como fechas y horas, ya que el marcaje
+  /// puede cruzar la medianoche.
+  pub async fn iniciar_pausa(
+    &self,
+    usuario: u32,
+    fecha_hora: chrono::NaiveDateTime,
+    tipo: TipoPausa,
+    autor: u32,
+  ) -> Result<u32, ServicioError> {
+    let hora = fecha_hora.time().to_short_time();
+
+    tracing::info!(
+      usuario = usuario,
+      fecha_hora = %fecha_hora,
+      tipo = tipo.as_str(),
+      "Iniciando pausa del marcaje del usuario"
+    );
+
+    let (fecha, marcaje) =
+      self.marcaje_abierto(usuario, fecha_hora.date()).await?;
+    let hora_inicio = marcaje.hora_inicio.unwrap();
+    let momento = fecha_hora.date().and_time(hora);
+
+    if fecha.and_time(hora_inicio) >= momento {
+      return Err(ServicioError::Usuario(format!(
+        "La pausa debe empezar después de la hora de inicio del marcaje: {}",
+        hora_inicio
+      )));
+    }
+
+    match self.ultima_pausa(marcaje.id).await? {
+      Some(pausa) if pausa.hora_fin.is_none() => {
+        return Err(ServicioError::Usuario(format!(
+          "Ya existe una pausa iniciada a las {} sin finalizar",
+          pausa.hora_inicio
+        )));
+      }
+      Some(pausa)
+        if pausa.hora_fin.is_some_and(|fin| {
+          fecha_hora_marcaje(fecha, hora_inicio, fin) > momento
+        }) =>
+      {
+        return Err(ServicioError::Usuario(format!(
+          "La pausa debe empezar después del fin de la pausa anterior: {}",
+          pausa.hora_fin.unwrap()
+        )));
+      }
+      _ => {}
+    }
+
+    let pausa = Pausa {
+      id: 0,
+      marcaje: marcaje.id,
+      tipo,
+      hora_inicio: hora,
+      hora_fin: None,
+    };
+    let version =
+      pausa.version(&self.cnfg.zona_usuario(usuario), fecha, hora_inicio);
+    let cambio = CambioMarcaje::new(Some(autor), None, &self.cnfg.zona_horaria);
+
+    let mut tr = self.transaccion_pausa(marcaje.id).await?;
+
+    let id = self
+      .repo
+      .agregar_pausa(&mut tr, &pausa, &version, &cambio)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          id_marcaje = marcaje.id,
+          hora = %hora,
+          error = %err,
+          "Agregando la pausa del marcaje"
+        );
+        ServicioError::from(err)
+      })?;
+
+    self
+      .srv_integridad
+      .sellar(&mut tr, TablaSellada::Pausas, id)
+      .await?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        id_marcaje = marcaje.id,
+        error = %err,
+        "Commit transacción para agregar la pausa del marcaje"
+      );
+      ServicioError::from(err)
+    })?;
+
+    Ok(id)
+  }
+
+  /// Finaliza la pausa abierta del marcaje sin finalizar del usuario.
+  pub async fn finalizar_pausa(
+    &self,
+    usuario: u32,
+    fecha_hora: chrono::NaiveDateTime,
+    autor: u32,
+  ) -> Result<(), ServicioError> {
+    let hora = fecha_hora.time().to_short_time();
+
+    tracing::info!(
+      usuario = usuario,
+      fecha_hora = %fecha_hora,
+      "Finalizando pausa del marcaje del usuario"
+    );
+
+    let (fecha, marcaje) =
+      self.marcaje_abierto(usuario, fecha_hora.date()).await?;
+    let momento = fecha_hora.date().and_time(hora);
+
+    let Some(pausa) = self
+      .ultima_pausa(marcaje.id)
+      .await?
+      .filter(|p| p.hora_fin.is_none())
+    else {
+      return Err(ServicioError::Usuario(
+        "No existe ninguna pausa iniciada para finalizar".to_string(),
+      ));
+    };
+
+    if fecha_hora_marcaje(
+      fecha,
+      marcaje.hora_inicio.unwrap(),
+      pausa.hora_inicio,
+    ) >= momento
+    {
+      return Err(ServicioError::Usuario(format!(
+        "La hora de fin: {} debe ser posterior a la hora de inicio \
+        de la pausa: {}",
+        hora, pausa.hora_inicio
+      )));
+    }
+
+    let cambio = CambioMarcaje::new(Some(autor), None, &self.cnfg.zona_horaria);
+
+    let mut tr = self.transaccion_pausa(marcaje.id).await?;
+
+    if !self
+      .finalizar_pausa_marcaje(
+        &mut tr,
+        usuario,
+        (fecha, marcaje.hora_inicio.unwrap()),
+        &pausa,
+        hora,
+        &cambio,
+      )
+      .await?
+    {
+      return Err(ServicioError::Usuario(
+        "No existe ninguna pausa iniciada para finalizar".to_string(),
+      ));
+    }
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        id_pausa = pausa.id,
+        error = %err,
+        "Commit transacción para finalizar la pausa del marcaje"
+      );
+      ServicioError::from(err)
+    })?;
+
+    Ok(())
+  }
+
+  /// Inicia la transacción para registrar un cambio en las pausas
+  /// de un marcaje.
+  async fn transaccion_pausa(
+    &self,
+    marcaje: u32,
+  ) -> Result<Transaccion<'_>, ServicioError> {
+    self
+      .repo
+      .conexion()
+      .empezar_transaccion()
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          id_marcaje = marcaje,
+          error = %err,
+          "Iniciando transacción para registrar la pausa del marcaje"
+        );
+        ServicioError::from(err)
+      })
+  }
+
+  /// Registra la hora de fin de una pausa abierta anotando el
+  /// cambio en el historial del marcaje.
+  ///
+  /// El marcaje se indica con su fecha y hora de inicio para
+  /// calcular los instantes UTC de la pausa.
+  ///
+  /// Devuelve False si la pausa ya estaba finalizada.
+  async fn finalizar_pausa_marcaje(
+    &self,
+    tr: &mut Transaccion<'_>,
+    usuario: u32,
+    (fecha, inicio): (NaiveDate, NaiveTime),
+    pausa: &Pausa,
+    hora_fin: NaiveTime,
+    cambio: &CambioMarcaje,
+  ) -> Result<bool, ServicioError> {
+    let tz = self.cnfg.zona_usuario(usuario);
+    let anterior = pausa.version(&tz, fecha, inicio);
+    let nuevo = Pausa {
+      hora_fin: Some(hora_fin),
+      ..pausa.clone()
+    }
+    .version(&tz, fecha, inicio);
+
+    if !self
+      .repo
+      .finalizar_pausa(tr, pausa, (&anterior, &nuevo), cambio)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          id_pausa = pausa.id,
+          hora = %hora_fin,
+          error = %err,
+          "Finalizando la pausa del marcaje"
+        );
+        ServicioError::from(err)
+      })?
+    {
+      return Ok(false);
+    }
+
+    self
+      .srv_integridad
+      .resellar(tr, TablaSellada::Pausas, pausa.id)
+      .await?;
+
+    Ok(true)
+  }
+
+  /// Cierra la pausa abierta de un marcaje que se finaliza.
+  ///
+  /// La pausa termina a la hora de fin del marcaje, salvo que
+  /// hubiera empezado después, en cuyo caso queda vacía.
+  async fn cerrar_pausa_abierta(
+    &self,
+    tr: &mut Transaccion<'_>,
+    usuario: u32,
+    (fecha, inicio): (NaiveDate, NaiveTime),
+    marcaje: u32,
+    hora_fin: NaiveTime,
+    cambio: &CambioMarcaje,
+  ) -> Result<(), ServicioError> {
+    let Some(pausa) =
+      self.repo.pausa_abierta(tr, marcaje).await.map_err(|err| {
+        tracing::error!(
+          id_marcaje = marcaje,
+          error = %err,
+          "Obteniendo la pausa abierta del marcaje para cerrarla"
+        );
+        ServicioError::from(err)
+      })?
+    else {
+      return Ok(());
+    };
+
+    let hora_fin = if fecha_hora_marcaje(fecha, inicio, pausa.hora_inicio)
+      > fecha_hora_marcaje(fecha, inicio, hora_fin)
+    {
+      pausa.hora_inicio
+    } else {
+      hora_fin
+    };
+
+    self
+      .finalizar_pausa_marcaje(
+        tr,
+        usuario,
+        (fecha, inicio),
+        &pausa,
+        hora_fin,
+        cambio,
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  /// Obtiene las pausas de un marcaje.
+  ///
+  /// Si se indica el usuario solo se obtienen las pausas
+  /// de sus marcajes.
+  pub async fn pausas(
+    &self,
+    marcaje: u32,
+    usuario: Option<u32>,
+  ) -> Result<Vec<Pausa>, ServicioError> {
+    self.repo.pausas(marcaje, usuario).await.map_err(|err| {
+      tracing::error!(
+        id_marcaje = marcaje,
+        usuario = ?usuario,
+        error = %err,
+        "Obteniendo las pausas del marcaje"
+      );
+      ServicioError::from(err)
+    })
+  }
+
+  /// Obtiene el marcaje sin finalizar del usuario en la fecha
+  /// o en el día anterior.
+  ///
+  /// Ver [`Self::buscar_marcaje_abierto`].
+  /// Si no existe devuelve un error para el usuario.
+  async fn marcaje_abierto(
+    &self,
+    usuario: u32,
+    fecha: NaiveDate,
+  ) -> Result<(NaiveDate, DescriptorMarcaje), ServicioError> {
+    self
+      .buscar_marcaje_abierto(usuario, fecha)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = usuario,
+          fecha = %fecha,
+          error = %err,
+          "Buscando el marcaje sin finalizar del usuario"
+        );
+        ServicioError::from(err)
+      })?
+      .ok_or_else(|| {
+        ServicioError::Usuario(format!(
+          "No existe ningún marcaje iniciado para el usuario: {} \
+          en la fecha: {}. No se puede registrar la pausa.",
+          usuario,
+          fecha.formato_corto()
+        ))
+      })
+  }
+
+  /// Busca el marcaje sin finalizar del usuario en la fecha o,
+  /// si no existe, en el día anterior por si cruza la medianoche.
+  ///
+  /// Devuelve la fecha del marcaje junto a su descriptor.
+  async fn buscar_marcaje_abierto(
+    &self,
+    usuario: u32,
+    fecha: NaiveDate,
+  ) -> Result<Option<(NaiveDate, DescriptorMarcaje)>, DBError> {
+    if let Some(marcaje) =
+      self.repo.marcaje_sin_hora_fin(usuario, fecha).await?
+    {
+      return Ok(Some((fecha, marcaje)));
+    }
+
+    let Some(anterior) = fecha.pred_opt() else {
+      return Ok(None);
+    };
+
+    Ok(
+      self
+        .repo
+        .marcaje_sin_hora_fin(usuario, anterior)
+        .await?
+        .map(|marcaje| (anterior, marcaje)),
+    )
+  }
+
+  /// Obtiene la última pausa de un marcaje.
+  async fn ultima_pausa(
+    &self,
+    marcaje: u32,
+  ) -> Result<Option<Pausa>, ServicioError> {
+    self.repo.ultima_pausa(marcaje).await.map_err(|err| {
+      tracing::error!(
+        id_marcaje = marcaje,
+        error = %err,
+        "Obteniendo la última pausa del marcaje"
+      );
+      ServicioError::from(err)
+    })
+  }
+
   /// Obtiene los últimos marcajes horarios de un usuario.
   pub async fn ultimos_marcajes(
     &self,
@@ -379,6 +1032,41 @@ impl MarcajeServicio {
       })
   }
 
+  /// Inserta el marcaje y lo sella en la cadena de hashes del usuario.
+  async fn insertar(
+    &self,
+    tr: &mut Transaccion<'_>,
+    reg: &Marcaje,
+    horario: u32,
+    cambio: &CambioMarcaje,
+  ) -> Result<u32, ServicioError> {
+    let id = self
+      .repo
+      .agregar(
+        tr,
+        reg,
+        horario,
+        reg.instantes_utc(&self.cnfg.zona_usuario(reg.usuario)),
+        cambio,
+      )
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          marcaje = ?reg,
+          error = %err,
+          "Creando marcaje horario"
+        );
+        ServicioError::from(err)
+      })?;
+
+    self
+      .srv_integridad
+      .sellar(tr, TablaSellada::Marcajes, id)
+      .await?;
+
+    Ok(id)
+  }
+
   /// Valida añadir un nuevo marcaje
   ///
   /// Se puede excluir un marcaje pasado como parámetro
@@ -405,9 +1093,25 @@ impl MarcajeServicio {
       )));
     }
 
+    if reg.hora_fin == Some(reg.hora_inicio) {
+      return Err(ServicioError::Usuario(format!(
+        "La hora de fin del marcaje debe ser distinta de la hora de \
+        inicio: {}. Si es anterior el marcaje termina al día siguiente.",
+        reg.hora_inicio
+      )));
+    }
+
+    let (inicio_utc, fin_utc) =
+      reg.instantes_utc(&self.cnfg.zona_usuario(reg.usuario));
+
     if self
       .repo
-      .hora_fin_vacia(reg.usuario, reg.fecha, excluir_marcaje_id)
+      .hora_fin_vacia(
+        reg.usuario,
+        reg.fecha,
+        (inicio_utc, fin_utc.unwrap_or(inicio_utc)),
+        excluir_marcaje_id,
+      )
       .await
       .map_err(ServicioError::from)?
     {
@@ -428,7 +1132,7 @@ impl MarcajeServicio {
         .hora_asignada_posterior(
           reg.usuario,
           reg.fecha,
-          reg.hora_inicio,
+          inicio_utc,
           excluir_marcaje_id,
         )
         .await
@@ -448,8 +1152,7 @@ impl MarcajeServicio {
       .repo
       .hora_asignada(
         reg.usuario,
-        reg.fecha,
-        reg.hora_inicio,
+        reg.fecha.and_time(reg.hora_inicio),
         excluir_marcaje_id,
       )
       .await
@@ -469,9 +1172,8 @@ impl MarcajeServicio {
         .repo
         .horas_solapadas(
           reg.usuario,
-          reg.fecha,
-          reg.hora_inicio,
-          hora_fin,
+          reg.fecha.and_time(reg.hora_inicio),
+          fecha_hora_marcaje(reg.fecha, reg.hora_inicio, hora_fin),
           excluir_marcaje_id,
         )
         .await
@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+
+/// Sesión activa de un usuario
+#[derive(Debug, Clone)]
+pub struct Sesion {
+  /// Identificador de la sesión firmado en el token
+  pub id: String,
+  pub usuario: u32,
+  /// Fecha de creación en UTC
+  pub creada_en: DateTime<Utc>,
+  /// Fecha de caducidad en UTC
+  pub caduca_en: DateTime<Utc>,
+}
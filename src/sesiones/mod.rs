@@ -0,0 +1,28 @@
+//! Gestiona las sesiones activas de los usuarios.
+//!
+//! El token de sesión es autocontenido y está firmado, pero
+//! para poder revocarlo antes de su caducidad cada sesión
+//! se registra en un almacén indexado por el identificador
+//! de la sesión. Una sesión que no se encuentra en el almacén
+//! se considera revocada.
+//!
+//! El almacén se mantiene en memoria. Opcionalmente se puede
+//! persistir en la base de datos para que las sesiones
+//! sobrevivan a los reinicios del servicio.
+//!
+//! Las sesiones de un usuario se revocan cuando:
+//! - El usuario cierra la sesión.
+//! - Se modifica la password, los roles o se desactiva el usuario.
+//! - Un administrador las finaliza.
+
+/// Módulo que gestiona el repositorio de las sesiones.
+mod repo;
+
+/// Módulo que gestiona el dominio de las sesiones.
+mod dominio;
+/// Módulo que gestiona el servicio de las sesiones.
+mod servicio;
+
+pub use dominio::*;
+pub use repo::*;
+pub use servicio::*;
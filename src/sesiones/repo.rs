@@ -0,0 +1,124 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::{
+  infra::{DBError, PoolConexion},
+  sesiones::Sesion,
+};
+
+/// Implementación del repositorio de las sesiones.
+pub struct SesionRepo {
+  pool: PoolConexion,
+}
+
+impl SesionRepo {
+  pub fn new(pool: PoolConexion) -> Self {
+    SesionRepo { pool }
+  }
+}
+
+impl SesionRepo {
+  /// Agrega una nueva sesión.
+  pub(in crate::sesiones) async fn agregar(
+    &self,
+    sesion: &Sesion,
+  ) -> Result<(), DBError> {
+    const QUERY: &str = "INSERT INTO sesiones
+      (id, usuario, creada_en, caduca_en)
+      VALUES (?, ?, ?, ?);";
+
+    sqlx::query(QUERY)
+      .bind(&sesion.id)
+      .bind(sesion.usuario)
+      .bind(sesion.creada_en)
+      .bind(sesion.caduca_en)
+      .execute(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(())
+  }
+
+  /// Actualiza la caducidad de una sesión.
+  pub(in crate::sesiones) async fn actualizar_caducidad(
+    &self,
+    id: &str,
+    caduca_en: DateTime<Utc>,
+  ) -> Result<(), DBError> {
+    const QUERY: &str = "UPDATE sesiones SET caduca_en = ? WHERE id = ?;";
+
+    sqlx::query(QUERY)
+      .bind(caduca_en)
+      .bind(id)
+      .execute(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(())
+  }
+
+  /// Elimina una sesión.
+  pub(in crate::sesiones) async fn eliminar(
+    &self,
+    id: &str,
+  ) -> Result<(), DBError> {
+    const QUERY: &str = "DELETE FROM sesiones WHERE id = ?;";
+
+    sqlx::query(QUERY)
+      .bind(id)
+      .execute(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(())
+  }
+
+  /// Elimina todas las sesiones de un usuario.
+  pub(in crate::sesiones) async fn eliminar_por_usuario(
+    &self,
+    usuario: u32,
+  ) -> Result<(), DBError> {
+    const QUERY: &str = "DELETE FROM sesiones WHERE usuario = ?;";
+
+    sqlx::query(QUERY)
+      .bind(usuario)
+      .execute(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(())
+  }
+
+  /// Elimina las sesiones caducadas y devuelve las vigentes.
+  pub(in crate::sesiones) async fn sesiones_vigentes(
+    &self,
+    ahora: DateTime<Utc>,
+  ) -> Result<Vec<Sesion>, DBError> {
+    const QUERY_ELIMINAR: &str = "DELETE FROM sesiones WHERE caduca_en <= ?;";
+    const QUERY: &str = "SELECT id, usuario, creada_en, caduca_en
+      FROM sesiones;";
+
+    sqlx::query(QUERY_ELIMINAR)
+      .bind(ahora)
+      .execute(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let rows = sqlx::query(QUERY)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(
+      rows
+        .iter()
+        .map(|row| Sesion {
+          id: row.get("id"),
+          usuario: row.get("usuario"),
+          creada_en: row.get("creada_en"),
+          caduca_en: row.get("caduca_en"),
+        })
+        .collect(),
+    )
+  }
+}
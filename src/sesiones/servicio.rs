@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+  infra::{ServicioError, middleware::DatosSesion},
+  sesiones::{Sesion, SesionRepo},
+};
+
+/// Servicio que mantiene el almacén de sesiones activas.
+///
+/// Las fechas de las sesiones se guardan en UTC, igual que
+/// los timestamps del token, para no depender de la zona horaria.
+pub struct SesionServicio {
+  // Si no existe el repositorio las sesiones solo están en memoria
+  repo: Option<SesionRepo>,
+  sesiones: RwLock<HashMap<String, Sesion>>,
+}
+
+impl SesionServicio {
+  pub fn new(repo: Option<SesionRepo>) -> Self {
+    SesionServicio {
+      repo,
+      sesiones: RwLock::new(HashMap::new()),
+    }
+  }
+}
+
+impl SesionServicio {
+  /// Carga las sesiones vigentes persistidas en la base de datos.
+  ///
+  /// Si las sesiones no se persisten no realiza ninguna acción.
+  pub async fn cargar(&self) -> Result<(), ServicioError> {
+    let Some(repo) = &self.repo else {
+      return Ok(());
+    };
+
+    let sesiones =
+      repo.sesiones_vigentes(self.ahora()).await.map_err(|err| {
+        tracing::error!(error = %err, "Cargando sesiones vigentes");
+        ServicioError::from(err)
+      })?;
+
+    tracing::info!(
+      sesiones = sesiones.len(),
+      "Se han cargado las sesiones vigentes"
+    );
+
+    let mut almacen = self.escribir();
+    for sesion in sesiones {
+      almacen.insert(sesion.id.clone(), sesion);
+    }
+
+    Ok(())
+  }
+
+  /// Registra una nueva sesión a partir de los datos del token.
+  ///
+  /// Aprovecha para descartar las sesiones caducadas.
+  pub async fn registrar(
+    &self,
+    datos: &DatosSesion,
+  ) -> Result<(), ServicioError> {
+    let ahora = self.ahora();
+
+    let sesion = Sesion {
+      id: datos.id.clone(),
+      usuario: datos.usuario,
+      creada_en: self.fecha(datos.iniciada_en),
+      caduca_en: self.fecha(datos.caduca_en),
+    };
+
+    if let Some(repo) = &self.repo {
+      repo.agregar(&sesion).await.map_err(|err| {
+        tracing::error!(
+          sesion = ?sesion, error = %err, "Persistiendo la sesión");
+        ServicioError::from(err)
+      })?;
+    }
+
+    let mut almacen = self.escribir();
+    almacen.retain(|_, s| s.caduca_en > ahora);
+    almacen.insert(sesion.id.clone(), sesion);
+
+    Ok(())
+  }
+
+  /// Actualiza la caducidad de una sesión renovada.
+  ///
+  /// Si la sesión ha sido revocada no se vuelve a registrar.
+  pub async fn renovar(
+    &self,
+    datos: &DatosSesion,
+  ) -> Result<(), ServicioError> {
+    let caduca_en = self.fecha(datos.caduca_en);
+
+    match self.escribir().get_mut(&datos.id) {
+      Some(sesion) => sesion.caduca_en = caduca_en,
+      None => return Ok(()),
+    }
+
+    if let Some(repo) = &self.repo {
+      repo
+        .actualizar_caducidad(&datos.id, caduca_en)
+        .await
+        .map_err(|err| {
+          tracing::error!(
+            sesion = datos.id,
+            error = %err,
+            "Actualizando la caducidad de la sesión"
+          );
+          ServicioError::from(err)
+        })?;
+    }
+
+    Ok(())
+  }
+
+  /// Devuelve true si la sesión no ha sido revocada.
+  pub fn activa(&self, id: &str) -> bool {
+    self.leer().contains_key(id)
+  }
+
+  /// Devuelve las sesiones activas de un usuario.
+  pub fn sesiones_usuario(&self, usuario: u32) -> Vec<Sesion> {
+    let ahora = self.ahora();
+
+    let mut sesiones: Vec<Sesion> = self
+      .leer()
+      .values()
+      .filter(|s| s.usuario == usuario && s.caduca_en > ahora)
+      .cloned()
+      .collect();
+
+    sesiones.sort_by_key(|s| s.creada_en);
+    sesiones
+  }
+
+  /// Revoca una sesión.
+  pub async fn revocar(&self, id: &str) -> Result<(), ServicioError> {
+    tracing::info!(sesion = id, "Revocando sesión");
+
+    self.escribir().remove(id);
+
+    if let Some(repo) = &self.repo {
+      repo.eliminar(id).await.map_err(|err| {
+        tracing::error!(sesion = id, error = %err, "Eliminando la sesión");
+        ServicioError::from(err)
+      })?;
+    }
+
+    Ok(())
+  }
+
+  /// Revoca todas las sesiones de un usuario.
+  pub async fn revocar_usuario(
+    &self,
+    usuario: u32,
+  ) -> Result<(), ServicioError> {
+    tracing::info!(usuario = usuario, "Revocando las sesiones del usuario");
+
+    self.escribir().retain(|_, s| s.usuario != usuario);
+
+    if let Some(repo) = &self.repo {
+      repo.eliminar_por_usuario(usuario).await.map_err(|err| {
+        tracing::error!(
+          usuario = usuario,
+          error = %err,
+          "Eliminando las sesiones del usuario"
+        );
+        ServicioError::from(err)
+      })?;
+    }
+
+    Ok(())
+  }
+
+  #[inline]
+  fn ahora(&self) -> DateTime<Utc> {
+    Utc::now()
+  }
+
+  /// Convierte un timestamp UNIX a la fecha en UTC
+  #[inline]
+  fn fecha(&self, timestamp: u64) -> DateTime<Utc> {
+    DateTime::<Utc>::from_timestamp(timestamp as i64, 0)
+      .unwrap_or_else(|| self.ahora())
+  }
+
+  #[inline]
+  fn leer(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Sesion>> {
+    self
+      .sesiones
+      .read()
+      .expect("Almacén de sesiones envenenado")
+  }
+
+  #[inline]
+  fn escribir(
+    &self,
+  ) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Sesion>> {
+    self
+      .sesiones
+      .write()
+      .expect("Almacén de sesiones envenenado")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::usuarios::Rol;
+
+  fn datos(id: &str, usuario: u32, caduca_en: u64) -> DatosSesion {
+    let ahora = Utc::now().timestamp() as u64;
+    DatosSesion {
+      id: id.to_string(),
+      caduca_en: ahora + caduca_en,
+      iniciada_en: ahora,
+      usuario,
+      roles: vec![Rol::Empleado as u8],
+    }
+  }
+
+  #[tokio::test]
+  async fn test_revocar_sesion() {
+    let servicio = SesionServicio::new(None);
+    servicio.registrar(&datos("a", 1, 3600)).await.unwrap();
+    servicio.registrar(&datos("b", 1, 3600)).await.unwrap();
+    assert!(servicio.activa("a"));
+
+    servicio.revocar("a").await.unwrap();
+    assert!(!servicio.activa("a"));
+    assert!(servicio.activa("b"));
+  }
+
+  #[tokio::test]
+  async fn test_revocar_sesiones_usuario() {
+    let servicio = SesionServicio::new(None);
+    servicio.registrar(&datos("a", 1, 3600)).await.unwrap();
+    servicio.registrar(&datos("b", 1, 3600)).await.unwrap();
+    servicio.registrar(&datos("c", 2, 3600)).await.unwrap();
+
+    servicio.revocar_usuario(1).await.unwrap();
+    assert!(!servicio.activa("a"));
+    assert!(!servicio.activa("b"));
+    assert!(servicio.activa("c"));
+    assert!(servicio.sesiones_usuario(1).is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_renovar_no_reactiva_sesion_revocada() {
+    let servicio = SesionServicio::new(None);
+    servicio.registrar(&datos("a", 1, 3600)).await.unwrap();
+    servicio.revocar("a").await.unwrap();
+
+    servicio.renovar(&datos("a", 1, 7200)).await.unwrap();
+    assert!(!servicio.activa("a"));
+    assert!(servicio.sesiones_usuario(1).is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_renovar_actualiza_caducidad_en_utc() {
+    let servicio = SesionServicio::new(None);
+    let inicial = datos("a", 1, 3600);
+    servicio.registrar(&inicial).await.unwrap();
+
+    let renovada = datos("a", 1, 7200);
+    servicio.renovar(&renovada).await.unwrap();
+
+    let sesiones = servicio.sesiones_usuario(1);
+    assert_eq!(sesiones.len(), 1);
+    assert_eq!(sesiones[0].caduca_en.timestamp() as u64, renovada.caduca_en);
+    assert_eq!(
+      sesiones[0].creada_en.timestamp() as u64,
+      inicial.iniciada_en
+    );
+  }
+
+  #[tokio::test]
+  async fn test_registrar_descarta_caducadas() {
+    let servicio = SesionServicio::new(None);
+    let mut caducada = datos("a", 1, 0);
+    caducada.caduca_en -= 60;
+    servicio.registrar(&caducada).await.unwrap();
+    assert!(servicio.sesiones_usuario(1).is_empty());
+
+    servicio.registrar(&datos("b", 1, 3600)).await.unwrap();
+    assert!(!servicio.activa("a"));
+    assert!(servicio.activa("b"));
+  }
+}
@@ -1,7 +1,9 @@
-use chrono::{NaiveDateTime, Utc};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use chrono_tz::Tz;
 use derive_builder::Builder;
 
+use crate::infra::DominioWithCacheUsuario;
+
 #[repr(u8)]
 #[derive(Copy, Clone, Debug)]
 pub enum TipoTraza {
@@ -17,6 +19,47 @@ pub enum TipoTraza {
   IncReSolicitar = 10,
   UsrCalendariosModificados = 11,
   IncCancelada = 12,
+  UsrBloqueado = 13,
+  UsrDesbloqueado = 14,
+  ConsultaInspeccion = 15,
+  UsrZonaHorariaModificada = 16,
+  UsrCentroModificado = 17,
+}
+
+impl TipoTraza {
+  /// Obtiene el tipo de traza de su valor numérico.
+  ///
+  /// Devuelve None si el valor no corresponde a ningún tipo.
+  pub fn from_u8(value: u8) -> Option<Self> {
+    let tipo = match value {
+      1 => TipoTraza::CreacionUsuario,
+      2 => TipoTraza::ActualizacionUsuario,
+      3 => TipoTraza::UsrDniModificado,
+      4 => TipoTraza::UsrNombreModificado,
+      5 => TipoTraza::UsrRolesModificados,
+      6 => TipoTraza::UsrActivoModificado,
+      7 => TipoTraza::PasswordModificada,
+      8 => TipoTraza::PrimerInicio,
+      9 => TipoTraza::IncConflicto,
+      10 => TipoTraza::IncReSolicitar,
+      11 => TipoTraza::UsrCalendariosModificados,
+      12 => TipoTraza::IncCancelada,
+      13 => TipoTraza::UsrBloqueado,
+      14 => TipoTraza::UsrDesbloqueado,
+      15 => TipoTraza::ConsultaInspeccion,
+      16 => TipoTraza::UsrZonaHorariaModificada,
+      17 => TipoTraza::UsrCentroModificado,
+      _ => return None,
+    };
+
+    Some(tipo)
+  }
+}
+
+impl From<u8> for TipoTraza {
+  fn from(value: u8) -> Self {
+    TipoTraza::from_u8(value).expect("Valor de tipo de traza no válido")
+  }
 }
 
 #[repr(u8)]
@@ -24,11 +67,38 @@ pub enum TipoTraza {
 pub enum Entidad {
   Usuario = 1,
   Incidencia = 2,
+  /// Consultas realizadas por la inspección de trabajo.
+  /// El identificador es el empleado consultado, o 0 si
+  /// la consulta no es de un empleado concreto
+  Inspeccion = 3,
+}
+
+impl Entidad {
+  /// Obtiene la entidad de su valor numérico.
+  ///
+  /// Devuelve None si el valor no corresponde a ninguna entidad.
+  pub fn from_u8(value: u8) -> Option<Self> {
+    match value {
+      1 => Some(Entidad::Usuario),
+      2 => Some(Entidad::Incidencia),
+      3 => Some(Entidad::Inspeccion),
+      _ => None,
+    }
+  }
+}
+
+impl From<u8> for Entidad {
+  fn from(value: u8) -> Self {
+    Entidad::from_u8(value).expect("Valor de entidad de traza no válido")
+  }
 }
 
 #[derive(Builder, Debug)]
 #[builder(pattern = "owned", build_fn(private, name = "final_build"))]
 pub struct Traza {
+  /// Identificador de la traza. Es 0 hasta que se persiste
+  #[builder(default)]
+  pub id: u32,
   pub autor: Option<u32>,
   pub tipo: TipoTraza,
   pub entidad: Entidad,
@@ -56,8 +126,86 @@ impl TrazaBuilder {
       .motivo(None)
   }
 
+  pub fn with_inspeccion(inspector: u32, empleado: u32) -> TrazaBuilder {
+    TrazaBuilder::default()
+      .autor(Some(inspector))
+      .tipo(TipoTraza::ConsultaInspeccion)
+      .entidad(Entidad::Inspeccion)
+      .entidad_id(empleado)
+      .motivo(None)
+  }
+
   pub fn build(mut self, tz: &Tz) -> Traza {
     self.fecha = Some(Utc::now().with_timezone(tz).naive_local());
     self.final_build().expect("Error al formar traza")
   }
 }
+
+/// Filtros para consultar las trazas.
+///
+/// Los filtros que no se indican no se aplican.
+#[derive(Debug, Default)]
+pub struct FiltroTrazas {
+  /// Trazas relacionadas con el usuario: las del propio usuario,
+  /// las de sus incidencias y las provocadas por él
+  pub usuario: Option<u32>,
+  pub entidad: Option<Entidad>,
+  pub entidad_id: Option<u32>,
+  pub autor: Option<u32>,
+  pub tipo: Option<TipoTraza>,
+  pub fecha_inicio: Option<NaiveDate>,
+  pub fecha_fin: Option<NaiveDate>,
+  /// Página a obtener, empezando en 1
+  pub pagina: u32,
+}
+
+impl FiltroTrazas {
+  /// Número de trazas que se saltan para obtener la página.
+  ///
+  /// La página 0 se trata como la primera.
+  pub fn desplazamiento(&self, tamanio: u32) -> u64 {
+    u64::from(self.pagina.saturating_sub(1)) * u64::from(tamanio)
+  }
+}
+
+/// Página de trazas que cumplen un filtro.
+pub struct PaginaTrazas {
+  pub trazas: DominioWithCacheUsuario<Traza>,
+  pub pagina: u32,
+  /// Número de trazas por página
+  pub tamanio: u32,
+  /// Número total de trazas que cumplen el filtro
+  pub total: u64,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_desplazamiento_pagina() {
+    let filtro = |pagina| FiltroTrazas {
+      pagina,
+      ..Default::default()
+    };
+
+    assert_eq!(filtro(0).desplazamiento(20), 0);
+    assert_eq!(filtro(1).desplazamiento(20), 0);
+    assert_eq!(filtro(3).desplazamiento(20), 40);
+  }
+
+  #[test]
+  fn test_valores_desconocidos() {
+    assert!(TipoTraza::from_u8(0).is_none());
+    assert!(matches!(
+      TipoTraza::from_u8(15),
+      Some(TipoTraza::ConsultaInspeccion)
+    ));
+    assert!(matches!(
+      TipoTraza::from_u8(16),
+      Some(TipoTraza::UsrZonaHorariaModificada)
+    ));
+    assert!(Entidad::from_u8(9).is_none());
+    assert!(matches!(Entidad::from_u8(2), Some(Entidad::Incidencia)));
+  }
+}
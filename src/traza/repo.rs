@@ -1,14 +1,23 @@
+use sqlx::{MySql, QueryBuilder, Row};
+
 use crate::{
-  infra::{DBError, Transaccion},
-  traza::Traza,
+  infra::{DBError, DominioWithCacheUsuario, PoolConexion, Transaccion},
+  traza::{Entidad, FiltroTrazas, Traza},
+  usuarios::DescriptorUsuario,
 };
 
 /// Implementación del repositorio de trazas.
-pub struct TrazaRepo {}
+pub struct TrazaRepo {
+  pool: PoolConexion,
+}
 
 impl TrazaRepo {
-  pub fn new() -> Self {
-    TrazaRepo {}
+  pub fn new(pool: PoolConexion) -> Self {
+    TrazaRepo { pool }
+  }
+
+  pub(in crate::traza) fn conexion(&self) -> &PoolConexion {
+    &self.pool
   }
 }
 
@@ -36,4 +45,122 @@ impl TrazaRepo {
 
     Ok(result.last_insert_id() as u32)
   }
+
+  /// Obtiene una página de las trazas que cumplen el filtro
+  /// junto con el número total de trazas que lo cumplen.
+  ///
+  /// Las trazas se ordenan de la más reciente a la más antigua.
+  pub(in crate::traza) async fn trazas(
+    &self,
+    filtro: &FiltroTrazas,
+    tamanio: u32,
+  ) -> Result<(DominioWithCacheUsuario<Traza>, u64), DBError> {
+    let mut qb = QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM trazas t");
+    push_filtros(&mut qb, filtro);
+
+    let total: i64 = qb
+      .build_query_scalar()
+      .fetch_one(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    let mut qb = QueryBuilder::<MySql>::new(
+      "SELECT t.id, t.autor, t.tipo, t.fecha,
+      t.entidad, t.entidad_id, t.motivo,
+      u.nombre, u.primer_apellido, u.segundo_apellido
+      FROM trazas t
+      LEFT JOIN usuarios u ON t.autor = u.id",
+    );
+    push_filtros(&mut qb, filtro);
+
+    qb.push(" ORDER BY t.fecha DESC, t.id DESC LIMIT ")
+      .push_bind(tamanio)
+      .push(" OFFSET ")
+      .push_bind(filtro.desplazamiento(tamanio));
+
+    let rows = qb
+      .build()
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok((trazas_from_rows(rows), total as u64))
+  }
+}
+
+/// Añade las condiciones del filtro de trazas a la consulta.
+fn push_filtros(qb: &mut QueryBuilder<MySql>, filtro: &FiltroTrazas) {
+  qb.push(" WHERE 1 = 1");
+
+  if let Some(usuario) = filtro.usuario {
+    qb.push(" AND ((t.entidad = ")
+      .push_bind(Entidad::Usuario as u8)
+      .push(" AND t.entidad_id = ")
+      .push_bind(usuario)
+      .push(") OR (t.entidad = ")
+      .push_bind(Entidad::Incidencia as u8)
+      .push(" AND t.entidad_id IN (SELECT i.id FROM incidencias i WHERE i.usuario = ")
+      .push_bind(usuario)
+      .push(")) OR t.autor = ")
+      .push_bind(usuario)
+      .push(")");
+  }
+
+  if let Some(entidad) = filtro.entidad {
+    qb.push(" AND t.entidad = ").push_bind(entidad as u8);
+  }
+
+  if let Some(entidad_id) = filtro.entidad_id {
+    qb.push(" AND t.entidad_id = ").push_bind(entidad_id);
+  }
+
+  if let Some(autor) = filtro.autor {
+    qb.push(" AND t.autor = ").push_bind(autor);
+  }
+
+  if let Some(tipo) = filtro.tipo {
+    qb.push(" AND t.tipo = ").push_bind(tipo as u8);
+  }
+
+  if let Some(fi) = filtro.fecha_inicio {
+    qb.push(" AND t.fecha >= ")
+      .push_bind(fi.and_hms_opt(0, 0, 0).unwrap()); // Inicio del día
+  }
+
+  if let Some(ff) = filtro.fecha_fin {
+    qb.push(" AND t.fecha <= ")
+      .push_bind(ff.and_hms_opt(23, 59, 59).unwrap()); // Fin del día
+  }
+}
+
+/// Convierte las filas de trazas con los datos del autor.
+fn trazas_from_rows(
+  rows: Vec<sqlx::mysql::MySqlRow>,
+) -> DominioWithCacheUsuario<Traza> {
+  let mut resultado = DominioWithCacheUsuario::<Traza>::new(rows.len());
+
+  for row in rows {
+    let autor = row.try_get::<u32, _>("autor").ok();
+
+    if let Some(id) = autor {
+      resultado.push_usuario(DescriptorUsuario {
+        id,
+        nombre: row.get("nombre"),
+        primer_apellido: row.get("primer_apellido"),
+        segundo_apellido: row.get("segundo_apellido"),
+      });
+    }
+
+    resultado.push_entidad(Traza {
+      id: row.get("id"),
+      autor,
+      tipo: row.get::<u8, _>("tipo").into(),
+      entidad: row.get::<u8, _>("entidad").into(),
+      entidad_id: row.get("entidad_id"),
+      fecha: row.get("fecha"),
+      motivo: row.get("motivo"),
+    });
+  }
+
+  resultado
 }
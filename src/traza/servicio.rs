@@ -1,15 +1,27 @@
 use crate::{
+  config::ConfigTrabajo,
   infra::{ServicioError, Transaccion},
-  traza::{Traza, TrazaRepo},
+  integridad::{IntegridadServicio, TablaSellada},
+  traza::{FiltroTrazas, PaginaTrazas, Traza, TrazaBuilder, TrazaRepo},
 };
 
 pub struct TrazaServicio {
+  cnfg: ConfigTrabajo,
   traza_repo: TrazaRepo,
+  srv_integridad: IntegridadServicio,
 }
 
 impl TrazaServicio {
-  pub fn new(traza_repo: TrazaRepo) -> Self {
-    TrazaServicio { traza_repo }
+  pub fn new(
+    cnfg: ConfigTrabajo,
+    traza_repo: TrazaRepo,
+    srv_integridad: IntegridadServicio,
+  ) -> Self {
+    TrazaServicio {
+      cnfg,
+      traza_repo,
+      srv_integridad,
+    }
   }
 }
 
@@ -20,18 +32,98 @@ impl TrazaServicio {
   /// una transacción que afecta a otros datos.
   /// Es necesario proporcionar una transacción
   /// para asegurar la consistencia de los datos.
+  ///
+  /// La traza se sella en la cadena de hashes de su entidad.
   pub async fn agregar(
     &self,
     trans: &mut Transaccion<'_>,
     traza: &Traza,
   ) -> Result<u32, ServicioError> {
-    self.traza_repo.agregar(trans, traza).await.map_err(|err| {
+    let id = self.traza_repo.agregar(trans, traza).await.map_err(|err| {
       tracing::error!(
         traza = ?traza,
         error = %err,
         "Agregando una nueva traza al sistema"
       );
       ServicioError::from(err)
-    })
+    })?;
+
+    self
+      .srv_integridad
+      .sellar(trans, TablaSellada::Trazas, id)
+      .await?;
+
+    Ok(id)
+  }
+
+  /// Registra una consulta realizada por la inspección de trabajo.
+  ///
+  /// La traza no depende de otros datos, por lo que
+  /// se agrega en su propia transacción.
+  pub async fn registrar_inspeccion(
+    &self,
+    inspector: u32,
+    empleado: u32,
+    motivo: String,
+  ) -> Result<u32, ServicioError> {
+    let traza = TrazaBuilder::with_inspeccion(inspector, empleado)
+      .motivo(Some(motivo))
+      .build(&self.cnfg.zona_horaria);
+
+    let mut tr = self
+      .traza_repo
+      .conexion()
+      .empezar_transaccion()
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          traza = ?traza,
+          error = %err,
+          "Iniciando transacción para registrar traza"
+        );
+        ServicioError::from(err)
+      })?;
+
+    let id = self.agregar(&mut tr, &traza).await?;
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+        traza = ?traza,
+        error = %err,
+        "Commit transacción para registrar traza"
+      );
+      ServicioError::from(err)
+    })?;
+
+    Ok(id)
+  }
+
+  /// Obtiene una página de las trazas que cumplen el filtro.
+  ///
+  /// El tamaño de la página viene dado por la configuración.
+  pub async fn trazas(
+    &self,
+    filtro: &FiltroTrazas,
+  ) -> Result<PaginaTrazas, ServicioError> {
+    let tamanio = u32::from(self.cnfg.limites.trazas);
+
+    self
+      .traza_repo
+      .trazas(filtro, tamanio)
+      .await
+      .map(|(trazas, total)| PaginaTrazas {
+        trazas,
+        pagina: filtro.pagina.max(1),
+        tamanio,
+        total,
+      })
+      .map_err(|err| {
+        tracing::error!(
+          filtro = ?filtro,
+          error = %err,
+          "Obteniendo trazas filtradas"
+        );
+        ServicioError::from(err)
+      })
   }
 }
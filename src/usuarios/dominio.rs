@@ -1,6 +1,7 @@
 use std::fmt::Debug;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, TimeDelta};
+use chrono_tz::Tz;
 use smallvec::SmallVec;
 
 use crate::infra::{Dni, Password};
@@ -31,21 +32,28 @@ pub enum Rol {
   Supervidor = 7,
 }
 
-impl From<u8> for Rol {
-  fn from(value: u8) -> Self {
+impl Rol {
+  /// Convierte el valor en un rol. Devuelve None si no es válido.
+  pub fn from_u8(value: u8) -> Option<Self> {
     match value {
-      1 => Rol::Empleado,
-      2 => Rol::Gestor,
-      3 => Rol::Admin,
-      4 => Rol::Director,
-      5 => Rol::Registrador,
-      6 => Rol::Inspector,
-      7 => Rol::Supervidor,
-      _ => panic!("Valor de Rol no válido"),
+      1 => Some(Rol::Empleado),
+      2 => Some(Rol::Gestor),
+      3 => Some(Rol::Admin),
+      4 => Some(Rol::Director),
+      5 => Some(Rol::Registrador),
+      6 => Some(Rol::Inspector),
+      7 => Some(Rol::Supervidor),
+      _ => None,
     }
   }
 }
 
+impl From<u8> for Rol {
+  fn from(value: u8) -> Self {
+    Rol::from_u8(value).expect("Valor de Rol no válido")
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UsuarioCalendario {
   pub calendario: u32,
@@ -74,6 +82,10 @@ pub struct Usuario {
   pub inicio: Option<NaiveDateTime>,
   pub roles: SmallVec<[Rol; 7]>,
   pub calendarios: Vec<UsuarioCalendario>,
+  // Si es None se usa la zona horaria de la configuración
+  pub zona_horaria: Option<Tz>,
+  // Centro de trabajo al que pertenece
+  pub centro: Option<u32>,
 }
 
 impl Usuario {
@@ -120,6 +132,13 @@ impl Usuario {
     true
   }
 
+  /// Indica si el usuario pertenece al centro de trabajo.
+  ///
+  /// Si no se indica el centro pertenecen todos los usuarios.
+  pub fn pertenece_centro(&self, centro: Option<u32>) -> bool {
+    centro.is_none() || self.centro == centro
+  }
+
   pub fn nombre_completo(&self) -> String {
     format!(
       "{} {} {}",
@@ -149,6 +168,185 @@ impl Debug for Usuario {
       .field("inicio", &self.inicio)
       .field("roles", &self.roles)
       .field("calendarios", &self.calendarios)
+      .field("zona_horaria", &self.zona_horaria)
+      .field("centro", &self.centro)
       .finish()
   }
 }
+
+/// Política de bloqueo de accesos por intentos fallidos de login.
+#[derive(Debug, Clone, Copy)]
+pub struct PoliticaBloqueo {
+  /// Número de fallos consecutivos a partir del cual se bloquea.
+  pub max_fallos: u32,
+  /// Segundos tras los cuales se olvidan los fallos previos.
+  pub ventana: u64,
+  /// Segundos de bloqueo al alcanzar el máximo de fallos.
+  /// Se duplica por cada fallo posterior (espera exponencial).
+  pub espera_base: u64,
+  /// Segundos máximos de bloqueo.
+  pub espera_maxima: u64,
+}
+
+impl PoliticaBloqueo {
+  /// Devuelve el tiempo de bloqueo para el número de fallos.
+  ///
+  /// Si no se ha alcanzado el máximo de fallos devuelve None.
+  pub fn espera(&self, fallos: u32) -> Option<TimeDelta> {
+    if fallos < self.max_fallos {
+      return None;
+    }
+
+    let exponente = (fallos - self.max_fallos).min(32);
+    let segundos = self
+      .espera_base
+      .saturating_mul(1u64 << exponente)
+      .min(self.espera_maxima);
+
+    Some(TimeDelta::seconds(segundos as i64))
+  }
+}
+
+/// Estado de los intentos fallidos de login de un usuario.
+///
+/// Sirve también para controlar los intentos por IP.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BloqueoAcceso {
+  pub intentos_fallidos: u32,
+  pub ultimo_fallo: Option<NaiveDateTime>,
+  pub bloqueado_hasta: Option<NaiveDateTime>,
+}
+
+impl BloqueoAcceso {
+  /// Devuelve true si el acceso está bloqueado en la fecha indicada.
+  #[inline]
+  pub fn bloqueado(&self, ahora: NaiveDateTime) -> bool {
+    self.bloqueado_hasta.is_some_and(|hasta| hasta > ahora)
+  }
+
+  /// Devuelve true si los fallos registrados han caducado.
+  pub fn caducado(
+    &self,
+    politica: &PoliticaBloqueo,
+    ahora: NaiveDateTime,
+  ) -> bool {
+    !self.bloqueado(ahora)
+      && self.ultimo_fallo.is_none_or(|ultimo| {
+        ahora - ultimo > TimeDelta::seconds(politica.ventana as i64)
+      })
+  }
+
+  /// Registra un intento fallido aplicando la política de bloqueo.
+  ///
+  /// Devuelve true si el intento provoca un bloqueo.
+  pub fn registrar_fallo(
+    &mut self,
+    politica: &PoliticaBloqueo,
+    ahora: NaiveDateTime,
+  ) -> bool {
+    if self.caducado(politica, ahora) {
+      self.intentos_fallidos = 0;
+    }
+
+    self.intentos_fallidos = self.intentos_fallidos.saturating_add(1);
+    self.ultimo_fallo = Some(ahora);
+
+    match politica.espera(self.intentos_fallidos) {
+      Some(espera) => {
+        self.bloqueado_hasta = Some(ahora + espera);
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::NaiveDate;
+
+  use super::*;
+
+  const POLITICA: PoliticaBloqueo = PoliticaBloqueo {
+    max_fallos: 3,
+    ventana: 600,
+    espera_base: 60,
+    espera_maxima: 300,
+  };
+
+  fn fecha(hora: u32, min: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2025, 3, 10)
+      .unwrap()
+      .and_hms_opt(hora, min, 0)
+      .unwrap()
+  }
+
+  fn usuario(centro: Option<u32>) -> Usuario {
+    Usuario {
+      id: 1,
+      dni: Dni::new("12345678Z".to_string()),
+      email: String::new(),
+      nombre: "Ana".to_string(),
+      primer_apellido: String::new(),
+      segundo_apellido: String::new(),
+      password: None,
+      activo: None,
+      inicio: None,
+      roles: SmallVec::new(),
+      calendarios: vec![],
+      zona_horaria: None,
+      centro,
+    }
+  }
+
+  #[test]
+  fn test_rol_from_u8() {
+    assert_eq!(Rol::from_u8(1), Some(Rol::Empleado));
+    assert_eq!(Rol::from_u8(7), Some(Rol::Supervidor));
+    assert_eq!(Rol::from_u8(0), None);
+    assert_eq!(Rol::from_u8(8), None);
+  }
+
+  #[test]
+  fn test_pertenece_centro() {
+    assert!(usuario(Some(2)).pertenece_centro(None));
+    assert!(usuario(Some(2)).pertenece_centro(Some(2)));
+    assert!(!usuario(Some(2)).pertenece_centro(Some(3)));
+    assert!(usuario(None).pertenece_centro(None));
+    assert!(!usuario(None).pertenece_centro(Some(2)));
+  }
+
+  #[test]
+  fn test_espera_exponencial() {
+    assert_eq!(POLITICA.espera(2), None);
+    assert_eq!(POLITICA.espera(3), Some(TimeDelta::seconds(60)));
+    assert_eq!(POLITICA.espera(4), Some(TimeDelta::seconds(120)));
+    assert_eq!(POLITICA.espera(5), Some(TimeDelta::seconds(240)));
+    assert_eq!(POLITICA.espera(6), Some(TimeDelta::seconds(300)));
+    assert_eq!(POLITICA.espera(100), Some(TimeDelta::seconds(300)));
+  }
+
+  #[test]
+  fn test_bloqueo_al_alcanzar_max_fallos() {
+    let mut bloqueo = BloqueoAcceso::default();
+
+    assert!(!bloqueo.registrar_fallo(&POLITICA, fecha(9, 0)));
+    assert!(!bloqueo.registrar_fallo(&POLITICA, fecha(9, 1)));
+    assert!(bloqueo.registrar_fallo(&POLITICA, fecha(9, 2)));
+
+    assert!(bloqueo.bloqueado(fecha(9, 2)));
+    assert!(!bloqueo.bloqueado(fecha(9, 3)));
+    assert_eq!(bloqueo.bloqueado_hasta, Some(fecha(9, 3)));
+  }
+
+  #[test]
+  fn test_fallos_caducados_fuera_de_ventana() {
+    let mut bloqueo = BloqueoAcceso::default();
+
+    bloqueo.registrar_fallo(&POLITICA, fecha(9, 0));
+    bloqueo.registrar_fallo(&POLITICA, fecha(9, 1));
+
+    assert!(!bloqueo.registrar_fallo(&POLITICA, fecha(9, 30)));
+    assert_eq!(bloqueo.intentos_fallidos, 1);
+  }
+}
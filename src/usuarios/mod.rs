@@ -13,6 +13,12 @@
 //!
 //! La definición de cada rol se encuentra en: [`dominio::Rol`]
 //!
+//! Cada usuario puede tener su propia zona horaria (por ejemplo, el
+//! personal de Canarias). Si no la tiene se usa la de la configuración.
+//! Las zonas se mantienen en memoria en [`crate::config::ConfigTrabajo`]
+//! para que el resto de módulos calculen la fecha y hora local del
+//! usuario en los marcajes, trazas, incidencias e informes.
+//!
 
 /// Módulo que gestiona el acceso a datos para los usuarios
 mod repo;
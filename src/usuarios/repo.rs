@@ -1,10 +1,13 @@
 use chrono::{NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
 use smallvec::SmallVec;
-use sqlx::{Row, mysql::MySqlRow};
+use sqlx::{MySql, QueryBuilder, Row, mysql::MySqlRow};
 
 use crate::{
   infra::{DBError, Dni, Password, PoolConexion, Transaccion},
-  usuarios::{DescriptorUsuario, Rol, Usuario, UsuarioCalendario},
+  usuarios::{
+    BloqueoAcceso, DescriptorUsuario, Rol, Usuario, UsuarioCalendario,
+  },
 };
 
 /// Implementación del repositorio de los usuarios y horarios.
@@ -88,9 +91,26 @@ impl UsuarioRepo {
     Ok(())
   }
 
+  /// Obtiene los calendarios por defecto de un centro de trabajo.
+  pub(in crate::usuarios) async fn calendarios_centro(
+    &self,
+    centro: u32,
+  ) -> Result<Vec<u32>, DBError> {
+    const QUERY: &str = "SELECT calendario
+      FROM centros_calendarios
+      WHERE centro = ?";
+
+    sqlx::query_scalar(QUERY)
+      .bind(centro)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)
+  }
+
   /// Crea un nuevo usuario.
   ///
-  /// El secreto es necesario para encriptar el DNI y la password.
+  /// El secreto es necesario para encriptar el DNI.
+  /// La password se almacena como hash.
   pub(in crate::usuarios) async fn crear_usuario(
     &self,
     trans: &mut Transaccion<'_>,
@@ -99,8 +119,8 @@ impl UsuarioRepo {
   ) -> Result<u32, DBError> {
     const QUERY: &str = "INSERT INTO usuarios 
       (dni, dni_hash, email, nombre, primer_apellido, segundo_apellido,
-      password, activo, inicio) 
-      VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?);";
+      password, activo, inicio, zona_horaria, centro) 
+      VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);";
 
     let dni = usuario
       .dni
@@ -111,7 +131,8 @@ impl UsuarioRepo {
       .password
       .as_ref()
       .unwrap()
-      .encriptar(secreto)
+      .hash_password()
+      .await
       .map_err(DBError::cripto_from)?;
 
     let result = sqlx::query(QUERY)
@@ -124,6 +145,8 @@ impl UsuarioRepo {
       .bind(&password)
       .bind(usuario.activo)
       .bind(usuario.inicio)
+      .bind(usuario.zona_horaria.map(|tz| tz.name()))
+      .bind(usuario.centro)
       .execute(&mut **trans.deref_mut())
       .await
       .map_err(DBError::from_sqlx)?;
@@ -133,7 +156,8 @@ impl UsuarioRepo {
 
   /// Actualiza un usuario.
   ///
-  /// Solo se puede actualizar el DNI, nombre, apellidos y activo.
+  /// Solo se puede actualizar el DNI, nombre, apellidos, activo,
+  /// zona horaria y centro de trabajo.
   ///
   /// El secreto es necesario para encriptar el DNI.
   pub(in crate::usuarios) async fn actualizar_usuario(
@@ -146,7 +170,7 @@ impl UsuarioRepo {
     const QUERY: &str = "UPDATE usuarios SET
       dni = ?, dni_hash = ?, email = ?, nombre = ?,
       primer_apellido = ?, segundo_apellido = ?,
-      activo = ?, inicio = ?
+      activo = ?, inicio = ?, zona_horaria = ?, centro = ?
       WHERE id = ?;";
 
     let dni = usuario
@@ -163,6 +187,8 @@ impl UsuarioRepo {
       .bind(&usuario.segundo_apellido)
       .bind(usuario.activo)
       .bind(inicio)
+      .bind(usuario.zona_horaria.map(|tz| tz.name()))
+      .bind(usuario.centro)
       .bind(usuario.id)
       .execute(&mut **trans.deref_mut())
       .await
@@ -177,17 +203,19 @@ impl UsuarioRepo {
 
   /// Actualiza la password.
   ///
-  /// El secreto es necesario para encriptar la password.
+  /// La password se almacena como hash.
   pub(in crate::usuarios) async fn actualizar_password(
     &self,
     trans: &mut Transaccion<'_>,
-    secreto: &str,
     usuario: u32,
     password: &Password,
   ) -> Result<(), DBError> {
     const QUERY: &str = "UPDATE usuarios SET password = ? WHERE id = ?;";
 
-    let pass = password.encriptar(secreto).map_err(DBError::cripto_from)?;
+    let pass = password
+      .hash_password()
+      .await
+      .map_err(DBError::cripto_from)?;
 
     let res = sqlx::query(QUERY)
       .bind(&pass)
@@ -228,6 +256,86 @@ impl UsuarioRepo {
     }
   }
 
+  /// Obtiene el estado de los intentos fallidos de login de un usuario.
+  pub(in crate::usuarios) async fn bloqueo(
+    &self,
+    usuario: u32,
+  ) -> Result<BloqueoAcceso, DBError> {
+    const QUERY: &str =
+      "SELECT intentos_fallidos, ultimo_fallo, bloqueado_hasta
+      FROM usuarios
+      WHERE id = ?;";
+
+    let row = sqlx::query(QUERY)
+      .bind(usuario)
+      .fetch_one(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(BloqueoAcceso {
+      intentos_fallidos: row.get("intentos_fallidos"),
+      ultimo_fallo: row.get("ultimo_fallo"),
+      bloqueado_hasta: row.get("bloqueado_hasta"),
+    })
+  }
+
+  /// Obtiene el estado de los intentos fallidos de login de un usuario
+  /// bloqueando su fila hasta que termine la transacción.
+  ///
+  /// Evita que dos logins fallidos simultáneos pierdan un intento.
+  pub(in crate::usuarios) async fn bloqueo_para_actualizar(
+    &self,
+    trans: &mut Transaccion<'_>,
+    usuario: u32,
+  ) -> Result<BloqueoAcceso, DBError> {
+    const QUERY: &str =
+      "SELECT intentos_fallidos, ultimo_fallo, bloqueado_hasta
+      FROM usuarios
+      WHERE id = ?
+      FOR UPDATE;";
+
+    let row = sqlx::query(QUERY)
+      .bind(usuario)
+      .fetch_one(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(BloqueoAcceso {
+      intentos_fallidos: row.get("intentos_fallidos"),
+      ultimo_fallo: row.get("ultimo_fallo"),
+      bloqueado_hasta: row.get("bloqueado_hasta"),
+    })
+  }
+
+  /// Actualiza el estado de los intentos fallidos de login de un usuario.
+  pub(in crate::usuarios) async fn actualizar_bloqueo(
+    &self,
+    trans: &mut Transaccion<'_>,
+    usuario: u32,
+    bloqueo: &BloqueoAcceso,
+  ) -> Result<(), DBError> {
+    const QUERY: &str = "UPDATE usuarios
+      SET intentos_fallidos = ?, ultimo_fallo = ?, bloqueado_hasta = ?
+      WHERE id = ?;";
+
+    let res = sqlx::query(QUERY)
+      .bind(bloqueo.intentos_fallidos)
+      .bind(bloqueo.ultimo_fallo)
+      .bind(bloqueo.bloqueado_hasta)
+      .bind(usuario)
+      .execute(&mut **trans.deref_mut())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    if res.rows_affected() == 0 {
+      Err(DBError::registro_vacio(
+        "Actualizando bloqueo de usuario".to_string(),
+      ))
+    } else {
+      Ok(())
+    }
+  }
+
   /// Verifica que no exista un dni duplicado.
   pub(in crate::usuarios) async fn dni_duplicado(
     &self,
@@ -247,14 +355,38 @@ impl UsuarioRepo {
     Ok(count > 0)
   }
 
-  /// Obtiene la password de un usuario
+  /// Migra la password encriptada de versiones previas a su hash.
   ///
-  /// La clave sirve para desencriptar las password
+  /// Solo actualiza si la password almacenada sigue siendo la
+  /// encriptada, para no pisar un cambio de password concurrente.
+  pub(in crate::usuarios) async fn migrar_password(
+    &self,
+    usuario: u32,
+    encriptada: &str,
+    hash: &str,
+  ) -> Result<(), DBError> {
+    const QUERY: &str =
+      "UPDATE usuarios SET password = ? WHERE id = ? AND password = ?;";
+
+    sqlx::query(QUERY)
+      .bind(hash)
+      .bind(usuario)
+      .bind(encriptada)
+      .execute(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(())
+  }
+
+  /// Obtiene la password almacenada de un usuario
+  ///
+  /// Devuelve el hash o, si no se ha migrado aún, la password
+  /// encriptada de versiones previas.
   pub(in crate::usuarios) async fn password(
     &self,
-    clave: &str,
     usuario: u32,
-  ) -> Result<Option<Password>, DBError> {
+  ) -> Result<Option<String>, DBError> {
     const QUERY: &str = "SELECT password
         FROM usuarios
         WHERE id = ? AND activo IS NOT NULL";
@@ -265,15 +397,7 @@ impl UsuarioRepo {
       .await
       .map_err(DBError::from_sqlx)?;
 
-    if let Some(r) = row {
-      let p: String = r.get("password");
-      Ok(Some(
-        Password::from_encriptado(Some(&p), clave)
-          .map_err(DBError::cripto_from)?,
-      ))
-    } else {
-      Ok(None)
-    }
+    Ok(row.map(|r| r.get("password")))
   }
 
   /// Obtiene todos los usuarios.
@@ -285,7 +409,7 @@ impl UsuarioRepo {
   ) -> Result<Vec<Usuario>, DBError> {
     const QUERY: &str = "SELECT id, dni, email,
       nombre, primer_apellido, segundo_apellido,
-      activo, inicio 
+      activo, inicio, zona_horaria, centro 
       FROM usuarios;";
 
     let rows = sqlx::query(QUERY)
@@ -311,7 +435,7 @@ impl UsuarioRepo {
   ) -> Result<Usuario, DBError> {
     const QUERY: &str = "SELECT id, dni, email,
       nombre, primer_apellido, segundo_apellido,
-      activo, inicio 
+      activo, inicio, zona_horaria, centro 
       FROM usuarios
       WHERE id = ?;";
 
@@ -341,7 +465,7 @@ impl UsuarioRepo {
   ) -> Result<Usuario, DBError> {
     const QUERY: &str = "SELECT id, dni, email,
       nombre, primer_apellido, segundo_apellido,
-      activo, inicio 
+      activo, inicio, zona_horaria, centro 
       FROM usuarios
       WHERE dni_hash = ?;";
 
@@ -362,18 +486,70 @@ impl UsuarioRepo {
   }
 
   /// Obtiene los usuarios que tienen un rol específico.
+  ///
+  /// Si se indica el centro solo se devuelven sus usuarios.
   pub(in crate::usuarios) async fn usuarios_por_rol(
     &self,
     rol: Rol,
+    centro: Option<u32>,
   ) -> Result<Vec<DescriptorUsuario>, DBError> {
     const QUERY: &str = "SELECT u.id, u.nombre,
           u.primer_apellido, u.segundo_apellido 
           FROM usuarios u
           JOIN roles_usuario ru ON u.id = ru.usuario
-          WHERE ru.rol = ?;";
+          WHERE ru.rol = ? AND (? IS NULL OR u.centro = ?);";
 
     let rows = sqlx::query(QUERY)
       .bind(rol as u32)
+      .bind(centro)
+      .bind(centro)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| DescriptorUsuario {
+          id: row.get("id"),
+          nombre: row.get("nombre"),
+          primer_apellido: row.get("primer_apellido"),
+          segundo_apellido: row.get("segundo_apellido"),
+        })
+        .collect(),
+    )
+  }
+
+  /// Obtiene los usuarios indicados y, si se indica, de un centro.
+  pub(in crate::usuarios) async fn usuarios_por_ids(
+    &self,
+    ids: &[u32],
+    centro: Option<u32>,
+  ) -> Result<Vec<DescriptorUsuario>, DBError> {
+    if ids.is_empty() {
+      return Ok(vec![]);
+    }
+
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+      "SELECT u.id, u.nombre, u.primer_apellido, u.segundo_apellido
+        FROM usuarios u
+        WHERE u.id IN (",
+    );
+    {
+      let mut separated = qb.separated(", ");
+      for id in ids {
+        separated.push_bind(*id);
+      }
+    }
+    qb.push(")");
+
+    if let Some(centro) = centro {
+      qb.push(" AND u.centro = ");
+      qb.push_bind(centro);
+    }
+
+    let rows = qb
+      .build()
       .fetch_all(self.pool.conexion())
       .await
       .map_err(DBError::from_sqlx)?;
@@ -506,6 +682,27 @@ impl UsuarioRepo {
       .map_err(DBError::from_sqlx)
   }
 
+  /// Obtiene los usuarios con zona horaria propia y su zona horaria.
+  pub(in crate::usuarios) async fn zonas_horarias(
+    &self,
+  ) -> Result<Vec<(u32, Tz)>, DBError> {
+    const QUERY: &str = "SELECT id, zona_horaria
+      FROM usuarios
+      WHERE zona_horaria IS NOT NULL";
+
+    let rows = sqlx::query(QUERY)
+      .fetch_all(self.pool.conexion())
+      .await
+      .map_err(DBError::from_sqlx)?;
+
+    Ok(
+      rows
+        .iter()
+        .filter_map(|row| zona_from_row(row).map(|tz| (row.get("id"), tz)))
+        .collect(),
+    )
+  }
+
   async fn usuario_from_row(
     &self,
     row: &MySqlRow,
@@ -528,6 +725,15 @@ impl UsuarioRepo {
       inicio: row.get("inicio"),
       roles,
       calendarios: vec![],
+      zona_horaria: zona_from_row(row),
+      centro: row.get("centro"),
     })
   }
 }
+
+/// Zona horaria de la fila. Si no es una zona válida se ignora.
+fn zona_from_row(row: &MySqlRow) -> Option<Tz> {
+  row
+    .get::<Option<String>, _>("zona_horaria")
+    .and_then(|zona| zona.parse().ok())
+}
@@ -1,18 +1,32 @@
-use chrono::{Utc};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use chrono::{NaiveDateTime, Utc};
 use smallvec::SmallVec;
 
 use crate::{
   agregar_traza, config::{BootAdmin, ConfigTrabajo},
-   infra::{DBError, Dni, Password, ServicioError, dni_valido, validar_password},
+   infra::{
+     DBError, Dni, Password, ServicioError, ShortDateTimeFormat, dni_valido,
+     es_hash_password, validar_password},
+   sesiones::SesionServicio,
    traza::{TipoTraza, TrazaBuilder, TrazaServicio},
-   usuarios::{DescriptorUsuario, Rol, Usuario, UsuarioRepo}
+   usuarios::{BloqueoAcceso, DescriptorUsuario, Rol, Usuario, UsuarioRepo}
 };
 
+const MSG_ACCESO_BLOQUEADO: &str =
+  "El acceso está bloqueado temporalmente por exceso de intentos fallidos. \
+  Inténtelo más tarde o contacte con el administrador";
+
 ///Servicio para manejar operaciones relacionadas con usuarios.
 pub struct UsuarioServicio {
   cnfg: ConfigTrabajo,
   repo: UsuarioRepo,
   srv_traza: TrazaServicio,
+  srv_sesion: Arc<SesionServicio>,
+  // Los intentos fallidos por IP solo se mantienen en memoria
+  intentos_ip: Mutex<HashMap<IpAddr, BloqueoAcceso>>,
 }
 
 impl UsuarioServicio {
@@ -20,11 +34,14 @@ impl UsuarioServicio {
     cnfg: ConfigTrabajo,
     repo: UsuarioRepo,
     srv_traza: TrazaServicio,
+    srv_sesion: Arc<SesionServicio>,
   ) -> Self {
     UsuarioServicio {
       cnfg,
       repo,
       srv_traza,
+      srv_sesion,
+      intentos_ip: Mutex::new(HashMap::new()),
     }
   }
 }
@@ -56,6 +73,8 @@ impl UsuarioServicio {
       inicio: None,
       roles: SmallVec::from_slice(&[Rol::Admin]),
       calendarios: vec![],
+      zona_horaria: None,
+      centro: None,
       password: Some(Password::new(boot_admin.password.clone())),
     };
 
@@ -64,6 +83,27 @@ impl UsuarioServicio {
     Ok(())
   }
 
+  /// Carga las zonas horarias propias de los usuarios en la configuración.
+  ///
+  /// Los usuarios sin zona horaria propia usan la zona por defecto.
+  pub async fn cargar_zonas_horarias(&self) -> Result<(), ServicioError> {
+    let zonas = self.repo.zonas_horarias().await.map_err(|err| {
+      tracing::error!(error = %err, "Cargando zonas horarias de los usuarios");
+      ServicioError::from(err)
+    })?;
+
+    tracing::info!(
+      usuarios = zonas.len(),
+      "Se han cargado las zonas horarias de los usuarios"
+    );
+
+    for (usuario, zona) in zonas {
+      self.cnfg.asignar_zona_usuario(usuario, Some(zona));
+    }
+
+    Ok(())
+  }
+
   /// Crea un nuevo usuario.
   /// 
   /// El usuario es creado por un usuario autor
@@ -109,13 +149,17 @@ impl UsuarioServicio {
       }
     };
 
-    let cal_ids: Vec<u32> = usuario
+    let mut cal_ids: Vec<u32> = usuario
       .calendarios
       .iter()
       .filter(|c| c.asignado)
       .map(|c| c.calendario)
       .collect();
 
+    if let Some(centro) = usuario.centro {
+      self.agregar_calendarios_centro(centro, &mut cal_ids).await?;
+    }
+
     for &cal_id in &cal_ids {
       let fechas_conflictivas = self
         .repo
@@ -179,6 +223,8 @@ impl UsuarioServicio {
       ServicioError::from(err)
     })?;
 
+    self.cnfg.asignar_zona_usuario(id, usuario.zona_horaria);
+
     tracing::debug!(
       usuario = id,
       "Se ha completado satisfactoriamente la creación del usuario"
@@ -192,6 +238,7 @@ impl UsuarioServicio {
   /// El usuario es modificado por un usuario autor
   /// Valida los datos del usuario antes de proceder con la actualización.
   /// Genera trazas de las modificaciones.
+  /// Si cambian los roles o se desactiva, revoca sus sesiones.
   pub async fn actualizar_usuario(
     &self,
     modificado_por: u32,
@@ -337,13 +384,39 @@ impl UsuarioServicio {
       }
     }
 
-    if !usr_persistido.eq_calendarios(usuario) {
-      let cal_ids_nuevos: Vec<u32> = usuario
-        .calendarios
-        .iter()
-        .filter(|c| c.asignado)
-        .map(|c| c.calendario)
-        .collect();
+    let mut cal_ids_nuevos: Vec<u32> = usuario
+      .calendarios
+      .iter()
+      .filter(|c| c.asignado)
+      .map(|c| c.calendario)
+      .collect();
+    let num_cal_usuario = cal_ids_nuevos.len();
+
+    if usr_persistido.centro != usuario.centro {
+      tracing::debug!(
+        usuario = usuario.id, "Ha cambiado el centro de trabajo del usuario");
+
+      if let Some(centro) = usuario.centro {
+        self.agregar_calendarios_centro(centro, &mut cal_ids_nuevos).await?;
+      }
+
+      let traza = TrazaBuilder::with_usuario(
+        TipoTraza::UsrCentroModificado, usuario.id)
+        .autor(Some(modificado_por))
+        .motivo(Some(format!(
+          "Centro de trabajo cambiado de {:?} a {:?}",
+          usr_persistido.centro, usuario.centro
+        )))
+        .build(&self.cnfg.zona_horaria);
+
+      agregar_traza!(
+        self, tr, traza,
+        "Creando traza modificación de centro", usuario = usuario.id);
+    }
+
+    // Añadir los calendarios del nuevo centro también es un cambio
+    if !usr_persistido.eq_calendarios(usuario)
+      || cal_ids_nuevos.len() != num_cal_usuario {
 
       let cal_ids_persistidos: std::collections::HashSet<u32> = usr_persistido
         .calendarios
@@ -398,6 +471,22 @@ impl UsuarioServicio {
       }
     }
 
+    if usr_persistido.zona_horaria != usuario.zona_horaria {
+      let traza = TrazaBuilder::with_usuario(
+        TipoTraza::UsrZonaHorariaModificada, usuario.id)
+        .autor(Some(modificado_por))
+        .motivo(Some(format!(
+          "Zona horaria cambiada de {:?} a {:?}",
+          usr_persistido.zona_horaria.map(|tz| tz.name()),
+          usuario.zona_horaria.map(|tz| tz.name())
+        )))
+        .build(&self.cnfg.zona_horaria);
+
+      agregar_traza!(
+        self, tr, traza,
+        "Creando traza modificación de zona horaria", usuario = usuario.id);
+    }
+
     let traza = TrazaBuilder::with_usuario(
       TipoTraza::ActualizacionUsuario, usuario.id)
       .autor(Some(modificado_por))
@@ -414,6 +503,17 @@ impl UsuarioServicio {
       ServicioError::from(err)
     })?;
 
+    self.cnfg.asignar_zona_usuario(usuario.id, usuario.zona_horaria);
+
+    // Los roles están firmados en la sesión y un usuario
+    // desactivado no puede seguir accediendo
+    if !usr_persistido.eq_roles(usuario) ||
+      (usr_persistido.activo.is_some() && usuario.activo.is_none()) {
+      // Si falla la persistencia, la sesión ya está revocada en memoria
+      // pero se informa del error
+      self.srv_sesion.revocar_usuario(usuario.id).await?;
+    }
+
     tracing::debug!(
       usuario = usuario.id,
       "Se ha completado satisfactoriamente la actualización del usuario"
@@ -424,10 +524,12 @@ impl UsuarioServicio {
 
   /// Actualiza la password de un usuario existente.
   ///
+  /// La password es modificada por un usuario autor.
   /// Valida la password antes de proceder con la actualización.
-  /// Genera trazas de las modificación.
+  /// Genera trazas de las modificación y revoca las sesiones del usuario.
   pub async fn actualizar_password(
     &self,
+    modificado_por: u32,
     usuario: u32,
     password: &Password,
     ) -> Result<(), ServicioError> {
@@ -448,6 +550,7 @@ impl UsuarioServicio {
 
     let traza = TrazaBuilder::with_usuario(
       TipoTraza::PasswordModificada, usuario)
+      .autor(Some(modificado_por))
       .build(&self.cnfg.zona_horaria);
 
     agregar_traza!(
@@ -456,7 +559,7 @@ impl UsuarioServicio {
 
     if let Err(err) = self
       .repo
-      .actualizar_password(&mut tr, &self.cnfg.secreto, usuario, password)
+      .actualizar_password(&mut tr, usuario, password)
       .await {
         tracing::error!( 
           usuario = usuario, error = %err,
@@ -471,6 +574,9 @@ impl UsuarioServicio {
       ServicioError::from(err)
     })?;
 
+    // Si falla la persistencia, la sesión ya está revocada en memoria
+    // pero se informa del error
+    self.srv_sesion.revocar_usuario(usuario).await?;
 
     tracing::debug!(
       usuario = usuario,
@@ -500,6 +606,45 @@ impl UsuarioServicio {
     Ok(())
   }
 
+  /// Verifica la password proporcionada contra la almacenada.
+  ///
+  /// Si la password almacenada es de versiones previas (encriptada),
+  /// se migra a hash tras una verificación correcta.
+  async fn verificar_password(
+      &self, usuario: u32, almacenada: &str, password: &Password
+    ) -> Result<bool, ServicioError> {
+    if es_hash_password(almacenada) {
+      return Ok(password.verificar_hash_password(almacenada).await);
+    }
+
+    let previa = Password::from_encriptado(Some(almacenada), &self.cnfg.secreto);
+    let hash = match previa {
+      Ok(previa) => previa.hash_password().await,
+      Err(err) => Err(err),
+    }
+    .map_err(|err| {
+      tracing::error!(
+        usuario = usuario, error = %err,
+        "Obteniendo hash de password encriptada");
+      ServicioError::from(DBError::cripto_from(err))
+    })?;
+
+    if !password.verificar_hash_password(&hash).await {
+      return Ok(false);
+    }
+
+    // Si falla la migración no se impide el login,
+    // se volverá a intentar en el siguiente inicio de sesión
+    match self.repo.migrar_password(usuario, almacenada, &hash).await {
+      Ok(_) => tracing::info!(
+        usuario = usuario, "Password migrada a hash"),
+      Err(err) => tracing::error!(
+        usuario = usuario, error = %err, "Migrando password a hash"),
+    }
+
+    Ok(true)
+  }
+
   fn valida_password(
       &self, usuario: u32, password: &Password) -> Result<(), ServicioError> {
     if password.is_empty() {
@@ -519,15 +664,224 @@ impl UsuarioServicio {
     Ok(())
   }
 
+  /// Desbloquea el acceso de un usuario bloqueado por intentos fallidos.
+  ///
+  /// El usuario es desbloqueado por un usuario autor.
+  /// Genera una traza del desbloqueo.
+  pub async fn desbloquear_usuario(
+    &self,
+    desbloqueado_por: u32,
+    usuario: u32,
+  ) -> Result<(), ServicioError> {
+    tracing::info!(
+      usuario = usuario, autor = desbloqueado_por,
+      "Se ha iniciado el servicio para desbloquear un usuario");
+
+    let mut tr = self.repo.conexion().empezar_transaccion().await.map_err(
+      |err| {
+        tracing::error!(
+           usuario = usuario, error = %err,
+           "Iniciando transacción para desbloquear usuario");
+        ServicioError::from(err)
+      },
+    )?;
+
+    if let Err(err) = self
+      .repo
+      .actualizar_bloqueo(&mut tr, usuario, &BloqueoAcceso::default())
+      .await {
+        tracing::error!(
+          usuario = usuario, error = %err, "Desbloqueando usuario");
+        return Err(ServicioError::from(err));
+    }
+
+    let traza = TrazaBuilder::with_usuario(
+      TipoTraza::UsrDesbloqueado, usuario)
+      .autor(Some(desbloqueado_por))
+      .build(&self.cnfg.zona_horaria);
+
+    agregar_traza!(
+      self, tr, traza,
+      "Creando traza desbloqueo de usuario", usuario = usuario);
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+         usuario = usuario, error = %err,
+        "Commit transacción para desbloquear usuario");
+      ServicioError::from(err)
+    })?;
+
+    tracing::debug!(
+      usuario = usuario,
+      "Se ha completado satisfactoriamente el desbloqueo del usuario"
+    );
+
+    Ok(())
+  }
+
+  /// Registra un intento fallido de login del usuario.
+  ///
+  /// El estado del bloqueo se lee y se actualiza dentro de la misma
+  /// transacción con la fila bloqueada, para no perder intentos
+  /// fallidos simultáneos.
+  ///
+  /// Si el intento provoca el bloqueo se añade una traza.
+  async fn registrar_fallo_usuario(
+      &self, usuario: u32, ahora: NaiveDateTime
+    ) -> Result<(), ServicioError> {
+    let mut tr = self.repo.conexion().empezar_transaccion().await.map_err(
+      |err| {
+        tracing::error!(
+           usuario = usuario, error = %err,
+           "Iniciando transacción para registrar intento fallido");
+        ServicioError::from(err)
+      },
+    )?;
+
+    let mut bloqueo = self
+      .repo
+      .bloqueo_para_actualizar(&mut tr, usuario)
+      .await
+      .map_err(|err| {
+        tracing::error!(
+          usuario = usuario, error = %err, "Obteniendo bloqueo de usuario");
+        ServicioError::from(err)
+      })?;
+
+    let bloqueado = bloqueo.registrar_fallo(&self.cnfg.bloqueo, ahora);
+
+    if let Err(err) = self
+      .repo
+      .actualizar_bloqueo(&mut tr, usuario, &bloqueo)
+      .await {
+        tracing::error!(
+          usuario = usuario, error = %err, "Registrando intento fallido");
+        return Err(ServicioError::from(err));
+    }
+
+    if bloqueado {
+      tracing::warn!(
+        usuario = usuario, bloqueo = ?bloqueo,
+        "Usuario bloqueado por exceso de intentos fallidos");
+
+      let traza = TrazaBuilder::with_usuario(
+        TipoTraza::UsrBloqueado, usuario)
+        .motivo(Some(format!(
+          "Bloqueado hasta {} tras {} intentos fallidos",
+          bloqueo.bloqueado_hasta.unwrap_or(ahora).formato_corto(),
+          bloqueo.intentos_fallidos
+        )))
+        .build(&self.cnfg.zona_horaria);
+
+      agregar_traza!(
+        self, tr, traza,
+        "Creando traza bloqueo de usuario", usuario = usuario);
+    }
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+         usuario = usuario, error = %err,
+        "Commit transacción para registrar intento fallido");
+      ServicioError::from(err)
+    })
+  }
+
+  /// Elimina los intentos fallidos de login del usuario
+  async fn limpiar_fallos_usuario(
+      &self, usuario: u32) -> Result<(), ServicioError> {
+    let mut tr = self.repo.conexion().empezar_transaccion().await.map_err(
+      |err| {
+        tracing::error!(
+           usuario = usuario, error = %err,
+           "Iniciando transacción para limpiar intentos fallidos");
+        ServicioError::from(err)
+      },
+    )?;
+
+    if let Err(err) = self
+      .repo
+      .actualizar_bloqueo(&mut tr, usuario, &BloqueoAcceso::default())
+      .await {
+        tracing::error!(
+          usuario = usuario, error = %err, "Limpiando intentos fallidos");
+        return Err(ServicioError::from(err));
+    }
+
+    tr.commit().await.map_err(|err| {
+      tracing::error!(
+         usuario = usuario, error = %err,
+        "Commit transacción para limpiar intentos fallidos");
+      ServicioError::from(err)
+    })
+  }
+
+  /// Devuelve true si la IP está bloqueada
+  fn ip_bloqueada(&self, ip: Option<IpAddr>, ahora: NaiveDateTime) -> bool {
+    ip.is_some_and(|ip| {
+      self
+        .intentos_ip
+        .lock()
+        .expect("Bloqueo de intentos por IP envenenado")
+        .get(&ip)
+        .is_some_and(|b| b.bloqueado(ahora))
+    })
+  }
+
+  /// Registra un intento fallido de login para la IP
+  fn registrar_fallo_ip(&self, ip: Option<IpAddr>, ahora: NaiveDateTime) {
+    let Some(ip) = ip else {
+      return;
+    };
+
+    let politica = &self.cnfg.bloqueo;
+    let mut intentos = self
+      .intentos_ip
+      .lock()
+      .expect("Bloqueo de intentos por IP envenenado");
+
+    // Se descartan los intentos caducados para no acumular IPs
+    intentos.retain(|_, b| !b.caducado(politica, ahora));
+
+    let bloqueo = intentos.entry(ip).or_default();
+    if bloqueo.registrar_fallo(politica, ahora) {
+      tracing::warn!(
+        ip = %ip, bloqueo = ?bloqueo,
+        "IP bloqueada por exceso de intentos fallidos");
+    }
+  }
+
+  /// Elimina los intentos fallidos de login para la IP
+  fn limpiar_fallos_ip(&self, ip: Option<IpAddr>) {
+    if let Some(ip) = ip {
+      self
+        .intentos_ip
+        .lock()
+        .expect("Bloqueo de intentos por IP envenenado")
+        .remove(&ip);
+    }
+  }
+
   /// Realiza el login de usuario
   /// 
   /// Si el usuario no inicio nunca sesión actualiza el inicio
   /// en la base de datos y añade una traza.
   /// 
+  /// Los intentos fallidos se controlan por usuario y por IP
+  /// según la política de bloqueo. Si el acceso está bloqueado
+  /// devuelve un error de acceso bloqueado sin verificar la password.
+  /// 
   /// Devuelve si la password proporcionada es correcta.
   pub async fn login_usuario(
-      &self, dni: &Dni, password: &Password
+      &self, dni: &Dni, password: &Password, ip: Option<IpAddr>
     ) -> Result<Option<Usuario>, ServicioError> {
+    let ahora = Utc::now()
+      .with_timezone(&self.cnfg.zona_horaria)
+      .naive_local();
+
+    if self.ip_bloqueada(ip, ahora) {
+      tracing::warn!(ip = ?ip, "Login rechazado, la IP está bloqueada");
+      return Err(ServicioError::AccesoBloqueado(MSG_ACCESO_BLOQUEADO.to_string()));
+    }
 
     let result = self.repo.usuario_por_dni(&self.cnfg.secreto, dni).await;
 
@@ -535,6 +889,7 @@ impl UsuarioServicio {
       Ok(u) => u,
       Err(DBError::RegistroVacio(_)) => {
         tracing::info!("No existe el usuario");
+        self.registrar_fallo_ip(ip, ahora);
          return Ok(None);
       },
       Err(err) => { 
@@ -547,22 +902,39 @@ impl UsuarioServicio {
       usuario = ?usr,
       "Se ha iniciado el servicio que valida el login de usuario");
 
-    let result = self.repo.password(&self.cnfg.secreto, usr.id)
+    let bloqueo = self.repo.bloqueo(usr.id).await.map_err(|err| {
+      tracing::error!(error = %err, "Obteniendo bloqueo de usuario");
+      ServicioError::from(err)
+    })?;
+
+    if bloqueo.bloqueado(ahora) {
+      tracing::warn!(
+        usuario = usr.id, bloqueo = ?bloqueo,
+        "Login rechazado, el usuario está bloqueado");
+      return Err(ServicioError::AccesoBloqueado(MSG_ACCESO_BLOQUEADO.to_string()));
+    }
+
+    let result = self.repo.password(usr.id)
       .await.map_err(|err| {
       tracing::error!(error = %err, "Obteniendo password de usuario");
       ServicioError::from(err)
     })?;
 
-    if let Some(passw) = result {
-      if passw != *password {
+    if let Some(almacenada) = result {
+      if !self.verificar_password(usr.id, &almacenada, password).await? {
         tracing::info!(usuario = usr.id, "La password es incorrecta");
+        self.registrar_fallo_ip(ip, ahora);
+        self.registrar_fallo_usuario(usr.id, ahora).await?;
         return Ok(None);
       }
 
+      self.limpiar_fallos_ip(ip);
+      if bloqueo != BloqueoAcceso::default() {
+        self.limpiar_fallos_usuario(usr.id).await?;
+      }
+
       if usr.inicio.is_none() {
-        let inicio = Utc::now()
-          .with_timezone(&self.cnfg.zona_horaria)
-          .naive_local();
+        let inicio = self.cnfg.ahora();
 
         tracing::debug!(
           usuario = ?usr, nuevo_inicio = %inicio,
@@ -608,6 +980,7 @@ impl UsuarioServicio {
     } else {
       tracing::info!(
         usuario = ?usr, "No existe el usuario o no esta activado");
+      self.registrar_fallo_ip(ip, ahora);
 
       Ok(None)
     }
@@ -663,18 +1036,61 @@ impl UsuarioServicio {
     Ok(usuario)
   }
 
+  /// Añade a los calendarios los calendarios por defecto del
+  /// centro de trabajo que aún no estén incluidos.
+  async fn agregar_calendarios_centro(
+    &self,
+    centro: u32,
+    calendarios: &mut Vec<u32>,
+  ) -> Result<(), ServicioError> {
+    let cal_centro = self.repo.calendarios_centro(centro).await.map_err(
+      |err| {
+        tracing::error!(
+          centro = centro, error = %err,
+          "Obteniendo los calendarios del centro de trabajo");
+        ServicioError::from(err)
+      })?;
+
+    for cal in cal_centro {
+      if !calendarios.contains(&cal) {
+        calendarios.push(cal);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Obtiene los usuarios de un rol y, si se indica, de un centro.
   pub async fn usuarios_por_rol(
     &self,
     rol: Rol,
+    centro: Option<u32>,
   ) -> Result<Vec<DescriptorUsuario>, ServicioError> {
-    self.repo.usuarios_por_rol(rol).await.map_err(|err| {
+    self.repo.usuarios_por_rol(rol, centro).await.map_err(|err| {
       tracing::error!(
           rol = ?rol,
+          centro = ?centro,
           error = %err,
           "Obteniendo usuarios por rol");
         ServicioError::from(err)
       })
   }
+
+  /// Obtiene los usuarios indicados y, si se indica, de un centro.
+  pub async fn usuarios_por_ids(
+    &self,
+    ids: &[u32],
+    centro: Option<u32>,
+  ) -> Result<Vec<DescriptorUsuario>, ServicioError> {
+    self.repo.usuarios_por_ids(ids, centro).await.map_err(|err| {
+      tracing::error!(
+          ids = ?ids,
+          centro = ?centro,
+          error = %err,
+          "Obteniendo usuarios por ids");
+        ServicioError::from(err)
+      })
+  }
 }
 
 fn valida_ids_usuario(
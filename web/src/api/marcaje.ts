@@ -134,7 +134,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 1,
           horario: {
             dia: 'L',
-            horas: 8
+            minutos: 480
           },
           fecha: '2024-01-15',
           hora_inicio: '08:00',
@@ -148,7 +148,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: null,
           horario: {
             dia: 'M',
-            horas: 8
+            minutos: 480
           },
           fecha: '2024-01-16',
           hora_inicio: '09:00',
@@ -162,7 +162,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 2,
           horario: {
             dia: 'X',
-            horas: 8
+            minutos: 480
           },
           fecha: '2024-01-17',
           hora_inicio: '07:30',
@@ -176,7 +176,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 3,
           horario: {
             dia: 'J',
-            horas: 8
+            minutos: 480
           },
           fecha: '2024-01-18',
           hora_inicio: '08:30',
@@ -189,7 +189,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 3,
           horario: {
             dia: 'V',
-            horas: 8
+            minutos: 480
           },
           fecha: '2024-01-19',
           hora_inicio: '07:00',
@@ -202,7 +202,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 2,
           horario: {
             dia: 'S',
-            horas: 4
+            minutos: 240
           },
           fecha: '2024-01-20',
           hora_inicio: '08:00',
@@ -215,7 +215,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 2,
           horario: {
             dia: 'S',
-            horas: 4
+            minutos: 240
           },
           fecha: '2024-01-20',
           hora_inicio: '08:00',
@@ -228,7 +228,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 2,
           horario: {
             dia: 'S',
-            horas: 6
+            minutos: 360
           },
           fecha: '2024-01-20',
           hora_inicio: '08:00',
@@ -241,7 +241,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 2,
           horario: {
             dia: 'S',
-            horas: 8
+            minutos: 480
           },
           fecha: '2024-01-20',
           hora_inicio: '08:00',
@@ -254,7 +254,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 2,
           horario: {
             dia: 'S',
-            horas: 8
+            minutos: 480
           },
           fecha: '2024-01-20',
           hora_inicio: '08:00',
@@ -267,7 +267,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 2,
           horario: {
             dia: 'S',
-            horas: 8
+            minutos: 480
           },
           fecha: '2024-01-20',
           hora_inicio: '08:00',
@@ -280,7 +280,7 @@ export class MarcajeTestApi implements MarcajeApi {
           usuario_reg: 2,
           horario: {
             dia: 'S',
-            horas: 8
+            minutos: 480
           },
           fecha: '2024-01-20',
           hora_inicio: '08:00',
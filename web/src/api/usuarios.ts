@@ -266,7 +266,7 @@ export class UsuariosTestApi implements UsuariosApi {
     return Horario.fromRequest(
       {
         dia: 'L',
-        horas: 2
+        minutos: 120
       });
   }
 
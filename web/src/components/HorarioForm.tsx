@@ -60,8 +60,8 @@ export function validaHorario(values: HorarioFormValues): ValidationResult {
 
   if (!values.horas) {
     issues.push({ message: 'Las horas son requeridas', path: ['horas'] });
-  } else if (values.horas < 1 || values.horas > 24) {
-    issues.push({ message: 'Las horas deben estar entre 1 y 24', path: ['horas'] });
+  } else if (values.horas <= 0 || values.horas > 24) {
+    issues.push({ message: 'Las horas deben ser mayores que 0 y como máximo 24', path: ['horas'] });
   }
 
   if (values.cortesia < 0 || values.cortesia > 120) {
@@ -236,7 +236,7 @@ export default function HorarioForm(props: HorarioFormProps) {
               helperText={formErrors.horas ?? ' '}
               fullWidth
               slotProps={{
-                htmlInput: { min: 1, max: 24, step: 1 }
+                htmlInput: { min: 0.25, max: 24, step: 0.25 }
               }}
             />
           </Grid>
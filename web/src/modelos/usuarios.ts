@@ -330,7 +330,7 @@ export class Horario {
     return plainToInstance(Horario, {
       id: obj.id,
       dia: diaSemanafromLetra[obj.dia],
-      horas: obj.horas,
+      horas: obj.minutos / 60,
     });
   }
 
@@ -361,7 +361,7 @@ export class ConfigHorario {
       horario: Horario.fromRequest({
         id: obj.id,
         dia: obj.dia,
-        horas: obj.horas,
+        minutos: obj.minutos,
       }),
       cortesia: obj.cortesia,
       fechaCreacion: dayjs(obj.fecha_creacion),
@@ -381,7 +381,7 @@ export class ConfigHorario {
       id: this.id,
       usuario: this.usuario,
       dia: this.horario.dia,
-      horas: this.horario.horas,
+      minutos: Math.round(this.horario.horas * 60),
       cortesia: this.cortesia,
       fecha_creacion: formatDateForServer(this.fechaCreacion),
       caducidad_fecha_ini: formatDateForServer(this.caducidadFechaIni),